@@ -0,0 +1,73 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Crates whose versions are embedded in the binary (reported by `rusimg --formats` and `rusimg --version --verbose`).
+const VERSIONED_CRATES: [&str; 5] = ["librusimg", "image", "mozjpeg", "oxipng", "webp"];
+
+/// Get the version of a crate from Cargo.lock.
+fn get_locked_version(lock: &str, name: &str) -> Option<String> {
+    let mut lines = lock.lines();
+    while let Some(line) = lines.next() {
+        if line.trim() == format!("name = \"{}\"", name) {
+            let version = lines.next()?.trim();
+            return version.strip_prefix("version = \"").and_then(|v| v.strip_suffix('"')).map(|v| v.to_string());
+        }
+    }
+    None
+}
+
+/// Get the short hash of the commit being built.
+/// RUSIMG_GIT_HASH overrides it for builds outside a git checkout (e.g. from a source tarball in a release pipeline).
+fn get_git_hash(manifest_dir: &Path) -> Option<String> {
+    if let Ok(hash) = std::env::var("RUSIMG_GIT_HASH") {
+        return Some(hash);
+    }
+    let output = Command::new("git").args(["rev-parse", "--short=12", "HEAD"]).current_dir(manifest_dir).output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    Some(String::from_utf8(output.stdout).ok()?.trim().to_string())
+}
+
+/// Get the version of the compiler (e.g. "rustc 1.80.0 (051478957 2024-07-21)").
+fn get_rustc_version() -> Option<String> {
+    let rustc = std::env::var("RUSTC").unwrap_or("rustc".to_string());
+    let output = Command::new(rustc).arg("--version").output().ok()?;
+    Some(String::from_utf8(output.stdout).ok()?.trim().to_string())
+}
+
+/// Get the cargo features enabled for this build, sorted and separated by commas (e.g. "app,jpeg-pure").
+fn get_features() -> String {
+    let mut features = std::env::vars()
+        .filter_map(|(key, _)| key.strip_prefix("CARGO_FEATURE_").map(|feature| feature.to_ascii_lowercase().replace('_', "-")))
+        .collect::<Vec<_>>();
+    features.sort();
+    features.join(",")
+}
+
+fn main() {
+    let manifest_dir = PathBuf::from(std::env::var("CARGO_MANIFEST_DIR").unwrap());
+    let lock_path = manifest_dir.join("Cargo.lock");
+    println!("cargo:rerun-if-changed={}", lock_path.display());
+    let lock = fs::read_to_string(&lock_path).unwrap_or_default();
+
+    for name in VERSIONED_CRATES {
+        let version = get_locked_version(&lock, name).unwrap_or("unknown".to_string());
+        println!("cargo:rustc-env=RUSIMG_VERSION_{}={}", name.to_ascii_uppercase(), version);
+    }
+
+    // ビルド情報 (rusimg --version --verbose)
+    for git_file in ["HEAD", "index"] {
+        let path = manifest_dir.join(".git").join(git_file);
+        if path.exists() {
+            println!("cargo:rerun-if-changed={}", path.display());
+        }
+    }
+    println!("cargo:rerun-if-env-changed=RUSIMG_GIT_HASH");
+    println!("cargo:rustc-env=RUSIMG_GIT_HASH={}", get_git_hash(&manifest_dir).unwrap_or("unknown".to_string()));
+    println!("cargo:rustc-env=RUSIMG_RUSTC_VERSION={}", get_rustc_version().unwrap_or("unknown".to_string()));
+    println!("cargo:rustc-env=RUSIMG_FEATURES={}", get_features());
+    println!("cargo:rustc-env=RUSIMG_TARGET={}", std::env::var("TARGET").unwrap_or_default());
+    println!("cargo:rustc-env=RUSIMG_PROFILE={}", std::env::var("PROFILE").unwrap_or_default());
+}
@@ -0,0 +1,22 @@
+use std::path::PathBuf;
+use std::sync::OnceLock;
+use image::{DynamicImage, Rgba, RgbaImage};
+use librusimg::tempdir::TempDir;
+
+/// Size of the fixture image.
+pub const WIDTH: u32 = 64;
+pub const HEIGHT: u32 = 48;
+
+static DIR: OnceLock<TempDir> = OnceLock::new();
+
+/// Get the fixture image (png with alpha, so that both resize paths of the backends are used).
+/// The fixture is written on the first call and shared by all runs of the process.
+pub fn path() -> PathBuf {
+    let dir = DIR.get_or_init(|| {
+        let dir = TempDir::new().expect("failed to create the temporary directory");
+        let image = RgbaImage::from_fn(WIDTH, HEIGHT, |x, y| Rgba([(x * 4) as u8, (y * 5) as u8, (x ^ y) as u8, (x * 2 + y) as u8]));
+        DynamicImage::ImageRgba8(image).save(dir.file("fixture.png")).expect("failed to save the fixture");
+        dir
+    });
+    dir.file("fixture.png")
+}
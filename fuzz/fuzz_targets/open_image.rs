@@ -0,0 +1,28 @@
+#![no_main]
+
+use std::fs;
+use std::sync::OnceLock;
+use libfuzzer_sys::fuzz_target;
+use librusimg::tempdir::TempDir;
+
+/// Extensions of the input file. The first byte of the input chooses one, so that every backend is reached.
+const EXTENSIONS: [&str; 4] = ["bmp", "jpg", "png", "webp"];
+
+/// Directory for the input files, shared by all runs of the process.
+static DIR: OnceLock<TempDir> = OnceLock::new();
+
+// Malformed input must be reported as RusimgError, not as a panic.
+fuzz_target!(|data: &[u8]| {
+    let Some((selector, data)) = data.split_first() else {
+        return;
+    };
+    let dir = DIR.get_or_init(|| TempDir::new().expect("failed to create the temporary directory"));
+    let path = dir.file(format!("input.{}", EXTENSIONS[*selector as usize % EXTENSIONS.len()]));
+    fs::write(&path, data).expect("failed to write the input");
+
+    if let Ok(image) = librusimg::open_image(&path) {
+        let size = image.get_image_size().expect("an opened image must have a size");
+        let decoded = image.as_dynamic_image();
+        assert_eq!((size.width, size.height), (decoded.width() as usize, decoded.height() as usize));
+    }
+});
@@ -0,0 +1,23 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+mod fixture;
+
+/// Largest ratio tried (in percent). Larger ratios only measure the allocator.
+const MAX_RATIO: f32 = 400.0;
+
+// RusImg::resize(): the new size is the old size times the ratio, rounded down, and the image has that size.
+fuzz_target!(|ratio: f32| {
+    if !ratio.is_finite() || ratio <= 0.0 || ratio > MAX_RATIO {
+        return;
+    }
+    let mut image = librusimg::open_image(&fixture::path()).expect("failed to open the fixture");
+    let Ok(size) = image.resize(ratio) else {
+        return;
+    };
+    assert_eq!(size.width, (fixture::WIDTH as f32 * (ratio / 100.0)) as usize);
+    assert_eq!(size.height, (fixture::HEIGHT as f32 * (ratio / 100.0)) as usize);
+    let decoded = image.as_dynamic_image();
+    assert_eq!((decoded.width() as usize, decoded.height() as usize), (size.width, size.height));
+});
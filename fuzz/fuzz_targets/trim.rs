@@ -0,0 +1,52 @@
+#![no_main]
+
+use libfuzzer_sys::arbitrary::{self, Arbitrary};
+use libfuzzer_sys::fuzz_target;
+use librusimg::{ImgSize, Rect};
+use librusimg::rect::TrimPolicy;
+
+mod fixture;
+
+/// A trim area and the image size it is clamped to.
+/// The size is arbitrary for the math, and the area is also applied to the fixture image.
+#[derive(Arbitrary, Debug)]
+struct Input {
+    width: u32,
+    height: u32,
+    x: u32,
+    y: u32,
+    w: u32,
+    h: u32,
+    error_policy: bool,
+}
+
+fuzz_target!(|input: Input| {
+    let rect = Rect { x: input.x, y: input.y, w: input.w, h: input.h };
+    let policy = if input.error_policy { TrimPolicy::Error } else { TrimPolicy::Clamp };
+
+    // Rect::clamp_to(): the result must fit in the image and in the requested area.
+    let size = ImgSize { width: input.width as usize, height: input.height as usize };
+    if let Ok(clamped) = rect.clamp_to(size, policy) {
+        assert_eq!((clamped.x, clamped.y), (rect.x, rect.y));
+        assert!(clamped.w <= rect.w && clamped.h <= rect.h);
+        assert!(clamped.x as u64 + clamped.w as u64 <= input.width as u64);
+        assert!(clamped.y as u64 + clamped.h as u64 <= input.height as u64);
+        if policy == TrimPolicy::Error {
+            assert_eq!(clamped, rect);
+        }
+    }
+
+    // RusImg::trim_rect(): the image must have the size of the clamped area, or the area must be rejected.
+    let mut image = librusimg::open_image(&fixture::path()).expect("failed to open the fixture");
+    let fixture_size = ImgSize { width: fixture::WIDTH as usize, height: fixture::HEIGHT as usize };
+    let expected = rect.clamp_to(fixture_size, TrimPolicy::Clamp);
+    match (image.trim_rect(rect.clone()), expected) {
+        (Ok(trimmed), Ok(expected)) => {
+            assert_eq!((trimmed.width, trimmed.height), (expected.w as usize, expected.h as usize));
+            let decoded = image.as_dynamic_image();
+            assert_eq!((decoded.width(), decoded.height()), (expected.w, expected.h));
+        },
+        (Err(_), Err(_)) => {},
+        (trimmed, expected) => panic!("trim_rect() returned {:?}, but clamp_to() returned {:?}", trimmed, expected),
+    }
+});
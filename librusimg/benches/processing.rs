@@ -0,0 +1,102 @@
+use std::path::{Path, PathBuf};
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion, Throughput};
+use image::{DynamicImage, Rgb, RgbImage};
+use librusimg::Extension;
+
+/// Size of the bundled fixture (generated at startup to keep the repository small).
+const FIXTURE_WIDTH: u32 = 1024;
+const FIXTURE_HEIGHT: u32 = 768;
+/// Number of images processed by the batch benchmark.
+const BATCH_SIZE: usize = 8;
+const FORMATS: [Extension; 4] = [Extension::Jpeg, Extension::Png, Extension::Webp, Extension::Bmp];
+
+/// Directory where the fixtures and outputs are written.
+fn fixture_dir() -> PathBuf {
+    let dir = std::env::temp_dir().join("rusimg-criterion");
+    std::fs::create_dir_all(&dir).expect("failed to create the fixture directory");
+    dir
+}
+
+/// Generate a photo-like fixture and save it in every supported format.
+fn write_fixtures(dir: &Path) -> Vec<(Extension, PathBuf)> {
+    let image = RgbImage::from_fn(FIXTURE_WIDTH, FIXTURE_HEIGHT, |x, y| {
+        let r = (x * 255 / FIXTURE_WIDTH) as u8;
+        let g = (y * 255 / FIXTURE_HEIGHT) as u8;
+        let b = ((x ^ y).wrapping_mul(2654435761) >> 24) as u8;
+        Rgb([r, g, b])
+    });
+    let source = dir.join("fixture-source.png");
+    DynamicImage::ImageRgb8(image).save(&source).expect("failed to save the fixture");
+
+    FORMATS.into_iter().map(|format| {
+        let path = dir.join(format!("fixture.{}", format));
+        let mut image = librusimg::open_image(&source).expect("failed to open the fixture");
+        if image.get_extension() != format {
+            image.convert(&format).expect("failed to convert the fixture");
+        }
+        image.save_image(path.to_str()).expect("failed to save the fixture");
+        (format, path)
+    }).collect()
+}
+
+fn bench_decode(c: &mut Criterion, fixtures: &[(Extension, PathBuf)]) {
+    let mut group = c.benchmark_group("decode");
+    for (format, path) in fixtures {
+        group.bench_function(format.to_string(), |b| {
+            b.iter(|| librusimg::open_image(path).unwrap())
+        });
+    }
+    group.finish();
+}
+
+fn bench_resize(c: &mut Criterion, fixtures: &[(Extension, PathBuf)]) {
+    let (_, path) = &fixtures[0];
+    c.bench_function("resize/50%", |b| {
+        b.iter_batched(|| librusimg::open_image(path).unwrap(), |mut image| image.resize(50.0).unwrap(), BatchSize::LargeInput)
+    });
+}
+
+fn bench_compress(c: &mut Criterion, fixtures: &[(Extension, PathBuf)]) {
+    let mut group = c.benchmark_group("compress");
+    group.sample_size(10);
+    // BMP cannot be compressed
+    for (format, path) in fixtures.iter().filter(|(f, _)| *f != Extension::Bmp) {
+        group.bench_function(format.to_string(), |b| {
+            b.iter_batched(|| librusimg::open_image(path).unwrap(), |mut image| image.compress(Some(75.0)).unwrap(), BatchSize::LargeInput)
+        });
+    }
+    group.finish();
+}
+
+fn bench_batch(c: &mut Criterion, fixtures: &[(Extension, PathBuf)], dir: &Path) {
+    let (_, path) = &fixtures[0];
+    let output = dir.join("batch-output.webp");
+    let mut group = c.benchmark_group("batch");
+    group.sample_size(10);
+    group.throughput(Throughput::Elements(BATCH_SIZE as u64));
+    // open -> resize -> convert -> compress -> save, as the CLI does for each file
+    group.bench_function("jpeg-to-webp", |b| {
+        b.iter(|| {
+            for _ in 0..BATCH_SIZE {
+                let mut image = librusimg::open_image(path).unwrap();
+                image.resize(50.0).unwrap();
+                image.convert(&Extension::Webp).unwrap();
+                image.compress(Some(75.0)).unwrap();
+                image.save_image(output.to_str()).unwrap();
+            }
+        })
+    });
+    group.finish();
+}
+
+fn benches(c: &mut Criterion) {
+    let dir = fixture_dir();
+    let fixtures = write_fixtures(&dir);
+    bench_decode(c, &fixtures);
+    bench_resize(c, &fixtures);
+    bench_compress(c, &fixtures);
+    bench_batch(c, &fixtures, &dir);
+}
+
+criterion_group!(processing, benches);
+criterion_main!(processing);
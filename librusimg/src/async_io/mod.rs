@@ -0,0 +1,54 @@
+#![cfg(feature = "tokio")]
+
+use std::path::{Path, PathBuf};
+use tokio::task::{self, JoinError};
+
+use super::{RusImg, RusimgError, OpenOptions, SaveOptions, SaveStatus};
+
+/// Get the result of a blocking task.
+/// A panic in the task is resumed in the caller, as if the function had been called directly.
+fn join<T>(result: Result<T, JoinError>, error: fn(String) -> RusimgError) -> Result<T, RusimgError> {
+    match result {
+        Ok(value) => Ok(value),
+        Err(e) if e.is_panic() => std::panic::resume_unwind(e.into_panic()),
+        // ランタイムの終了で中断された場合
+        Err(e) => Err(error(e.to_string())),
+    }
+}
+
+/// Open an image without blocking the executor.
+/// The file is read and decoded on the blocking thread pool of tokio (see open_image()).
+pub async fn open_image_async(path: &Path) -> Result<RusImg, RusimgError> {
+    open_image_with_async(path, &OpenOptions::default()).await
+}
+
+/// Open an image with options without blocking the executor (see open_image_with()).
+pub async fn open_image_with_async(path: &Path, options: &OpenOptions) -> Result<RusImg, RusimgError> {
+    let path = path.to_path_buf();
+    let options = options.clone();
+    join(task::spawn_blocking(move || super::open_image_with(&path, &options)).await, RusimgError::FailedToOpenImage)?
+}
+
+impl RusImg {
+    /// Save the image without blocking the executor (see save_image()).
+    /// The image is encoded and written on the blocking thread pool of tokio, so the task takes the image and gives it back with the status.
+    pub async fn save_image_async(self, path: Option<&Path>) -> Result<(RusImg, SaveStatus), RusimgError> {
+        self.save_image_with_async(path, &SaveOptions::default()).await
+    }
+
+    /// Save the image with options without blocking the executor (see save_image_with()).
+    /// If saving fails, the image is dropped with the error.
+    pub async fn save_image_with_async(mut self, path: Option<&Path>, options: &SaveOptions) -> Result<(RusImg, SaveStatus), RusimgError> {
+        let path = path.map(PathBuf::from);
+        let options = options.clone();
+        join(task::spawn_blocking(move || -> Result<(RusImg, SaveStatus), RusimgError> {
+            // save_image_with() takes the path as &str
+            let path = match &path {
+                Some(path) => Some(path.to_str().ok_or(RusimgError::FailedToConvertPathToString)?),
+                None => None,
+            };
+            let status = self.save_image_with(path, &options)?;
+            Ok((self, status))
+        }).await, RusimgError::FailedToSaveImage)?
+    }
+}
@@ -0,0 +1,238 @@
+use image::DynamicImage;
+
+use std::collections::HashMap;
+
+use super::super::options::BmpFormat;
+use super::super::quantize;
+
+/// Size of BITMAPFILEHEADER.
+const FILE_HEADER_SIZE: u32 = 14;
+/// Size of BITMAPINFOHEADER.
+const INFO_HEADER_SIZE: u32 = 40;
+/// Size of BITMAPV4HEADER. 32-bit images use it to declare the alpha mask, otherwise most readers ignore the alpha channel.
+const V4_HEADER_SIZE: u32 = 108;
+
+const BI_RGB: u32 = 0;
+const BI_RLE8: u32 = 1;
+const BI_BITFIELDS: u32 = 3;
+
+/// Pixel rows of a BMP file are padded to a multiple of 4 bytes.
+fn row_stride(width: u32, bits_per_pixel: u16) -> usize {
+    (width as usize * bits_per_pixel as usize).div_ceil(32) * 4
+}
+
+/// Indexed pixels and the palette (up to 256 colors).
+struct Indexed {
+    palette: Vec<[u8; 3]>,
+    indices: Vec<u8>,
+}
+
+/// Build the exact palette of the image.
+/// Returns None if the image has more than max_colors colors.
+fn exact_palette(image: &DynamicImage, max_colors: usize) -> Option<Indexed> {
+    let rgb = image.to_rgb8();
+    let mut palette = Vec::new();
+    let mut lookup = HashMap::new();
+    let mut indices = Vec::with_capacity(rgb.width() as usize * rgb.height() as usize);
+    for pixel in rgb.pixels() {
+        let index = match lookup.get(&pixel.0) {
+            Some(index) => *index,
+            None => {
+                if palette.len() == max_colors {
+                    return None;
+                }
+                palette.push(pixel.0);
+                lookup.insert(pixel.0, (palette.len() - 1) as u8);
+                (palette.len() - 1) as u8
+            },
+        };
+        indices.push(index);
+    }
+    Some(Indexed { palette, indices })
+}
+
+/// Get the indexed pixels with up to max_colors colors.
+/// If the image has more colors, the palette is reduced with the quantize module and each pixel is mapped to the nearest color.
+fn reduced_palette(image: &DynamicImage, max_colors: usize) -> Indexed {
+    if let Some(indexed) = exact_palette(image, max_colors) {
+        return indexed;
+    }
+    let palette = quantize::palette(image, max_colors);
+    let indices = image.to_rgb8().pixels().map(|p| quantize::nearest(&palette, p.0) as u8).collect();
+    Indexed { palette, indices }
+}
+
+/// Write BITMAPFILEHEADER and BITMAPINFOHEADER (or BITMAPV4HEADER).
+fn write_headers(buf: &mut Vec<u8>, width: u32, height: u32, bits_per_pixel: u16, compression: u32, palette: &[[u8; 3]], data_size: u32) {
+    let info_size = if compression == BI_BITFIELDS { V4_HEADER_SIZE } else { INFO_HEADER_SIZE };
+    let offset = FILE_HEADER_SIZE + info_size + palette.len() as u32 * 4;
+
+    // BITMAPFILEHEADER
+    buf.extend_from_slice(b"BM");
+    buf.extend_from_slice(&(offset + data_size).to_le_bytes());
+    buf.extend_from_slice(&[0; 4]);
+    buf.extend_from_slice(&offset.to_le_bytes());
+
+    // BITMAPINFOHEADER
+    buf.extend_from_slice(&info_size.to_le_bytes());
+    buf.extend_from_slice(&(width as i32).to_le_bytes());
+    buf.extend_from_slice(&(height as i32).to_le_bytes());
+    buf.extend_from_slice(&1u16.to_le_bytes());
+    buf.extend_from_slice(&bits_per_pixel.to_le_bytes());
+    buf.extend_from_slice(&compression.to_le_bytes());
+    buf.extend_from_slice(&data_size.to_le_bytes());
+    buf.extend_from_slice(&2835i32.to_le_bytes());     // 72 dpi
+    buf.extend_from_slice(&2835i32.to_le_bytes());
+    buf.extend_from_slice(&(palette.len() as u32).to_le_bytes());
+    buf.extend_from_slice(&0u32.to_le_bytes());
+
+    if compression == BI_BITFIELDS {
+        // BITMAPV4HEADER: R, G, B, A masks, color space ("Win "), endpoints and gamma (unused)
+        for mask in [0x00ff0000u32, 0x0000ff00, 0x000000ff, 0xff000000] {
+            buf.extend_from_slice(&mask.to_le_bytes());
+        }
+        buf.extend_from_slice(b" niW");
+        buf.extend_from_slice(&[0; 48]);
+    }
+
+    // パレットは BGRA の順
+    for color in palette {
+        buf.extend_from_slice(&[color[2], color[1], color[0], 0]);
+    }
+}
+
+/// Write the rows bottom-up, with padding.
+fn write_rows(buf: &mut Vec<u8>, height: u32, stride: usize, row: impl Fn(u32, &mut [u8])) {
+    let mut line = vec![0u8; stride];
+    for y in (0..height).rev() {
+        line.fill(0);
+        row(y, &mut line);
+        buf.extend_from_slice(&line);
+    }
+}
+
+/// Compress one row of indices with RLE8.
+/// Runs of the same index are written in encoded mode (count, index), and other pixels in absolute mode (0, count, indices...).
+fn rle8_row(row: &[u8], out: &mut Vec<u8>) {
+    let mut i = 0;
+    while i < row.len() {
+        let run = row[i..].iter().take(255).take_while(|v| **v == row[i]).count();
+        if run >= 2 {
+            out.extend_from_slice(&[run as u8, row[i]]);
+            i += run;
+            continue;
+        }
+
+        // 次の連続が始まるまでをリテラルとしてまとめる
+        let mut j = i;
+        while j < row.len() && j - i < 255 && !(j + 1 < row.len() && row[j] == row[j + 1]) {
+            j += 1;
+        }
+        let literal = &row[i..j];
+        if literal.len() < 3 {
+            // 絶対モードは 3 画素以上のみ
+            for v in literal {
+                out.extend_from_slice(&[1, *v]);
+            }
+        }
+        else {
+            out.extend_from_slice(&[0, literal.len() as u8]);
+            out.extend_from_slice(literal);
+            if literal.len() % 2 == 1 {
+                out.push(0);
+            }
+        }
+        i = j;
+    }
+}
+
+/// Write 8-bit paletted pixels, optionally compressed with RLE8.
+fn write_indexed(buf: &mut Vec<u8>, width: u32, height: u32, indexed: &Indexed, rle: bool) {
+    if rle {
+        let mut data = Vec::new();
+        for y in (0..height).rev() {
+            let start = (y * width) as usize;
+            rle8_row(&indexed.indices[start..start + width as usize], &mut data);
+            // 最終行のみ end of bitmap
+            data.extend_from_slice(if y == 0 { &[0, 1] } else { &[0, 0] });
+        }
+        write_headers(buf, width, height, 8, BI_RLE8, &indexed.palette, data.len() as u32);
+        buf.extend_from_slice(&data);
+    }
+    else {
+        let stride = row_stride(width, 8);
+        write_headers(buf, width, height, 8, BI_RGB, &indexed.palette, (stride * height as usize) as u32);
+        write_rows(buf, height, stride, |y, line| {
+            let start = (y * width) as usize;
+            line[..width as usize].copy_from_slice(&indexed.indices[start..start + width as usize]);
+        });
+    }
+}
+
+/// Encode the image as a BMP file into buf.
+/// If rle is true, 8-bit paletted images are compressed with RLE8.
+/// A 24-bit image with up to 256 colors is also written as a RLE8 paletted image, because it is lossless and much smaller.
+/// RLE is not available for 32-bit, 4-bit and 1-bit images, so it is ignored for them.
+pub fn encode(image: &DynamicImage, format: BmpFormat, rle: bool, buf: &mut Vec<u8>) {
+    let (width, height) = (image.width(), image.height());
+    if rle && format == BmpFormat::Rgb24 {
+        if let Some(indexed) = exact_palette(image, 256) {
+            write_indexed(buf, width, height, &indexed, true);
+            return;
+        }
+    }
+
+    match format {
+        BmpFormat::Rgb24 => {
+            let rgb = image.to_rgb8();
+            let stride = row_stride(width, 24);
+            write_headers(buf, width, height, 24, BI_RGB, &[], (stride * height as usize) as u32);
+            write_rows(buf, height, stride, |y, line| {
+                for (x, out) in line.chunks_exact_mut(3).take(width as usize).enumerate() {
+                    let p = rgb.get_pixel(x as u32, y).0;
+                    out.copy_from_slice(&[p[2], p[1], p[0]]);
+                }
+            });
+        },
+        BmpFormat::Bgra32 => {
+            let rgba = image.to_rgba8();
+            let stride = row_stride(width, 32);
+            write_headers(buf, width, height, 32, BI_BITFIELDS, &[], (stride * height as usize) as u32);
+            write_rows(buf, height, stride, |y, line| {
+                for (x, out) in line.chunks_exact_mut(4).enumerate() {
+                    let p = rgba.get_pixel(x as u32, y).0;
+                    out.copy_from_slice(&[p[2], p[1], p[0], p[3]]);
+                }
+            });
+        },
+        BmpFormat::Indexed8 => {
+            let indexed = reduced_palette(image, 256);
+            write_indexed(buf, width, height, &indexed, rle);
+        },
+        BmpFormat::Indexed4 => {
+            let indexed = reduced_palette(image, 16);
+            let stride = row_stride(width, 4);
+            write_headers(buf, width, height, 4, BI_RGB, &indexed.palette, (stride * height as usize) as u32);
+            write_rows(buf, height, stride, |y, line| {
+                let start = (y * width) as usize;
+                for (x, index) in indexed.indices[start..start + width as usize].iter().enumerate() {
+                    // 上位 4 ビットが左の画素
+                    line[x / 2] |= if x % 2 == 0 { index << 4 } else { *index };
+                }
+            });
+        },
+        BmpFormat::Mono1 => {
+            // 輝度 50% で二値化
+            let luma = image.to_luma8();
+            let stride = row_stride(width, 1);
+            write_headers(buf, width, height, 1, BI_RGB, &[[0, 0, 0], [255, 255, 255]], (stride * height as usize) as u32);
+            write_rows(buf, height, stride, |y, line| {
+                for x in 0..width {
+                    if luma.get_pixel(x, y).0[0] >= 128 {
+                        line[(x / 8) as usize] |= 0x80 >> (x % 8);
+                    }
+                }
+            });
+        },
+    }
+}
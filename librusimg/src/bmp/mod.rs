@@ -0,0 +1,269 @@
+use image::DynamicImage;
+
+use std::fs::Metadata;
+use std::time::{Duration, Instant};
+use std::sync::Arc;
+use std::path::PathBuf;
+
+use super::{ImgSize, RusimgError, RusimgTrait, Rect, Extension};
+use super::rect::TrimPolicy;
+use super::history::History;
+use super::options::{SaveOptions, MetadataPolicy};
+use super::source::SourceData;
+use super::pool;
+use super::warning::{self, Warning};
+
+mod encoder;
+
+#[derive(Debug, Clone)]
+pub struct BmpImage {
+    pub image: Arc<DynamicImage>,
+    size: ImgSize,
+    pub metadata_input: Metadata,
+    pub metadata_output: Option<Metadata>,
+    pub filepath_input: PathBuf,
+    pub filepath_output: Option<PathBuf>,
+    operations: Vec<String>,
+    bytes_written: Option<u64>,
+    encode_duration: Option<Duration>,
+    warnings: Vec<Warning>,
+    rle: bool,
+    history: History<Self>,
+}
+
+impl BmpImage {
+    /// Record the current state so that the next operation can be undone.
+    fn record(&mut self) {
+        if !self.history.is_enabled() {
+            return;
+        }
+        let snapshot = self.clone();
+        self.history.push(snapshot);
+    }
+}
+
+impl RusimgTrait for BmpImage {
+    /// Import an image from a DynamicImage object.
+    fn import(image: DynamicImage, source_path: PathBuf, source_metadata: Metadata) -> Result<Self, RusimgError> {
+        let size = ImgSize { width: image.width() as usize, height: image.height() as usize };
+
+        Ok(Self {
+            image: Arc::new(image),
+            size,
+            metadata_input: source_metadata,
+            metadata_output: None,
+            filepath_input: source_path,
+            filepath_output: None,
+            operations: Vec::new(),
+            bytes_written: None,
+            encode_duration: None,
+            warnings: Vec::new(),
+            rle: false,
+            history: History::default(),
+        })
+    }
+
+    /// Open an image from a image buffer.
+    /// The buffer is only used for decoding, so a memory-mapped file is decoded without copying.
+    fn open(path: PathBuf, image_buf: SourceData, metadata: Metadata) -> Result<Self, RusimgError> {
+        let image = image::load_from_memory(&image_buf).map_err(|e| RusimgError::FailedToOpenImage(e.to_string()))?;
+        let size = ImgSize { width: image.width() as usize, height: image.height() as usize };
+        let warnings = warning::check_format(&path, &image_buf).into_iter().collect();
+
+        Ok(Self {
+            image: Arc::new(image),
+            size,
+            metadata_input: metadata,
+            metadata_output: None,
+            filepath_input: path,
+            filepath_output: None,
+            operations: Vec::new(),
+            bytes_written: None,
+            encode_duration: None,
+            warnings: warnings,
+            rle: false,
+            history: History::default(),
+        })
+    }
+
+    /// Save the image to a file.
+    /// The pixel format is selected with options.bmp.format (default: 24-bit RGB).
+    /// The image is compressed with RLE8 if options.bmp.rle is true or compress() has been called.
+    /// BMP has no metadata, so MetadataPolicy::Keep adds a warning.
+    fn save(&mut self, path: Option<PathBuf>, options: &SaveOptions) -> Result<(), RusimgError> {
+        let start = Instant::now();
+        let save_path = Self::get_save_filepath(self, &self.filepath_input, path, &Extension::Bmp.extension_for(&self.filepath_input))?;
+        options.check_overwrite(&save_path)?;
+
+        if options.metadata == MetadataPolicy::Keep {
+            warning::push(&mut self.warnings, Warning::MetadataDropped("metadata".to_string()));
+        }
+
+        let mut buf = pool::global().take(self.size.width * self.size.height * 4);
+        encoder::encode(&self.image, options.bmp.format, options.bmp.rle || self.rle, &mut buf);
+        let result = std::fs::write(&save_path, &buf).map_err(|e| RusimgError::FailedToSaveImage(e.to_string()));
+        pool::global().give(buf);
+        result?;
+
+        self.metadata_output = Some(std::fs::metadata(&save_path).map_err(|e| RusimgError::FailedToGetMetadata(e.to_string()))?);
+        self.bytes_written = self.metadata_output.as_ref().map(|m| m.len());
+        self.encode_duration = Some(start.elapsed());
+        self.filepath_output = Some(save_path);
+
+        Ok(())
+    }
+
+    /// Compress the image with RLE8 when it is saved.
+    /// RLE8 is lossless, so the quality is ignored. Only images with up to 256 colors (or saved as 8-bit paletted) get smaller;
+    /// other images are saved uncompressed.
+    fn compress(&mut self, _quality: Option<f32>) -> Result<(), RusimgError> {
+        self.record();
+        self.rle = true;
+        self.operations.push("compress (rle8)".to_string());
+        Ok(())
+    }
+
+    /// Resize the image.
+    /// resize_ratio: f32 in percent (must be 0.0 < resize_ratio, e.g. 12.5)
+    fn resize(&mut self, resize_ratio: f32) -> Result<ImgSize, RusimgError> {
+        self.record();
+        let nwidth = (self.size.width as f32 * (resize_ratio / 100.0)) as usize;
+        let nheight = (self.size.height as f32 * (resize_ratio / 100.0)) as usize;
+        
+        self.image = Arc::new(self.image.resize(nwidth as u32, nheight as u32, image::imageops::FilterType::Lanczos3));
+
+        self.size.width = nwidth;
+        self.size.height = nheight;
+        self.operations.push(format!("resize {}%", resize_ratio));
+
+        Ok(self.size)
+    }
+
+    /// Trim the image.
+    /// Set the trim area with the librusimg::Rect structure.
+    /// The area is clamped to the image size with Rect::clamp_to().
+    fn trim(&mut self, trim: Rect) -> Result<ImgSize, RusimgError> {
+        let requested = trim;
+        let trim = requested.clamp_to(self.get_size(), TrimPolicy::Clamp)?;
+        self.record();
+        if let Some(clamped) = warning::check_trim(&requested, &trim) {
+            warning::push(&mut self.warnings, clamped);
+        }
+
+        self.image = Arc::new(self.image.crop_imm(trim.x, trim.y, trim.w, trim.h));
+
+        self.size.width = trim.w as usize;
+        self.size.height = trim.h as usize;
+        self.operations.push(format!("trim {}x{}+{}x{}", trim.x, trim.y, trim.w, trim.h));
+
+        Ok(self.size)
+    }
+
+    /// Convert the image to grayscale.
+    fn grayscale(&mut self) {
+        self.record();
+        self.image = Arc::new(self.image.grayscale());
+        self.operations.push("grayscale".to_string());
+    }
+
+    /// Set the image to a DynamicImage object.
+    /// The image size is updated to the size of the new image.
+    fn set_dynamic_image(&mut self, image: DynamicImage) -> Result<(), RusimgError> {
+        self.record();
+        self.image = Arc::new(image);
+        self.size = ImgSize { width: self.image.width() as usize, height: self.image.height() as usize };
+        Ok(())
+    }
+    
+    /// Get the DynamicImage object.
+    /// This copies the whole image; use as_dynamic_image() to avoid the copy.
+    fn get_dynamic_image(&mut self) -> Result<DynamicImage, RusimgError> {
+        Ok(self.to_owned_dynamic_image())
+    }
+
+    /// Borrow the DynamicImage object without copying.
+    fn as_dynamic_image(&self) -> &DynamicImage {
+        &self.image
+    }
+
+    /// Get a copy of the DynamicImage object.
+    fn to_owned_dynamic_image(&self) -> DynamicImage {
+        self.image.as_ref().clone()
+    }
+
+    /// Clone the image into a new trait object.
+    /// The pixel data is shared with the original until either of them is modified (copy-on-write), so this is cheap.
+    fn box_clone(&self) -> Box<dyn RusimgTrait + Send + Sync> {
+        Box::new(self.clone())
+    }
+
+    /// Set the maximum number of operations that can be undone (0: disabled, default).
+    fn set_history_limit(&mut self, limit: usize) {
+        self.history.set_limit(limit);
+    }
+
+    /// Undo the last operation (resize, trim, grayscale, compress or set_dynamic_image).
+    /// Returns false if there is nothing to undo.
+    fn undo(&mut self) -> bool {
+        match self.history.pop() {
+            Some(previous) => {
+                let history = std::mem::take(&mut self.history);
+                *self = previous;
+                self.history = history;
+                true
+            },
+            None => false,
+        }
+    }
+
+    /// Get the source file path.
+    fn get_source_filepath(&self) -> PathBuf {
+        self.filepath_input.clone()
+    }
+
+    /// Get the destination file path.
+    fn get_destination_filepath(&self) -> Option<PathBuf> {
+        self.filepath_output.clone()
+    }
+
+    /// Get the source metadata.
+    fn get_metadata_src(&self) -> Metadata {
+        self.metadata_input.clone()
+    }
+
+    /// Get the destination metadata.
+    fn get_metadata_dest(&self) -> Option<Metadata> {
+        self.metadata_output.clone()
+    }
+
+    /// Get the image size.
+    fn get_size(&self) -> ImgSize {
+        self.size
+    }
+
+    /// Get the list of applied operations.
+    fn get_operations(&self) -> Vec<String> {
+        self.operations.clone()
+    }
+
+    /// Get the number of bytes written by the last save.
+    fn get_bytes_written(&self) -> Option<u64> {
+        self.bytes_written
+    }
+
+    /// Get the time taken to encode and write the image by the last save.
+    fn get_encode_duration(&self) -> Option<Duration> {
+        self.encode_duration
+    }
+
+    /// Get the warnings found while processing the image.
+    fn get_warnings(&self) -> Vec<Warning> {
+        self.warnings.clone()
+    }
+}
+
+// RusImg holds the backend as Box<dyn RusimgTrait + Send + Sync>, so BmpImage must stay Send + Sync.
+const _: fn() = || {
+    fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<BmpImage>();
+};
@@ -0,0 +1,169 @@
+use std::fmt;
+use std::path::Path;
+use image::{DynamicImage, ImageDecoder, ImageReader, Rgba32FImage};
+
+use super::{RusImg, RusimgError};
+use super::filters::with_color_type;
+
+/// Linear Display P3 -> linear sRGB (D65).
+const P3_TO_SRGB: [[f32; 3]; 3] = [
+    [1.224_940_2, -0.224_940_4, 0.0],
+    [-0.042_056_9, 1.042_057_1, 0.0],
+    [-0.019_637_6, -0.078_636_1, 1.098_273_5],
+];
+/// Linear sRGB -> linear Display P3 (D65).
+const SRGB_TO_P3: [[f32; 3]; 3] = [
+    [0.822_462_1, 0.177_538, 0.0],
+    [0.033_194_1, 0.966_805_8, 0.0],
+    [0.017_082_7, 0.072_397_4, 0.910_519_9],
+];
+
+/// ColorSpace is the color space of the pixel values of an image.
+/// - Srgb: sRGB. Images without an ICC profile are assumed to be sRGB, as on the web.
+/// - DisplayP3: Display P3, the wide gamut of recent phones and displays. It has the transfer function of sRGB.
+/// - LinearSrgb: The primaries of sRGB with linear light values (no transfer function).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ColorSpace {
+    #[default]
+    Srgb,
+    DisplayP3,
+    LinearSrgb,
+}
+
+impl fmt::Display for ColorSpace {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ColorSpace::Srgb => write!(f, "sRGB"),
+            ColorSpace::DisplayP3 => write!(f, "Display P3"),
+            ColorSpace::LinearSrgb => write!(f, "linear sRGB"),
+        }
+    }
+}
+
+impl ColorSpace {
+    /// Detect the color space of an ICC profile from its description ('desc' tag), e.g. "Display P3" or "sRGB IEC61966-2.1".
+    /// Returns None if the profile is broken or of another color space (e.g. Adobe RGB).
+    pub fn from_icc(icc: &[u8]) -> Option<ColorSpace> {
+        let description = icc_description(icc)?.to_lowercase();
+        if description.contains("p3") {
+            Some(ColorSpace::DisplayP3)
+        }
+        else if description.contains("srgb") {
+            Some(ColorSpace::Srgb)
+        }
+        else {
+            None
+        }
+    }
+
+    /// Detect the color space of an image file from its embedded ICC profile.
+    /// Files without a profile are sRGB. Returns None if the profile is of an unsupported color space.
+    pub fn of_file(path: &Path) -> Result<Option<ColorSpace>, RusimgError> {
+        match read_icc(path)? {
+            Some(icc) => Ok(ColorSpace::from_icc(&icc)),
+            None => Ok(Some(ColorSpace::Srgb)),
+        }
+    }
+
+    /// Convert a pixel (R, G, B in 0.0-1.0) of this color space to linear sRGB.
+    fn to_linear_srgb(self, rgb: [f32; 3]) -> [f32; 3] {
+        match self {
+            ColorSpace::Srgb => rgb.map(srgb_to_linear),
+            ColorSpace::DisplayP3 => multiply(&P3_TO_SRGB, rgb.map(srgb_to_linear)),
+            ColorSpace::LinearSrgb => rgb,
+        }
+    }
+
+    /// Convert a pixel of linear sRGB to this color space. Colors outside of the gamut are clipped.
+    fn encode_linear_srgb(self, rgb: [f32; 3]) -> [f32; 3] {
+        let rgb = match self {
+            ColorSpace::DisplayP3 => multiply(&SRGB_TO_P3, rgb),
+            _ => rgb,
+        };
+        let rgb = rgb.map(|c| c.clamp(0.0, 1.0));
+        match self {
+            ColorSpace::LinearSrgb => rgb,
+            _ => rgb.map(linear_to_srgb),
+        }
+    }
+}
+
+/// Read the embedded ICC profile of an image file. Returns None if the file has no profile.
+pub fn read_icc(path: &Path) -> Result<Option<Vec<u8>>, RusimgError> {
+    let reader = ImageReader::open(path).map_err(|e| RusimgError::FailedToOpenFile(e.to_string()))?
+        .with_guessed_format().map_err(|e| RusimgError::FailedToReadFile(e.to_string()))?;
+    let mut decoder = reader.into_decoder().map_err(|e| RusimgError::FailedToOpenImage(e.to_string()))?;
+    decoder.icc_profile().map_err(|e| RusimgError::FailedToOpenImage(e.to_string()))
+}
+
+/// Get the description of an ICC profile: the ASCII text of a 'desc' tag (ICC v2), or the first record of a 'mluc' tag (ICC v4).
+fn icc_description(icc: &[u8]) -> Option<String> {
+    let u32_at = |offset: usize| icc.get(offset..offset + 4).map(|b| u32::from_be_bytes([b[0], b[1], b[2], b[3]]) as usize);
+    let tag_count = u32_at(128)?;
+    let (offset, size) = (0..tag_count).find_map(|i| {
+        let entry = 132 + i * 12;
+        match icc.get(entry..entry + 4) {
+            Some(b"desc") => Some((u32_at(entry + 4)?, u32_at(entry + 8)?)),
+            _ => None,
+        }
+    })?;
+    let tag = icc.get(offset..offset.checked_add(size)?)?;
+
+    match tag.get(0..4)? {
+        b"desc" => {
+            let len = u32_at(offset + 8)?;
+            let text = tag.get(12..12 + len)?;
+            Some(String::from_utf8_lossy(text).trim_end_matches('\0').to_string())
+        },
+        b"mluc" => {
+            let (len, start) = (u32_at(offset + 20)?, u32_at(offset + 24)?);
+            let text = tag.get(start..start + len)?;
+            let units = text.chunks_exact(2).map(|c| u16::from_be_bytes([c[0], c[1]])).collect::<Vec<_>>();
+            Some(String::from_utf16_lossy(&units))
+        },
+        _ => None,
+    }
+}
+
+/// sRGB transfer function: encoded value -> linear light.
+pub(crate) fn srgb_to_linear(c: f32) -> f32 {
+    if c <= 0.04045 { c / 12.92 } else { ((c + 0.055) / 1.055).powf(2.4) }
+}
+
+/// Inverse of srgb_to_linear(): linear light -> encoded value.
+pub(crate) fn linear_to_srgb(c: f32) -> f32 {
+    if c <= 0.003_130_8 { c * 12.92 } else { 1.055 * c.powf(1.0 / 2.4) - 0.055 }
+}
+
+/// Multiply an RGB vector by a 3x3 matrix.
+fn multiply(matrix: &[[f32; 3]; 3], rgb: [f32; 3]) -> [f32; 3] {
+    matrix.map(|row| row[0] * rgb[0] + row[1] * rgb[1] + row[2] * rgb[2])
+}
+
+/// Convert the pixel values of an image from one color space to another.
+/// The conversion is done in 32-bit float, and the color type of the image (e.g. 8-bit RGB, 16-bit RGBA) is kept. Alpha is not changed.
+pub fn convert_colorspace(image: &DynamicImage, from: ColorSpace, to: ColorSpace) -> DynamicImage {
+    if from == to {
+        return image.clone();
+    }
+    let mut pixels: Rgba32FImage = image.to_rgba32f();
+    for pixel in pixels.pixels_mut() {
+        let [r, g, b, a] = pixel.0;
+        let [r, g, b] = to.encode_linear_srgb(from.to_linear_srgb([r, g, b]));
+        pixel.0 = [r, g, b, a];
+    }
+
+    with_color_type(DynamicImage::ImageRgba32F(pixels), image.color())
+}
+
+impl RusImg {
+    /// Convert the pixel values of the image from one color space to another (e.g. DisplayP3 -> Srgb).
+    /// Outputs carry no ICC profile, so convert wide-gamut images to sRGB before saving them for the web.
+    pub fn convert_colorspace(&mut self, from: ColorSpace, to: ColorSpace) -> Result<(), RusimgError> {
+        if from == to {
+            return Ok(());
+        }
+        let converted = convert_colorspace(self.as_dynamic_image(), from, to);
+        self.set_dynamic_image(converted)
+    }
+}
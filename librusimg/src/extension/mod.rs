@@ -0,0 +1,96 @@
+use std::path::Path;
+
+use super::{Extension, RusimgError};
+
+/// File extensions of each format (lowercase). The first one is the extension used for new files.
+const BMP_EXTENSIONS: [&str; 2] = ["bmp", "dib"];
+const JPEG_EXTENSIONS: [&str; 4] = ["jpeg", "jpg", "jpe", "jfif"];
+const PNG_EXTENSIONS: [&str; 1] = ["png"];
+const WEBP_EXTENSIONS: [&str; 1] = ["webp"];
+
+impl Extension {
+    /// Get the format of a file extension or a format name (e.g. "jpg", "JPEG", ".jfif", "dib").
+    /// The leading dot is ignored, and the extension is matched case-insensitively.
+    pub fn from_extension(extension: &str) -> Result<Extension, RusimgError> {
+        let extension = extension.trim_start_matches('.').to_ascii_lowercase();
+        Self::builtin().into_iter()
+            .find(|format| format.aliases().contains(&extension.as_str()))
+            .ok_or(RusimgError::UnsupportedFileExtension)
+    }
+
+    /// Get the format of a file from its extension.
+    /// Only the extension needs to be UTF-8; the rest of the path may contain any characters.
+    pub fn from_path(path: &Path) -> Result<Extension, RusimgError> {
+        let extension = path.extension().and_then(|s| s.to_str()).ok_or(RusimgError::UnsupportedFileExtension)?;
+        Self::from_extension(extension)
+    }
+
+    /// File extensions accepted for the format (lowercase). ExternalFormat accepts none.
+    pub fn aliases(&self) -> &'static [&'static str] {
+        match self {
+            Extension::Bmp => &BMP_EXTENSIONS,
+            Extension::Jpeg => &JPEG_EXTENSIONS,
+            Extension::Png => &PNG_EXTENSIONS,
+            Extension::Webp => &WEBP_EXTENSIONS,
+            Extension::ExternalFormat(_) => &[],
+        }
+    }
+
+    /// The extension used for new files of the format (e.g. converted images). It is the same as the Display of the format.
+    /// Files opened from a source keep the extension of the source when they are saved without a path.
+    pub fn preferred_extension(&self) -> String {
+        match self.aliases().first() {
+            Some(extension) => extension.to_string(),
+            None => self.to_string(),
+        }
+    }
+
+    /// Get the extension of a file of the format saved in place of the source.
+    /// The extension of the source is kept exactly as written (e.g. Photo.JPG, photo.jpe) if it is an extension of the format, so that overwriting does not rename the file.
+    /// Otherwise (e.g. a converted image), preferred_extension() is used.
+    pub fn extension_for(&self, source: &Path) -> String {
+        match source.extension().and_then(|s| s.to_str()) {
+            Some(extension) if Self::from_extension(extension).map(|e| e == *self).unwrap_or(false) => extension.to_string(),
+            _ => self.preferred_extension(),
+        }
+    }
+
+    /// Name of the library that decodes the format in this build (e.g. "image", "webp").
+    /// ExternalFormat and the formats disabled by the features have none.
+    pub fn decoder(&self) -> Option<&'static str> {
+        match self {
+            #[cfg(feature = "bmp")]
+            Extension::Bmp => Some("image"),
+            #[cfg(any(feature = "jpeg", feature = "jpeg-pure"))]
+            Extension::Jpeg => Some("image"),
+            #[cfg(feature = "png")]
+            Extension::Png => Some("image"),
+            #[cfg(any(feature = "webp", feature = "webp-pure"))]
+            Extension::Webp => Some(super::webp::CODEC),
+            #[allow(unreachable_patterns)]
+            _ => None,
+        }
+    }
+
+    /// Name of the library that encodes the format in this build (e.g. "mozjpeg", or "image" with the jpeg-pure feature).
+    /// PNG outputs are optimized by oxipng when they are compressed. ExternalFormat and the formats disabled by the features have none.
+    pub fn encoder(&self) -> Option<&'static str> {
+        match self {
+            #[cfg(feature = "bmp")]
+            Extension::Bmp => Some("librusimg"),
+            #[cfg(any(feature = "jpeg", feature = "jpeg-pure"))]
+            Extension::Jpeg => Some(super::jpeg::ENCODER),
+            #[cfg(feature = "png")]
+            Extension::Png => Some("image"),
+            #[cfg(any(feature = "webp", feature = "webp-pure"))]
+            Extension::Webp => Some(super::webp::CODEC),
+            #[allow(unreachable_patterns)]
+            _ => None,
+        }
+    }
+
+    /// The formats built into the library.
+    pub fn builtin() -> [Extension; 4] {
+        [Extension::Bmp, Extension::Jpeg, Extension::Png, Extension::Webp]
+    }
+}
@@ -0,0 +1,412 @@
+use std::fs;
+use std::path::Path;
+use image::{ColorType, DynamicImage, GrayImage, Luma};
+
+use super::{RusImg, RusimgError};
+
+/// Lut3d is a 3D color lookup table, e.g. a color grade exported as a .cube file.
+/// - size: Number of the entries on each axis (2 or more).
+/// - table: Output colors (R, G, B) of size^3 entries. Red changes fastest, then green, then blue (the order of .cube files).
+/// - domain_min, domain_max: Input range of each channel (default: 0.0 - 1.0).
+#[derive(Debug, Clone, PartialEq)]
+pub struct Lut3d {
+    pub size: usize,
+    pub table: Vec<[f32; 3]>,
+    pub domain_min: [f32; 3],
+    pub domain_max: [f32; 3],
+}
+
+/// Parse three numbers of a line of a .cube file.
+fn parse_triple(values: &[&str]) -> Option<[f32; 3]> {
+    match values {
+        [r, g, b] => Some([r.parse().ok()?, g.parse().ok()?, b.parse().ok()?]),
+        _ => None,
+    }
+}
+
+impl Lut3d {
+    /// Parse the text of a .cube file (Adobe/Resolve format).
+    /// The keywords TITLE, LUT_3D_SIZE, DOMAIN_MIN and DOMAIN_MAX are read, and lines starting with '#' are comments.
+    /// 1D LUTs (LUT_1D_SIZE) are not supported.
+    pub fn parse(text: &str) -> Result<Lut3d, String> {
+        let mut size = None;
+        let mut table = Vec::new();
+        let (mut domain_min, mut domain_max) = ([0.0; 3], [1.0; 3]);
+
+        for (number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let words = line.split_whitespace().collect::<Vec<_>>();
+            let error = |message: &str| format!("line {}: {}", number + 1, message);
+            match words[0] {
+                "TITLE" => {},
+                "LUT_1D_SIZE" => return Err(error("1D LUTs are not supported")),
+                "LUT_3D_SIZE" => {
+                    let n = words.get(1).and_then(|n| n.parse::<usize>().ok()).filter(|n| (2..=256).contains(n));
+                    size = Some(n.ok_or_else(|| error("LUT_3D_SIZE must be 2-256"))?);
+                },
+                "DOMAIN_MIN" => domain_min = parse_triple(&words[1..]).ok_or_else(|| error("DOMAIN_MIN must be three numbers"))?,
+                "DOMAIN_MAX" => domain_max = parse_triple(&words[1..]).ok_or_else(|| error("DOMAIN_MAX must be three numbers"))?,
+                // 未知のキーワードは無視する
+                keyword if keyword.starts_with(|c: char| c.is_ascii_alphabetic()) => {},
+                _ => table.push(parse_triple(&words).ok_or_else(|| error("an entry must be three numbers"))?),
+            }
+        }
+
+        let size = size.ok_or("LUT_3D_SIZE is missing")?;
+        if table.len() != size * size * size {
+            return Err(format!("{} entries for LUT_3D_SIZE {} (expected {})", table.len(), size, size * size * size));
+        }
+        if (0..3).any(|c| domain_max[c] <= domain_min[c]) {
+            return Err("DOMAIN_MAX must be larger than DOMAIN_MIN".to_string());
+        }
+        Ok(Lut3d { size: size, table: table, domain_min: domain_min, domain_max: domain_max })
+    }
+
+    /// Load a .cube file.
+    pub fn load(path: &Path) -> Result<Lut3d, RusimgError> {
+        let text = fs::read_to_string(path).map_err(|e| RusimgError::FailedToReadFile(format!("{}: {}", path.display(), e)))?;
+        Lut3d::parse(&text).map_err(|e| RusimgError::FailedToReadFile(format!("{}: {}", path.display(), e)))
+    }
+
+    /// Look up a color with trilinear interpolation. Inputs outside of the domain are clamped to it.
+    pub fn lookup(&self, rgb: [f32; 3]) -> [f32; 3] {
+        let max = (self.size - 1) as f32;
+        // 各チャンネルの格子上の位置 (下側の添字と補間係数)
+        let position = |c: usize| {
+            let t = ((rgb[c] - self.domain_min[c]) / (self.domain_max[c] - self.domain_min[c])).clamp(0.0, 1.0) * max;
+            let i = (t.floor() as usize).min(self.size - 2);
+            (i, t - i as f32)
+        };
+        let ((r, fr), (g, fg), (b, fb)) = (position(0), position(1), position(2));
+        let entry = |r: usize, g: usize, b: usize| self.table[r + self.size * (g + self.size * b)];
+
+        let mut out = [0.0; 3];
+        for (c, value) in out.iter_mut().enumerate() {
+            let lerp = |a: f32, b: f32, t: f32| a + (b - a) * t;
+            let c00 = lerp(entry(r, g, b)[c], entry(r + 1, g, b)[c], fr);
+            let c10 = lerp(entry(r, g + 1, b)[c], entry(r + 1, g + 1, b)[c], fr);
+            let c01 = lerp(entry(r, g, b + 1)[c], entry(r + 1, g, b + 1)[c], fr);
+            let c11 = lerp(entry(r, g + 1, b + 1)[c], entry(r + 1, g + 1, b + 1)[c], fr);
+            *value = lerp(lerp(c00, c10, fg), lerp(c01, c11, fg), fb);
+        }
+        out
+    }
+}
+
+/// Number of the entries of the lookup tables of the curves and the gamma. Values between them are linearly interpolated.
+const CURVE_LUT_SIZE: usize = 4096;
+
+/// Curve is a tonal curve through control points, as the curves tool of photo editors.
+/// The points are connected with a monotone cubic spline, so the curve does not overshoot between them.
+/// - points: Control points (input, output) in 0.0-1.0, sorted by input. Inputs before the first / after the last point keep the output of that point.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Curve {
+    pub points: Vec<(f32, f32)>,
+}
+
+impl Default for Curve {
+    /// The identity curve.
+    fn default() -> Self {
+        Curve { points: vec![(0.0, 0.0), (1.0, 1.0)] }
+    }
+}
+
+impl Curve {
+    /// Parse a curve spec: control points "input,output" separated by spaces (e.g. "0,0 0.5,0.58 1,1").
+    /// At least 2 points are required, the values must be in 0.0-1.0, and the inputs must increase.
+    pub fn parse(spec: &str) -> Result<Curve, String> {
+        let mut points = Vec::new();
+        for point in spec.split_whitespace() {
+            let (x, y) = point.split_once(',').ok_or_else(|| format!("'{}' is not 'input,output'", point))?;
+            let parse = |v: &str| v.trim().parse::<f32>().ok().filter(|v| (0.0..=1.0).contains(v));
+            match (parse(x), parse(y)) {
+                (Some(x), Some(y)) => points.push((x, y)),
+                _ => return Err(format!("'{}': the values must be 0.0-1.0", point)),
+            }
+        }
+        if points.len() < 2 {
+            return Err("at least 2 points are required".to_string());
+        }
+        if points.windows(2).any(|pair| pair[1].0 <= pair[0].0) {
+            return Err("the inputs of the points must increase".to_string());
+        }
+        Ok(Curve { points: points })
+    }
+
+    /// Get the output of the curve for an input in 0.0-1.0.
+    pub fn evaluate(&self, x: f32) -> f32 {
+        let points = &self.points;
+        let (first, last) = (points[0], points[points.len() - 1]);
+        if x <= first.0 {
+            return first.1;
+        }
+        if x >= last.0 {
+            return last.1;
+        }
+
+        // 区間の傾きと各点の接線 (Fritsch-Carlson 法で単調性を保つ)
+        let slopes = points.windows(2).map(|p| (p[1].1 - p[0].1) / (p[1].0 - p[0].0)).collect::<Vec<_>>();
+        let tangent = |i: usize| {
+            if i == 0 {
+                slopes[0]
+            }
+            else if i == slopes.len() {
+                slopes[i - 1]
+            }
+            else if slopes[i - 1] * slopes[i] <= 0.0 {
+                0.0
+            }
+            else {
+                // 調和平均は隣の区間の傾きの 3 倍を超えない
+                2.0 / (1.0 / slopes[i - 1] + 1.0 / slopes[i])
+            }
+        };
+
+        let i = points.windows(2).position(|p| x < p[1].0).unwrap_or(slopes.len() - 1);
+        let ((x0, y0), (x1, y1)) = (points[i], points[i + 1]);
+        let h = x1 - x0;
+        let t = (x - x0) / h;
+        let (t2, t3) = (t * t, t * t * t);
+        let y = (2.0 * t3 - 3.0 * t2 + 1.0) * y0
+            + (t3 - 2.0 * t2 + t) * h * tangent(i)
+            + (-2.0 * t3 + 3.0 * t2) * y1
+            + (t3 - t2) * h * tangent(i + 1);
+        y.clamp(0.0, 1.0)
+    }
+}
+
+/// Curves is a set of tonal curves.
+/// - rgb: Curve of all channels, applied after the curve of each channel.
+/// - red, green, blue: Curve of each channel.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Curves {
+    pub rgb: Curve,
+    pub red: Curve,
+    pub green: Curve,
+    pub blue: Curve,
+}
+
+/// Get the value of a 1D lookup table of 0.0-1.0 with linear interpolation.
+fn lookup_1d(table: &[f32], x: f32) -> f32 {
+    let t = x.clamp(0.0, 1.0) * (table.len() - 1) as f32;
+    let i = (t.floor() as usize).min(table.len() - 2);
+    table[i] + (table[i + 1] - table[i]) * (t - i as f32)
+}
+
+/// Apply tonal curves to the colors of an image. Alpha is not changed, and the color type of the image is kept.
+/// The curves are sampled into a lookup table of each channel first, so the spline is not evaluated for each pixel.
+pub fn apply_curves(image: &DynamicImage, curves: &Curves) -> DynamicImage {
+    let tables = [&curves.red, &curves.green, &curves.blue].map(|curve| {
+        (0..CURVE_LUT_SIZE)
+            .map(|i| curves.rgb.evaluate(curve.evaluate(i as f32 / (CURVE_LUT_SIZE - 1) as f32)))
+            .collect::<Vec<_>>()
+    });
+
+    let mut pixels = image.to_rgba32f();
+    for pixel in pixels.pixels_mut() {
+        for (value, table) in pixel.0.iter_mut().zip(tables.iter()) {
+            *value = lookup_1d(table, *value);
+        }
+    }
+    with_color_type(DynamicImage::ImageRgba32F(pixels), image.color())
+}
+
+/// Apply a gamma correction to the colors of an image: output = input^(1 / gamma).
+/// A gamma above 1.0 brightens the midtones (e.g. for e-ink screens, which show them dark), and below 1.0 darkens them.
+/// Black and white are not changed. Alpha is not changed, and the color type of the image is kept.
+pub fn apply_gamma(image: &DynamicImage, gamma: f32) -> DynamicImage {
+    let table = (0..CURVE_LUT_SIZE).map(|i| (i as f32 / (CURVE_LUT_SIZE - 1) as f32).powf(1.0 / gamma)).collect::<Vec<_>>();
+    let mut pixels = image.to_rgba32f();
+    for pixel in pixels.pixels_mut() {
+        for value in pixel.0.iter_mut().take(3) {
+            *value = lookup_1d(&table, *value);
+        }
+    }
+    with_color_type(DynamicImage::ImageRgba32F(pixels), image.color())
+}
+
+/// Threshold is the level of threshold(). Pixels brighter than the level become white, and the others black.
+/// - Otsu: The level is chosen from the histogram of the image by Otsu's method.
+/// - Adaptive: The level of each pixel is ADAPTIVE_THRESHOLD_RATIO of the mean brightness around it (Bradley's method).
+///   Unlike a global level, it keeps the text of unevenly lit scans and photos of receipts.
+/// - Value: A fixed level (0-255).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Threshold {
+    Otsu,
+    Adaptive,
+    Value(u8),
+}
+
+/// Side of the window of the adaptive threshold, as a fraction of the long side of the image.
+const ADAPTIVE_THRESHOLD_WINDOW: u32 = 8;
+/// Pixels darker than this percentage of the mean brightness of their window become black in the adaptive threshold.
+const ADAPTIVE_THRESHOLD_RATIO: u64 = 85;
+
+/// Get the level of an image by Otsu's method: the level maximizing the variance between the dark and the bright pixels.
+pub fn otsu_level(image: &GrayImage) -> u8 {
+    let mut histogram = [0u64; 256];
+    for pixel in image.pixels() {
+        histogram[pixel.0[0] as usize] += 1;
+    }
+    let total = histogram.iter().sum::<u64>() as f64;
+    let sum_all = histogram.iter().enumerate().map(|(v, c)| v as f64 * *c as f64).sum::<f64>();
+
+    let (mut best, mut best_variance) = (0, 0.0);
+    let (mut count, mut sum) = (0.0, 0.0);
+    for (level, c) in histogram.iter().enumerate() {
+        // level 以下が暗い側
+        count += *c as f64;
+        sum += level as f64 * *c as f64;
+        if count == 0.0 || count == total {
+            continue;
+        }
+        let (mean_dark, mean_bright) = (sum / count, (sum_all - sum) / (total - count));
+        let variance = count * (total - count) * (mean_dark - mean_bright).powi(2);
+        if variance > best_variance {
+            best = level;
+            best_variance = variance;
+        }
+    }
+    best as u8
+}
+
+/// Binarize an image by Bradley's method: compare each pixel with the mean of the window around it.
+/// The sums of the windows are taken from an integral image, so the time does not depend on the size of the window.
+fn adaptive_threshold(gray: &mut GrayImage) {
+    let (width, height) = (gray.width() as usize, gray.height() as usize);
+    // integral[y][x] = (0, 0) から (x - 1, y - 1) までの合計
+    let mut integral = vec![0u64; (width + 1) * (height + 1)];
+    for y in 0..height {
+        let mut row = 0u64;
+        for x in 0..width {
+            row += gray.get_pixel(x as u32, y as u32).0[0] as u64;
+            integral[(y + 1) * (width + 1) + x + 1] = integral[y * (width + 1) + x + 1] + row;
+        }
+    }
+
+    let half = (width.max(height) / ADAPTIVE_THRESHOLD_WINDOW as usize / 2).max(1);
+    for (x, y, pixel) in gray.enumerate_pixels_mut() {
+        let (x, y) = (x as usize, y as usize);
+        let (x0, y0) = (x.saturating_sub(half), y.saturating_sub(half));
+        let (x1, y1) = ((x + half + 1).min(width), (y + half + 1).min(height));
+        let sum = integral[y1 * (width + 1) + x1] + integral[y0 * (width + 1) + x0] - integral[y0 * (width + 1) + x1] - integral[y1 * (width + 1) + x0];
+        let count = ((x1 - x0) * (y1 - y0)) as u64;
+        pixel.0[0] = if pixel.0[0] as u64 * count * 100 <= sum * ADAPTIVE_THRESHOLD_RATIO { 0 } else { 255 };
+    }
+}
+
+/// Binarize an image by the brightness: pixels brighter than the level become white (255), and the others black (0).
+/// Returns the 8-bit grayscale image (with alpha if the image has alpha) and the level (None for Threshold::Adaptive, which has a level for each pixel).
+/// The result has only 2 values, so it is reduced to a 1-bit png by the bit depth reduction.
+pub fn threshold(image: &DynamicImage, method: Threshold) -> (DynamicImage, Option<u8>) {
+    let mut gray = image.to_luma8();
+    let level = match method {
+        Threshold::Otsu => Some(otsu_level(&gray)),
+        Threshold::Adaptive => None,
+        Threshold::Value(level) => Some(level),
+    };
+    match level {
+        Some(level) => {
+            for pixel in gray.pixels_mut() {
+                pixel.0[0] = if pixel.0[0] > level { 255 } else { 0 };
+            }
+        },
+        None => adaptive_threshold(&mut gray),
+    }
+
+    if image.color().has_alpha() {
+        let mut gray_alpha = image.to_luma_alpha8();
+        for (pixel, binary) in gray_alpha.pixels_mut().zip(gray.pixels()) {
+            pixel.0[0] = binary.0[0];
+        }
+        (DynamicImage::ImageLumaA8(gray_alpha), level)
+    }
+    else {
+        (DynamicImage::ImageLuma8(gray), level)
+    }
+}
+
+/// Detect the edges of an image with the Sobel operator.
+/// Returns an 8-bit grayscale image of the strength of the gradient of the brightness: edges are white, flat areas black.
+/// A step from black to white gives 255. Pixels outside of the image are the nearest pixels of the border.
+pub fn edges(image: &DynamicImage) -> DynamicImage {
+    let gray = image.to_luma8();
+    let (width, height) = gray.dimensions();
+    let at = |x: i64, y: i64| gray.get_pixel(x.clamp(0, width as i64 - 1) as u32, y.clamp(0, height as i64 - 1) as u32).0[0] as f32;
+
+    let out = GrayImage::from_fn(width, height, |x, y| {
+        let (x, y) = (x as i64, y as i64);
+        let gx = (at(x + 1, y - 1) + 2.0 * at(x + 1, y) + at(x + 1, y + 1)) - (at(x - 1, y - 1) + 2.0 * at(x - 1, y) + at(x - 1, y + 1));
+        let gy = (at(x - 1, y + 1) + 2.0 * at(x, y + 1) + at(x + 1, y + 1)) - (at(x - 1, y - 1) + 2.0 * at(x, y - 1) + at(x + 1, y - 1));
+        // カーネルの重みの合計 (4) で割る
+        Luma([((gx * gx + gy * gy).sqrt() / 4.0).round().clamp(0.0, 255.0) as u8])
+    });
+    DynamicImage::ImageLuma8(out)
+}
+
+/// Convert an image back to a color type (e.g. after processing it as 32-bit float RGBA).
+/// Color types without a DynamicImage variant of their own are kept as the given image.
+pub(crate) fn with_color_type(image: DynamicImage, color: ColorType) -> DynamicImage {
+    match color {
+        ColorType::L8 => DynamicImage::ImageLuma8(image.to_luma8()),
+        ColorType::La8 => DynamicImage::ImageLumaA8(image.to_luma_alpha8()),
+        ColorType::Rgb8 => DynamicImage::ImageRgb8(image.to_rgb8()),
+        ColorType::Rgba8 => DynamicImage::ImageRgba8(image.to_rgba8()),
+        ColorType::L16 => DynamicImage::ImageLuma16(image.to_luma16()),
+        ColorType::La16 => DynamicImage::ImageLumaA16(image.to_luma_alpha16()),
+        ColorType::Rgb16 => DynamicImage::ImageRgb16(image.to_rgb16()),
+        ColorType::Rgba16 => DynamicImage::ImageRgba16(image.to_rgba16()),
+        ColorType::Rgb32F => DynamicImage::ImageRgb32F(image.to_rgb32f()),
+        _ => image,
+    }
+}
+
+/// Apply a 3D LUT to the colors of an image. Alpha is not changed, and the color type of the image is kept.
+pub fn apply_lut(image: &DynamicImage, lut: &Lut3d) -> DynamicImage {
+    let mut pixels = image.to_rgba32f();
+    for pixel in pixels.pixels_mut() {
+        let [r, g, b, a] = pixel.0;
+        let [r, g, b] = lut.lookup([r, g, b]);
+        pixel.0 = [r.clamp(0.0, 1.0), g.clamp(0.0, 1.0), b.clamp(0.0, 1.0), a];
+    }
+    with_color_type(DynamicImage::ImageRgba32F(pixels), image.color())
+}
+
+impl RusImg {
+    /// Apply a 3D LUT (e.g. loaded with Lut3d::load()) to the colors of the image.
+    pub fn apply_lut(&mut self, lut: &Lut3d) -> Result<(), RusimgError> {
+        let graded = apply_lut(self.as_dynamic_image(), lut);
+        self.set_dynamic_image(graded)
+    }
+
+    /// Apply tonal curves to the colors of the image.
+    pub fn apply_curves(&mut self, curves: &Curves) -> Result<(), RusimgError> {
+        let adjusted = apply_curves(self.as_dynamic_image(), curves);
+        self.set_dynamic_image(adjusted)
+    }
+
+    /// Apply a gamma correction to the colors of the image.
+    /// See filters::apply_gamma().
+    pub fn apply_gamma(&mut self, gamma: f32) -> Result<(), RusimgError> {
+        let corrected = apply_gamma(self.as_dynamic_image(), gamma);
+        self.set_dynamic_image(corrected)
+    }
+
+    /// Binarize the image to black and white. Returns the level (None for Threshold::Adaptive).
+    /// See filters::threshold().
+    pub fn threshold(&mut self, method: Threshold) -> Result<Option<u8>, RusimgError> {
+        let (binary, level) = threshold(self.as_dynamic_image(), method);
+        self.set_dynamic_image(binary)?;
+        Ok(level)
+    }
+
+    /// Replace the image with its edges.
+    /// See filters::edges().
+    pub fn detect_edges(&mut self) -> Result<(), RusimgError> {
+        let detected = edges(self.as_dynamic_image());
+        self.set_dynamic_image(detected)
+    }
+}
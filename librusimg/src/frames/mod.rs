@@ -0,0 +1,309 @@
+use std::cell::Cell;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+use std::time::Duration;
+use image::{AnimationDecoder, DynamicImage, ImageBuffer, ImageFormat, ImageReader, Luma, LumaA, Rgb, Rgba};
+use image::codecs::gif::GifDecoder;
+use image::codecs::png::PngDecoder;
+use image::codecs::webp::WebPDecoder;
+use image::imageops::FilterType;
+use tiff::ColorType as TiffColorType;
+use tiff::decoder::{Decoder as TiffDecoder, DecodingResult};
+
+use super::{RusImg, Rect, ImgSize, RusimgError};
+use super::rect::TrimPolicy;
+
+/// Frame is a frame of an animation (or a page of a multi-page image).
+/// - image: The pixels of the whole canvas. Decoded frames are already composed onto the previous frames.
+/// - delay: How long the frame is shown. Zero for still images and pages.
+#[derive(Debug, Clone)]
+pub struct Frame {
+    pub image: DynamicImage,
+    pub delay: Duration,
+}
+
+/// Frames is the frames of an image in the order they are shown.
+/// The operations of Frames (resize, trim, grayscale, map) apply to every frame, so that all frames keep the same size.
+/// - frames: The frames. There is at least one frame.
+/// - loop_count: Number of times to play the animation. 0 is forever.
+#[derive(Debug, Clone)]
+pub struct Frames {
+    frames: Vec<Frame>,
+    pub loop_count: u16,
+}
+
+impl Frames {
+    /// Make the frames of a still image: the image is the only frame.
+    pub fn from_image(image: DynamicImage) -> Frames {
+        Frames {
+            frames: vec![Frame { image: image, delay: Duration::ZERO }],
+            loop_count: 0,
+        }
+    }
+
+    /// Make the frames of an animation shown at a constant frame rate (fps > 0.0).
+    /// Returns None if there are no images.
+    pub fn with_frame_rate(images: Vec<DynamicImage>, fps: f32) -> Option<Frames> {
+        if images.is_empty() {
+            return None;
+        }
+        let delay = Duration::from_secs_f64(1.0 / fps as f64);
+        Some(Frames {
+            frames: images.into_iter().map(|image| Frame { image: image, delay: delay }).collect(),
+            loop_count: 0,
+        })
+    }
+
+    /// Make the frames of the pages of a document. Returns None if there are no images.
+    pub fn from_pages(images: Vec<DynamicImage>) -> Option<Frames> {
+        if images.is_empty() {
+            return None;
+        }
+        Some(Frames {
+            frames: images.into_iter().map(|image| Frame { image: image, delay: Duration::ZERO }).collect(),
+            loop_count: 0,
+        })
+    }
+
+    /// Decode the frames of an image file.
+    /// Animated gif, webp and png (APNG) files are decoded frame by frame, multi-page tiff files page by page, and the other images as one frame.
+    pub fn decode(path: &Path) -> Result<Frames, RusimgError> {
+        Frames::decode_selected(path, |_| true)?.ok_or(RusimgError::FailedToOpenImage(format!("{}: no frames", path.display())))
+    }
+
+    /// Decode the selected frames of an image file. selected gets the index of each frame (starting from 0).
+    /// The pages of tiff files that are not selected are skipped without decoding them.
+    /// Returns None if no frame is selected.
+    pub fn decode_selected<F: Fn(usize) -> bool>(path: &Path, selected: F) -> Result<Option<Frames>, RusimgError> {
+        match decode_frames(path, &selected)? {
+            Some(frames) => Ok(Frames::from_frames(frames)),
+            None if selected(0) => {
+                let image = image::open(path).map_err(|e| RusimgError::FailedToOpenImage(e.to_string()))?;
+                Ok(Some(Frames::from_image(image)))
+            },
+            None => Ok(None),
+        }
+    }
+
+    /// Count the frames (or the pages) of an image file without decoding the pixels of tiff pages.
+    pub fn count(path: &Path) -> Result<usize, RusimgError> {
+        let count = Cell::new(0);
+        // 選択しないフレームは数えるだけで、tiff のページは展開しない
+        let frames = decode_frames(path, &|_| {
+            count.set(count.get() + 1);
+            false
+        })?;
+        Ok(if frames.is_some() { count.get() } else { 1 })
+    }
+
+    /// Make the frames from decoded frames. Returns None if there are no frames.
+    fn from_frames(frames: Vec<Frame>) -> Option<Frames> {
+        if frames.is_empty() {
+            return None;
+        }
+        Some(Frames {
+            frames: frames,
+            loop_count: 0,
+        })
+    }
+
+    /// Number of the frames.
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    /// Check if there are no frames. Frames made by the functions above always have a frame.
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+
+    /// Check if there are two or more frames.
+    pub fn is_animated(&self) -> bool {
+        self.frames.len() > 1
+    }
+
+    /// Get the first frame (the one shown by viewers without animation support).
+    pub fn first(&self) -> &Frame {
+        &self.frames[0]
+    }
+
+    /// Iterate over the frames.
+    pub fn iter(&self) -> std::slice::Iter<'_, Frame> {
+        self.frames.iter()
+    }
+
+    /// Take the frames.
+    pub fn into_vec(self) -> Vec<Frame> {
+        self.frames
+    }
+
+    /// Size of the frames.
+    pub fn size(&self) -> ImgSize {
+        let image = &self.first().image;
+        ImgSize::new(image.width() as usize, image.height() as usize)
+    }
+
+    /// Total time to play the frames once.
+    pub fn total_duration(&self) -> Duration {
+        self.frames.iter().map(|frame| frame.delay).sum()
+    }
+
+    /// Apply an operation to the image of every frame. The delays are kept.
+    /// The operation must return images of the same size for all frames (e.g. not a content-aware crop).
+    pub fn map<F: Fn(&DynamicImage) -> DynamicImage>(&mut self, operation: F) {
+        for frame in self.frames.iter_mut() {
+            frame.image = operation(&frame.image);
+        }
+    }
+
+    /// Apply an operation that may fail to the image of every frame.
+    /// If it fails on a frame, the frames are left unchanged.
+    pub fn try_map<F: Fn(&DynamicImage) -> Result<DynamicImage, RusimgError>>(&mut self, operation: F) -> Result<(), RusimgError> {
+        let images = self.frames.iter().map(|frame| operation(&frame.image)).collect::<Result<Vec<_>, _>>()?;
+        for (frame, image) in self.frames.iter_mut().zip(images) {
+            frame.image = image;
+        }
+        Ok(())
+    }
+
+    /// Resize every frame. Set ratio to 100.0 to keep the original size.
+    pub fn resize(&mut self, ratio: f32) -> ImgSize {
+        let size = self.size();
+        let width = ((size.width as f32 * ratio / 100.0) as u32).max(1);
+        let height = ((size.height as f32 * ratio / 100.0) as u32).max(1);
+        self.resize_exact(width, height)
+    }
+
+    /// Resize every frame to the size in pixels.
+    pub fn resize_exact(&mut self, width: u32, height: u32) -> ImgSize {
+        self.map(|image| image.resize_exact(width, height, FilterType::Lanczos3));
+        self.size()
+    }
+
+    /// Trim every frame to the area. The area is clamped to the frames.
+    pub fn trim_rect(&mut self, area: Rect) -> Result<ImgSize, RusimgError> {
+        let area = area.clamp_to(self.size(), TrimPolicy::Clamp)?;
+        self.map(|image| image.crop_imm(area.x, area.y, area.w, area.h));
+        Ok(self.size())
+    }
+
+    /// Grayscale every frame.
+    pub fn grayscale(&mut self) {
+        self.map(|image| image.grayscale());
+    }
+}
+
+/// Decode the selected frames of an animated or multi-page image. selected is called once for each frame, in order.
+/// Returns None if the file does not have multiple frames (e.g. a jpeg, or a gif with one frame).
+fn decode_frames(path: &Path, selected: &dyn Fn(usize) -> bool) -> Result<Option<Vec<Frame>>, RusimgError> {
+    let reader = ImageReader::open(path).map_err(|e| RusimgError::FailedToOpenFile(e.to_string()))?
+        .with_guessed_format().map_err(|e| RusimgError::FailedToOpenFile(e.to_string()))?;
+    let format = reader.format();
+    let reader = reader.into_inner();
+    let decode_err = |e: image::ImageError| RusimgError::FailedToOpenImage(e.to_string());
+
+    let frames = match format {
+        Some(ImageFormat::Tiff) => return decode_tiff_pages(reader, selected),
+        Some(ImageFormat::Gif) => GifDecoder::new(reader).map_err(decode_err)?.into_frames().collect_frames().map_err(decode_err)?,
+        Some(ImageFormat::Png) => {
+            let decoder = PngDecoder::new(reader).map_err(decode_err)?;
+            if !decoder.is_apng().map_err(decode_err)? {
+                return Ok(None);
+            }
+            decoder.apng().map_err(decode_err)?.into_frames().collect_frames().map_err(decode_err)?
+        },
+        Some(ImageFormat::WebP) => {
+            let decoder = WebPDecoder::new(reader).map_err(decode_err)?;
+            if !decoder.has_animation() {
+                return Ok(None);
+            }
+            decoder.into_frames().collect_frames().map_err(decode_err)?
+        },
+        _ => return Ok(None),
+    };
+    if frames.len() < 2 {
+        return Ok(None);
+    }
+
+    // アニメーションのフレームは前のフレームに重ねて合成されるので、すべて展開してから選ぶ
+    let frames = frames.into_iter().enumerate().filter(|(index, _)| selected(*index)).map(|(_, frame)| {
+        let (numer, denom) = frame.delay().numer_denom_ms();
+        Frame {
+            delay: Duration::from_secs_f64(numer as f64 / denom.max(1) as f64 / 1000.0),
+            image: DynamicImage::ImageRgba8(frame.into_buffer()),
+        }
+    }).collect();
+    Ok(Some(frames))
+}
+
+/// Decode the selected pages of a multi-page tiff file.
+/// Returns None if the file has one page, to be decoded by the image crate.
+fn decode_tiff_pages(reader: BufReader<File>, selected: &dyn Fn(usize) -> bool) -> Result<Option<Vec<Frame>>, RusimgError> {
+    let tiff_err = |e: tiff::TiffError| RusimgError::FailedToOpenImage(e.to_string());
+    let mut decoder = TiffDecoder::new(reader).map_err(tiff_err)?;
+    if !decoder.more_images() {
+        return Ok(None);
+    }
+
+    let mut pages = Vec::new();
+    let mut index = 0;
+    loop {
+        if selected(index) {
+            let (width, height) = decoder.dimensions().map_err(tiff_err)?;
+            let color_type = decoder.colortype().map_err(tiff_err)?;
+            let image = tiff_page_image(width, height, color_type, decoder.read_image().map_err(tiff_err)?)
+                .ok_or(RusimgError::FailedToOpenImage(format!("Unsupported color type of page {}: {:?}", index + 1, color_type)))?;
+            pages.push(Frame {
+                image: image,
+                delay: Duration::ZERO,
+            });
+        }
+        if !decoder.more_images() {
+            break;
+        }
+        decoder.next_image().map_err(tiff_err)?;
+        index += 1;
+    }
+    Ok(Some(pages))
+}
+
+/// Make an image from the pixels of a tiff page.
+/// Bilevel pages (e.g. fax and document scans) are expanded to 8-bit grayscale.
+fn tiff_page_image(width: u32, height: u32, color_type: TiffColorType, data: DecodingResult) -> Option<DynamicImage> {
+    match (color_type, data) {
+        (TiffColorType::Gray(1), DecodingResult::U8(data)) => {
+            // 1 ビット: 各行はバイト境界まで詰められている (1 = 白)
+            let row_bytes = (width as usize).div_ceil(8);
+            let pixels = (0..height as usize).flat_map(|y| (0..width as usize).map(move |x| (y, x)))
+                .map(|(y, x)| data.get(y * row_bytes + x / 8).map_or(0, |byte| if byte & (0x80 >> (x % 8)) != 0 { 255 } else { 0 }))
+                .collect::<Vec<u8>>();
+            ImageBuffer::<Luma<u8>, _>::from_raw(width, height, pixels).map(DynamicImage::ImageLuma8)
+        },
+        (TiffColorType::Gray(8), DecodingResult::U8(data)) => ImageBuffer::<Luma<u8>, _>::from_raw(width, height, data).map(DynamicImage::ImageLuma8),
+        (TiffColorType::GrayA(8), DecodingResult::U8(data)) => ImageBuffer::<LumaA<u8>, _>::from_raw(width, height, data).map(DynamicImage::ImageLumaA8),
+        (TiffColorType::RGB(8), DecodingResult::U8(data)) => ImageBuffer::<Rgb<u8>, _>::from_raw(width, height, data).map(DynamicImage::ImageRgb8),
+        (TiffColorType::RGBA(8), DecodingResult::U8(data)) => ImageBuffer::<Rgba<u8>, _>::from_raw(width, height, data).map(DynamicImage::ImageRgba8),
+        (TiffColorType::Gray(16), DecodingResult::U16(data)) => ImageBuffer::<Luma<u16>, _>::from_raw(width, height, data).map(DynamicImage::ImageLuma16),
+        (TiffColorType::RGB(16), DecodingResult::U16(data)) => ImageBuffer::<Rgb<u16>, _>::from_raw(width, height, data).map(DynamicImage::ImageRgb16),
+        (TiffColorType::RGBA(16), DecodingResult::U16(data)) => ImageBuffer::<Rgba<u16>, _>::from_raw(width, height, data).map(DynamicImage::ImageRgba16),
+        _ => None,
+    }
+}
+
+impl RusImg {
+    /// Get the frames of the image.
+    /// Animated gif, webp and png (APNG) sources are decoded frame by frame from the input file, as RusImg keeps only the first frame;
+    /// the operations on RusImg (resize, trim, ...) are not applied to them, so use the operations of Frames instead.
+    /// For other images (and images not opened from a file), the current image is the only frame.
+    pub fn frames(&self) -> Result<Frames, RusimgError> {
+        let path = self.get_input_filepath();
+        if !path.is_file() {
+            return Ok(Frames::from_image(self.as_dynamic_image().clone()));
+        }
+        match decode_frames(&path, &|_| true)?.and_then(Frames::from_frames) {
+            Some(frames) => Ok(frames),
+            None => Ok(Frames::from_image(self.as_dynamic_image().clone())),
+        }
+    }
+}
@@ -0,0 +1,70 @@
+/// History keeps the states of an image before each operation, so that the operations can be undone.
+/// The history is disabled by default (limit: 0), because each snapshot keeps the pixel data before the operation alive,
+/// which is not wanted in batch processing. Interactive/GUI applications enable it with set_limit().
+/// Cloning an image does not clone its history; the clone starts with an empty history with the same limit.
+#[derive(Debug)]
+pub struct History<T> {
+    snapshots: Vec<T>,
+    limit: usize,
+}
+
+impl<T> Default for History<T> {
+    fn default() -> Self {
+        Self {
+            snapshots: Vec::new(),
+            limit: 0,
+        }
+    }
+}
+
+impl<T> Clone for History<T> {
+    fn clone(&self) -> Self {
+        Self {
+            snapshots: Vec::new(),
+            limit: self.limit,
+        }
+    }
+}
+
+impl<T> History<T> {
+    /// Set the maximum number of operations that can be undone.
+    /// Older snapshots are dropped if the history is longer than the new limit. 0 disables the history.
+    pub fn set_limit(&mut self, limit: usize) {
+        self.limit = limit;
+        if self.snapshots.len() > limit {
+            self.snapshots.drain(..self.snapshots.len() - limit);
+        }
+    }
+
+    /// Record the state before an operation.
+    /// The oldest snapshot is dropped if the history is full.
+    pub fn push(&mut self, snapshot: T) {
+        if self.limit == 0 {
+            return;
+        }
+        if self.snapshots.len() >= self.limit {
+            self.snapshots.remove(0);
+        }
+        self.snapshots.push(snapshot);
+    }
+
+    /// Check if the history is enabled.
+    pub fn is_enabled(&self) -> bool {
+        self.limit > 0
+    }
+
+    /// Take the state before the last operation.
+    pub fn pop(&mut self) -> Option<T> {
+        self.snapshots.pop()
+    }
+
+    /// Number of operations that can be undone.
+    pub fn len(&self) -> usize {
+        self.snapshots.len()
+    }
+
+    /// Check if there is nothing to undo.
+    pub fn is_empty(&self) -> bool {
+        self.snapshots.is_empty()
+    }
+}
@@ -0,0 +1,59 @@
+use super::super::RusimgError;
+use super::super::options::JpegOptions;
+use super::super::pool;
+
+/// Name of the JPEG encoder compiled into this build.
+#[cfg(not(feature = "jpeg-pure"))]
+pub const NAME: &str = "mozjpeg";
+#[cfg(feature = "jpeg-pure")]
+pub const NAME: &str = "image";
+
+/// Whether the encoder supports progressive JPEG and JpegOptions (mozjpeg only).
+pub const ADVANCED: bool = cfg!(not(feature = "jpeg-pure"));
+
+/// Encode 8-bit pixels (RGB, or luma if grayscale is true) with mozjpeg.
+/// Restart intervals and arithmetic coding are not exposed by the mozjpeg crate, so they are encoded with the compressor of mozjpeg-sys (raw::encode()).
+/// The output buffer is taken from the buffer pool.
+#[cfg(not(feature = "jpeg-pure"))]
+pub fn encode(pixels: &[u8], grayscale: bool, width: usize, height: usize, quality: f32, progressive: bool, options: &JpegOptions) -> Result<Vec<u8>, RusimgError> {
+    use mozjpeg::{Compress, ColorSpace, ScanMode};
+
+    if options.restart_interval != 0 || options.arithmetic_coding {
+        return super::raw::encode(pixels, grayscale, width, height, quality, progressive, options);
+    }
+
+    let color_space = if grayscale { ColorSpace::JCS_GRAYSCALE } else { ColorSpace::JCS_RGB };
+    let mut compress = Compress::new(color_space);
+    // fastest プロファイルは trellis 量子化を無効にする（他の設定より先に適用）
+    if !options.trellis {
+        compress.set_fastest_defaults();
+    }
+    // set_scan_optimization_mode() はパラメータを初期化するので、先に呼ぶ
+    compress.set_scan_optimization_mode(ScanMode::AllComponentsTogether);
+    compress.set_optimize_coding(options.optimize_coding);
+    compress.set_smoothing_factor(options.smoothing.min(100));
+    compress.set_size(width, height);
+    compress.set_quality(quality);
+    if progressive {
+        compress.set_progressive_mode();
+    }
+    let mut comp = compress.start_compress(pool::global().take(pixels.len() / 8)).map_err(|e| RusimgError::FailedToCompressImage(Some(e.to_string())))?;
+    comp.write_scanlines(pixels).map_err(|e| RusimgError::FailedToCompressImage(Some(e.to_string())))?;
+
+    comp.finish().map_err(|e| RusimgError::FailedToCompressImage(Some(e.to_string())))
+}
+
+/// Encode 8-bit pixels (RGB, or luma if grayscale is true) with the pure Rust encoder of the image crate.
+/// The image crate writes baseline JPEG only, and has no settings other than the quality, so progressive and options are ignored.
+#[cfg(feature = "jpeg-pure")]
+pub fn encode(pixels: &[u8], grayscale: bool, width: usize, height: usize, quality: f32, _progressive: bool, _options: &JpegOptions) -> Result<Vec<u8>, RusimgError> {
+    use image::ExtendedColorType;
+    use image::codecs::jpeg::JpegEncoder;
+
+    let color_type = if grayscale { ExtendedColorType::L8 } else { ExtendedColorType::Rgb8 };
+    let mut buf = pool::global().take(pixels.len() / 8);
+    JpegEncoder::new_with_quality(&mut buf, quality.clamp(1.0, 100.0) as u8)
+        .encode(pixels, width as u32, height as u32, color_type)
+        .map_err(|e| RusimgError::FailedToCompressImage(Some(e.to_string())))?;
+    Ok(buf)
+}
@@ -0,0 +1,331 @@
+use image::DynamicImage;
+
+use std::fs::Metadata;
+use std::time::{Duration, Instant};
+use std::io::Write;
+use std::sync::Arc;
+use std::path::PathBuf;
+
+use super::{RusimgTrait, RusimgError, ImgSize, Rect, Extension};
+use super::rect::TrimPolicy;
+use super::history::History;
+use super::options::{SaveOptions, JpegOptions, MetadataPolicy};
+use super::source::SourceData;
+use super::pool;
+use super::stats;
+use super::warning::{self, Warning};
+
+mod encoder;
+#[cfg(not(feature = "jpeg-pure"))]
+mod raw;
+
+pub use encoder::NAME as ENCODER;
+
+#[derive(Debug, Clone)]
+pub struct JpegImage {
+    pub image: Arc<DynamicImage>,
+    image_bytes: Option<Vec<u8>>,
+    size: ImgSize,
+    operations_count: u32,
+    quality: Option<f32>,
+    pub metadata_input: Metadata,
+    pub metadata_output: Option<Metadata>,
+    pub filepath_input: PathBuf,
+    pub filepath_output: Option<PathBuf>,
+    operations: Vec<String>,
+    bytes_written: Option<u64>,
+    encode_duration: Option<Duration>,
+    warnings: Vec<Warning>,
+    history: History<Self>,
+}
+
+impl JpegImage {
+    /// Record the current state so that the next operation can be undone.
+    fn record(&mut self) {
+        if !self.history.is_enabled() {
+            return;
+        }
+        let snapshot = self.clone();
+        self.history.push(snapshot);
+    }
+
+    /// Encode the image with the JPEG encoder of this build (mozjpeg, or the image crate with the jpeg-pure feature).
+    /// quality: f32 0.0 - 100.0
+    /// progressive: Encode as a progressive JPEG.
+    /// options: mozjpeg encoder settings.
+    /// RGB/grayscale images are encoded without copying the pixels.
+    /// Grayscale images (including RGB images whose pixels are all gray) are encoded as single-channel JPEG.
+    fn encode(&self, quality: f32, progressive: bool, options: &JpegOptions) -> Result<Vec<u8>, RusimgError> {
+        let converted;
+        let (pixels, grayscale) = match self.image.as_ref() {
+            DynamicImage::ImageLuma8(image) => (image.as_raw(), true),
+            DynamicImage::ImageRgb8(image) if !stats::is_grayscale(&self.image) => (image.as_raw(), false),
+            _ if stats::is_grayscale(&self.image) => {
+                converted = self.image.to_luma8().into_raw();
+                (&converted, true)
+            },
+            _ => {
+                converted = self.image.to_rgb8().into_raw();
+                (&converted, false)
+            },
+        };
+        encoder::encode(pixels, grayscale, self.size.width, self.size.height, quality, progressive, options)
+    }
+}
+
+impl RusimgTrait for JpegImage {
+    /// Import an image from a DynamicImage object.
+    fn import(image: DynamicImage, source_path: PathBuf, source_metadata: Metadata) -> Result<Self, RusimgError> {
+        let size = ImgSize { width: image.width() as usize, height: image.height() as usize };
+
+        Ok(Self {
+            image: Arc::new(image),
+            image_bytes: None,
+            size,
+            operations_count: 0,
+            quality: None,
+            metadata_input: source_metadata,
+            metadata_output: None,
+            filepath_input: source_path,
+            filepath_output: None,
+            operations: Vec::new(),
+            bytes_written: None,
+            encode_duration: None,
+            warnings: Vec::new(),
+            history: History::default(),
+        })
+    }
+
+    /// Open an image from a image buffer.
+    /// The buffer is only used for decoding, so a memory-mapped file is decoded without copying.
+    fn open(path: PathBuf, image_buf: SourceData, metadata: Metadata) -> Result<Self, RusimgError> {
+        let image = image::load_from_memory(&image_buf).map_err(|e| RusimgError::FailedToOpenImage(e.to_string()))?;
+        let size = ImgSize { width: image.width() as usize, height: image.height() as usize };
+        let warnings = warning::check_format(&path, &image_buf).into_iter().collect();
+
+        Ok(Self {
+            image: Arc::new(image),
+            image_bytes: None,
+            size,
+            operations_count: 0,
+            quality: None,
+            metadata_input: metadata,
+            metadata_output: None,
+            filepath_input: path,
+            filepath_output: None,
+            operations: Vec::new(),
+            bytes_written: None,
+            encode_duration: None,
+            warnings: warnings,
+            history: History::default(),
+        })
+    }
+
+    /// Save the image to a file.
+    /// If options.progressive is true or options.jpeg is not the default, the image is (re-)encoded with the compress quality (default: 75.0).
+    /// The encoders do not copy the metadata of the source, so MetadataPolicy::Keep adds a warning.
+    /// The jpeg-pure encoder writes baseline JPEG only, so options.progressive and options.jpeg add a warning.
+    fn save(&mut self, path: Option<PathBuf>, options: &SaveOptions) -> Result<(), RusimgError> {
+        let start = Instant::now();
+        let save_path = Self::get_save_filepath(self, &self.filepath_input, path, &Extension::Jpeg.extension_for(&self.filepath_input))?;
+        options.check_overwrite(&save_path)?;
+
+        if options.metadata == MetadataPolicy::Keep {
+            warning::push(&mut self.warnings, Warning::MetadataDropped("metadata".to_string()));
+        }
+        if options.progressive && !encoder::ADVANCED {
+            warning::push(&mut self.warnings, Warning::OptionIgnored(format!("progressive ({})", encoder::NAME)));
+        }
+        if options.jpeg != JpegOptions::default() && !encoder::ADVANCED {
+            warning::push(&mut self.warnings, Warning::OptionIgnored(format!("jpeg options ({})", encoder::NAME)));
+        }
+
+        if options.progressive || options.jpeg != JpegOptions::default() {
+            let data = self.encode(self.quality.unwrap_or(75.0), options.progressive, &options.jpeg)?;
+            pool::replace(&mut self.image_bytes, data);
+        }
+        
+        // image_bytes != None の場合、mozjpeg::Compress で圧縮したバイナリデータを保存
+        if let Some(image_bytes) = &self.image_bytes {
+            let mut file = std::fs::File::create(&save_path).map_err(|e| RusimgError::FailedToCreateFile(e.to_string()))?;
+            file.write_all(image_bytes).map_err(|e| RusimgError::FailedToWriteFIle(e.to_string()))?;
+            self.metadata_output = Some(file.metadata().map_err(|e| RusimgError::FailedToGetMetadata(e.to_string()))?);
+        }
+        // image_bytes == None の場合、DynamicImage を 保存（グレースケールは 1 チャンネルで保存）
+        else {
+            let result = if stats::is_grayscale(&self.image) { self.image.to_luma8().save(&save_path) } else { self.image.to_rgb8().save(&save_path) };
+            result.map_err(|e| RusimgError::FailedToSaveImage(e.to_string()))?;
+            self.metadata_output = Some(std::fs::metadata(&save_path).map_err(|e| RusimgError::FailedToGetMetadata(e.to_string()))?);
+        }
+
+        self.bytes_written = self.metadata_output.as_ref().map(|m| m.len());
+
+        self.encode_duration = Some(start.elapsed());
+
+        self.filepath_output = Some(save_path);
+
+        Ok(())
+    }
+
+    /// Compress the image.
+    /// quality: Option<f32> 0.0 - 100.0
+    fn compress(&mut self, quality: Option<f32>) -> Result<(), RusimgError> {
+        let quality = quality.unwrap_or(75.0);  // default quality: 75.0
+
+        let data = self.encode(quality, false, &JpegOptions::default())?;
+        self.record();
+        pool::replace(&mut self.image_bytes, data);
+        self.quality = Some(quality);
+        self.operations.push(format!("compress {}", quality));
+
+        self.operations_count += 1;
+
+        Ok(())
+    }
+
+    /// Resize the image.
+    /// resize_ratio: f32 in percent (must be 0.0 < resize_ratio, e.g. 12.5)
+    fn resize(&mut self, resize_ratio: f32) -> Result<ImgSize, RusimgError> {
+        self.record();
+        let nwidth = (self.size.width as f32 * (resize_ratio / 100.0)) as usize;
+        let nheight = (self.size.height as f32 * (resize_ratio / 100.0)) as usize;
+        
+        self.image = Arc::new(self.image.resize(nwidth as u32, nheight as u32, image::imageops::FilterType::Lanczos3));
+
+        self.size.width = nwidth;
+        self.size.height = nheight;
+
+        self.operations_count += 1;
+        self.operations.push(format!("resize {}%", resize_ratio));
+        Ok(self.size)
+    }
+
+    /// Trim the image.
+    /// trim: librusimg::Rect { x: u32, y: u32, w: u32, h: u32 }
+    /// The area is clamped to the image size with Rect::clamp_to().
+    fn trim(&mut self, trim: Rect) -> Result<ImgSize, RusimgError> {
+        let requested = trim;
+        let trim = requested.clamp_to(self.get_size(), TrimPolicy::Clamp)?;
+        self.record();
+        if let Some(clamped) = warning::check_trim(&requested, &trim) {
+            warning::push(&mut self.warnings, clamped);
+        }
+
+        self.image = Arc::new(self.image.crop_imm(trim.x, trim.y, trim.w, trim.h));
+
+        self.size.width = trim.w as usize;
+        self.size.height = trim.h as usize;
+        self.operations.push(format!("trim {}x{}+{}x{}", trim.x, trim.y, trim.w, trim.h));
+
+        Ok(self.size)
+    }
+
+    /// Convert the image to grayscale.
+    fn grayscale(&mut self) {
+        self.record();
+        self.image = Arc::new(self.image.grayscale());
+        self.operations.push("grayscale".to_string());
+        self.operations_count += 1;
+    }
+
+    /// Set the image to a DynamicImage object.
+    /// The image size is updated to the size of the new image.
+    fn set_dynamic_image(&mut self, image: DynamicImage) -> Result<(), RusimgError> {
+        self.record();
+        self.image = Arc::new(image);
+        self.size = ImgSize { width: self.image.width() as usize, height: self.image.height() as usize };
+        Ok(())
+    }
+
+    /// Get the DynamicImage object.
+    /// This copies the whole image; use as_dynamic_image() to avoid the copy.
+    fn get_dynamic_image(&mut self) -> Result<DynamicImage, RusimgError> {
+        Ok(self.to_owned_dynamic_image())
+    }
+
+    /// Borrow the DynamicImage object without copying.
+    fn as_dynamic_image(&self) -> &DynamicImage {
+        &self.image
+    }
+
+    /// Get a copy of the DynamicImage object.
+    fn to_owned_dynamic_image(&self) -> DynamicImage {
+        self.image.as_ref().clone()
+    }
+
+    /// Clone the image into a new trait object.
+    /// The pixel data is shared with the original until either of them is modified (copy-on-write), so this is cheap.
+    fn box_clone(&self) -> Box<dyn RusimgTrait + Send + Sync> {
+        Box::new(self.clone())
+    }
+
+    /// Set the maximum number of operations that can be undone (0: disabled, default).
+    fn set_history_limit(&mut self, limit: usize) {
+        self.history.set_limit(limit);
+    }
+
+    /// Undo the last operation (resize, trim, grayscale, compress or set_dynamic_image).
+    /// Returns false if there is nothing to undo.
+    fn undo(&mut self) -> bool {
+        match self.history.pop() {
+            Some(previous) => {
+                let history = std::mem::take(&mut self.history);
+                *self = previous;
+                self.history = history;
+                true
+            },
+            None => false,
+        }
+    }
+
+    /// Get the source file path.
+    fn get_source_filepath(&self) -> PathBuf {
+        self.filepath_input.clone()
+    }
+
+    /// Get the destination file path.
+    fn get_destination_filepath(&self) -> Option<PathBuf> {
+        self.filepath_output.clone()
+    }
+
+    /// Get the source metadata.
+    fn get_metadata_src(&self) -> Metadata {
+        self.metadata_input.clone()
+    }
+
+    /// Get the destination metadata.
+    fn get_metadata_dest(&self) -> Option<Metadata> {
+        self.metadata_output.clone()
+    }
+
+    /// Get the image size.
+    fn get_size(&self) -> ImgSize {
+        self.size
+    }
+
+    /// Get the list of applied operations.
+    fn get_operations(&self) -> Vec<String> {
+        self.operations.clone()
+    }
+
+    /// Get the number of bytes written by the last save.
+    fn get_bytes_written(&self) -> Option<u64> {
+        self.bytes_written
+    }
+
+    /// Get the time taken to encode and write the image by the last save.
+    fn get_encode_duration(&self) -> Option<Duration> {
+        self.encode_duration
+    }
+
+    /// Get the warnings found while processing the image.
+    fn get_warnings(&self) -> Vec<Warning> {
+        self.warnings.clone()
+    }
+}
+
+// RusImg holds the backend as Box<dyn RusimgTrait + Send + Sync>, so JpegImage must stay Send + Sync.
+const _: fn() = || {
+    fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<JpegImage>();
+};
@@ -0,0 +1,129 @@
+use std::ffi::{c_int, c_uint, c_ulong};
+use std::mem;
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+
+use mozjpeg_sys as ffi;
+
+use super::super::RusimgError;
+use super::super::options::JpegOptions;
+use super::super::pool;
+
+/// Compressor of mozjpeg, for the settings that the mozjpeg crate does not expose (restart intervals and arithmetic coding).
+/// - cinfo: The compressor. It points to err, so the structure is always boxed.
+/// - err: Error handler. Errors of mozjpeg unwind with the message, as in the mozjpeg crate.
+/// - outbuffer, outsize: Output buffer allocated by jpeg_mem_dest().
+struct RawCompress {
+    cinfo: ffi::jpeg_compress_struct,
+    err: ffi::jpeg_error_mgr,
+    outbuffer: *mut u8,
+    outsize: c_ulong,
+}
+
+impl RawCompress {
+    fn new(grayscale: bool) -> Box<RawCompress> {
+        // Safety: the structures are plain C structures, initialized by jpeg_std_error() and jpeg_CreateCompress() before use.
+        unsafe {
+            let mut compress = Box::new(RawCompress {
+                cinfo: mem::zeroed(),
+                err: mem::zeroed(),
+                outbuffer: ptr::null_mut(),
+                outsize: 0,
+            });
+            ffi::jpeg_std_error(&mut compress.err);
+            compress.err.error_exit = Some(error_exit);
+            compress.err.emit_message = Some(emit_message);
+            compress.cinfo.common.err = &mut compress.err;
+            ffi::jpeg_CreateCompress(&mut compress.cinfo, ffi::JPEG_LIB_VERSION, mem::size_of::<ffi::jpeg_compress_struct>());
+
+            let (color_space, components) = if grayscale { (ffi::J_COLOR_SPACE::JCS_GRAYSCALE, 1) } else { (ffi::J_COLOR_SPACE::JCS_RGB, 3) };
+            compress.cinfo.in_color_space = color_space;
+            compress.cinfo.input_components = components;
+            ffi::jpeg_set_defaults(&mut compress.cinfo);
+            compress
+        }
+    }
+}
+
+impl Drop for RawCompress {
+    fn drop(&mut self) {
+        // Safety: cinfo was created by jpeg_CreateCompress(), and outbuffer is null or allocated with malloc by jpeg_mem_dest().
+        unsafe {
+            ffi::jpeg_destroy_compress(&mut self.cinfo);
+            if !self.outbuffer.is_null() {
+                libc::free(self.outbuffer as *mut libc::c_void);
+            }
+        }
+    }
+}
+
+/// Raise the error of mozjpeg as a panic with the message, to be caught in encode().
+extern "C-unwind" fn error_exit(cinfo: &mut ffi::jpeg_common_struct) {
+    let mut buffer = [0u8; 80];
+    // Safety: err is set by RawCompress::new(), and format_message writes a nul-terminated message of up to 80 bytes.
+    let message = unsafe {
+        let err = &*cinfo.err;
+        match err.format_message {
+            Some(format_message) => {
+                let format_message: unsafe extern "C-unwind" fn(&mut ffi::jpeg_common_struct, &mut [u8; 80]) = mem::transmute(format_message);
+                format_message(cinfo, &mut buffer);
+                String::from_utf8_lossy(buffer.split(|c| *c == 0).next().unwrap_or_default()).into_owned()
+            },
+            None => format!("code {}", err.msg_code),
+        }
+    };
+    panic::resume_unwind(Box::new(message));
+}
+
+/// Warnings of mozjpeg are not printed, as the library never writes to stderr.
+extern "C-unwind" fn emit_message(_cinfo: &mut ffi::jpeg_common_struct, _msg_level: c_int) {
+}
+
+/// Encode 8-bit pixels (RGB, or luma if grayscale is true) with the compressor of mozjpeg.
+/// The settings are the same as encoder::encode(), with options.restart_interval and options.arithmetic_coding in addition.
+/// The output buffer is taken from the buffer pool.
+pub fn encode(pixels: &[u8], grayscale: bool, width: usize, height: usize, quality: f32, progressive: bool, options: &JpegOptions) -> Result<Vec<u8>, RusimgError> {
+    let result = panic::catch_unwind(AssertUnwindSafe(|| {
+        let mut compress = RawCompress::new(grayscale);
+        let cinfo = &mut compress.cinfo;
+        // Safety: the parameters are set before jpeg_start_compress(), and each scanline has width * components bytes.
+        unsafe {
+            // jpeg_set_defaults() はパラメータを初期化するので、プロファイルの後に他の設定を行う
+            if !options.trellis {
+                ffi::jpeg_c_set_int_param(cinfo, ffi::J_INT_PARAM::JINT_COMPRESS_PROFILE, ffi::JINT_COMPRESS_PROFILE_VALUE::JCP_FASTEST as c_int);
+            }
+            ffi::jpeg_c_set_int_param(cinfo, ffi::J_INT_PARAM::JINT_DC_SCAN_OPT_MODE, mozjpeg::ScanMode::AllComponentsTogether as c_int);
+            ffi::jpeg_set_defaults(cinfo);
+            // 算術符号化は適応型なので、ハフマンテーブルの最適化は行わない（行うとエラーになる）
+            cinfo.optimize_coding = (options.optimize_coding && !options.arithmetic_coding) as ffi::boolean;
+            cinfo.smoothing_factor = options.smoothing.min(100) as c_int;
+            cinfo.image_width = width as ffi::JDIMENSION;
+            cinfo.image_height = height as ffi::JDIMENSION;
+            ffi::jpeg_set_quality(cinfo, quality as c_int, 0);
+            if progressive {
+                ffi::jpeg_simple_progression(cinfo);
+            }
+            cinfo.restart_interval = options.restart_interval as c_uint;
+            cinfo.arith_code = options.arithmetic_coding as ffi::boolean;
+
+            ffi::jpeg_mem_dest(cinfo, &mut compress.outbuffer, &mut compress.outsize);
+            ffi::jpeg_start_compress(cinfo, 1);
+            let stride = width * cinfo.input_components as usize;
+            for row in pixels.chunks_exact(stride).take(height) {
+                let row_pointer = row.as_ptr();
+                ffi::jpeg_write_scanlines(cinfo, &row_pointer, 1);
+            }
+            ffi::jpeg_finish_compress(cinfo);
+        }
+
+        let mut buf = pool::global().take(compress.outsize as usize);
+        // Safety: jpeg_finish_compress() has written outsize bytes to outbuffer.
+        buf.extend_from_slice(unsafe { std::slice::from_raw_parts(compress.outbuffer, compress.outsize as usize) });
+        buf
+    }));
+
+    result.map_err(|e| {
+        let message = e.downcast_ref::<String>().cloned().unwrap_or_else(|| "mozjpeg error".to_string());
+        RusimgError::FailedToCompressImage(Some(message))
+    })
+}
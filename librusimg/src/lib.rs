@@ -0,0 +1,449 @@
+// The library never writes to stdout or stderr. This applies to every module of the crate.
+#![deny(clippy::print_stdout, clippy::print_stderr, clippy::dbg_macro)]
+
+#[cfg(feature = "bmp")]
+mod bmp;
+#[cfg(any(feature = "jpeg", feature = "jpeg-pure"))]
+mod jpeg;
+#[cfg(feature = "png")]
+mod png;
+#[cfg(any(feature = "webp", feature = "webp-pure"))]
+mod webp;
+
+mod extension;
+mod size;
+pub mod async_io;
+pub mod colorspace;
+pub mod filters;
+pub mod frames;
+pub mod history;
+pub mod metrics;
+pub mod options;
+pub mod pool;
+pub mod quantize;
+pub mod rect;
+pub mod region;
+pub mod render;
+pub mod source;
+pub mod stats;
+pub mod tempdir;
+pub mod tonemap;
+pub mod warning;
+
+use std::fmt;
+use std::fs::Metadata;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use image::DynamicImage;
+
+pub use options::{SaveOptions, MetadataPolicy, JpegOptions, PngOptions, WebpOptions, BmpOptions, BmpFormat};
+pub use source::OpenOptions;
+use source::SourceData;
+use stats::{Histogram, ImageStats};
+use warning::Warning;
+
+/// RusimgError is the error type of the library.
+/// Some errors have a string parameter to store the error message.
+#[derive(Debug, Clone, PartialEq)]
+pub enum RusimgError {
+    FailedToOpenFile(String),
+    FailedToReadFile(String),
+    FailedToGetMetadata(String),
+    FailedToOpenImage(String),
+    FailedToSaveImage(String),
+    FailedToCopyBinaryData(String),
+    FailedToGetFilename(PathBuf),
+    FailedToCreateFile(String),
+    FailedToWriteFIle(String),
+    FailedToDecodeWebp,
+    FailedToEncodeWebp(String),
+    FailedToCompressImage(Option<String>),
+    FailedToConvertPathToString,
+    FailedToConvertExtension,
+    InvalidCompressionLevel,
+    InvalidTrimXY,
+    InvalidResizeRatio,
+    ImageFormatCannotBeCompressed,
+    UnsupportedFileExtension,
+    UnsupportedFeature,
+}
+impl fmt::Display for RusimgError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RusimgError::FailedToOpenFile(s) => write!(f, "Failed to open file: \n\t{}", s),
+            RusimgError::FailedToReadFile(s) => write!(f, "Failed to read file: \n\t{}", s),
+            RusimgError::FailedToGetMetadata(s) => write!(f, "Failed to get metadata: \n\t{}", s),
+            RusimgError::FailedToOpenImage(s) => write!(f, "Failed to open image: \n\t{}", s),
+            RusimgError::FailedToSaveImage(s) => write!(f, "Failed to save image: \n\t{}", s),
+            RusimgError::FailedToCopyBinaryData(s) => write!(f, "Failed to copy binary data to memory: \n\t{}", s),
+            RusimgError::FailedToGetFilename(s) => write!(f, "Failed to get filename: \n\t{}", s.display()),
+            RusimgError::FailedToCreateFile(s) => write!(f, "Failed to create file: \n\t{}", s),
+            RusimgError::FailedToWriteFIle(s) => write!(f, "Failed to write file: \n\t{}", s),
+            RusimgError::FailedToDecodeWebp => write!(f, "Failed to decode webp"),
+            RusimgError::FailedToEncodeWebp(s) => write!(f, "Failed to encode webp: \n\t{}", s),
+            RusimgError::FailedToCompressImage(s) => {
+                if let Some(s) = s {
+                    write!(f, "Failed to compress image: \n\t{}", s)
+                }
+                else {
+                    write!(f, "Failed to compress image")
+                }
+            },
+            RusimgError::FailedToConvertPathToString => write!(f, "Failed to convert path to string"),
+            RusimgError::FailedToConvertExtension => write!(f, "Failed to convert extension"),
+            RusimgError::InvalidCompressionLevel => write!(f, "Invalid compression level"),
+            RusimgError::InvalidTrimXY => write!(f, "Invalid trim XY"),
+            RusimgError::InvalidResizeRatio => write!(f, "Invalid resize ratio"),
+            RusimgError::ImageFormatCannotBeCompressed => write!(f, "This image format cannot be compressed"),
+            RusimgError::UnsupportedFileExtension => write!(f, "Unsupported file extension"),
+            RusimgError::UnsupportedFeature => write!(f, "Unsupported feature"),
+        }
+    }
+}
+impl std::error::Error for RusimgError {}
+
+/// Extension is the format of an image.
+/// ExternalFormat(String) is provided for the library crate users to use if they wish to implement their own alternate image file format.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Extension {
+    Bmp,
+    Jpeg,
+    Png,
+    Webp,
+    ExternalFormat(String),
+}
+impl fmt::Display for Extension {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Extension::Bmp => write!(f, "bmp"),
+            Extension::Jpeg => write!(f, "jpeg"),
+            Extension::Png => write!(f, "png"),
+            Extension::Webp => write!(f, "webp"),
+            Extension::ExternalFormat(s) => write!(f, "{}", s),
+        }
+    }
+}
+
+/// Rect is an area of an image in pixels.
+/// This is used for trimming an image.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rect {
+    pub x: u32,
+    pub y: u32,
+    pub w: u32,
+    pub h: u32,
+}
+
+/// ImgSize is the size of an image in pixels.
+#[derive(Debug, Clone, PartialEq, Copy, Default)]
+pub struct ImgSize {
+    pub width: usize,
+    pub height: usize,
+}
+impl ImgSize {
+    pub fn new(width: usize, height: usize) -> Self {
+        Self {
+            width: width,
+            height: height,
+        }
+    }
+}
+
+/// SaveStatus is the result of saving an image.
+/// - output_path: Path of the saved file.
+/// - before_filesize: File size of the source.
+/// - after_filesize: Number of bytes written by the save.
+/// - elapsed: Time taken to encode and write the image.
+/// - operations: Applied operations (e.g. "resize 50%").
+/// - warnings: Warnings found while processing the image.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SaveStatus {
+    pub output_path: Option<PathBuf>,
+    pub before_filesize: u64,
+    pub after_filesize: Option<u64>,
+    pub elapsed: Option<Duration>,
+    pub operations: Vec<String>,
+    pub warnings: Vec<Warning>,
+}
+
+/// RusimgTrait is the trait of the image of each format.
+/// RusImg holds the image as a trait object and wraps these functions.
+pub trait RusimgTrait {
+    /// Import an image from a DynamicImage object.
+    fn import(image: DynamicImage, source_path: PathBuf, source_metadata: Metadata) -> Result<Self, RusimgError> where Self: Sized;
+    /// Open an image from the content of the source file.
+    fn open(path: PathBuf, image_buf: SourceData, metadata: Metadata) -> Result<Self, RusimgError> where Self: Sized;
+    /// Save the image to the path. If the path is None, the image is saved next to the source with the extension of the format.
+    fn save(&mut self, path: Option<PathBuf>, options: &SaveOptions) -> Result<(), RusimgError>;
+    /// Compress the image with the quality (0.0-100.0).
+    fn compress(&mut self, quality: Option<f32>) -> Result<(), RusimgError>;
+    /// Resize the image by the ratio in percent.
+    fn resize(&mut self, resize_ratio: f32) -> Result<ImgSize, RusimgError>;
+    /// Trim the image to the area.
+    fn trim(&mut self, trim: Rect) -> Result<ImgSize, RusimgError>;
+    /// Convert the image to grayscale.
+    fn grayscale(&mut self);
+    /// Replace the image with a DynamicImage object.
+    fn set_dynamic_image(&mut self, image: DynamicImage) -> Result<(), RusimgError>;
+    /// Get a copy of the DynamicImage object.
+    fn get_dynamic_image(&mut self) -> Result<DynamicImage, RusimgError>;
+    /// Borrow the DynamicImage object without copying.
+    fn as_dynamic_image(&self) -> &DynamicImage;
+    /// Get a copy of the DynamicImage object.
+    fn to_owned_dynamic_image(&self) -> DynamicImage;
+    /// Clone the image into a new trait object.
+    fn box_clone(&self) -> Box<dyn RusimgTrait + Send + Sync>;
+    /// Set the maximum number of operations that can be undone.
+    fn set_history_limit(&mut self, limit: usize);
+    /// Undo the last operation. Returns false if there is nothing to undo.
+    fn undo(&mut self) -> bool;
+    /// Get the source file path.
+    fn get_source_filepath(&self) -> PathBuf;
+    /// Get the destination file path.
+    fn get_destination_filepath(&self) -> Option<PathBuf>;
+    /// Get the source metadata.
+    fn get_metadata_src(&self) -> Metadata;
+    /// Get the destination metadata.
+    fn get_metadata_dest(&self) -> Option<Metadata>;
+    /// Get the image size.
+    fn get_size(&self) -> ImgSize;
+    /// Get the list of applied operations.
+    fn get_operations(&self) -> Vec<String>;
+    /// Get the number of bytes written by the last save.
+    fn get_bytes_written(&self) -> Option<u64>;
+    /// Get the time taken to encode and write the image by the last save.
+    fn get_encode_duration(&self) -> Option<Duration>;
+    /// Get the warnings found while processing the image.
+    fn get_warnings(&self) -> Vec<Warning>;
+
+    /// Get the file path to save the image to.
+    /// If the destination is a directory, the image is saved in it with the file name of the source.
+    /// If the destination is None, the image is saved next to the source with the new extension.
+    fn get_save_filepath(&self, source_filepath: &Path, destination_filepath: Option<PathBuf>, new_extension: &str) -> Result<PathBuf, RusimgError> {
+        match destination_filepath {
+            Some(path) if path.is_dir() => {
+                let filename = source_filepath.file_name().ok_or(RusimgError::FailedToGetFilename(source_filepath.to_path_buf()))?;
+                Ok(path.join(filename).with_extension(new_extension))
+            },
+            Some(path) => Ok(path),
+            None => Ok(source_filepath.with_extension(new_extension)),
+        }
+    }
+}
+
+/// RusImg is an image opened by the library.
+/// - extension: Format of the image.
+/// - data: The image of the format. RusImg wraps its functions.
+pub struct RusImg {
+    pub extension: Extension,
+    pub data: Box<dyn RusimgTrait + Send + Sync>,
+}
+
+impl Clone for RusImg {
+    fn clone(&self) -> Self {
+        self.clone_image()
+    }
+}
+
+impl RusImg {
+    /// Get image size.
+    pub fn get_image_size(&self) -> Result<ImgSize, RusimgError> {
+        Ok(self.data.get_size())
+    }
+
+    /// Resize an image.
+    /// Set ratio to 100.0 to keep the original size.
+    pub fn resize(&mut self, ratio: f32) -> Result<ImgSize, RusimgError> {
+        if ratio <= 0.0 || !ratio.is_finite() {
+            return Err(RusimgError::InvalidResizeRatio);
+        }
+        self.data.resize(ratio)
+    }
+
+    /// Trim an image. Set the trim area with four u32 values: x, y, w, h.
+    pub fn trim(&mut self, trim_x: u32, trim_y: u32, trim_w: u32, trim_h: u32) -> Result<ImgSize, RusimgError> {
+        self.data.trim(Rect::new(trim_x, trim_y, trim_w, trim_h))
+    }
+
+    /// Trim an image. Set the trim area with a Rect object.
+    pub fn trim_rect(&mut self, trim_area: Rect) -> Result<ImgSize, RusimgError> {
+        self.data.trim(trim_area)
+    }
+
+    /// Grayscale an image.
+    pub fn grayscale(&mut self) -> Result<(), RusimgError> {
+        self.data.grayscale();
+        Ok(())
+    }
+
+    /// Compress an image.
+    /// Set quality to 100 to keep the original quality.
+    pub fn compress(&mut self, quality: Option<f32>) -> Result<(), RusimgError> {
+        if let Some(quality) = quality {
+            if !(0.0..=100.0).contains(&quality) {
+                return Err(RusimgError::InvalidCompressionLevel);
+            }
+        }
+        self.data.compress(quality)
+    }
+
+    /// Convert an image to another format.
+    /// The image is imported into the new format, so the source path and metadata are kept.
+    pub fn convert(&mut self, new_extension: &Extension) -> Result<(), RusimgError> {
+        let dynamic_image = self.data.to_owned_dynamic_image();
+        let filepath = self.data.get_source_filepath();
+        let metadata = self.data.get_metadata_src();
+
+        self.data = import_image(new_extension, dynamic_image, filepath, metadata)?;
+        self.extension = new_extension.clone();
+        Ok(())
+    }
+
+    /// Set a DynamicImage to an Img.
+    pub fn set_dynamic_image(&mut self, image: DynamicImage) -> Result<(), RusimgError> {
+        self.data.set_dynamic_image(image)
+    }
+
+    /// Get a DynamicImage from an Img.
+    /// This copies the whole image; use as_dynamic_image() to avoid the copy.
+    pub fn get_dynamic_image(&mut self) -> Result<DynamicImage, RusimgError> {
+        self.data.get_dynamic_image()
+    }
+
+    /// Borrow the DynamicImage of an Img without copying.
+    pub fn as_dynamic_image(&self) -> &DynamicImage {
+        self.data.as_dynamic_image()
+    }
+
+    /// Get a copy of the DynamicImage of an Img.
+    pub fn to_owned_dynamic_image(&self) -> DynamicImage {
+        self.data.to_owned_dynamic_image()
+    }
+
+    /// Clone an image.
+    /// The pixel data is shared until either image is modified (copy-on-write), so branching a pipeline is cheap.
+    pub fn clone_image(&self) -> RusImg {
+        RusImg {
+            extension: self.extension.clone(),
+            data: self.data.box_clone(),
+        }
+    }
+
+    /// Enable undo. limit is the maximum number of operations that can be undone (0: disabled, default).
+    pub fn set_history_limit(&mut self, limit: usize) {
+        self.data.set_history_limit(limit);
+    }
+
+    /// Undo the last operation (resize, trim, grayscale, compress or set_dynamic_image).
+    /// Returns false if there is nothing to undo.
+    pub fn undo(&mut self) -> bool {
+        self.data.undo()
+    }
+
+    /// Get the list of applied operations (e.g. "resize 50%", "grayscale").
+    pub fn history(&self) -> Vec<String> {
+        self.data.get_operations()
+    }
+
+    /// Get the warnings found while processing the image (e.g. a clamped trim area).
+    pub fn warnings(&self) -> Vec<Warning> {
+        self.data.get_warnings()
+    }
+
+    /// Get the histogram of each channel (8-bit).
+    pub fn histogram(&self) -> Histogram {
+        stats::histogram(self.as_dynamic_image())
+    }
+
+    /// Get the mean, standard deviation, min and max of each channel and of the brightness (luma).
+    pub fn stats(&self) -> ImageStats {
+        stats::stats(self.as_dynamic_image())
+    }
+
+    /// Get file extension.
+    pub fn get_extension(&self) -> Extension {
+        self.extension.clone()
+    }
+
+    /// Get input file path.
+    pub fn get_input_filepath(&self) -> PathBuf {
+        self.data.get_source_filepath()
+    }
+
+    /// Save an image to a file.
+    /// If path is None, the image is saved next to the source with the extension of the format.
+    pub fn save_image(&mut self, path: Option<&str>) -> Result<SaveStatus, RusimgError> {
+        self.save_image_with(path, &SaveOptions::default())
+    }
+
+    /// Save an image to a file with options.
+    /// If path is None, the image is saved next to the source with the extension of the format.
+    pub fn save_image_with(&mut self, path: Option<&str>, options: &SaveOptions) -> Result<SaveStatus, RusimgError> {
+        let before_filesize = self.data.get_metadata_src().len();
+        self.data.save(path.map(PathBuf::from), options)?;
+
+        Ok(SaveStatus {
+            output_path: self.data.get_destination_filepath(),
+            before_filesize: before_filesize,
+            after_filesize: self.data.get_bytes_written(),
+            elapsed: self.data.get_encode_duration(),
+            operations: self.data.get_operations(),
+            warnings: self.data.get_warnings(),
+        })
+    }
+}
+
+/// Open an image file.
+/// The format is detected from the content of the file, not from its extension.
+pub fn open_image(path: &Path) -> Result<RusImg, RusimgError> {
+    open_image_with(path, &OpenOptions::default())
+}
+
+/// Open an image file with options (see OpenOptions).
+pub fn open_image_with(path: &Path, options: &OpenOptions) -> Result<RusImg, RusimgError> {
+    let buf = source::read(path, options)?;
+    let metadata = std::fs::metadata(path).map_err(|e| RusimgError::FailedToGetMetadata(e.to_string()))?;
+    let path = path.to_path_buf();
+
+    let format = image::guess_format(&buf).map_err(|e| RusimgError::FailedToOpenImage(e.to_string()))?;
+    match format {
+        #[cfg(feature = "bmp")]
+        image::ImageFormat::Bmp => Ok(RusImg {
+            extension: Extension::Bmp,
+            data: Box::new(bmp::BmpImage::open(path, buf, metadata)?),
+        }),
+        #[cfg(any(feature = "jpeg", feature = "jpeg-pure"))]
+        image::ImageFormat::Jpeg => Ok(RusImg {
+            extension: Extension::Jpeg,
+            data: Box::new(jpeg::JpegImage::open(path, buf, metadata)?),
+        }),
+        #[cfg(feature = "png")]
+        image::ImageFormat::Png => Ok(RusImg {
+            extension: Extension::Png,
+            data: Box::new(png::PngImage::open(path, buf, metadata)?),
+        }),
+        #[cfg(any(feature = "webp", feature = "webp-pure"))]
+        image::ImageFormat::WebP => Ok(RusImg {
+            extension: Extension::Webp,
+            data: Box::new(webp::WebpImage::open(path, buf, metadata)?),
+        }),
+        _ => Err(RusimgError::UnsupportedFileExtension),
+    }
+}
+
+/// Import a DynamicImage into the image of a format.
+fn import_image(extension: &Extension, image: DynamicImage, source_path: PathBuf, source_metadata: Metadata) -> Result<Box<dyn RusimgTrait + Send + Sync>, RusimgError> {
+    match extension {
+        #[cfg(feature = "bmp")]
+        Extension::Bmp => Ok(Box::new(bmp::BmpImage::import(image, source_path, source_metadata)?)),
+        #[cfg(any(feature = "jpeg", feature = "jpeg-pure"))]
+        Extension::Jpeg => Ok(Box::new(jpeg::JpegImage::import(image, source_path, source_metadata)?)),
+        #[cfg(feature = "png")]
+        Extension::Png => Ok(Box::new(png::PngImage::import(image, source_path, source_metadata)?)),
+        #[cfg(any(feature = "webp", feature = "webp-pure"))]
+        Extension::Webp => Ok(Box::new(webp::WebpImage::import(image, source_path, source_metadata)?)),
+        Extension::ExternalFormat(_) => Err(RusimgError::FailedToConvertExtension),
+        #[allow(unreachable_patterns)]
+        _ => Err(RusimgError::UnsupportedFileExtension),
+    }
+}
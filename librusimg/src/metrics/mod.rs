@@ -0,0 +1,71 @@
+use image::DynamicImage;
+
+/// Size of the windows compared by ssim().
+const WINDOW: usize = 8;
+/// Step between the windows. Windows overlap by half, which is close to the full sliding window and 4 times faster.
+const STEP: usize = 4;
+/// Stabilizing constants of SSIM for 8-bit values: (0.01 * 255)^2 and (0.03 * 255)^2.
+const C1: f64 = 6.5025;
+const C2: f64 = 58.5225;
+
+/// SSIM of one window of two luma planes.
+fn window_ssim(a: &[u8], b: &[u8], width: usize, x: usize, y: usize) -> f64 {
+    let n = (WINDOW * WINDOW) as f64;
+    let (mut sum_a, mut sum_b, mut sum_aa, mut sum_bb, mut sum_ab) = (0.0, 0.0, 0.0, 0.0, 0.0);
+    for row in y..y + WINDOW {
+        let start = row * width + x;
+        for (va, vb) in a[start..start + WINDOW].iter().zip(&b[start..start + WINDOW]) {
+            let (va, vb) = (*va as f64, *vb as f64);
+            sum_a += va;
+            sum_b += vb;
+            sum_aa += va * va;
+            sum_bb += vb * vb;
+            sum_ab += va * vb;
+        }
+    }
+    let (mean_a, mean_b) = (sum_a / n, sum_b / n);
+    let var_a = sum_aa / n - mean_a * mean_a;
+    let var_b = sum_bb / n - mean_b * mean_b;
+    let covariance = sum_ab / n - mean_a * mean_b;
+
+    ((2.0 * mean_a * mean_b + C1) * (2.0 * covariance + C2))
+        / ((mean_a * mean_a + mean_b * mean_b + C1) * (var_a + var_b + C2))
+}
+
+/// Compute the structural similarity (SSIM) of two images.
+/// The brightness of the images is compared in 8x8 windows, and the mean of the windows is returned (1.0: identical, lower is more different).
+/// Returns None if the images have different sizes.
+/// Images smaller than a window are compared as a single window of the whole image.
+pub fn ssim(reference: &DynamicImage, image: &DynamicImage) -> Option<f64> {
+    if reference.width() != image.width() || reference.height() != image.height() {
+        return None;
+    }
+    let (width, height) = (reference.width() as usize, reference.height() as usize);
+    if width == 0 || height == 0 {
+        return Some(1.0);
+    }
+    let (a, b) = (reference.to_luma8(), image.to_luma8());
+    let (a, b) = (a.as_raw(), b.as_raw());
+
+    if width < WINDOW || height < WINDOW {
+        // 画像全体を 1 つの窓として比較
+        let n = (width * height) as f64;
+        let mean = |p: &[u8]| p.iter().map(|v| *v as f64).sum::<f64>() / n;
+        let (mean_a, mean_b) = (mean(a), mean(b));
+        let var_a = a.iter().map(|v| (*v as f64 - mean_a).powi(2)).sum::<f64>() / n;
+        let var_b = b.iter().map(|v| (*v as f64 - mean_b).powi(2)).sum::<f64>() / n;
+        let covariance = a.iter().zip(b).map(|(va, vb)| (*va as f64 - mean_a) * (*vb as f64 - mean_b)).sum::<f64>() / n;
+        return Some(((2.0 * mean_a * mean_b + C1) * (2.0 * covariance + C2))
+            / ((mean_a * mean_a + mean_b * mean_b + C1) * (var_a + var_b + C2)));
+    }
+
+    let mut total = 0.0;
+    let mut count = 0usize;
+    for y in (0..=height - WINDOW).step_by(STEP) {
+        for x in (0..=width - WINDOW).step_by(STEP) {
+            total += window_ssim(a, b, width, x, y);
+            count += 1;
+        }
+    }
+    Some(total / count as f64)
+}
@@ -0,0 +1,144 @@
+use std::path::Path;
+
+use super::RusimgError;
+
+/// MetadataPolicy determines whether metadata chunks are kept in the output.
+/// This is applied when the encoder rewrites the file (e.g. oxipng for png).
+/// - Strip: Remove metadata which is not needed to display the image.
+/// - Keep: Keep metadata as far as the encoder allows.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum MetadataPolicy {
+    #[default]
+    Strip,
+    Keep,
+}
+
+/// JpegOptions is a structure that represents the mozjpeg encoder settings for power users.
+/// - trellis: Use trellis quantization and scan optimization (default: true). false uses the fastest mozjpeg profile (faster, slightly larger).
+/// - optimize_coding: Optimize the Huffman tables (default: true). Ignored with arithmetic_coding.
+/// - smoothing: Smoothing factor 0-100 (default: 0). Reduces the noise of dithered sources before encoding.
+/// - restart_interval: Number of MCUs between restart markers (default: 0, no markers). Decoders can resynchronize at the markers after corrupted data, at the cost of a slightly larger file.
+/// - arithmetic_coding: Use arithmetic coding instead of Huffman coding (default: false). Files are a few percent smaller, but many decoders (including browsers and the image crate) cannot read them.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct JpegOptions {
+    pub trellis: bool,
+    pub optimize_coding: bool,
+    pub smoothing: u8,
+    pub restart_interval: u16,
+    pub arithmetic_coding: bool,
+}
+impl Default for JpegOptions {
+    fn default() -> Self {
+        Self {
+            trellis: true,
+            optimize_coding: true,
+            smoothing: 0,
+            restart_interval: 0,
+            arithmetic_coding: false,
+        }
+    }
+}
+
+/// WebpOptions is a structure that represents the libwebp encoder settings for images with alpha.
+/// - alpha_quality: Quality of the alpha channel 0-100 (default: 100). Lower values make the alpha plane smaller.
+/// - exact: Keep the RGB values under fully transparent pixels (default: false). By default libwebp replaces them to compress better.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WebpOptions {
+    pub alpha_quality: u8,
+    pub exact: bool,
+}
+impl Default for WebpOptions {
+    fn default() -> Self {
+        Self {
+            alpha_quality: 100,
+            exact: false,
+        }
+    }
+}
+
+/// PngOptions is a structure that represents the PNG encoder settings.
+/// - interlace: Save as an interlaced (Adam7) PNG, which is displayed progressively while loading. Interlaced files are slightly larger.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct PngOptions {
+    pub interlace: bool,
+}
+
+/// BmpFormat is the pixel format of a BMP file.
+/// - Rgb24: 24-bit RGB (default).
+/// - Bgra32: 32-bit BGRA with an alpha channel (BITMAPV4HEADER).
+/// - Indexed8: 8-bit paletted. The exact palette is used if the image has up to 256 colors; otherwise the colors are reduced with the quantize module.
+/// - Indexed4: 4-bit paletted (16 colors), in the same way as Indexed8.
+/// - Mono1: 1-bit black and white, thresholded at 50% brightness.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum BmpFormat {
+    #[default]
+    Rgb24,
+    Bgra32,
+    Indexed8,
+    Indexed4,
+    Mono1,
+}
+
+/// BmpOptions is a structure that represents the BMP encoder settings.
+/// - format: Pixel format of the output.
+/// - rle: Compress 8-bit paletted images with RLE8. 24-bit images with up to 256 colors are written as RLE8 paletted images too (lossless).
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct BmpOptions {
+    pub format: BmpFormat,
+    pub rle: bool,
+}
+
+/// SaveOptions is a structure that represents the options for saving an image.
+/// Options that are not supported by the image format are ignored.
+/// - progressive: Save as a progressive image (jpeg).
+/// - metadata: Whether to keep metadata (png).
+/// - effort: Compression effort 0-100 (png, webp). Higher is smaller but slower. If None, the default of each format is used.
+/// - overwrite: Overwrite the destination file if it already exists.
+/// - jpeg: mozjpeg encoder settings (jpeg).
+/// - png: PNG encoder settings (png).
+/// - webp: libwebp settings for the alpha channel (webp).
+/// - bmp: Pixel format of the output (bmp).
+/// - encoder_threads: Maximum number of threads used by the encoder (png: oxipng, webp: multithreaded encoding if > 1). If None, the default of each encoder is used. mozjpeg (jpeg) always encodes in a single thread.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SaveOptions {
+    pub progressive: bool,
+    pub metadata: MetadataPolicy,
+    pub effort: Option<u8>,
+    pub overwrite: bool,
+    pub encoder_threads: Option<usize>,
+    pub jpeg: JpegOptions,
+    pub png: PngOptions,
+    pub webp: WebpOptions,
+    pub bmp: BmpOptions,
+}
+impl Default for SaveOptions {
+    fn default() -> Self {
+        Self {
+            progressive: false,
+            metadata: MetadataPolicy::default(),
+            effort: None,
+            overwrite: true,
+            encoder_threads: None,
+            jpeg: JpegOptions::default(),
+            png: PngOptions::default(),
+            webp: WebpOptions::default(),
+            bmp: BmpOptions::default(),
+        }
+    }
+}
+
+impl SaveOptions {
+    /// Check if the image can be saved to the path.
+    /// If overwrite is false and the file already exists, an error is returned.
+    pub fn check_overwrite(&self, path: &Path) -> Result<(), RusimgError> {
+        if !self.overwrite && path.exists() {
+            return Err(RusimgError::FailedToSaveImage(format!("{} already exists", path.display())));
+        }
+        Ok(())
+    }
+
+    /// Convert the compression effort (0-100) to a level between min and max.
+    pub fn effort_level(&self, min: u8, max: u8) -> Option<u8> {
+        self.effort.map(|e| min + ((e.min(100) as f32 / 100.0) * (max - min) as f32).round() as u8)
+    }
+}
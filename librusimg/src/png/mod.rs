@@ -0,0 +1,443 @@
+use std::io::{Write, Cursor};
+use std::fs::Metadata;
+use std::time::{Duration, Instant};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::path::PathBuf;
+use std::borrow::Cow;
+use image::DynamicImage;
+
+use super::{RusimgTrait, RusimgError, ImgSize, Rect, Extension};
+use super::rect::TrimPolicy;
+use super::history::History;
+use super::options::{SaveOptions, MetadataPolicy};
+use super::pool;
+use super::source::SourceData;
+use super::stats;
+use super::warning::{self, Warning};
+
+/// Get the thread pool for oxipng with the given number of threads.
+/// The pools are built once per number of threads and shared by all saves.
+fn encoder_pool(threads: usize) -> Result<Arc<rayon::ThreadPool>, RusimgError> {
+    static POOLS: OnceLock<Mutex<HashMap<usize, Arc<rayon::ThreadPool>>>> = OnceLock::new();
+    let threads = threads.max(1);
+    let mut pools = POOLS.get_or_init(|| Mutex::new(HashMap::new())).lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(pool) = pools.get(&threads) {
+        return Ok(Arc::clone(pool));
+    }
+    let pool = Arc::new(rayon::ThreadPoolBuilder::new().num_threads(threads).build().map_err(|e| RusimgError::FailedToSaveImage(e.to_string()))?);
+    pools.insert(threads, Arc::clone(&pool));
+    Ok(pool)
+}
+
+#[derive(Debug, Clone)]
+pub struct PngImage {
+    binary_data: Arc<Vec<u8>>,
+    pub image: Arc<DynamicImage>,
+    image_bytes: Option<Vec<u8>>,
+    width: usize,
+    height: usize,
+    operations_count: u32,
+    pixels_modified: bool,
+    compress_level: Option<u8>,
+    pub metadata_input: Metadata,
+    pub metadata_output: Option<Metadata>,
+    pub filepath_input: PathBuf,
+    pub filepath_output: Option<PathBuf>,
+    operations: Vec<String>,
+    bytes_written: Option<u64>,
+    encode_duration: Option<Duration>,
+    warnings: Vec<Warning>,
+    history: History<Self>,
+}
+
+impl PngImage {
+    /// Record the current state so that the next operation can be undone.
+    fn record(&mut self) {
+        if !self.history.is_enabled() {
+            return;
+        }
+        let snapshot = self.clone();
+        self.history.push(snapshot);
+    }
+
+    /// Mark the pixels as modified.
+    /// The compressed data no longer matches the image, so it is dropped; save() compresses the new pixels again.
+    fn invalidate(&mut self) {
+        self.pixels_modified = true;
+        if let Some(image_bytes) = self.image_bytes.take() {
+            pool::global().give(image_bytes);
+        }
+    }
+
+    /// Get the image to be encoded.
+    /// A color image whose pixels are all gray is converted to the single-channel type with the same bit depth and alpha.
+    fn output_image(&self) -> Cow<'_, DynamicImage> {
+        if !self.image.color().has_color() || !stats::is_grayscale(&self.image) {
+            return Cow::Borrowed(self.image.as_ref());
+        }
+        Cow::Owned(match self.image.as_ref() {
+            DynamicImage::ImageRgb8(_) => DynamicImage::ImageLuma8(self.image.to_luma8()),
+            DynamicImage::ImageRgba8(_) => DynamicImage::ImageLumaA8(self.image.to_luma_alpha8()),
+            DynamicImage::ImageRgb16(_) => DynamicImage::ImageLuma16(self.image.to_luma16()),
+            DynamicImage::ImageRgba16(_) => DynamicImage::ImageLumaA16(self.image.to_luma_alpha16()),
+            _ => return Cow::Borrowed(self.image.as_ref()),
+        })
+    }
+
+    /// Encode the current image as PNG.
+    /// The color type of the image (L, LA, RGB, RGBA, 8/16-bit) is kept, so the alpha channel is never dropped or added.
+    /// Gray color images are written as single-channel PNG.
+    fn encode_current(&self) -> Result<Vec<u8>, RusimgError> {
+        let mut png_data = pool::global().take(self.width * self.height);
+        self.output_image().write_to(&mut Cursor::new(&mut png_data), image::ImageFormat::Png)
+            .map_err(|e| RusimgError::FailedToSaveImage(e.to_string()))?;
+        Ok(png_data)
+    }
+
+    /// Compress the image with oxipng.
+    /// The original file is used as long as the pixels are not modified; otherwise the current image is encoded first.
+    fn optimize(&self, level: u8) -> Result<Vec<u8>, RusimgError> {
+        let current = if self.pixels_modified { Some(self.encode_current()?) } else { None };
+        let source = current.as_deref().unwrap_or(self.binary_data.as_slice());
+
+        // インターレースの有無は元のデータのまま（oxipng のデフォルトでは解除される）
+        let mut oxipng_options = oxipng::Options::from_preset(level);
+        oxipng_options.interlace = None;
+        let result = oxipng::optimize_from_memory(source, &oxipng_options);
+        if let Some(current) = current {
+            pool::global().give(current);
+        }
+
+        result.map_err(|e| {
+            let oxipng_err = match e {
+                oxipng::PngError::DeflatedDataTooLong(s) => format!("(oxipng) deflated data too long: {}", s),
+                oxipng::PngError::TimedOut => "(oxipng) timed out".to_string(),
+                oxipng::PngError::NotPNG => "(oxipng) not png".to_string(),
+                oxipng::PngError::APNGNotSupported => "(oxipng) apng not supported".to_string(),
+                oxipng::PngError::InvalidData => "(oxipng) invalid data".to_string(),
+                oxipng::PngError::TruncatedData => "(oxipng) truncated data".to_string(),
+                oxipng::PngError::ChunkMissing(s) => format!("(oxipng) chunk missing: {}", s),
+                oxipng::PngError::Other(s) => format!("(oxipng) other: {}", s),
+                _ => "unknown error".to_string(),
+            };
+            RusimgError::FailedToCompressImage(Some(oxipng_err))
+        })
+    }
+}
+
+impl RusimgTrait for PngImage {
+    /// Import an image from a DynamicImage object.
+    fn import(image: DynamicImage, source_path: PathBuf, source_metadata: Metadata) -> Result<Self, RusimgError> {
+        let (width, height) = (image.width() as usize, image.height() as usize);
+
+        let mut new_binary_data = pool::global().take(width * height);
+        image.write_to(&mut Cursor::new(&mut new_binary_data), image::ImageFormat::Png)
+            .map_err(|e| RusimgError::FailedToCopyBinaryData(e.to_string()))?;
+
+        Ok(Self {
+            binary_data: Arc::new(new_binary_data),
+            image: Arc::new(image),
+            image_bytes: None,
+            width,
+            height,
+            operations_count: 0,
+            pixels_modified: false,
+            compress_level: None,
+            metadata_input: source_metadata,
+            metadata_output: None,
+            filepath_input: source_path,
+            filepath_output: None,
+            operations: Vec::new(),
+            bytes_written: None,
+            encode_duration: None,
+            warnings: Vec::new(),
+            history: History::default(),
+        })
+    }
+
+    /// Open an image from a image buffer.
+    /// The original bytes are kept for compress(), so a memory-mapped file is copied after decoding.
+    fn open(path: PathBuf, image_buf: SourceData, metadata: Metadata) -> Result<Self, RusimgError> {
+        let image = image::load_from_memory(&image_buf).map_err(|e| RusimgError::FailedToOpenImage(e.to_string()))?;
+        let (width, height) = (image.width() as usize, image.height() as usize);
+        let warnings = warning::check_format(&path, &image_buf).into_iter().collect();
+
+        Ok(Self {
+            binary_data: Arc::new(image_buf.into_vec()),
+            image: Arc::new(image),
+            image_bytes: None,
+            width,
+            height,
+            operations_count: 0,
+            pixels_modified: false,
+            compress_level: None,
+            metadata_input: metadata,
+            metadata_output: None,
+            filepath_input: path,
+            filepath_output: None,
+            operations: Vec::new(),
+            bytes_written: None,
+            encode_duration: None,
+            warnings: warnings,
+            history: History::default(),
+        })
+    }
+
+    /// Save the image to a file.
+    /// If options.effort is specified, the output is optimized by oxipng with the corresponding level (1-6).
+    /// If options.encoder_threads is specified, oxipng runs on a thread pool of that size (built once and reused by later saves).
+    /// If options.png.interlace is true, the output is rewritten as an Adam7 interlaced PNG by oxipng (level 1 if effort is not specified).
+    /// Otherwise oxipng keeps the interlacing of the data as it is.
+    /// Metadata is kept only if oxipng rewrites the source data; with MetadataPolicy::Keep, a warning is added if the pixels are encoded again.
+    fn save(&mut self, path: Option<PathBuf>, options: &SaveOptions) -> Result<(), RusimgError> {
+        let start = Instant::now();
+        let save_path = Self::get_save_filepath(self, &self.filepath_input, path, &Extension::Png.extension_for(&self.filepath_input))?;
+        options.check_overwrite(&save_path)?;
+
+        // compress() の後に画素が変更された場合、同じ level で再圧縮
+        if let (None, Some(level)) = (&self.image_bytes, self.compress_level) {
+            let data = self.optimize(level)?;
+            self.image_bytes = Some(data);
+        }
+
+        // effort または interlace が指定されている場合、oxipng で最適化してから保存
+        if options.effort.is_some() || options.png.interlace {
+            let level = options.effort_level(1, 6).unwrap_or(1);
+            let png_data = match &self.image_bytes {
+                Some(image_bytes) => image_bytes.clone(),
+                None => self.encode_current()?,
+            };
+            let mut oxipng_options = oxipng::Options::from_preset(level);
+            oxipng_options.strip = match options.metadata {
+                MetadataPolicy::Strip => oxipng::StripChunks::Safe,
+                MetadataPolicy::Keep => oxipng::StripChunks::None,
+            };
+            oxipng_options.interlace = if options.png.interlace { Some(oxipng::Interlacing::Adam7) } else { None };
+            let optimize = || oxipng::optimize_from_memory(&png_data, &oxipng_options);
+            let optimized = match options.encoder_threads {
+                // oxipng is parallelized on a rayon thread pool, which is shared by the saves with the same number of threads.
+                Some(threads) => encoder_pool(threads)?.install(optimize),
+                None => optimize(),
+            };
+            let optimized = optimized.map_err(|e| RusimgError::FailedToSaveImage(e.to_string()))?;
+            pool::global().give(png_data);
+            pool::replace(&mut self.image_bytes, optimized);
+        }
+        
+        // 元のデータを使わない場合、メタデータは残らない
+        if options.metadata == MetadataPolicy::Keep && (self.pixels_modified || self.image_bytes.is_none()) {
+            warning::push(&mut self.warnings, Warning::MetadataDropped("metadata".to_string()));
+        }
+
+        // image_bytes != None の場合、oxipng で圧縮したバイナリデータを保存
+        if let Some(image_bytes) = &self.image_bytes {
+            let mut file = std::fs::File::create(&save_path).map_err(|e| RusimgError::FailedToCreateFile(e.to_string()))?;
+            file.write_all(image_bytes).map_err(|e| RusimgError::FailedToWriteFIle(e.to_string()))?;
+            self.metadata_output = Some(file.metadata().map_err(|e| RusimgError::FailedToGetMetadata(e.to_string()))?);
+        }
+        // image_bytes == None の場合、DynamicImage を 保存（色の種類はそのまま）
+        else {
+            self.output_image().save_with_format(&save_path, image::ImageFormat::Png).map_err(|e| RusimgError::FailedToSaveImage(e.to_string()))?;
+            self.metadata_output = Some(std::fs::metadata(&save_path).map_err(|e| RusimgError::FailedToGetMetadata(e.to_string()))?);
+        }
+
+        self.bytes_written = self.metadata_output.as_ref().map(|m| m.len());
+
+        self.encode_duration = Some(start.elapsed());
+
+        self.filepath_output = Some(save_path);
+
+        Ok(())
+    }
+
+    /// Compress the image.
+    /// quality: Option<f32> 0.0 - 100.0
+    /// Because oxipng supports only 6 levels of compression, the quality value is converted to a level value.
+    /// If the image is modified after compress(), the modified image is compressed again with the same level when it is saved.
+    fn compress(&mut self, quality: Option<f32>) -> Result<(), RusimgError> {
+        // quality の値に応じて level を設定
+        let level = if let Some(q) = quality {
+            if q <= 17.0 {
+                1
+            }
+            else if q > 17.0 && q <= 34.0 {
+                2
+            }
+            else if q > 34.0 && q <= 51.0 {
+                3
+            }
+            else if q > 51.0 && q <= 68.0 {
+                4
+            }
+            else if q > 68.0 && q <= 85.0 {
+                5
+            }
+            else {
+                6
+            }
+        }
+        else {
+            5       // default
+        };
+
+        let data = self.optimize(level)?;
+        self.record();
+        pool::replace(&mut self.image_bytes, data);
+        self.compress_level = Some(level);
+        self.operations_count += 1;
+        self.operations.push(format!("compress (level {})", level));
+        Ok(())
+    }
+
+    /// Resize the image.
+    /// resize_ratio: f32 in percent (must be 0.0 < resize_ratio, e.g. 12.5)
+    fn resize(&mut self, resize_ratio: f32) -> Result<ImgSize, RusimgError> {
+        self.record();
+        let nwidth = (self.width as f32 * (resize_ratio / 100.0)) as usize;
+        let nheight = (self.height as f32 * (resize_ratio / 100.0)) as usize;
+
+        self.image = Arc::new(self.image.resize(nwidth as u32, nheight as u32, image::imageops::FilterType::Lanczos3));
+        self.invalidate();
+
+        self.width = nwidth;
+        self.height = nheight;
+
+        self.operations_count += 1;
+        self.operations.push(format!("resize {}%", resize_ratio));
+        Ok(ImgSize::new(self.width, self.height))
+    }
+
+    /// Trim the image.
+    /// trim: librusimg::Rect { x: u32, y: u32, w: u32, h: u32 }
+    /// The area is clamped to the image size with Rect::clamp_to().
+    fn trim(&mut self, trim: Rect) -> Result<ImgSize, RusimgError> {
+        let requested = trim;
+        let trim = requested.clamp_to(self.get_size(), TrimPolicy::Clamp)?;
+        self.record();
+        if let Some(clamped) = warning::check_trim(&requested, &trim) {
+            warning::push(&mut self.warnings, clamped);
+        }
+
+        self.image = Arc::new(self.image.crop_imm(trim.x, trim.y, trim.w, trim.h));
+        self.invalidate();
+
+        self.width = trim.w as usize;
+        self.height = trim.h as usize;
+        self.operations.push(format!("trim {}x{}+{}x{}", trim.x, trim.y, trim.w, trim.h));
+
+        Ok(ImgSize::new(self.width, self.height))
+    }
+
+    /// Convert the image to grayscale.
+    fn grayscale(&mut self) {
+        self.record();
+        self.image = Arc::new(self.image.grayscale());
+        self.invalidate();
+        self.operations.push("grayscale".to_string());
+        self.operations_count += 1;
+    }
+
+    /// Set the image to a DynamicImage object.
+    /// The image size is updated to the size of the new image.
+    fn set_dynamic_image(&mut self, image: DynamicImage) -> Result<(), RusimgError> {
+        self.record();
+        self.image = Arc::new(image);
+        self.invalidate();
+        self.width = self.image.width() as usize;
+        self.height = self.image.height() as usize;
+        Ok(())
+    }
+
+    /// Get the DynamicImage object.
+    /// This copies the whole image; use as_dynamic_image() to avoid the copy.
+    fn get_dynamic_image(&mut self) -> Result<DynamicImage, RusimgError> {
+        Ok(self.to_owned_dynamic_image())
+    }
+
+    /// Borrow the DynamicImage object without copying.
+    fn as_dynamic_image(&self) -> &DynamicImage {
+        &self.image
+    }
+
+    /// Get a copy of the DynamicImage object.
+    fn to_owned_dynamic_image(&self) -> DynamicImage {
+        self.image.as_ref().clone()
+    }
+
+    /// Clone the image into a new trait object.
+    /// The pixel data is shared with the original until either of them is modified (copy-on-write), so this is cheap.
+    fn box_clone(&self) -> Box<dyn RusimgTrait + Send + Sync> {
+        Box::new(self.clone())
+    }
+
+    /// Set the maximum number of operations that can be undone (0: disabled, default).
+    fn set_history_limit(&mut self, limit: usize) {
+        self.history.set_limit(limit);
+    }
+
+    /// Undo the last operation (resize, trim, grayscale, compress or set_dynamic_image).
+    /// Returns false if there is nothing to undo.
+    fn undo(&mut self) -> bool {
+        match self.history.pop() {
+            Some(previous) => {
+                let history = std::mem::take(&mut self.history);
+                *self = previous;
+                self.history = history;
+                true
+            },
+            None => false,
+        }
+    }
+
+    /// Get the source file path.
+    fn get_source_filepath(&self) -> PathBuf {
+        self.filepath_input.clone()
+    }
+
+    /// Get the destination file path.
+    fn get_destination_filepath(&self) -> Option<PathBuf> {
+        self.filepath_output.clone()
+    }
+
+    /// Get the source metadata.
+    fn get_metadata_src(&self) -> Metadata {
+        self.metadata_input.clone()
+    }
+
+    /// Get the destination metadata.
+    fn get_metadata_dest(&self) -> Option<Metadata> {
+        self.metadata_output.clone()
+    }
+
+    /// Get the image size.
+    fn get_size(&self) -> ImgSize {
+        ImgSize::new(self.width, self.height)
+    }
+
+    /// Get the list of applied operations.
+    fn get_operations(&self) -> Vec<String> {
+        self.operations.clone()
+    }
+
+    /// Get the number of bytes written by the last save.
+    fn get_bytes_written(&self) -> Option<u64> {
+        self.bytes_written
+    }
+
+    /// Get the time taken to encode and write the image by the last save.
+    fn get_encode_duration(&self) -> Option<Duration> {
+        self.encode_duration
+    }
+
+    /// Get the warnings found while processing the image.
+    fn get_warnings(&self) -> Vec<Warning> {
+        self.warnings.clone()
+    }
+}
+
+// RusImg holds the backend as Box<dyn RusimgTrait + Send + Sync>, so PngImage must stay Send + Sync.
+const _: fn() = || {
+    fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<PngImage>();
+};
@@ -0,0 +1,125 @@
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Maximum number of buffers kept in the pool.
+const MAX_POOLED_BUFFERS: usize = 16;
+/// Default limit of the total capacity of the buffers kept in the pool (64 MiB).
+/// Buffers larger than the limit are not kept, so that huge images do not pin their memory for the whole batch.
+pub const DEFAULT_MAX_POOLED_BYTES: usize = 64 * 1024 * 1024;
+
+/// BufferPool keeps byte buffers that are no longer used so that the next image can reuse them.
+/// Backends take a buffer for encoded output and give it back when the data is replaced, which reduces allocator churn in long batch runs.
+/// The pool is shared by all threads.
+/// - buffers: The pooled buffers.
+/// - max_bytes: The limit of the total capacity of the pooled buffers.
+#[derive(Debug)]
+pub struct BufferPool {
+    buffers: Mutex<Vec<Vec<u8>>>,
+    max_bytes: AtomicUsize,
+}
+
+impl Default for BufferPool {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl BufferPool {
+    /// Create an empty pool with the default limit (DEFAULT_MAX_POOLED_BYTES).
+    pub const fn new() -> Self {
+        Self::with_limit(DEFAULT_MAX_POOLED_BYTES)
+    }
+
+    /// Create an empty pool that keeps at most `max_bytes` bytes of buffers in total.
+    pub const fn with_limit(max_bytes: usize) -> Self {
+        Self { buffers: Mutex::new(Vec::new()), max_bytes: AtomicUsize::new(max_bytes) }
+    }
+
+    /// The limit of the total capacity of the pooled buffers.
+    pub fn limit(&self) -> usize {
+        self.max_bytes.load(Ordering::Relaxed)
+    }
+
+    /// Change the limit of the total capacity of the pooled buffers.
+    /// Buffers over the new limit are dropped (the smallest first). 0 disables pooling.
+    pub fn set_limit(&self, max_bytes: usize) {
+        self.max_bytes.store(max_bytes, Ordering::Relaxed);
+        let mut buffers = self.buffers.lock().unwrap_or_else(|e| e.into_inner());
+        Self::shrink(&mut buffers, max_bytes);
+    }
+
+    /// Take an empty buffer with at least `capacity` bytes reserved.
+    /// The smallest pooled buffer that is large enough is reused; if there is none, a new buffer is allocated.
+    pub fn take(&self, capacity: usize) -> Vec<u8> {
+        let mut buffers = self.buffers.lock().unwrap_or_else(|e| e.into_inner());
+        let found = buffers.iter().enumerate()
+            .filter(|(_, b)| b.capacity() >= capacity)
+            .min_by_key(|(_, b)| b.capacity())
+            .map(|(i, _)| i);
+        match found {
+            Some(i) => buffers.swap_remove(i),
+            None => Vec::with_capacity(capacity),
+        }
+    }
+
+    /// Give a buffer back to the pool.
+    /// The buffer is cleared; it is dropped if it is larger than the limit.
+    /// If the pool is full (by the number of buffers or by the total capacity), the smallest buffers are dropped.
+    pub fn give(&self, mut buffer: Vec<u8>) {
+        let max_bytes = self.limit();
+        if buffer.capacity() == 0 || buffer.capacity() > max_bytes {
+            return;
+        }
+        buffer.clear();
+        let mut buffers = self.buffers.lock().unwrap_or_else(|e| e.into_inner());
+        buffers.push(buffer);
+        Self::shrink(&mut buffers, max_bytes);
+    }
+
+    /// Total capacity of the buffers currently kept in the pool.
+    pub fn pooled_bytes(&self) -> usize {
+        self.buffers.lock().unwrap_or_else(|e| e.into_inner()).iter().map(|b| b.capacity()).sum()
+    }
+
+    /// Drop the smallest buffers until the pool fits in the number of buffers and `max_bytes`.
+    fn shrink(buffers: &mut Vec<Vec<u8>>, max_bytes: usize) {
+        let mut total: usize = buffers.iter().map(|b| b.capacity()).sum();
+        while buffers.len() > MAX_POOLED_BUFFERS || total > max_bytes {
+            // 最も小さいバッファから捨てる
+            let Some((i, _)) = buffers.iter().enumerate().min_by_key(|(_, b)| b.capacity()) else {
+                break;
+            };
+            total -= buffers.swap_remove(i).capacity();
+        }
+    }
+
+    /// Number of buffers currently kept in the pool.
+    pub fn len(&self) -> usize {
+        self.buffers.lock().unwrap_or_else(|e| e.into_inner()).len()
+    }
+
+    /// Check if the pool has no buffers.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Drop all pooled buffers and release their memory.
+    pub fn clear(&self) {
+        self.buffers.lock().unwrap_or_else(|e| e.into_inner()).clear();
+    }
+}
+
+/// The pool shared by all backends.
+static POOL: BufferPool = BufferPool::new();
+
+/// Get the pool shared by all backends.
+pub fn global() -> &'static BufferPool {
+    &POOL
+}
+
+/// Replace the data in the slot, and give the old buffer back to the global pool.
+pub fn replace(slot: &mut Option<Vec<u8>>, data: Vec<u8>) {
+    if let Some(old) = slot.replace(data) {
+        POOL.give(old);
+    }
+}
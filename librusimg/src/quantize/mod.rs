@@ -0,0 +1,122 @@
+use image::DynamicImage;
+
+use super::stats;
+
+/// Maximum number of pixels used to build a palette. Larger images are sampled at regular intervals.
+const MAX_SAMPLES: usize = 65536;
+
+/// Find the channel with the widest range of values in a set of colors.
+/// Returns the channel and its range.
+fn widest_channel(colors: &[[u8; 3]]) -> (usize, u8) {
+    (0..3).map(|c| {
+        let min = colors.iter().map(|p| p[c]).min().unwrap_or(0);
+        let max = colors.iter().map(|p| p[c]).max().unwrap_or(0);
+        (c, max - min)
+    }).max_by_key(|(_, range)| *range).unwrap_or((0, 0))
+}
+
+/// Build a palette with the median cut algorithm.
+/// The box with the widest channel is split at its median until there are enough boxes, and each box becomes the average of its colors.
+fn median_cut(samples: Vec<[u8; 3]>, colors: usize) -> Vec<[u8; 3]> {
+    let mut boxes = vec![samples];
+    while boxes.len() < colors {
+        let widest = boxes.iter().enumerate()
+            .map(|(i, b)| (i, widest_channel(b)))
+            .max_by_key(|(_, (_, range))| *range);
+        let (index, channel) = match widest {
+            Some((index, (channel, range))) if range > 0 => (index, channel),
+            // これ以上分割できない（色数が colors 未満）
+            _ => break,
+        };
+        let mut lower = boxes.swap_remove(index);
+        lower.sort_unstable_by_key(|p| p[channel]);
+        let upper = lower.split_off(lower.len() / 2);
+        boxes.push(lower);
+        boxes.push(upper);
+    }
+
+    boxes.iter().filter(|b| !b.is_empty()).map(|b| {
+        let mut sum = [0u64; 3];
+        for p in b {
+            for (s, v) in sum.iter_mut().zip(p.iter()) {
+                *s += *v as u64;
+            }
+        }
+        sum.map(|s| (s as f64 / b.len() as f64).round() as u8)
+    }).collect()
+}
+
+/// Build a palette of up to `colors` colors for the image.
+/// Gray images get evenly spaced gray levels (e.g. black and white for 2 colors), so that reduced documents keep the full contrast.
+/// Color images get a palette built with the median cut algorithm.
+pub fn palette(image: &DynamicImage, colors: usize) -> Vec<[u8; 3]> {
+    let colors = colors.clamp(2, 256);
+    if stats::is_grayscale(image) {
+        return (0..colors).map(|i| {
+            let v = (i * 255 / (colors - 1)) as u8;
+            [v, v, v]
+        }).collect();
+    }
+
+    let rgb = image.to_rgb8();
+    let step = (rgb.pixels().len() / MAX_SAMPLES).max(1);
+    let samples = rgb.pixels().step_by(step).map(|p| p.0).collect();
+    median_cut(samples, colors)
+}
+
+/// Find the index of the palette color nearest to the color.
+pub fn nearest(palette: &[[u8; 3]], color: [u8; 3]) -> usize {
+    palette.iter().enumerate().min_by_key(|(_, p)| {
+        p.iter().zip(color.iter()).map(|(a, b)| (*a as i32 - *b as i32).pow(2)).sum::<i32>()
+    }).map(|(i, _)| i).unwrap_or(0)
+}
+
+/// Map each pixel to the nearest palette color.
+/// If dither is true, the quantization error is diffused to the neighboring pixels (Floyd-Steinberg), which keeps gradients and gray tones visible with few colors.
+/// The alpha channel is not changed. Gray images are returned as Luma8 (LumaA8), and color images as Rgb8 (Rgba8).
+pub fn remap(image: &DynamicImage, palette: &[[u8; 3]], dither: bool) -> DynamicImage {
+    let mut rgba = image.to_rgba8();
+    let (width, height) = (rgba.width() as usize, rgba.height() as usize);
+    let mut values: Vec<[f32; 3]> = rgba.pixels().map(|p| [p.0[0] as f32, p.0[1] as f32, p.0[2] as f32]).collect();
+
+    for (i, pixel) in rgba.pixels_mut().enumerate() {
+        let value = values[i].map(|v| v.round().clamp(0.0, 255.0));
+        let color = palette[nearest(palette, value.map(|v| v as u8))];
+        pixel.0[..3].copy_from_slice(&color);
+
+        if dither {
+            let error = [value[0] - color[0] as f32, value[1] - color[1] as f32, value[2] - color[2] as f32];
+            let (x, y) = (i % width, i / width);
+            // 右、左下、下、右下に誤差を拡散
+            let neighbors = [
+                (x + 1 < width, i + 1, 7.0 / 16.0),
+                (x > 0 && y + 1 < height, i + width - 1, 3.0 / 16.0),
+                (y + 1 < height, i + width, 5.0 / 16.0),
+                (x + 1 < width && y + 1 < height, i + width + 1, 1.0 / 16.0),
+            ];
+            for (inside, j, weight) in neighbors {
+                if inside {
+                    for (v, e) in values[j].iter_mut().zip(error.iter()) {
+                        *v += e * weight;
+                    }
+                }
+            }
+        }
+    }
+
+    let has_alpha = image.color().has_alpha();
+    let rgba = DynamicImage::ImageRgba8(rgba);
+    match (palette.iter().all(|p| p[0] == p[1] && p[1] == p[2]), has_alpha) {
+        (true, false) => DynamicImage::ImageLuma8(rgba.to_luma8()),
+        (true, true) => DynamicImage::ImageLumaA8(rgba.to_luma_alpha8()),
+        (false, false) => DynamicImage::ImageRgb8(rgba.to_rgb8()),
+        (false, true) => rgba,
+    }
+}
+
+/// Reduce the image to at most `colors` colors (2-256).
+/// e.g. 2 colors for 1-bit, 16 colors for 4-bit and 256 colors for 8-bit output.
+pub fn quantize(image: &DynamicImage, colors: usize, dither: bool) -> DynamicImage {
+    let palette = palette(image, colors);
+    remap(image, &palette, dither)
+}
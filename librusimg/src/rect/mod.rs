@@ -0,0 +1,53 @@
+use super::{Rect, ImgSize, RusimgError};
+
+/// TrimPolicy determines how a trim area that exceeds the image is handled.
+/// - Clamp: Shrink the width/height so that the area fits in the image. The origin (x, y) must be inside the image.
+/// - Error: Return RusimgError::InvalidTrimXY if any part of the area is outside of the image.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum TrimPolicy {
+    #[default]
+    Clamp,
+    Error,
+}
+
+impl Rect {
+    /// Create a trim area from the origin (x, y) and the size (w, h) in pixels.
+    pub fn new(x: u32, y: u32, w: u32, h: u32) -> Rect {
+        Rect { x: x, y: y, w: w, h: h }
+    }
+
+    /// Create a trim area from percentages of the image size (e.g. 10.0, 10.0, 50.0, 50.0 for the area from 10% to 60% of each side).
+    /// x and w are percentages of the width, y and h of the height. Each percentage is clamped to 0.0-100.0.
+    /// The pixels are rounded to the nearest integer, and w and h are at least 1.
+    /// The area may still exceed the image (e.g. 60% + 50%); clamp_to() fits it as for any other Rect.
+    pub fn from_percent(x: f32, y: f32, w: f32, h: f32, size: ImgSize) -> Rect {
+        let percent_of = |length: usize, percent: f32| (length as f64 * percent.clamp(0.0, 100.0) as f64 / 100.0).round() as u32;
+        Rect {
+            x: percent_of(size.width, x),
+            y: percent_of(size.height, y),
+            w: percent_of(size.width, w).max(1),
+            h: percent_of(size.height, h).max(1),
+        }
+    }
+
+    /// Fit the trim area to the image size according to the policy.
+    /// Returns the area to be actually trimmed.
+    /// In both policies, an area whose origin (x, y) is outside of the image is an error.
+    pub fn clamp_to(&self, size: ImgSize, policy: TrimPolicy) -> Result<Rect, RusimgError> {
+        let (width, height) = (size.width as u64, size.height as u64);
+        if self.x as u64 >= width || self.y as u64 >= height {
+            return Err(RusimgError::InvalidTrimXY);
+        }
+
+        let exceeds = self.x as u64 + self.w as u64 > width || self.y as u64 + self.h as u64 > height;
+        match policy {
+            TrimPolicy::Error if exceeds => Err(RusimgError::InvalidTrimXY),
+            _ => Ok(Rect {
+                x: self.x,
+                y: self.y,
+                w: (self.w as u64).min(width - self.x as u64) as u32,
+                h: (self.h as u64).min(height - self.y as u64) as u32,
+            }),
+        }
+    }
+}
@@ -0,0 +1,56 @@
+use image::DynamicImage;
+use image::imageops::{self, FilterType};
+
+use super::{RusImg, Rect, ImgSize, RusimgError};
+use super::rect::TrimPolicy;
+
+/// Fit an area to the image (TrimPolicy::Clamp).
+fn clamp(image: &DynamicImage, area: Rect) -> Result<Rect, RusimgError> {
+    area.clamp_to(ImgSize::new(image.width() as usize, image.height() as usize), TrimPolicy::Clamp)
+}
+
+/// Pixelate an area of an image with square blocks of block_size pixels.
+/// Each block becomes the average color of its pixels. The blocks start at the origin of the area, and the last ones are cut at its edges.
+/// The area is clamped to the image, and the color type of the image is kept.
+pub fn pixelate(image: &DynamicImage, area: Rect, block_size: u32) -> Result<DynamicImage, RusimgError> {
+    let area = clamp(image, area)?;
+    let block_size = block_size.max(1);
+    let (columns, rows) = (area.w.div_ceil(block_size), area.h.div_ceil(block_size));
+
+    let region = image.crop_imm(area.x, area.y, area.w, area.h);
+    let blocks = region.resize_exact(columns, rows, FilterType::Triangle)
+        .resize_exact(columns * block_size, rows * block_size, FilterType::Nearest)
+        .crop_imm(0, 0, area.w, area.h);
+
+    let mut pixelated = image.clone();
+    imageops::replace(&mut pixelated, &blocks, area.x as i64, area.y as i64);
+    Ok(pixelated)
+}
+
+/// Blur an area of an image with a gaussian blur of the standard deviation sigma.
+/// Only the pixels of the area are used, so the edges of the area stay sharp and nothing outside of it is smeared in.
+/// The area is clamped to the image, and the color type of the image is kept.
+pub fn blur(image: &DynamicImage, area: Rect, sigma: f32) -> Result<DynamicImage, RusimgError> {
+    let area = clamp(image, area)?;
+    let blurred = image.crop_imm(area.x, area.y, area.w, area.h).blur(sigma);
+
+    let mut image = image.clone();
+    imageops::replace(&mut image, &blurred, area.x as i64, area.y as i64);
+    Ok(image)
+}
+
+impl RusImg {
+    /// Pixelate an area of the image with square blocks of block_size pixels (e.g. to anonymize faces or license plates).
+    /// See region::pixelate().
+    pub fn pixelate_region(&mut self, area: Rect, block_size: u32) -> Result<(), RusimgError> {
+        let pixelated = pixelate(self.as_dynamic_image(), area, block_size)?;
+        self.set_dynamic_image(pixelated)
+    }
+
+    /// Blur an area of the image with a gaussian blur of the standard deviation sigma.
+    /// See region::blur().
+    pub fn blur_region(&mut self, area: Rect, sigma: f32) -> Result<(), RusimgError> {
+        let blurred = blur(self.as_dynamic_image(), area, sigma)?;
+        self.set_dynamic_image(blurred)
+    }
+}
@@ -0,0 +1,49 @@
+use image::{imageops, RgbaImage};
+
+use super::{RusImg, ImgSize};
+
+/// Multiply the color channels by the alpha channel (rounded to the nearest value).
+fn premultiply(image: &mut RgbaImage) {
+    for pixel in image.pixels_mut() {
+        let alpha = pixel[3] as u32;
+        for channel in &mut pixel.0[..3] {
+            *channel = ((*channel as u32 * alpha + 127) / 255) as u8;
+        }
+    }
+}
+
+/// Get the size of an image fitted within the bounds, keeping the aspect ratio. Images that already fit are not enlarged.
+fn fit(size: ImgSize, bounds: ImgSize) -> ImgSize {
+    if size.fits_within(bounds) {
+        return size;
+    }
+    let fitted = if size.aspect_ratio() > bounds.aspect_ratio() {
+        size.scaled_to_width(bounds.width)
+    }
+    else {
+        size.scaled_to_height(bounds.height)
+    };
+    ImgSize::new(fitted.width.min(bounds.width), fitted.height.min(bounds.height))
+}
+
+impl RusImg {
+    /// Render the image for a preview: a premultiplied RGBA buffer (8 bits per channel, rows from the top) that fits within max_width x max_height.
+    /// The image is downscaled keeping the aspect ratio, but never enlarged. A bound of 0 is treated as 1.
+    /// The alpha is premultiplied before downscaling, so transparent pixels do not bleed their color into the edges.
+    /// Returns the buffer (width * height * 4 bytes) and its size, ready to be copied to a texture or a window surface.
+    pub fn render_rgba(&self, max_width: u32, max_height: u32) -> (Vec<u8>, ImgSize) {
+        let mut rgba = self.as_dynamic_image().to_rgba8();
+        premultiply(&mut rgba);
+
+        let size = ImgSize::new(rgba.width() as usize, rgba.height() as usize);
+        let bounds = ImgSize::new(max_width.max(1) as usize, max_height.max(1) as usize);
+        let target = fit(size, bounds);
+        let rgba = if target == size {
+            rgba
+        }
+        else {
+            imageops::thumbnail(&rgba, target.width as u32, target.height as u32)
+        };
+        (rgba.into_raw(), target)
+    }
+}
@@ -0,0 +1,38 @@
+use super::ImgSize;
+
+impl ImgSize {
+    /// Aspect ratio of the image (width / height). An image with zero height is treated as 1 pixel high.
+    pub fn aspect_ratio(&self) -> f64 {
+        self.width as f64 / self.height.max(1) as f64
+    }
+
+    /// Whether the image fits within the bounds, i.e. neither the width nor the height exceeds them.
+    pub fn fits_within(&self, bounds: ImgSize) -> bool {
+        self.width <= bounds.width && self.height <= bounds.height
+    }
+
+    /// Get the size scaled to the width, keeping the aspect ratio.
+    /// The height is rounded to the nearest pixel and is at least 1.
+    pub fn scaled_to_width(&self, width: usize) -> ImgSize {
+        ImgSize::new(width, scale(self.height, width, self.width))
+    }
+
+    /// Get the size scaled to the height, keeping the aspect ratio.
+    /// The width is rounded to the nearest pixel and is at least 1.
+    pub fn scaled_to_height(&self, height: usize) -> ImgSize {
+        ImgSize::new(scale(self.width, height, self.height), height)
+    }
+
+    /// Number of pixels in millions (e.g. 12.0 for 4000x3000).
+    pub fn megapixels(&self) -> f64 {
+        (self.width as u64 * self.height as u64) as f64 / 1_000_000.0
+    }
+}
+
+/// Compute length * numerator / denominator rounded to the nearest integer (at least 1).
+/// u128 keeps the product exact for any usize lengths.
+fn scale(length: usize, numerator: usize, denominator: usize) -> usize {
+    let denominator = denominator.max(1) as u128;
+    let scaled = (length as u128 * numerator as u128 + denominator / 2) / denominator;
+    (scaled as usize).max(1)
+}
@@ -0,0 +1,80 @@
+use std::fs::File;
+use std::io::Read;
+use std::ops::Deref;
+use std::path::Path;
+
+use super::RusimgError;
+
+/// Files smaller than this are always read into memory, because mapping small files is not worth the cost.
+const DEFAULT_MMAP_THRESHOLD: u64 = 16 * 1024 * 1024;
+
+/// OpenOptions is a structure that represents the options for opening an image.
+/// - mmap: Map the source file into memory instead of reading it, if the file is at least mmap_threshold bytes (requires the mmap feature, otherwise the file is read).
+/// - mmap_threshold: Minimum file size (in bytes) to be mapped.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OpenOptions {
+    pub mmap: bool,
+    pub mmap_threshold: u64,
+}
+impl Default for OpenOptions {
+    fn default() -> Self {
+        Self {
+            mmap: false,
+            mmap_threshold: DEFAULT_MMAP_THRESHOLD,
+        }
+    }
+}
+
+/// SourceData is the content of a source file.
+/// - Owned: The file was read into memory.
+/// - Mapped: The file is mapped into memory. Pages are loaded on demand and can be reclaimed by the OS, so the peak memory stays low even if many threads open large files at the same time.
+pub enum SourceData {
+    Owned(Vec<u8>),
+    #[cfg(feature = "mmap")]
+    Mapped(memmap2::Mmap),
+}
+
+impl Deref for SourceData {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match self {
+            SourceData::Owned(data) => data,
+            #[cfg(feature = "mmap")]
+            SourceData::Mapped(data) => data,
+        }
+    }
+}
+
+impl SourceData {
+    /// Convert to an owned buffer.
+    /// This copies the data if the file is mapped, so backends only call it when they need to keep the original bytes.
+    pub fn into_vec(self) -> Vec<u8> {
+        match self {
+            SourceData::Owned(data) => data,
+            #[cfg(feature = "mmap")]
+            SourceData::Mapped(data) => data.to_vec(),
+        }
+    }
+}
+
+/// Read a source file.
+/// If options.mmap is true and the file is large enough, the file is mapped into memory.
+/// If mapping fails (e.g. on file systems that do not support it) or the mmap feature is disabled, the file is read as usual.
+#[cfg_attr(not(feature = "mmap"), allow(unused_variables))]
+pub fn read(path: &Path, options: &OpenOptions) -> Result<SourceData, RusimgError> {
+    let mut file = File::open(path).map_err(|e| RusimgError::FailedToOpenFile(e.to_string()))?;
+    let len = file.metadata().map_err(|e| RusimgError::FailedToGetMetadata(e.to_string()))?.len();
+
+    #[cfg(feature = "mmap")]
+    if options.mmap && len >= options.mmap_threshold {
+        // Safety: the mapping is only read while decoding. If another process truncates the file meanwhile, the behavior is undefined, as documented in memmap2.
+        if let Ok(mmap) = unsafe { memmap2::Mmap::map(&file) } {
+            return Ok(SourceData::Mapped(mmap));
+        }
+    }
+
+    let mut data = Vec::with_capacity(len as usize);
+    file.read_to_end(&mut data).map_err(|e| RusimgError::FailedToReadFile(e.to_string()))?;
+    Ok(SourceData::Owned(data))
+}
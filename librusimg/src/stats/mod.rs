@@ -0,0 +1,96 @@
+use image::DynamicImage;
+
+/// Histogram is the distribution of 8-bit values for each channel.
+/// The channels are the same as the image (L, LA, RGB or RGBA). 16-bit and float images are converted to 8-bit.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Histogram {
+    pub channels: Vec<[u64; 256]>,
+}
+
+/// ChannelStats is the statistics of one channel.
+/// - mean: Mean value (0.0-255.0).
+/// - stddev: Standard deviation.
+/// - min, max: Minimum and maximum values.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct ChannelStats {
+    pub mean: f64,
+    pub stddev: f64,
+    pub min: u8,
+    pub max: u8,
+}
+
+/// ImageStats is the statistics of an image.
+/// - channels: Statistics of each channel (same order as Histogram).
+/// - luma: Statistics of the brightness. Useful to detect nearly-black (low mean) or blank (low stddev) images.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImageStats {
+    pub channels: Vec<ChannelStats>,
+    pub luma: ChannelStats,
+}
+
+/// Count the values of each channel of interleaved 8-bit pixels.
+fn count(pixels: &[u8], channel_count: usize) -> Vec<[u64; 256]> {
+    let mut channels = vec![[0u64; 256]; channel_count];
+    for pixel in pixels.chunks_exact(channel_count) {
+        for (channel, value) in channels.iter_mut().zip(pixel) {
+            channel[*value as usize] += 1;
+        }
+    }
+    channels
+}
+
+/// Calculate the histogram of an image.
+pub fn histogram(image: &DynamicImage) -> Histogram {
+    let channels = match image.color().channel_count() {
+        1 => count(image.to_luma8().as_raw(), 1),
+        2 => count(image.to_luma_alpha8().as_raw(), 2),
+        3 => count(image.to_rgb8().as_raw(), 3),
+        _ => count(image.to_rgba8().as_raw(), 4),
+    };
+    Histogram { channels }
+}
+
+impl Histogram {
+    /// Calculate the statistics of a channel from its histogram.
+    pub fn channel_stats(channel: &[u64; 256]) -> ChannelStats {
+        let total = channel.iter().sum::<u64>();
+        if total == 0 {
+            return ChannelStats::default();
+        }
+        let mean = channel.iter().enumerate().map(|(v, c)| v as f64 * *c as f64).sum::<f64>() / total as f64;
+        let variance = channel.iter().enumerate().map(|(v, c)| (v as f64 - mean).powi(2) * *c as f64).sum::<f64>() / total as f64;
+        let min = channel.iter().position(|c| *c > 0).unwrap_or(0) as u8;
+        let max = channel.iter().rposition(|c| *c > 0).unwrap_or(0) as u8;
+        ChannelStats {
+            mean,
+            stddev: variance.sqrt(),
+            min,
+            max,
+        }
+    }
+}
+
+/// Check if all pixels of the image are gray (R == G == B).
+/// Single-channel images (L, LA) are always gray. Encoders use it to write a smaller single-channel file for a color image that only contains gray pixels (e.g. document scans).
+pub fn is_grayscale(image: &DynamicImage) -> bool {
+    match image {
+        DynamicImage::ImageLuma8(_) | DynamicImage::ImageLumaA8(_) | DynamicImage::ImageLuma16(_) | DynamicImage::ImageLumaA16(_) => true,
+        DynamicImage::ImageRgb8(image) => image.pixels().all(|p| p.0[0] == p.0[1] && p.0[1] == p.0[2]),
+        DynamicImage::ImageRgba8(image) => image.pixels().all(|p| p.0[0] == p.0[1] && p.0[1] == p.0[2]),
+        DynamicImage::ImageRgb16(image) => image.pixels().all(|p| p.0[0] == p.0[1] && p.0[1] == p.0[2]),
+        DynamicImage::ImageRgba16(image) => image.pixels().all(|p| p.0[0] == p.0[1] && p.0[1] == p.0[2]),
+        DynamicImage::ImageRgb32F(image) => image.pixels().all(|p| p.0[0] == p.0[1] && p.0[1] == p.0[2]),
+        DynamicImage::ImageRgba32F(image) => image.pixels().all(|p| p.0[0] == p.0[1] && p.0[1] == p.0[2]),
+        _ => false,
+    }
+}
+
+/// Calculate the statistics of an image.
+pub fn stats(image: &DynamicImage) -> ImageStats {
+    let channels = histogram(image).channels.iter().map(Histogram::channel_stats).collect();
+    let luma = count(image.to_luma8().as_raw(), 1);
+    ImageStats {
+        channels,
+        luma: Histogram::channel_stats(&luma[0]),
+    }
+}
@@ -0,0 +1,76 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Prefix of the directories created by TempDir.
+const PREFIX: &str = "rusimg-";
+
+/// Base directory of the temporary directories. If None, the temporary directory of the OS is used.
+static LOCATION: RwLock<Option<PathBuf>> = RwLock::new(None);
+/// Counter for the names of the directories, so that tasks in the same process do not share a directory.
+static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// Set the base directory of the temporary directories (e.g. a fast local disk or a RAM disk).
+/// The directory is created when the first TempDir is created. None restores the temporary directory of the OS.
+pub fn set_location(location: Option<PathBuf>) {
+    *LOCATION.write().unwrap_or_else(|e| e.into_inner()) = location;
+}
+
+/// Get the base directory of the temporary directories.
+pub fn location() -> PathBuf {
+    match LOCATION.read().unwrap_or_else(|e| e.into_inner()).as_ref() {
+        Some(location) => location.clone(),
+        None => std::env::temp_dir(),
+    }
+}
+
+/// TempDir is a scratch directory owned by one task (e.g. the trial encodes of one image).
+/// Each TempDir is a new directory named rusimg-<process id>-<number> under location(), so tasks running at the same time never see each other's files.
+/// The directory and its contents are removed when the TempDir is dropped.
+#[derive(Debug)]
+pub struct TempDir {
+    path: PathBuf,
+}
+
+impl TempDir {
+    /// Create a new temporary directory under location().
+    pub fn new() -> io::Result<Self> {
+        let base = location();
+        fs::create_dir_all(&base)?;
+        loop {
+            let path = base.join(format!("{}{}-{}", PREFIX, std::process::id(), COUNTER.fetch_add(1, Ordering::Relaxed)));
+            // create_dir は既存のディレクトリでは失敗するので、前回のプロセスの残りとは共有しない
+            match fs::create_dir(&path) {
+                Ok(_) => return Ok(Self { path: path }),
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    /// Path of the directory.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Path of a file in the directory. The file is not created.
+    pub fn file<P: AsRef<Path>>(&self, name: P) -> PathBuf {
+        self.path.join(name)
+    }
+
+    /// Remove the directory now and report the error, instead of ignoring it on drop.
+    pub fn close(mut self) -> io::Result<()> {
+        let path = std::mem::take(&mut self.path);
+        fs::remove_dir_all(path)
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        if !self.path.as_os_str().is_empty() {
+            let _ = fs::remove_dir_all(&self.path);
+        }
+    }
+}
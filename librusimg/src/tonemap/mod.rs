@@ -0,0 +1,57 @@
+use image::DynamicImage;
+
+use super::{RusImg, RusimgError};
+use super::colorspace::{srgb_to_linear, linear_to_srgb};
+
+/// ToneMap is the operator that maps HDR values (linear light, 1.0 = diffuse white) to the range of SDR displays.
+/// - Reinhard: x / (1 + x). Keeps the colors, but flattens the highlights.
+/// - Aces: The filmic curve of ACES (Narkowicz's fit). More contrast and a softer roll-off of the highlights.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum ToneMap {
+    Reinhard,
+    #[default]
+    Aces,
+}
+
+impl ToneMap {
+    /// Map a linear value to 0.0-1.0.
+    fn apply(&self, x: f32) -> f32 {
+        let x = x.max(0.0);
+        let mapped = match self {
+            ToneMap::Reinhard => x / (1.0 + x),
+            ToneMap::Aces => (x * (2.51 * x + 0.03)) / (x * (2.43 * x + 0.59) + 0.14),
+        };
+        mapped.clamp(0.0, 1.0)
+    }
+}
+
+/// Tone map an image to 8-bit sRGB (RGB, or RGBA if the image has alpha), e.g. before saving an HDR source as jpeg or webp.
+/// Float images (e.g. OpenEXR, Radiance HDR) are linear light. Integer images are sRGB and are linearized first.
+/// The light is multiplied by exposure before the operator (e.g. 2.0 is one stop brighter). Alpha is not changed.
+pub fn tonemap(image: &DynamicImage, operator: ToneMap, exposure: f32) -> DynamicImage {
+    let linear = matches!(image, DynamicImage::ImageRgb32F(_) | DynamicImage::ImageRgba32F(_));
+    let mut pixels = image.to_rgba32f();
+    for pixel in pixels.pixels_mut() {
+        for value in pixel.0.iter_mut().take(3) {
+            let light = if linear { *value } else { srgb_to_linear(*value) };
+            *value = linear_to_srgb(operator.apply(light * exposure));
+        }
+    }
+
+    let mapped = DynamicImage::ImageRgba32F(pixels);
+    if image.color().has_alpha() {
+        DynamicImage::ImageRgba8(mapped.to_rgba8())
+    }
+    else {
+        DynamicImage::ImageRgb8(mapped.to_rgb8())
+    }
+}
+
+impl RusImg {
+    /// Tone map the image to 8-bit sRGB with the operator and the exposure.
+    /// See tonemap::tonemap().
+    pub fn tonemap(&mut self, operator: ToneMap, exposure: f32) -> Result<(), RusimgError> {
+        let mapped = tonemap(self.as_dynamic_image(), operator, exposure);
+        self.set_dynamic_image(mapped)
+    }
+}
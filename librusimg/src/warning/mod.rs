@@ -0,0 +1,77 @@
+use std::fmt;
+use std::path::Path;
+use image::ImageFormat;
+
+use super::{Extension, Rect};
+
+/// Warning is a non-fatal issue found while processing an image.
+/// The processing goes on, but the result may not be what the caller expected, so applications should show or log the warnings.
+/// - TrimClamped: The trim area exceeded the image and was shrunk to fit in it (requested, applied).
+/// - MetadataDropped: Metadata could not be written to the output (the name of the metadata, e.g. "metadata", "density").
+/// - FormatMismatch: The content of the source is not in the format of its file extension (the extension, the detected format).
+/// - OptionIgnored: An option is not supported by the encoder compiled into this build and was ignored (e.g. progressive with jpeg-pure).
+#[derive(Debug, Clone, PartialEq)]
+pub enum Warning {
+    TrimClamped { requested: Rect, applied: Rect },
+    MetadataDropped(String),
+    FormatMismatch { extension: String, detected: Extension },
+    OptionIgnored(String),
+}
+
+impl Warning {
+    /// Machine-readable name of the warning (e.g. in JSON reports).
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Warning::TrimClamped { .. } => "trim_clamped",
+            Warning::MetadataDropped(_) => "metadata_dropped",
+            Warning::FormatMismatch { .. } => "format_mismatch",
+            Warning::OptionIgnored(_) => "option_ignored",
+        }
+    }
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Warning::TrimClamped { requested, applied } => write!(f, "trim area {}x{}+{}x{} exceeds the image and was clamped to {}x{}+{}x{}",
+                requested.x, requested.y, requested.w, requested.h, applied.x, applied.y, applied.w, applied.h),
+            Warning::MetadataDropped(metadata) => write!(f, "{} cannot be kept in the output and was dropped", metadata),
+            Warning::FormatMismatch { extension, detected } => write!(f, "the file extension is .{} but the content is {}", extension, detected),
+            Warning::OptionIgnored(option) => write!(f, "{} is not supported by the encoder of this build and was ignored", option),
+        }
+    }
+}
+
+/// Check that the content of a source is in the format of its file extension.
+/// Sources without an extension are not checked, because their format is always detected from the content.
+pub fn check_format(path: &Path, data: &[u8]) -> Option<Warning> {
+    let detected = match image::guess_format(data).ok()? {
+        ImageFormat::Bmp => Extension::Bmp,
+        ImageFormat::Jpeg => Extension::Jpeg,
+        ImageFormat::Png => Extension::Png,
+        ImageFormat::WebP => Extension::Webp,
+        _ => return None,
+    };
+    let extension = path.extension()?.to_string_lossy().to_string();
+    match Extension::from_extension(&extension) {
+        Ok(format) if format == detected => None,
+        _ => Some(Warning::FormatMismatch { extension: extension, detected: detected }),
+    }
+}
+
+/// Get the warning of a trim area clamped by Rect::clamp_to(), if the area was changed.
+pub fn check_trim(requested: &Rect, applied: &Rect) -> Option<Warning> {
+    if requested == applied {
+        None
+    }
+    else {
+        Some(Warning::TrimClamped { requested: requested.clone(), applied: applied.clone() })
+    }
+}
+
+/// Add a warning unless the same warning has already been added (e.g. by an earlier save of the same image).
+pub fn push(warnings: &mut Vec<Warning>, warning: Warning) {
+    if !warnings.contains(&warning) {
+        warnings.push(warning);
+    }
+}
@@ -0,0 +1,65 @@
+use image::DynamicImage;
+
+use super::super::RusimgError;
+use super::super::options::SaveOptions;
+#[cfg(not(feature = "webp-pure"))]
+use super::super::options::WebpOptions;
+
+/// Name of the WebP decoder/encoder compiled into this build.
+#[cfg(not(feature = "webp-pure"))]
+pub const NAME: &str = "webp";
+#[cfg(feature = "webp-pure")]
+pub const NAME: &str = "image";
+
+/// Whether the encoder can write lossy WebP. Without it, the quality is ignored and the output is lossless.
+pub const LOSSY: bool = cfg!(not(feature = "webp-pure"));
+
+/// Decode WebP data with libwebp. Returns None if the data is not a valid WebP image.
+#[cfg(not(feature = "webp-pure"))]
+pub fn decode(data: &[u8]) -> Option<DynamicImage> {
+    dep_webp::Decoder::new(data).decode().map(|image| image.to_image())
+}
+
+/// Decode WebP data with the pure Rust decoder of the image crate. Returns None if the data is not a valid WebP image.
+#[cfg(feature = "webp-pure")]
+pub fn decode(data: &[u8]) -> Option<DynamicImage> {
+    image::load_from_memory_with_format(data, image::ImageFormat::WebP).ok()
+}
+
+/// Encode RGBA pixels with libwebp.
+/// If options.effort is specified, it is used as the encoding method (0-6) of libwebp.
+/// If options.encoder_threads is more than 1, libwebp encodes with multiple threads.
+/// options.webp sets the quality of the alpha channel and whether to keep the colors under transparent pixels.
+#[cfg(not(feature = "webp-pure"))]
+pub fn encode(rgba: &[u8], width: u32, height: u32, quality: f32, options: &SaveOptions) -> Result<Vec<u8>, RusimgError> {
+    let encoder = dep_webp::Encoder::from_rgba(rgba, width, height);
+    let encoded_webp = if options.effort.is_some() || options.encoder_threads.is_some() || options.webp != WebpOptions::default() {
+        let mut config = dep_webp::WebPConfig::new().map_err(|_| RusimgError::FailedToSaveImage("failed to initialize webp config".to_string()))?;
+        config.quality = quality;
+        if let Some(method) = options.effort_level(0, 6) {
+            config.method = method as i32;
+        }
+        config.thread_level = if options.encoder_threads.unwrap_or(1) > 1 { 1 } else { 0 };
+        config.alpha_quality = options.webp.alpha_quality.min(100) as i32;
+        config.exact = if options.webp.exact { 1 } else { 0 };
+        encoder.encode_advanced(&config).map_err(|e| RusimgError::FailedToSaveImage(format!("{:?}", e)))?
+    }
+    else {
+        encoder.encode(quality)
+    };
+    Ok(encoded_webp.to_vec())
+}
+
+/// Encode RGBA pixels with the pure Rust encoder of the image crate.
+/// The image crate writes lossless WebP only, so the quality and the libwebp settings are ignored.
+#[cfg(feature = "webp-pure")]
+pub fn encode(rgba: &[u8], width: u32, height: u32, _quality: f32, _options: &SaveOptions) -> Result<Vec<u8>, RusimgError> {
+    use image::ExtendedColorType;
+    use image::codecs::webp::WebPEncoder;
+
+    let mut buf = Vec::new();
+    WebPEncoder::new_lossless(&mut buf)
+        .encode(rgba, width, height, ExtendedColorType::Rgba8)
+        .map_err(|e| RusimgError::FailedToSaveImage(e.to_string()))?;
+    Ok(buf)
+}
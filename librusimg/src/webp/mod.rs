@@ -0,0 +1,346 @@
+use image::DynamicImage;
+
+use std::fs::Metadata;
+use std::time::{Duration, Instant};
+use std::io::Write;
+use std::sync::Arc;
+use std::path::PathBuf;
+
+use super::{RusimgTrait, RusimgError, ImgSize, Rect, Extension};
+use super::rect::TrimPolicy;
+use super::history::History;
+use super::options::{SaveOptions, MetadataPolicy};
+use super::source::SourceData;
+use super::warning::{self, Warning};
+
+mod codec;
+
+pub use codec::NAME as CODEC;
+
+#[derive(Debug, Clone)]
+pub struct WebpImage {
+    pub image: Arc<DynamicImage>,
+    image_bytes: Option<Vec<u8>>,
+    width: usize,
+    height: usize,
+    operations_count: u32,
+    required_quality: Option<f32>,
+    pub metadata_input: Metadata,
+    pub metadata_output: Option<Metadata>,
+    pub filepath_input: PathBuf,
+    pub filepath_output: Option<PathBuf>,
+    operations: Vec<String>,
+    bytes_written: Option<u64>,
+    encode_duration: Option<Duration>,
+    warnings: Vec<Warning>,
+    history: History<Self>,
+}
+
+impl WebpImage {
+    /// Resize an image with alpha in premultiplied space.
+    /// Resizing straight alpha mixes the (invisible) colors of transparent pixels into the edges, which produces halos.
+    fn resize_premultiplied(image: &DynamicImage, width: u32, height: u32) -> DynamicImage {
+        let mut rgba = image.to_rgba32f();
+        for pixel in rgba.pixels_mut() {
+            let alpha = pixel.0[3];
+            for value in pixel.0.iter_mut().take(3) {
+                *value *= alpha;
+            }
+        }
+        let mut resized = image::imageops::resize(&rgba, width, height, image::imageops::FilterType::Lanczos3);
+        for pixel in resized.pixels_mut() {
+            let alpha = pixel.0[3].clamp(0.0, 1.0);
+            pixel.0[3] = alpha;
+            for value in pixel.0.iter_mut().take(3) {
+                *value = if alpha > 0.0 { (*value / alpha).clamp(0.0, 1.0) } else { 0.0 };
+            }
+        }
+        DynamicImage::ImageRgba8(DynamicImage::ImageRgba32F(resized).to_rgba8())
+    }
+
+    /// Record the current state so that the next operation can be undone.
+    fn record(&mut self) {
+        if !self.history.is_enabled() {
+            return;
+        }
+        let snapshot = self.clone();
+        self.history.push(snapshot);
+    }
+}
+
+impl RusimgTrait for WebpImage {
+    /// Import an image from a DynamicImage object.
+    fn import(image: DynamicImage, source_path: PathBuf, source_metadata: Metadata) -> Result<Self, RusimgError> {
+        let (width, height) = (image.width() as usize, image.height() as usize);
+
+        Ok(Self {
+            image: Arc::new(image),
+            image_bytes: None,
+            width,
+            height,
+            operations_count: 0,
+            required_quality: None,
+            metadata_input: source_metadata,
+            metadata_output: None,
+            filepath_input: source_path,
+            filepath_output: None,
+            operations: Vec::new(),
+            bytes_written: None,
+            encode_duration: None,
+            warnings: Vec::new(),
+            history: History::default(),
+        })
+    }
+
+    /// Open an image from a image buffer.
+    /// The original bytes are kept to save the image without re-encoding, so a memory-mapped file is copied after decoding.
+    fn open(path: PathBuf, image_buf: SourceData, metadata: Metadata) -> Result<Self, RusimgError> {
+        if let Some(image) = codec::decode(&image_buf) {
+            let (width, height) = (image.width() as usize, image.height() as usize);
+            let warnings = warning::check_format(&path, &image_buf).into_iter().collect();
+
+            Ok(Self {
+                image: Arc::new(image),
+                image_bytes: Some(image_buf.into_vec()),
+                width,
+                height,
+                operations_count: 0,
+                required_quality: None,
+                metadata_input: metadata,
+                metadata_output: None,
+                filepath_input: path,
+                filepath_output: None,
+                operations: Vec::new(),
+                bytes_written: None,
+                encode_duration: None,
+                warnings: warnings,
+                history: History::default(),
+            })
+        }
+        else {
+            Err(RusimgError::FailedToDecodeWebp)
+        }
+    }
+
+    /// Save the image to a file.
+    /// The image is encoded with codec::encode() (libwebp, or the lossless encoder of the image crate with the webp-pure feature).
+    /// With webp-pure, a compressed image is saved losslessly and a warning is added.
+    fn save(&mut self, path: Option<PathBuf>, options: &SaveOptions) -> Result<(), RusimgError> {
+        let start = Instant::now();
+        let save_path = Self::get_save_filepath(self, &self.filepath_input, path, &Extension::Webp.extension_for(&self.filepath_input))?;
+        options.check_overwrite(&save_path)?;
+
+        // 元が webp かつ操作回数が 0 なら encode しない
+        let source_is_webp = Extension::from_path(&self.filepath_input).map(|e| e == Extension::Webp).unwrap_or(false);
+        if let Some(image_bytes) = self.image_bytes.as_ref().filter(|_| source_is_webp && self.operations_count == 0) {
+            let mut file = std::fs::File::create(&save_path).map_err(|e| RusimgError::FailedToCreateFile(e.to_string()))?;
+            file.write_all(image_bytes).map_err(|e| RusimgError::FailedToWriteFIle(e.to_string()))?;
+
+            self.metadata_output = Some(file.metadata().map_err(|e| RusimgError::FailedToGetMetadata(e.to_string()))?);
+            self.bytes_written = self.metadata_output.as_ref().map(|m| m.len());
+            self.encode_duration = Some(start.elapsed());
+            self.filepath_output = Some(save_path);
+
+            return Ok(());
+        }
+
+        // 再エンコードするとメタデータは失われる
+        if options.metadata == MetadataPolicy::Keep {
+            warning::push(&mut self.warnings, Warning::MetadataDropped("metadata".to_string()));
+        }
+
+        // quality
+        let quality = self.required_quality.unwrap_or(75.0);   // 指定されていなければ既定値 75.0
+       
+        // DynamicImage を （圧縮＆）保存
+        // RGBA 画像はコピーせずにエンコード
+        let rgba;
+        let pixels = match self.image.as_ref() {
+            DynamicImage::ImageRgba8(image) => image.as_raw(),
+            _ => {
+                rgba = self.image.to_rgba8();
+                rgba.as_raw()
+            },
+        };
+        if self.required_quality.is_some() && !codec::LOSSY {
+            warning::push(&mut self.warnings, Warning::OptionIgnored(format!("quality ({} encodes lossless only)", codec::NAME)));
+        }
+        let encoded_webp = codec::encode(pixels, self.image.width(), self.image.height(), quality, options)?;
+
+        let mut file = std::fs::File::create(&save_path).map_err(|e| RusimgError::FailedToCreateFile(e.to_string()))?;
+        file.write_all(&encoded_webp).map_err(|e| RusimgError::FailedToWriteFIle(e.to_string()))?;
+
+        self.metadata_output = Some(file.metadata().map_err(|e| RusimgError::FailedToGetMetadata(e.to_string()))?);
+        self.bytes_written = self.metadata_output.as_ref().map(|m| m.len());
+        self.encode_duration = Some(start.elapsed());
+        self.filepath_output = Some(save_path);
+
+        Ok(())
+    }
+
+    /// Compress the image.
+    /// quality: Option<f32> 0.0 - 100.0
+    /// Because the webp crate compresses the image when saving it, the compress() method does not need to do anything.
+    /// So this method only sets the quality value.
+    fn compress(&mut self, quality: Option<f32>) -> Result<(), RusimgError> {
+        self.record();
+        // compress later when saving
+        self.required_quality = quality;
+        self.operations_count += 1;
+        self.operations.push(format!("compress {}", quality.unwrap_or(75.0)));
+        Ok(())
+    }
+
+    /// Resize the image.
+    /// resize_ratio: f32 in percent (must be 0.0 < resize_ratio, e.g. 12.5)
+    fn resize(&mut self, resize_ratio: f32) -> Result<ImgSize, RusimgError> {
+        self.record();
+        let nwidth = (self.width as f32 * (resize_ratio / 100.0)) as usize;
+        let nheight = (self.height as f32 * (resize_ratio / 100.0)) as usize;
+
+        // アルファ付きの画像は乗算済みアルファでリサイズ（縁のハロー防止）
+        self.image = if self.image.color().has_alpha() {
+            Arc::new(Self::resize_premultiplied(&self.image, nwidth as u32, nheight as u32))
+        }
+        else {
+            Arc::new(self.image.resize(nwidth as u32, nheight as u32, image::imageops::FilterType::Lanczos3))
+        };
+
+        self.width = nwidth;
+        self.height = nheight;
+
+        self.operations_count += 1;
+        self.operations.push(format!("resize {}%", resize_ratio));
+        Ok(ImgSize::new(self.width, self.height))
+    }
+
+    /// Trim the image.
+    /// trim: librusimg::Rect { x: u32, y: u32, w: u32, h: u32 }
+    /// The area is clamped to the image size with Rect::clamp_to().
+    fn trim(&mut self, trim: Rect) -> Result<ImgSize, RusimgError> {
+        let requested = trim;
+        let trim = requested.clamp_to(self.get_size(), TrimPolicy::Clamp)?;
+        self.record();
+        if let Some(clamped) = warning::check_trim(&requested, &trim) {
+            warning::push(&mut self.warnings, clamped);
+        }
+
+        self.image = Arc::new(self.image.crop_imm(trim.x, trim.y, trim.w, trim.h));
+
+        self.width = trim.w as usize;
+        self.height = trim.h as usize;
+        self.operations.push(format!("trim {}x{}+{}x{}", trim.x, trim.y, trim.w, trim.h));
+
+        Ok(ImgSize::new(self.width, self.height))
+    }
+
+    /// Convert the image to grayscale.
+    fn grayscale(&mut self) {
+        self.record();
+        self.image = Arc::new(self.image.grayscale());
+        self.operations.push("grayscale".to_string());
+        self.operations_count += 1;
+    }
+
+    /// Set the image to a DynamicImage object.
+    /// The image size is updated to the size of the new image.
+    fn set_dynamic_image(&mut self, image: DynamicImage) -> Result<(), RusimgError> {
+        self.record();
+        self.image = Arc::new(image);
+        self.width = self.image.width() as usize;
+        self.height = self.image.height() as usize;
+        Ok(())
+    }
+
+    /// Get the DynamicImage object.
+    /// This copies the whole image; use as_dynamic_image() to avoid the copy.
+    fn get_dynamic_image(&mut self) -> Result<DynamicImage, RusimgError> {
+        Ok(self.to_owned_dynamic_image())
+    }
+
+    /// Borrow the DynamicImage object without copying.
+    fn as_dynamic_image(&self) -> &DynamicImage {
+        &self.image
+    }
+
+    /// Get a copy of the DynamicImage object.
+    fn to_owned_dynamic_image(&self) -> DynamicImage {
+        self.image.as_ref().clone()
+    }
+
+    /// Clone the image into a new trait object.
+    /// The pixel data is shared with the original until either of them is modified (copy-on-write), so this is cheap.
+    fn box_clone(&self) -> Box<dyn RusimgTrait + Send + Sync> {
+        Box::new(self.clone())
+    }
+
+    /// Set the maximum number of operations that can be undone (0: disabled, default).
+    fn set_history_limit(&mut self, limit: usize) {
+        self.history.set_limit(limit);
+    }
+
+    /// Undo the last operation (resize, trim, grayscale, compress or set_dynamic_image).
+    /// Returns false if there is nothing to undo.
+    fn undo(&mut self) -> bool {
+        match self.history.pop() {
+            Some(previous) => {
+                let history = std::mem::take(&mut self.history);
+                *self = previous;
+                self.history = history;
+                true
+            },
+            None => false,
+        }
+    }
+
+    /// Get the source file path.
+    fn get_source_filepath(&self) -> PathBuf {
+        self.filepath_input.clone()
+    }
+
+    /// Get the destination file path.
+    fn get_destination_filepath(&self) -> Option<PathBuf> {
+        self.filepath_output.clone()
+    }
+
+    /// Get the source metadata.
+    fn get_metadata_src(&self) -> Metadata {
+        self.metadata_input.clone()
+    }
+
+    /// Get the destination metadata.
+    fn get_metadata_dest(&self) -> Option<Metadata> {
+        self.metadata_output.clone()
+    }
+
+    /// Get the image size.
+    fn get_size(&self) -> ImgSize {
+        ImgSize::new(self.width, self.height)
+    }
+
+    /// Get the list of applied operations.
+    fn get_operations(&self) -> Vec<String> {
+        self.operations.clone()
+    }
+
+    /// Get the number of bytes written by the last save.
+    fn get_bytes_written(&self) -> Option<u64> {
+        self.bytes_written
+    }
+
+    /// Get the time taken to encode and write the image by the last save.
+    fn get_encode_duration(&self) -> Option<Duration> {
+        self.encode_duration
+    }
+
+    /// Get the warnings found while processing the image.
+    fn get_warnings(&self) -> Vec<Warning> {
+        self.warnings.clone()
+    }
+}
+
+// RusImg holds the backend as Box<dyn RusimgTrait + Send + Sync>, so WebpImage must stay Send + Sync.
+const _: fn() = || {
+    fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<WebpImage>();
+};
@@ -0,0 +1,207 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use image::{DynamicImage, Rgb, RgbImage};
+use librusimg::{Extension, RusImg, RusimgError};
+use librusimg::tempdir::TempDir;
+use sha2::{Digest, Sha256};
+
+/// Size of the fixture image.
+const FIXTURE_WIDTH: u32 = 96;
+const FIXTURE_HEIGHT: u32 = 64;
+const FORMATS: [Extension; 4] = [Extension::Jpeg, Extension::Png, Extension::Webp, Extension::Bmp];
+/// Minimum SSIM of a lossy output whose pixels differ from the golden.
+/// Encoders may round a little differently between versions, but a change in the processing itself drops far below this.
+const LOSSY_SSIM: f64 = 0.995;
+/// Set this environment variable to write the fixtures and goldens from the current outputs.
+const BLESS_VAR: &str = "RUSIMG_BLESS";
+
+fn golden_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests").join("golden")
+}
+
+/// Path of the manifest: one line "<case> <width>x<height> <sha256 of the RGBA pixels>" for each case.
+fn manifest_path() -> PathBuf {
+    golden_dir().join("goldens.txt")
+}
+
+/// Path of the decoded golden output of a case (png), used for the SSIM comparison of lossy outputs.
+fn expected_path(case: &str) -> PathBuf {
+    golden_dir().join("expected").join(format!("{}.png", case))
+}
+
+fn is_lossy(format: &Extension) -> bool {
+    matches!(format, Extension::Jpeg | Extension::Webp)
+}
+
+/// Generate the source of the fixtures: gradients, a hard edge and noise, so that resampling and compression both show.
+fn generate_source() -> DynamicImage {
+    let image = RgbImage::from_fn(FIXTURE_WIDTH, FIXTURE_HEIGHT, |x, y| {
+        let r = (x * 255 / FIXTURE_WIDTH) as u8;
+        let g = if x > FIXTURE_WIDTH / 2 { 255 - (y * 255 / FIXTURE_HEIGHT) as u8 } else { (y * 255 / FIXTURE_HEIGHT) as u8 };
+        let b = ((x ^ y).wrapping_mul(2654435761) >> 24) as u8;
+        Rgb([r, g, b])
+    });
+    DynamicImage::ImageRgb8(image)
+}
+
+/// Get the bundled fixture of the format.
+/// The fixtures are written once in bless mode and kept in the repository, so that a change of an encoder does not change the inputs.
+fn fixture(format: &Extension, bless: bool) -> PathBuf {
+    let path = golden_dir().join("fixtures").join(format!("fixture.{}", format));
+    if !path.exists() {
+        assert!(bless, "{} is missing. Run `{}=1 cargo test -p librusimg --test golden` to write the fixtures.", path.display(), BLESS_VAR);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        let temp_dir = TempDir::new().unwrap();
+        let source = temp_dir.file("source.png");
+        generate_source().save(&source).unwrap();
+        let mut image = librusimg::open_image(&source).unwrap();
+        if image.get_extension() != *format {
+            image.convert(format).unwrap();
+        }
+        image.save_image(path.to_str()).unwrap();
+    }
+    path
+}
+
+/// An operation on an image. It may convert the image, and the output is saved in the resulting format.
+type Apply = Box<dyn Fn(&mut RusImg) -> Result<(), RusimgError>>;
+
+/// An operation applied to a fixture.
+/// - name: Name of the operation in the case names.
+/// - apply: The operation.
+struct Operation {
+    name: String,
+    apply: Apply,
+}
+
+fn operation(name: &str, apply: impl Fn(&mut RusImg) -> Result<(), RusimgError> + 'static) -> Operation {
+    Operation { name: name.to_string(), apply: Box::new(apply) }
+}
+
+/// The operations run on the fixture of the format.
+fn operations(format: &Extension) -> Vec<Operation> {
+    let mut ret = vec![
+        operation("open", |_| Ok(())),
+        operation("resize50", |image| image.resize(50.0).map(|_| ())),
+        operation("resize150", |image| image.resize(150.0).map(|_| ())),
+        operation("trim", |image| image.trim(10, 8, 48, 32).map(|_| ())),
+        operation("grayscale", |image| image.grayscale()),
+        operation("compress", |image| image.compress(Some(75.0))),
+        operation("resize50-compress", |image| {
+            image.resize(50.0)?;
+            image.compress(Some(75.0))
+        }),
+    ];
+    // BMP の compress は 256 色以下の画像だけ RLE8 で書き出すので、グレースケールでも確認する
+    if *format == Extension::Bmp {
+        ret.push(operation("grayscale-compress", |image| {
+            image.grayscale()?;
+            image.compress(Some(75.0))
+        }));
+    }
+    for target in FORMATS.iter().filter(|f| *f != format) {
+        let target = target.clone();
+        ret.push(operation(&format!("convert-{}", target), move |image| image.convert(&target)));
+    }
+    ret
+}
+
+/// Run the operation on the fixture, save the output and decode it again.
+/// The saved output is compared (not the image in memory), so that the encoders are covered as well.
+fn run_case(fixture: &Path, operation: &Operation, temp_dir: &TempDir) -> Result<(Extension, DynamicImage), RusimgError> {
+    let mut image = librusimg::open_image(fixture)?;
+    (operation.apply)(&mut image)?;
+    let extension = image.get_extension();
+    let output = temp_dir.file(format!("{}.{}", operation.name, extension));
+    image.save_image(output.to_str())?;
+    let decoded = librusimg::open_image(&output)?.as_dynamic_image().clone();
+    Ok((extension, decoded))
+}
+
+/// SHA-256 of the RGBA pixels. Pixels are hashed instead of the file, so that metadata written by the encoders does not matter.
+fn pixel_hash(image: &DynamicImage) -> String {
+    let rgba = image.to_rgba8();
+    Sha256::digest(rgba.as_raw()).iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn read_manifest() -> BTreeMap<String, (String, String)> {
+    let Ok(manifest) = fs::read_to_string(manifest_path()) else {
+        return BTreeMap::new();
+    };
+    manifest.lines()
+        .filter(|line| !line.trim().is_empty() && !line.starts_with('#'))
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            Some((fields.next()?.to_string(), (fields.next()?.to_string(), fields.next()?.to_string())))
+        })
+        .collect()
+}
+
+fn write_manifest(goldens: &BTreeMap<String, (String, String)>) {
+    let mut manifest = String::from("# Written by `RUSIMG_BLESS=1 cargo test -p librusimg --test golden`. Do not edit by hand.\n");
+    for (case, (size, hash)) in goldens {
+        manifest.push_str(&format!("{} {} {}\n", case, size, hash));
+    }
+    fs::write(manifest_path(), manifest).unwrap();
+}
+
+/// Compare the output of the case with the golden.
+/// Outputs must have the same size, and the same pixels; lossy outputs may instead have an SSIM of at least LOSSY_SSIM with the golden.
+fn compare(case: &str, extension: &Extension, decoded: &DynamicImage, golden: Option<&(String, String)>) -> Result<(), String> {
+    let Some((golden_size, golden_hash)) = golden else {
+        return Err(format!("{}: no golden", case));
+    };
+    let size = format!("{}x{}", decoded.width(), decoded.height());
+    if size != *golden_size {
+        return Err(format!("{}: size {} != golden {}", case, size, golden_size));
+    }
+    if pixel_hash(decoded) == *golden_hash {
+        return Ok(());
+    }
+    if !is_lossy(extension) {
+        return Err(format!("{}: pixels differ from the golden (lossless output)", case));
+    }
+    let expected = image::open(expected_path(case)).map_err(|e| format!("{}: {}", case, e))?;
+    match librusimg::metrics::ssim(&expected, decoded) {
+        Some(ssim) if ssim >= LOSSY_SSIM => Ok(()),
+        Some(ssim) => Err(format!("{}: SSIM {:.5} < {}", case, ssim, LOSSY_SSIM)),
+        None => Err(format!("{}: failed to compare with the golden", case)),
+    }
+}
+
+#[test]
+fn golden() {
+    let bless = std::env::var_os(BLESS_VAR).is_some();
+    let mut goldens = read_manifest();
+    let temp_dir = TempDir::new().unwrap();
+
+    let mut failures = Vec::new();
+    for format in FORMATS.iter() {
+        let fixture = fixture(format, bless);
+        for operation in operations(format) {
+            let case = format!("{}-{}", format, operation.name);
+            let (extension, decoded) = match run_case(&fixture, &operation, &temp_dir) {
+                Ok(output) => output,
+                Err(e) => {
+                    failures.push(format!("{}: {}", case, e));
+                    continue;
+                },
+            };
+
+            if bless {
+                fs::create_dir_all(expected_path(&case).parent().unwrap()).unwrap();
+                decoded.save(expected_path(&case)).unwrap();
+                goldens.insert(case, (format!("{}x{}", decoded.width(), decoded.height()), pixel_hash(&decoded)));
+            }
+            else if let Err(e) = compare(&case, &extension, &decoded, goldens.get(&case)) {
+                failures.push(e);
+            }
+        }
+    }
+
+    if bless {
+        write_manifest(&goldens);
+    }
+    assert!(failures.is_empty(), "golden tests failed (run `{}=1 cargo test -p librusimg --test golden` if the change is intended):\n{}", BLESS_VAR, failures.join("\n"));
+}
@@ -0,0 +1,54 @@
+use std::fs;
+use std::path::PathBuf;
+use image::{DynamicImage, Rgb, RgbImage};
+use librusimg::{Extension, JpegOptions, SaveOptions};
+use librusimg::tempdir::TempDir;
+
+/// Markers of the JPEG format (the byte after 0xFF).
+const SOF9: u8 = 0xC9;
+const SOF10: u8 = 0xCA;
+const RST0: u8 = 0xD0;
+const DRI: u8 = 0xDD;
+
+/// Save a gradient as jpeg with the JpegOptions, and return the saved data.
+fn save_jpeg(temp_dir: &TempDir, name: &str, jpeg: JpegOptions) -> Vec<u8> {
+    let source = temp_dir.file(format!("{}.png", name));
+    let image = RgbImage::from_fn(64, 48, |x, y| Rgb([(x * 4) as u8, (y * 5) as u8, ((x + y) * 2) as u8]));
+    DynamicImage::ImageRgb8(image).save(&source).unwrap();
+
+    let mut image = librusimg::open_image(&source).unwrap();
+    image.convert(&Extension::Jpeg).unwrap();
+    let output: PathBuf = temp_dir.file(format!("{}.jpg", name));
+    let options = SaveOptions {
+        jpeg: jpeg,
+        ..SaveOptions::default()
+    };
+    image.save_image_with(output.to_str(), &options).unwrap();
+    fs::read(output).unwrap()
+}
+
+/// Check if the data has the marker. 0xFF in the entropy-coded data is followed by 0x00 or a restart marker, so other markers are found only in the headers.
+fn has_marker(data: &[u8], marker: u8) -> bool {
+    data.windows(2).any(|w| w == [0xFF, marker])
+}
+
+#[test]
+fn restart_interval_writes_restart_markers() {
+    let temp_dir = TempDir::new().unwrap();
+    let data = save_jpeg(&temp_dir, "restart", JpegOptions { restart_interval: 2, ..JpegOptions::default() });
+    assert!(has_marker(&data, DRI));
+    assert!(has_marker(&data, RST0));
+
+    let data = save_jpeg(&temp_dir, "default", JpegOptions::default());
+    assert!(!has_marker(&data, DRI));
+}
+
+#[test]
+fn arithmetic_coding_writes_arithmetic_frames() {
+    let temp_dir = TempDir::new().unwrap();
+    let data = save_jpeg(&temp_dir, "arithmetic", JpegOptions { arithmetic_coding: true, ..JpegOptions::default() });
+    assert!(has_marker(&data, SOF9) || has_marker(&data, SOF10));
+
+    let data = save_jpeg(&temp_dir, "huffman", JpegOptions::default());
+    assert!(!has_marker(&data, SOF9) && !has_marker(&data, SOF10));
+}
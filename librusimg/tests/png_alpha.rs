@@ -0,0 +1,66 @@
+use std::path::PathBuf;
+use image::{DynamicImage, GenericImageView, Rgba, RgbaImage};
+use librusimg::RusImg;
+use librusimg::tempdir::TempDir;
+
+/// Size of the source image. The left half is fully transparent, and the right half is opaque.
+const WIDTH: u32 = 64;
+const HEIGHT: u32 = 32;
+
+/// Write the transparent source png and open it.
+fn open_source(temp_dir: &TempDir, name: &str) -> RusImg {
+    let source = temp_dir.file(format!("{}-source.png", name));
+    let image = RgbaImage::from_fn(WIDTH, HEIGHT, |x, y| {
+        if x < WIDTH / 2 { Rgba([0, 0, 0, 0]) } else { Rgba([(x * 4) as u8, (y * 8) as u8, 128, 255]) }
+    });
+    DynamicImage::ImageRgba8(image).save(&source).unwrap();
+    librusimg::open_image(&source).unwrap()
+}
+
+/// Save the image and decode the saved png again.
+fn save_and_decode(image: &mut RusImg, temp_dir: &TempDir, name: &str) -> DynamicImage {
+    let output: PathBuf = temp_dir.file(format!("{}.png", name));
+    image.save_image(output.to_str()).unwrap();
+    image::open(&output).unwrap()
+}
+
+/// The saved png must keep the alpha channel, the transparent half and the opaque half.
+fn assert_transparency(decoded: &DynamicImage, width: u32, height: u32) {
+    assert_eq!(decoded.dimensions(), (width, height));
+    assert!(decoded.color().has_alpha(), "the alpha channel was dropped ({:?})", decoded.color());
+    let rgba = decoded.to_rgba8();
+    assert_eq!(rgba.get_pixel(1, height / 2)[3], 0, "the transparent pixels became opaque");
+    assert_eq!(rgba.get_pixel(width - 2, height / 2)[3], 255, "the opaque pixels became transparent");
+}
+
+#[test]
+fn resize_then_compress_keeps_alpha() {
+    let temp_dir = TempDir::new().unwrap();
+    let mut image = open_source(&temp_dir, "resize-compress");
+    image.resize(50.0).unwrap();
+    image.compress(Some(75.0)).unwrap();
+
+    let decoded = save_and_decode(&mut image, &temp_dir, "resize-compress");
+    assert_transparency(&decoded, WIDTH / 2, HEIGHT / 2);
+}
+
+#[test]
+fn compress_then_resize_keeps_alpha() {
+    let temp_dir = TempDir::new().unwrap();
+    let mut image = open_source(&temp_dir, "compress-resize");
+    image.compress(Some(75.0)).unwrap();
+    image.resize(50.0).unwrap();
+
+    let decoded = save_and_decode(&mut image, &temp_dir, "compress-resize");
+    assert_transparency(&decoded, WIDTH / 2, HEIGHT / 2);
+}
+
+#[test]
+fn compress_keeps_alpha() {
+    let temp_dir = TempDir::new().unwrap();
+    let mut image = open_source(&temp_dir, "compress");
+    image.compress(Some(75.0)).unwrap();
+
+    let decoded = save_and_decode(&mut image, &temp_dir, "compress");
+    assert_transparency(&decoded, WIDTH, HEIGHT);
+}
@@ -0,0 +1,29 @@
+use librusimg::pool::BufferPool;
+
+#[test]
+fn total_capacity_stays_within_the_limit() {
+    let pool = BufferPool::with_limit(1000);
+    for _ in 0..8 {
+        pool.give(Vec::with_capacity(300));
+    }
+    assert!(pool.pooled_bytes() <= 1000);
+    assert_eq!(pool.len(), 3);
+
+    // A buffer larger than the limit is not kept.
+    pool.give(Vec::with_capacity(2000));
+    assert_eq!(pool.len(), 3);
+}
+
+#[test]
+fn lowering_the_limit_drops_the_smallest_buffers() {
+    let pool = BufferPool::with_limit(1000);
+    pool.give(Vec::with_capacity(100));
+    pool.give(Vec::with_capacity(600));
+    pool.set_limit(650);
+    assert_eq!(pool.len(), 1);
+    assert!(pool.take(500).capacity() >= 600);
+
+    pool.set_limit(0);
+    pool.give(Vec::with_capacity(100));
+    assert!(pool.is_empty());
+}
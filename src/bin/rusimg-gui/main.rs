@@ -0,0 +1,287 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::time::Duration;
+use eframe::egui;
+use rayon::prelude::*;
+
+use librusimg::{Extension, RusimgError, SaveOptions};
+
+/// Formats selectable as the output format.
+const FORMATS: [Extension; 4] = [Extension::Jpeg, Extension::Png, Extension::Webp, Extension::Bmp];
+
+/// Settings of the batch conversion.
+/// - format: Output format. If None, the format of each source is kept.
+/// - compress: Compress the images with the quality.
+/// - quality: Image quality (0.0 <= q <= 100.0).
+/// - resize: Resize ratio in percent. If None, the size is kept.
+/// - output_dir: Output directory. If empty, the outputs are saved next to the sources.
+/// - overwrite: Overwrite existing files.
+/// - lowercase_extension: Lowercase the extensions of the outputs (e.g. Photo.JPG -> Photo.jpg). Otherwise the source extension is kept as written if the format is not changed.
+#[derive(Debug, Clone)]
+struct Settings {
+    format: Option<Extension>,
+    compress: bool,
+    quality: f32,
+    resize: Option<f32>,
+    output_dir: String,
+    overwrite: bool,
+    lowercase_extension: bool,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            format: None,
+            compress: false,
+            quality: 75.0,
+            resize: None,
+            output_dir: String::new(),
+            overwrite: false,
+            lowercase_extension: false,
+        }
+    }
+}
+
+/// Status of each file in the list.
+#[derive(Debug, Clone, PartialEq)]
+enum FileStatus {
+    Pending,
+    Running,
+    Done { output: PathBuf, before: u64, after: u64 },
+    Failed(String),
+}
+
+/// Message from the worker threads to the UI.
+enum Update {
+    Status(usize, FileStatus),
+    Finished,
+}
+
+/// Get the format of a file from its extension.
+fn get_extension(path: &Path) -> Option<Extension> {
+    Extension::from_path(path).ok()
+}
+
+/// Get the output path: the source name with the extension of the format, in the output directory (or next to the source).
+/// The source extension is kept as written if it is of the format (e.g. Photo.JPG stays Photo.JPG), unless lowercase is set.
+fn output_path(source: &Path, extension: &Extension, output_dir: &str, lowercase: bool) -> PathBuf {
+    let mut extension = extension.extension_for(source);
+    if lowercase {
+        extension = extension.to_ascii_lowercase();
+    }
+    let file_name = Path::new(source.file_stem().unwrap_or_default()).with_extension(extension);
+    if output_dir.trim().is_empty() {
+        source.with_file_name(file_name)
+    }
+    else {
+        Path::new(output_dir.trim()).join(file_name)
+    }
+}
+
+/// Convert one file with the settings.
+fn process_file(source: &Path, settings: &Settings) -> Result<FileStatus, RusimgError> {
+    let mut image = librusimg::open_image(source)?;
+    let before = fs::metadata(source).map(|m| m.len()).unwrap_or(0);
+
+    if let Some(ratio) = settings.resize {
+        image.resize(ratio)?;
+    }
+    if let Some(format) = &settings.format {
+        if *format != image.extension {
+            image.convert(format)?;
+        }
+    }
+    // bmp は圧縮できない
+    if settings.compress && image.extension != Extension::Bmp {
+        image.compress(Some(settings.quality))?;
+    }
+
+    let output = output_path(source, &image.extension, &settings.output_dir, settings.lowercase_extension);
+    let output_str = output.to_str().ok_or(RusimgError::FailedToConvertPathToString)?;
+    let save_options = SaveOptions {
+        overwrite: settings.overwrite,
+        ..Default::default()
+    };
+    let status = image.save_image_with(Some(output_str), &save_options)?;
+    Ok(FileStatus::Done {
+        output: output,
+        before: before,
+        after: status.after_filesize.unwrap_or(0),
+    })
+}
+
+/// App is the state of the GUI.
+/// - files: Files to be converted and their status.
+/// - settings: Settings of the conversion.
+/// - receiver: Updates from the running batch. None if no batch is running.
+#[derive(Default)]
+struct App {
+    files: Vec<(PathBuf, FileStatus)>,
+    settings: Settings,
+    receiver: Option<Receiver<Update>>,
+}
+
+impl App {
+    /// Add a dropped file, or the image files in a dropped directory.
+    fn add(&mut self, path: PathBuf) {
+        if path.is_dir() {
+            let mut entries = fs::read_dir(&path).into_iter().flatten().flatten()
+                .map(|entry| entry.path())
+                .filter(|p| p.is_file() && get_extension(p).is_some())
+                .collect::<Vec<_>>();
+            entries.sort();
+            entries.into_iter().for_each(|p| self.add(p));
+        }
+        else if get_extension(&path).is_some() && !self.files.iter().any(|(p, _)| *p == path) {
+            self.files.push((path, FileStatus::Pending));
+        }
+    }
+
+    fn is_running(&self) -> bool {
+        self.receiver.is_some()
+    }
+
+    /// Start the batch on the rayon thread pool.
+    fn start(&mut self, ctx: &egui::Context) {
+        let (sender, receiver): (Sender<Update>, Receiver<Update>) = mpsc::channel();
+        let files = self.files.iter().map(|(p, _)| p.clone()).collect::<Vec<_>>();
+        let settings = self.settings.clone();
+        let ctx = ctx.clone();
+        for (_, status) in self.files.iter_mut() {
+            *status = FileStatus::Pending;
+        }
+        self.receiver = Some(receiver);
+
+        std::thread::spawn(move || {
+            files.par_iter().enumerate().for_each(|(i, path)| {
+                let _ = sender.send(Update::Status(i, FileStatus::Running));
+                ctx.request_repaint();
+                let status = process_file(path, &settings).unwrap_or_else(|e| FileStatus::Failed(e.to_string()));
+                let _ = sender.send(Update::Status(i, status));
+                ctx.request_repaint();
+            });
+            let _ = sender.send(Update::Finished);
+            ctx.request_repaint();
+        });
+    }
+
+    /// Apply the updates from the running batch.
+    fn receive(&mut self) {
+        let Some(receiver) = &self.receiver else {
+            return;
+        };
+        let mut finished = false;
+        for update in receiver.try_iter() {
+            match update {
+                Update::Status(i, status) => {
+                    if let Some((_, s)) = self.files.get_mut(i) {
+                        *s = status;
+                    }
+                },
+                Update::Finished => finished = true,
+            }
+        }
+        if finished {
+            self.receiver = None;
+        }
+    }
+
+    /// Settings panel.
+    fn settings_ui(&mut self, ui: &mut egui::Ui) {
+        let running = self.is_running();
+        ui.heading("Settings");
+        ui.add_enabled_ui(!running, |ui| {
+            let selected = self.settings.format.as_ref().map(|f| f.to_string()).unwrap_or("keep".to_string());
+            egui::ComboBox::from_label("Format").selected_text(selected).show_ui(ui, |ui| {
+                ui.selectable_value(&mut self.settings.format, None, "keep");
+                for format in FORMATS {
+                    let label = format.to_string();
+                    ui.selectable_value(&mut self.settings.format, Some(format), label);
+                }
+            });
+
+            ui.checkbox(&mut self.settings.compress, "Compress");
+            ui.add_enabled(self.settings.compress, egui::Slider::new(&mut self.settings.quality, 0.0..=100.0).text("Quality"));
+
+            let mut resize = self.settings.resize.is_some();
+            ui.checkbox(&mut resize, "Resize");
+            let mut ratio = self.settings.resize.unwrap_or(50.0);
+            ui.add_enabled(resize, egui::Slider::new(&mut ratio, 1.0..=100.0).text("%"));
+            self.settings.resize = if resize { Some(ratio) } else { None };
+
+            ui.label("Output directory (empty: next to the sources)");
+            ui.text_edit_singleline(&mut self.settings.output_dir);
+            ui.checkbox(&mut self.settings.overwrite, "Overwrite existing files");
+            ui.checkbox(&mut self.settings.lowercase_extension, "Lowercase extensions");
+        });
+
+        ui.separator();
+        ui.horizontal(|ui| {
+            if ui.add_enabled(!running && !self.files.is_empty(), egui::Button::new("Convert")).clicked() {
+                self.start(ui.ctx());
+            }
+            if ui.add_enabled(!running && !self.files.is_empty(), egui::Button::new("Clear")).clicked() {
+                self.files.clear();
+            }
+        });
+        if running {
+            let done = self.files.iter().filter(|(_, s)| matches!(s, FileStatus::Done { .. } | FileStatus::Failed(_))).count();
+            ui.add(egui::ProgressBar::new(done as f32 / self.files.len().max(1) as f32).show_percentage());
+        }
+    }
+
+    /// File list.
+    fn files_ui(&mut self, ui: &mut egui::Ui) {
+        if self.files.is_empty() {
+            ui.centered_and_justified(|ui| {
+                ui.label("Drop images or folders here");
+            });
+            return;
+        }
+        egui::ScrollArea::vertical().show(ui, |ui| {
+            egui::Grid::new("files").striped(true).num_columns(2).show(ui, |ui| {
+                for (path, status) in &self.files {
+                    ui.label(path.display().to_string());
+                    match status {
+                        FileStatus::Pending => ui.label(""),
+                        FileStatus::Running => ui.spinner(),
+                        FileStatus::Done { output, before, after } => ui.label(format!("{} ({} -> {} bytes)", output.display(), before, after)),
+                        FileStatus::Failed(e) => ui.colored_label(egui::Color32::RED, e),
+                    };
+                    ui.end_row();
+                }
+            });
+        });
+    }
+}
+
+impl eframe::App for App {
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        self.receive();
+
+        // ドロップされたファイルを追加
+        let dropped = ctx.input(|i| i.raw.dropped_files.iter().filter_map(|f| f.path.clone()).collect::<Vec<_>>());
+        if !self.is_running() {
+            dropped.into_iter().for_each(|p| self.add(p));
+        }
+
+        egui::SidePanel::left("settings").resizable(false).show(ctx, |ui| self.settings_ui(ui));
+        egui::CentralPanel::default().show(ctx, |ui| self.files_ui(ui));
+
+        if self.is_running() {
+            ctx.request_repaint_after(Duration::from_millis(200));
+        }
+    }
+}
+
+fn main() -> Result<(), eframe::Error> {
+    let options = eframe::NativeOptions {
+        viewport: egui::ViewportBuilder::default()
+            .with_title("rusimg")
+            .with_inner_size([800.0, 500.0])
+            .with_drag_and_drop(true),
+        ..Default::default()
+    };
+    eframe::run_native("rusimg", options, Box::new(|_cc| Ok(Box::<App>::default())))
+}
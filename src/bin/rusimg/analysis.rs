@@ -0,0 +1,240 @@
+use image::{DynamicImage, GenericImageView, Rgba, RgbaImage};
+use image::imageops::FilterType;
+use librusimg::Rect;
+
+/// Check if two pixels are the same color within the tolerance.
+/// The tolerance is compared with the largest difference of the RGBA channels.
+fn is_similar_color(a: &Rgba<u8>, b: &Rgba<u8>, tolerance: u8) -> bool {
+    a.0.iter().zip(b.0.iter()).all(|(a, b)| a.abs_diff(*b) <= tolerance)
+}
+
+/// Detect uniform-color borders of the image.
+/// The color of the top-left pixel is used as the border color.
+/// Returns the area inside the borders, or None if the whole image is a uniform color.
+/// - image: The image to be analyzed.
+/// - tolerance: Allowed difference of each channel from the border color (0-255).
+pub fn detect_border(image: &DynamicImage, tolerance: u8) -> Option<Rect> {
+    let (width, height) = image.dimensions();
+    if width == 0 || height == 0 {
+        return None;
+    }
+    let border_color = image.get_pixel(0, 0);
+    let row_is_border = |y: u32| (0..width).all(|x| is_similar_color(&image.get_pixel(x, y), &border_color, tolerance));
+    let column_is_border = |x: u32, top: u32, bottom: u32| (top..bottom).all(|y| is_similar_color(&image.get_pixel(x, y), &border_color, tolerance));
+
+    // 上下の余白
+    let top = (0..height).find(|y| !row_is_border(*y))?;
+    let bottom = (0..height).rev().find(|y| !row_is_border(*y))? + 1;
+
+    // 左右の余白
+    let left = (0..width).find(|x| !column_is_border(*x, top, bottom))?;
+    let right = (0..width).rev().find(|x| !column_is_border(*x, top, bottom))? + 1;
+
+    Some(Rect {
+        x: left,
+        y: top,
+        w: right - left,
+        h: bottom - top,
+    })
+}
+
+/// Maximum angle (in degrees) detected by detect_skew_angle().
+const MAX_SKEW_ANGLE: f32 = 15.0;
+/// Step of the angle search (in degrees).
+const SKEW_ANGLE_STEP: f32 = 0.25;
+/// Images are downscaled to this size before the skew detection to keep it fast.
+const SKEW_ANALYSIS_SIZE: u32 = 800;
+
+/// Detect the dominant skew angle of text lines/edges (in degrees, within ±MAX_SKEW_ANGLE).
+/// A positive angle means the lines go down to the right; rotating the image counterclockwise by this angle straightens it.
+/// The projection profile of dark pixels is computed for each candidate angle, and the angle with the sharpest profile is chosen.
+pub fn detect_skew_angle(image: &DynamicImage) -> f32 {
+    let luma = image.thumbnail(SKEW_ANALYSIS_SIZE, SKEW_ANALYSIS_SIZE).to_luma8();
+    let (width, height) = luma.dimensions();
+
+    // 暗い画素（文字・罫線）を抽出
+    let dark_pixels = luma.enumerate_pixels()
+        .filter(|(_, _, p)| p.0[0] < 128)
+        .map(|(x, y, _)| (x as f32, y as f32))
+        .collect::<Vec<_>>();
+    if dark_pixels.is_empty() {
+        return 0.0;
+    }
+
+    let bins = (width + height) as usize * 2;
+    let offset = (width + height) as f32;
+    let mut best_angle = 0.0;
+    let mut best_score = 0.0;
+    let steps = (MAX_SKEW_ANGLE / SKEW_ANGLE_STEP) as i32;
+    for step in -steps..=steps {
+        let angle = step as f32 * SKEW_ANGLE_STEP;
+        let (sin, cos) = angle.to_radians().sin_cos();
+        let mut profile = vec![0u64; bins];
+        for (x, y) in &dark_pixels {
+            let row = (y * cos - x * sin + offset) as usize;
+            profile[row.min(bins - 1)] += 1;
+        }
+        let score = profile.iter().map(|c| (c * c) as f64).sum::<f64>();
+        if score > best_score {
+            best_score = score;
+            best_angle = angle;
+        }
+    }
+    best_angle
+}
+
+/// Images are downscaled to this size before the line art detection to keep it fast.
+const LINE_ART_ANALYSIS_SIZE: u32 = 512;
+/// Pixels within this distance from black or white are ink or paper in the line art detection.
+const LINE_ART_TOLERANCE: u8 = 48;
+/// Images with at least this ratio of ink and paper pixels are line art.
+const LINE_ART_RATIO: f32 = 0.9;
+
+/// Check if an image is line art or text (e.g. manga, comics, document scans) rather than a photo.
+/// Line art is mostly black ink on white paper (screentones included), which png compresses well and without artifacts; photos are better as jpeg.
+/// The image is downscaled with the nearest neighbor filter, so that thin lines are not blurred into gray.
+pub fn is_line_art(image: &DynamicImage) -> bool {
+    let luma = image.resize(LINE_ART_ANALYSIS_SIZE, LINE_ART_ANALYSIS_SIZE, FilterType::Nearest).to_luma8();
+    let total = luma.pixels().len();
+    let ink_or_paper = luma.pixels().filter(|p| p.0[0] <= LINE_ART_TOLERANCE || p.0[0] >= 255 - LINE_ART_TOLERANCE).count();
+    total > 0 && ink_or_paper as f32 >= total as f32 * LINE_ART_RATIO
+}
+
+/// Images are downscaled to this size before the photo detection to keep it fast.
+const PHOTO_ANALYSIS_SIZE: u32 = 1000;
+/// Gaps narrower than this (in pixels of the downscaled image) are bridged, so that bright areas of a photo do not split it.
+const PHOTO_GAP: u32 = 3;
+
+/// Estimate the background color of a scan: the median of each channel of the pixels on the edges.
+/// Unlike the top-left pixel, the median is not affected by dust or a photo touching a corner.
+pub fn edge_color(image: &RgbaImage) -> Rgba<u8> {
+    let (width, height) = image.dimensions();
+    let edge = image.enumerate_pixels()
+        .filter(|(x, y, _)| *x == 0 || *y == 0 || *x + 1 == width || *y + 1 == height)
+        .map(|(_, _, p)| *p)
+        .collect::<Vec<_>>();
+    if edge.is_empty() {
+        return Rgba([255, 255, 255, 255]);
+    }
+    let mut color = [0; 4];
+    for (channel, value) in color.iter_mut().enumerate() {
+        let mut values = edge.iter().map(|p| p.0[channel]).collect::<Vec<_>>();
+        values.sort_unstable();
+        *value = values[values.len() / 2];
+    }
+    Rgba(color)
+}
+
+/// Get the mask of the pixels that differ from the background color (true: foreground).
+pub fn foreground_mask(image: &RgbaImage, background: &Rgba<u8>, tolerance: u8) -> Vec<bool> {
+    image.pixels().map(|p| !is_similar_color(p, background, tolerance)).collect()
+}
+
+/// Grow the foreground of a mask by radius pixels (a square max filter, applied to each axis).
+fn dilate(mask: &[bool], width: usize, height: usize, radius: usize) -> Vec<bool> {
+    let mut horizontal = vec![false; mask.len()];
+    for y in 0..height {
+        for x in 0..width {
+            horizontal[y * width + x] = (x.saturating_sub(radius)..(x + radius + 1).min(width)).any(|x| mask[y * width + x]);
+        }
+    }
+    let mut dilated = vec![false; mask.len()];
+    for y in 0..height {
+        for x in 0..width {
+            dilated[y * width + x] = (y.saturating_sub(radius)..(y + radius + 1).min(height)).any(|y| horizontal[y * width + x]);
+        }
+    }
+    dilated
+}
+
+/// Get the bounding boxes (left, top, right, bottom) of the connected regions of a mask (4-neighborhood) with at least min_pixels pixels.
+fn connected_regions(mask: &[bool], width: usize, height: usize, min_pixels: u64) -> Vec<(usize, usize, usize, usize)> {
+    let mut visited = vec![false; mask.len()];
+    let mut regions = Vec::new();
+    for start in 0..mask.len() {
+        if !mask[start] || visited[start] {
+            continue;
+        }
+        visited[start] = true;
+        let mut stack = vec![start];
+        let (mut left, mut top, mut right, mut bottom, mut pixels) = (width, height, 0, 0, 0);
+        while let Some(i) = stack.pop() {
+            let (x, y) = (i % width, i / width);
+            left = left.min(x);
+            top = top.min(y);
+            right = right.max(x + 1);
+            bottom = bottom.max(y + 1);
+            pixels += 1;
+            let neighbors = [(x > 0).then(|| i - 1), (x + 1 < width).then(|| i + 1), (y > 0).then(|| i - width), (y + 1 < height).then(|| i + width)];
+            for neighbor in neighbors.into_iter().flatten() {
+                if mask[neighbor] && !visited[neighbor] {
+                    visited[neighbor] = true;
+                    stack.push(neighbor);
+                }
+            }
+        }
+        if pixels >= min_pixels {
+            regions.push((left, top, right, bottom));
+        }
+    }
+    regions
+}
+
+/// Sort areas in the reading order: rows from top to bottom, and the areas of each row from left to right.
+/// An area belongs to the current row if it starts above the bottom of the row.
+fn reading_order(mut areas: Vec<Rect>) -> Vec<Rect> {
+    areas.sort_by_key(|a| (a.y, a.x));
+    let mut rows: Vec<(u32, Vec<Rect>)> = Vec::new();
+    for area in areas {
+        match rows.last_mut() {
+            Some((bottom, row)) if area.y < *bottom => {
+                *bottom = (*bottom).max(area.y + area.h);
+                row.push(area);
+            },
+            _ => rows.push((area.y + area.h, vec![area])),
+        }
+    }
+    rows.into_iter().flat_map(|(_, mut row)| {
+        row.sort_by_key(|a| a.x);
+        row
+    }).collect()
+}
+
+/// Detect separate photos on a flatbed scan.
+/// The pixels that differ from the background (see edge_color()) are grouped into connected regions, and the bounding box of each region is a photo.
+/// The boxes include a small margin of the background, so that a skewed photo can be straightened without losing its corners.
+/// Returns the areas in the reading order. Regions smaller than min_area percent of the scan (e.g. dust) and regions inside another photo are ignored.
+/// - image: The scan.
+/// - tolerance: Allowed difference of each channel from the background color (0-255).
+/// - min_area: Minimum area of a photo in percent of the scan.
+pub fn detect_photos(image: &DynamicImage, tolerance: u8, min_area: f32) -> Vec<Rect> {
+    let (width, height) = image.dimensions();
+    if width == 0 || height == 0 {
+        return Vec::new();
+    }
+    let small = image.thumbnail(PHOTO_ANALYSIS_SIZE, PHOTO_ANALYSIS_SIZE).to_rgba8();
+    let (small_width, small_height) = (small.width() as usize, small.height() as usize);
+    let mask = foreground_mask(&small, &edge_color(&small), tolerance);
+    // 写真の中の明るい部分で領域が分かれないように、隙間を埋める
+    let mask = dilate(&mask, small_width, small_height, PHOTO_GAP as usize);
+    let min_pixels = ((small_width * small_height) as f64 * min_area as f64 / 100.0).ceil().max(1.0) as u64;
+    let regions = connected_regions(&mask, small_width, small_height, min_pixels);
+
+    // 他の写真の中にある領域を除く
+    let inside = |a: &(usize, usize, usize, usize), b: &(usize, usize, usize, usize)| a != b && b.0 <= a.0 && b.1 <= a.1 && a.2 <= b.2 && a.3 <= b.3;
+    let (scale_x, scale_y) = (width as f64 / small_width as f64, height as f64 / small_height as f64);
+    let photos = regions.iter()
+        .filter(|a| !regions.iter().any(|b| inside(a, b)))
+        .map(|(left, top, right, bottom)| {
+            let (left, top) = ((*left as f64 * scale_x).floor() as u32, (*top as f64 * scale_y).floor() as u32);
+            let (right, bottom) = (((*right as f64 * scale_x).ceil() as u32).min(width), ((*bottom as f64 * scale_y).ceil() as u32).min(height));
+            Rect {
+                x: left,
+                y: top,
+                w: right - left,
+                h: bottom - top,
+            }
+        })
+        .collect();
+    reading_order(photos)
+}
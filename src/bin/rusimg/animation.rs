@@ -0,0 +1,306 @@
+use std::fs;
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use image::{Delay, DynamicImage, ExtendedColorType, Frame, GenericImageView, ImageEncoder};
+use image::codecs::gif::{GifEncoder, Repeat};
+use image::codecs::png::PngEncoder;
+use image::codecs::webp::WebPEncoder;
+use image::imageops::FilterType;
+
+use librusimg::frames::Frames;
+
+use crate::split;
+
+/// Frame rate when --fps is not specified.
+pub const DEFAULT_FPS: f32 = 10.0;
+/// Speed of the color quantization of gif frames (1-30). 1 is the best quality but too slow for long sequences.
+const GIF_QUANTIZE_SPEED: i32 = 10;
+
+/// Formats of animations, chosen by the extension of the output.
+/// - Gif: .gif (256 colors per frame).
+/// - Webp: .webp (lossless frames).
+/// - Apng: .png or .apng.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AnimationFormat {
+    Gif,
+    Webp,
+    Apng,
+}
+
+impl AnimationFormat {
+    /// Get the format of an output path.
+    pub fn from_path(path: &Path) -> Option<AnimationFormat> {
+        let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+        match extension.as_str() {
+            "gif" => Some(AnimationFormat::Gif),
+            "webp" => Some(AnimationFormat::Webp),
+            "png" | "apng" => Some(AnimationFormat::Apng),
+            _ => None,
+        }
+    }
+}
+
+/// ToGifOptions is the options of the to-gif subcommand.
+/// - frames: Frames (file paths or wildcard patterns).
+/// - output: Path of the animation.
+/// - format: Format of the animation (from the extension of output).
+/// - fps: Frames per second.
+/// - loops: Number of times to play the animation. 0 is forever.
+#[derive(Debug, Clone)]
+pub struct ToGifOptions {
+    pub frames: Vec<PathBuf>,
+    pub output: PathBuf,
+    pub format: AnimationFormat,
+    pub fps: f32,
+    pub loops: u16,
+}
+
+/// Load the frames from image files, in the order of paths, to be shown at fps.
+/// Frames of a different size from the first frame are resized to its size.
+pub fn load_frames(paths: &[PathBuf], fps: f32) -> Result<Frames, String> {
+    let mut images: Vec<DynamicImage> = Vec::with_capacity(paths.len());
+    for path in paths {
+        let image = image::open(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        let image = match images.first() {
+            Some(first) if first.dimensions() != image.dimensions() => {
+                println!("{}: resized from {}x{} to {}x{} (the size of the first frame)", path.display(), image.width(), image.height(), first.width(), first.height());
+                image.resize_exact(first.width(), first.height(), FilterType::Lanczos3)
+            },
+            _ => image,
+        };
+        images.push(image);
+    }
+    Frames::with_frame_rate(images, fps).ok_or("No frames".to_string())
+}
+
+/// Get the duration of each frame in 1/units_per_second seconds.
+/// The durations are rounded from the timestamps, so that the total does not drift at rates such as 12 fps (8.33 / 100 seconds in gif).
+fn durations(frames: &Frames, units_per_second: u32) -> Vec<u32> {
+    let mut elapsed = Duration::ZERO;
+    let mut previous = 0;
+    frames.iter().map(|frame| {
+        elapsed += frame.delay;
+        let timestamp = (elapsed.as_secs_f64() * units_per_second as f64).round() as u32;
+        let duration = timestamp.saturating_sub(previous).max(1);
+        previous = timestamp;
+        duration
+    }).collect()
+}
+
+/// Get the natural sort key of a file name: runs of digits are compared by value (frame2 before frame10).
+fn natural_key(path: &Path) -> Vec<(String, u64)> {
+    let name = path.file_name().unwrap_or_default().to_string_lossy().to_lowercase();
+    let mut key = Vec::new();
+    let mut text = String::new();
+    let mut chars = name.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c.is_ascii_digit() {
+            let mut number = c.to_digit(10).unwrap_or(0) as u64;
+            while let Some(digit) = chars.peek().and_then(|d| d.to_digit(10)) {
+                number = number.saturating_mul(10).saturating_add(digit as u64);
+                chars.next();
+            }
+            key.push((std::mem::take(&mut text), number));
+        }
+        else {
+            text.push(c);
+        }
+    }
+    key.push((text, 0));
+    key
+}
+
+/// Write the frames as a gif.
+fn write_gif(frames: &Frames, output: &Path) -> Result<(), String> {
+    let err = |e: &dyn std::fmt::Display| format!("{}: {}", output.display(), e);
+    let file = fs::File::create(output).map_err(|e| err(&e))?;
+    let mut encoder = GifEncoder::new_with_speed(BufWriter::new(file), GIF_QUANTIZE_SPEED);
+    let repeat = if frames.loop_count == 0 { Repeat::Infinite } else { Repeat::Finite(frames.loop_count) };
+    encoder.set_repeat(repeat).map_err(|e| err(&e))?;
+    // gif の遅延は 1/100 秒単位
+    for (frame, centiseconds) in frames.iter().zip(durations(frames, 100)) {
+        let delay = Delay::from_numer_denom_ms(centiseconds * 10, 1);
+        encoder.encode_frame(Frame::from_parts(frame.image.to_rgba8(), 0, 0, delay)).map_err(|e| err(&e))?;
+    }
+    Ok(())
+}
+
+/// CRC-32 of a png chunk (type and data).
+fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = 0xFFFF_FFFFu32;
+    for byte in bytes {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 { (crc >> 1) ^ 0xEDB8_8320 } else { crc >> 1 };
+        }
+    }
+    !crc
+}
+
+/// Append a png chunk: length, type, data and CRC.
+fn png_chunk(out: &mut Vec<u8>, chunk_type: &[u8; 4], data: &[u8]) {
+    out.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    let start = out.len();
+    out.extend_from_slice(chunk_type);
+    out.extend_from_slice(data);
+    let crc = crc32(&out[start..]);
+    out.extend_from_slice(&crc.to_be_bytes());
+}
+
+/// Split a png file into its chunks (type, data).
+fn png_chunks(png: &[u8]) -> Vec<([u8; 4], &[u8])> {
+    let mut chunks = Vec::new();
+    // 8 バイトのシグネチャの後にチャンクが続く
+    let mut i = 8;
+    while i + 12 <= png.len() {
+        let length = u32::from_be_bytes([png[i], png[i + 1], png[i + 2], png[i + 3]]) as usize;
+        let chunk_type = [png[i + 4], png[i + 5], png[i + 6], png[i + 7]];
+        let Some(data) = png.get(i + 8..i + 8 + length) else { break };
+        chunks.push((chunk_type, data));
+        i += 12 + length;
+    }
+    chunks
+}
+
+/// Write the frames as an animated png (APNG).
+/// Each frame is encoded as a png, and its image data is moved to the frame chunks (fcTL, fdAT) of the animation.
+/// Viewers without APNG support show the first frame.
+fn write_apng(frames: &Frames, output: &Path) -> Result<(), String> {
+    let (width, height) = frames.first().image.dimensions();
+    let mut apng = b"\x89PNG\r\n\x1a\n".to_vec();
+    // fcTL と fdAT で共通の通し番号
+    let mut sequence_number = 0u32;
+
+    for (index, (frame, milliseconds)) in frames.iter().zip(durations(frames, 1000)).enumerate() {
+        let mut png = Vec::new();
+        PngEncoder::new(&mut png).write_image(frame.image.to_rgba8().as_raw(), width, height, ExtendedColorType::Rgba8).map_err(|e| e.to_string())?;
+        let chunks = png_chunks(&png);
+        if index == 0 {
+            let (_, header) = chunks.iter().find(|(t, _)| t == b"IHDR").ok_or("Failed to encode the frame")?;
+            png_chunk(&mut apng, b"IHDR", header);
+            let mut control = (frames.len() as u32).to_be_bytes().to_vec();
+            control.extend_from_slice(&(frames.loop_count as u32).to_be_bytes());
+            png_chunk(&mut apng, b"acTL", &control);
+        }
+
+        // 位置 (0, 0)、遅延 milliseconds / 1000 秒、dispose: none、blend: source
+        let mut frame_control = Vec::with_capacity(26);
+        for value in [sequence_number, width, height, 0, 0] {
+            frame_control.extend_from_slice(&value.to_be_bytes());
+        }
+        frame_control.extend_from_slice(&(milliseconds.min(u16::MAX as u32) as u16).to_be_bytes());
+        frame_control.extend_from_slice(&1000u16.to_be_bytes());
+        frame_control.extend_from_slice(&[0, 0]);
+        png_chunk(&mut apng, b"fcTL", &frame_control);
+        sequence_number += 1;
+
+        // 最初のフレームは IDAT のまま (APNG 非対応のビューアで表示される)、以降は fdAT にする
+        for (_, data) in chunks.iter().filter(|(t, _)| t == b"IDAT") {
+            if index == 0 {
+                png_chunk(&mut apng, b"IDAT", data);
+            }
+            else {
+                let mut frame_data = sequence_number.to_be_bytes().to_vec();
+                frame_data.extend_from_slice(data);
+                png_chunk(&mut apng, b"fdAT", &frame_data);
+                sequence_number += 1;
+            }
+        }
+    }
+    png_chunk(&mut apng, b"IEND", &[]);
+
+    fs::write(output, apng).map_err(|e| format!("{}: {}", output.display(), e))
+}
+
+/// Append a RIFF chunk of webp: type, length, data and a padding byte to an even length.
+fn riff_chunk(out: &mut Vec<u8>, fourcc: &[u8; 4], data: &[u8]) {
+    out.extend_from_slice(fourcc);
+    out.extend_from_slice(&(data.len() as u32).to_le_bytes());
+    out.extend_from_slice(data);
+    if data.len() % 2 == 1 {
+        out.push(0);
+    }
+}
+
+/// Append a 24-bit little-endian value (the sizes and durations of the animation chunks of webp).
+fn push_u24(out: &mut Vec<u8>, value: u32) {
+    out.extend_from_slice(&value.to_le_bytes()[..3]);
+}
+
+/// Write the frames as an animated webp.
+/// Each frame is encoded as a lossless webp, and its bitstream (VP8L chunk) is put into a frame chunk (ANMF) of the animation.
+fn write_webp(frames: &Frames, output: &Path) -> Result<(), String> {
+    let (width, height) = frames.first().image.dimensions();
+    let mut chunks = Vec::new();
+
+    // VP8X: アニメーション (0x02) とアルファ (0x10) のフラグ、キャンバスの幅 - 1、高さ - 1
+    let mut header = vec![0x02 | 0x10, 0, 0, 0];
+    push_u24(&mut header, width - 1);
+    push_u24(&mut header, height - 1);
+    riff_chunk(&mut chunks, b"VP8X", &header);
+    // ANIM: 背景色 (BGRA) とループ回数
+    let mut animation = vec![0, 0, 0, 0];
+    animation.extend_from_slice(&frames.loop_count.to_le_bytes());
+    riff_chunk(&mut chunks, b"ANIM", &animation);
+
+    for (frame, milliseconds) in frames.iter().zip(durations(frames, 1000)) {
+        let mut webp = Vec::new();
+        WebPEncoder::new_lossless(&mut webp).write_image(frame.image.to_rgba8().as_raw(), width, height, ExtendedColorType::Rgba8).map_err(|e| e.to_string())?;
+
+        // RIFF ヘッダー (12 バイト) の後のチャンクから VP8L を取り出す
+        let mut bitstream = None;
+        let mut i = 12;
+        while i + 8 <= webp.len() {
+            let length = u32::from_le_bytes([webp[i + 4], webp[i + 5], webp[i + 6], webp[i + 7]]) as usize;
+            if &webp[i..i + 4] == b"VP8L" {
+                bitstream = webp.get(i + 8..i + 8 + length);
+                break;
+            }
+            i += 8 + length + length % 2;
+        }
+        let bitstream = bitstream.ok_or("Failed to encode the frame")?;
+
+        // ANMF: 位置 (0, 0)、幅 - 1、高さ - 1、表示時間、フラグ (ブレンドしない)
+        let mut frame_chunk = Vec::new();
+        push_u24(&mut frame_chunk, 0);
+        push_u24(&mut frame_chunk, 0);
+        push_u24(&mut frame_chunk, width - 1);
+        push_u24(&mut frame_chunk, height - 1);
+        push_u24(&mut frame_chunk, milliseconds.min(0xFF_FFFF));
+        frame_chunk.push(0x02);
+        riff_chunk(&mut frame_chunk, b"VP8L", bitstream);
+        riff_chunk(&mut chunks, b"ANMF", &frame_chunk);
+    }
+
+    let mut riff = b"RIFF".to_vec();
+    riff.extend_from_slice(&(4 + chunks.len() as u32).to_le_bytes());
+    riff.extend_from_slice(b"WEBP");
+    riff.extend_from_slice(&chunks);
+    fs::write(output, riff).map_err(|e| format!("{}: {}", output.display(), e))
+}
+
+/// Assemble the frames into an animation.
+/// The frames are ordered by their file names, with numbers compared by value (frame2 before frame10).
+pub fn run(options: &ToGifOptions) -> Result<(), String> {
+    let mut paths = Vec::new();
+    for path in &options.frames {
+        paths.append(&mut split::expand(path)?);
+    }
+    paths.sort_by_cached_key(|path| natural_key(path));
+
+    let mut frames = load_frames(&paths, options.fps)?;
+    frames.loop_count = options.loops;
+    if let Some(parent) = options.output.parent().filter(|p| !p.as_os_str().is_empty()) {
+        fs::create_dir_all(parent).map_err(|e| format!("{}: {}", parent.display(), e))?;
+    }
+    match options.format {
+        AnimationFormat::Gif => write_gif(&frames, &options.output)?,
+        AnimationFormat::Webp => write_webp(&frames, &options.output)?,
+        AnimationFormat::Apng => write_apng(&frames, &options.output)?,
+    }
+
+    let size = frames.size();
+    println!("{}: {} frames, {}x{}, {} fps", options.output.display(), frames.len(), size.width, size.height, options.fps);
+    Ok(())
+}
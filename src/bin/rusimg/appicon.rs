@@ -0,0 +1,214 @@
+use std::collections::HashSet;
+use std::fs;
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+use image::{DynamicImage, ExtendedColorType, Rgba, RgbaImage, imageops::FilterType};
+use image::codecs::ico::{IcoEncoder, IcoFrame};
+use serde_json::json;
+
+/// Platform of the icon set.
+/// - Ios: AppIcon.appiconset for Xcode (iPhone, iPad and App Store).
+/// - Android: mipmap-* launcher icons (square and round) and the Play Store icon.
+/// - Web: favicons, touch icons and a web app manifest.
+#[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq)]
+pub enum IconTarget {
+    Ios,
+    Android,
+    Web,
+}
+
+/// AppIconOptions is the options of the appicon subcommand.
+/// - image: Source image (square, 1024x1024 or larger is recommended).
+/// - target: Platform of the icon set.
+/// - output: Output directory. If None, <image name>-<target>-icons next to the image.
+#[derive(Debug, Clone)]
+pub struct AppIconOptions {
+    pub image: PathBuf,
+    pub target: IconTarget,
+    pub output: Option<PathBuf>,
+}
+
+/// iOS icons: idiom, size in points and scale.
+const IOS_ICONS: [(&str, f32, u32); 18] = [
+    ("iphone", 20.0, 2), ("iphone", 20.0, 3),
+    ("iphone", 29.0, 2), ("iphone", 29.0, 3),
+    ("iphone", 40.0, 2), ("iphone", 40.0, 3),
+    ("iphone", 60.0, 2), ("iphone", 60.0, 3),
+    ("ipad", 20.0, 1), ("ipad", 20.0, 2),
+    ("ipad", 29.0, 1), ("ipad", 29.0, 2),
+    ("ipad", 40.0, 1), ("ipad", 40.0, 2),
+    ("ipad", 76.0, 1), ("ipad", 76.0, 2),
+    ("ipad", 83.5, 2),
+    ("ios-marketing", 1024.0, 1),
+];
+/// Android launcher icons: density and size in pixels.
+const ANDROID_ICONS: [(&str, u32); 5] = [("mdpi", 48), ("hdpi", 72), ("xhdpi", 96), ("xxhdpi", 144), ("xxxhdpi", 192)];
+/// Size of the Play Store icon.
+const ANDROID_PLAY_STORE_SIZE: u32 = 512;
+/// Web icons: file name and size in pixels.
+const WEB_ICONS: [(&str, u32); 5] = [
+    ("favicon-16x16.png", 16),
+    ("favicon-32x32.png", 32),
+    ("apple-touch-icon.png", 180),
+    ("android-chrome-192x192.png", 192),
+    ("android-chrome-512x512.png", 512),
+];
+/// Sizes in favicon.ico.
+const FAVICON_SIZES: [u32; 3] = [16, 32, 48];
+
+/// Crop the center of the image to a square.
+fn square(image: &DynamicImage) -> DynamicImage {
+    let size = image.width().min(image.height());
+    image.crop_imm((image.width() - size) / 2, (image.height() - size) / 2, size, size)
+}
+
+/// Resize the square source to size x size.
+fn icon(source: &DynamicImage, size: u32) -> RgbaImage {
+    source.resize_exact(size, size, FilterType::Lanczos3).to_rgba8()
+}
+
+/// Put the icon on a white background. iOS icons must not have an alpha channel.
+fn opaque(icon: &RgbaImage) -> DynamicImage {
+    let mut background = RgbaImage::from_pixel(icon.width(), icon.height(), Rgba([255, 255, 255, 255]));
+    image::imageops::overlay(&mut background, icon, 0, 0);
+    DynamicImage::ImageRgb8(DynamicImage::ImageRgba8(background).to_rgb8())
+}
+
+/// Cut the icon into a circle (Android round icons).
+fn round(icon: &RgbaImage) -> RgbaImage {
+    let mut round = icon.clone();
+    let radius = icon.width() as f32 / 2.0;
+    for (x, y, pixel) in round.enumerate_pixels_mut() {
+        let (dx, dy) = (x as f32 + 0.5 - radius, y as f32 + 0.5 - radius);
+        // 縁は 1 画素分でアンチエイリアス
+        let coverage = (radius - (dx * dx + dy * dy).sqrt() + 0.5).clamp(0.0, 1.0);
+        pixel.0[3] = (pixel.0[3] as f32 * coverage).round() as u8;
+    }
+    round
+}
+
+/// Save an image and print its path.
+fn save(image: &DynamicImage, path: &Path) -> Result<(), String> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(|e| format!("{}: {}", parent.display(), e))?;
+    }
+    image.save(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    println!("{}", path.display());
+    Ok(())
+}
+
+/// Write a text file (Contents.json, manifest) and print its path.
+fn write_text(path: &Path, text: &str) -> Result<(), String> {
+    fs::write(path, text).map_err(|e| format!("{}: {}", path.display(), e))?;
+    println!("{}", path.display());
+    Ok(())
+}
+
+/// Format a size in points without a needless fraction (e.g. 20, 83.5).
+fn points(size: f32) -> String {
+    if size.fract() == 0.0 { format!("{}", size as u32) } else { format!("{}", size) }
+}
+
+/// iOS: AppIcon.appiconset with Contents.json.
+fn ios(source: &DynamicImage, output: &Path) -> Result<(), String> {
+    let dir = output.join("AppIcon.appiconset");
+    let mut images = Vec::new();
+    let mut written = HashSet::new();
+    for (idiom, size, scale) in IOS_ICONS {
+        let file_name = format!("Icon-{}@{}x.png", points(size), scale);
+        // iPhone と iPad で同じファイルを共有
+        if written.insert(file_name.clone()) {
+            save(&opaque(&icon(source, (size * scale as f32).round() as u32)), &dir.join(&file_name))?;
+        }
+        images.push(json!({
+            "filename": file_name,
+            "idiom": idiom,
+            "scale": format!("{}x", scale),
+            "size": format!("{}x{}", points(size), points(size)),
+        }));
+    }
+    let contents = json!({ "images": images, "info": { "author": "rusimg", "version": 1 } });
+    write_text(&dir.join("Contents.json"), &serde_json::to_string_pretty(&contents).unwrap_or_default())
+}
+
+/// Android: res/mipmap-*/ic_launcher.png and ic_launcher_round.png, and the Play Store icon.
+fn android(source: &DynamicImage, output: &Path) -> Result<(), String> {
+    for (density, size) in ANDROID_ICONS {
+        let dir = output.join("res").join(format!("mipmap-{}", density));
+        let square = icon(source, size);
+        save(&DynamicImage::ImageRgba8(round(&square)), &dir.join("ic_launcher_round.png"))?;
+        save(&DynamicImage::ImageRgba8(square), &dir.join("ic_launcher.png"))?;
+    }
+    save(&DynamicImage::ImageRgba8(icon(source, ANDROID_PLAY_STORE_SIZE)), &output.join("ic_launcher-playstore.png"))?;
+
+    println!("\nAndroidManifest.xml:");
+    println!("<application");
+    println!("    android:icon=\"@mipmap/ic_launcher\"");
+    println!("    android:roundIcon=\"@mipmap/ic_launcher_round\"");
+    println!("    ...>");
+    Ok(())
+}
+
+/// Web: favicons, touch icons, favicon.ico (16, 32 and 48 px) and site.webmanifest.
+fn web(source: &DynamicImage, output: &Path) -> Result<(), String> {
+    for (file_name, size) in WEB_ICONS {
+        save(&DynamicImage::ImageRgba8(icon(source, size)), &output.join(file_name))?;
+    }
+
+    let mut frames = Vec::new();
+    for size in FAVICON_SIZES {
+        let icon = icon(source, size);
+        frames.push(IcoFrame::as_png(icon.as_raw(), size, size, ExtendedColorType::Rgba8).map_err(|e| e.to_string())?);
+    }
+    let ico_path = output.join("favicon.ico");
+    let file = fs::File::create(&ico_path).map_err(|e| format!("{}: {}", ico_path.display(), e))?;
+    IcoEncoder::new(BufWriter::new(file)).encode_images(&frames).map_err(|e| format!("{}: {}", ico_path.display(), e))?;
+    println!("{}", ico_path.display());
+
+    let manifest = json!({
+        "icons": [
+            { "src": "/android-chrome-192x192.png", "sizes": "192x192", "type": "image/png" },
+            { "src": "/android-chrome-512x512.png", "sizes": "512x512", "type": "image/png" },
+        ],
+    });
+    write_text(&output.join("site.webmanifest"), &serde_json::to_string_pretty(&manifest).unwrap_or_default())?;
+
+    println!("\nHTML:");
+    println!("<link rel=\"icon\" href=\"/favicon.ico\" sizes=\"48x48\">");
+    println!("<link rel=\"icon\" type=\"image/png\" sizes=\"32x32\" href=\"/favicon-32x32.png\">");
+    println!("<link rel=\"icon\" type=\"image/png\" sizes=\"16x16\" href=\"/favicon-16x16.png\">");
+    println!("<link rel=\"apple-touch-icon\" sizes=\"180x180\" href=\"/apple-touch-icon.png\">");
+    println!("<link rel=\"manifest\" href=\"/site.webmanifest\">");
+    Ok(())
+}
+
+/// Generate the icon set of the target from the image.
+/// Non-square images are cropped to the center square.
+pub fn run(options: &AppIconOptions) -> Result<(), String> {
+    let source = librusimg::open_image(&options.image).map_err(|e| e.to_string())?;
+    let source = source.as_dynamic_image();
+    if source.width() != source.height() {
+        println!("The image is not square ({}x{}); the center is used.", source.width(), source.height());
+    }
+    let source = square(source);
+
+    let output = match &options.output {
+        Some(output) => output.clone(),
+        None => {
+            let stem = options.image.file_stem().and_then(|s| s.to_str()).unwrap_or("icon");
+            let target = match options.target {
+                IconTarget::Ios => "ios",
+                IconTarget::Android => "android",
+                IconTarget::Web => "web",
+            };
+            options.image.with_file_name(format!("{}-{}-icons", stem, target))
+        },
+    };
+    fs::create_dir_all(&output).map_err(|e| format!("{}: {}", output.display(), e))?;
+
+    match options.target {
+        IconTarget::Ios => ios(&source, &output),
+        IconTarget::Android => android(&source, &output),
+        IconTarget::Web => web(&source, &output),
+    }
+}
@@ -0,0 +1,82 @@
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter};
+use std::path::{Path, PathBuf};
+use zip::{AesMode, CompressionMethod, ZipArchive, ZipWriter};
+use zip::write::SimpleFileOptions;
+
+use crate::flatten::{DedupeMode, Names};
+
+/// Write the outputs to a zip archive.
+/// The entries are named after the file names of the outputs; duplicate names get the name of the parent directory appended.
+/// If a password is given, each entry is encrypted with AES-256 (WinZip AE-2), which is supported by 7-Zip, WinZip and most unzip tools except the Windows Explorer.
+/// Images are already compressed, so the entries are stored without compression.
+pub fn write_zip(archive: &Path, files: &[PathBuf], password: Option<&str>) -> Result<(), String> {
+    let err = |e: &dyn std::fmt::Display| format!("{}: {}", archive.display(), e);
+    let file = File::create(archive).map_err(|e| err(&e))?;
+    let mut zip = ZipWriter::new(BufWriter::new(file));
+
+    let mut options = SimpleFileOptions::default().compression_method(CompressionMethod::Stored);
+    if let Some(password) = password {
+        options = options.with_aes_encryption(AesMode::Aes256, password);
+    }
+
+    let mut names = Names::default();
+    for path in files {
+        let name = PathBuf::from(path.file_name().unwrap_or_default());
+        let Some(name) = names.unique(name, path, Some(DedupeMode::Parent)) else {
+            continue;
+        };
+        zip.start_file(name.to_string_lossy(), options).map_err(|e| err(&e))?;
+        let mut reader = BufReader::new(File::open(path).map_err(|e| format!("{}: {}", path.display(), e))?);
+        io::copy(&mut reader, &mut zip).map_err(|e| err(&e))?;
+    }
+    zip.finish().map_err(|e| err(&e))?;
+    Ok(())
+}
+
+/// Get the file name of the n-th entry extracted by extract_entries(), e.g. "00012.jpg".
+/// The names of the entries are not used as paths, so that an archive cannot write outside of the directory.
+pub fn entry_file_name(index: usize, name: &str) -> String {
+    match Path::new(name).extension() {
+        Some(extension) => format!("{:05}.{}", index, extension.to_string_lossy()),
+        None => format!("{:05}", index),
+    }
+}
+
+/// Extract the files of a zip archive (e.g. a CBZ comic) to a directory, in the order of the archive.
+/// Returns the names of the entries, and the n-th entry is written as entry_file_name(n, name). Directories are skipped.
+pub fn extract_entries(archive: &Path, dir: &Path) -> Result<Vec<String>, String> {
+    let err = |e: &dyn std::fmt::Display| format!("{}: {}", archive.display(), e);
+    let file = File::open(archive).map_err(|e| err(&e))?;
+    let mut zip = ZipArchive::new(BufReader::new(file)).map_err(|e| err(&e))?;
+
+    let mut names = Vec::new();
+    for i in 0..zip.len() {
+        let mut entry = zip.by_index(i).map_err(|e| err(&e))?;
+        if entry.is_dir() {
+            continue;
+        }
+        let path = dir.join(entry_file_name(names.len(), entry.name()));
+        let mut writer = BufWriter::new(File::create(&path).map_err(|e| format!("{}: {}", path.display(), e))?);
+        io::copy(&mut entry, &mut writer).map_err(|e| err(&e))?;
+        names.push(entry.name().to_string());
+    }
+    Ok(names)
+}
+
+/// Write files to a zip archive (e.g. a CBZ comic) as the entries of the given names, in the given order.
+/// Images are already compressed, so the entries are stored without compression.
+pub fn write_entries(archive: &Path, entries: &[(String, PathBuf)]) -> Result<(), String> {
+    let err = |e: &dyn std::fmt::Display| format!("{}: {}", archive.display(), e);
+    let file = File::create(archive).map_err(|e| err(&e))?;
+    let mut zip = ZipWriter::new(BufWriter::new(file));
+    let options = SimpleFileOptions::default().compression_method(CompressionMethod::Stored);
+
+    for (name, path) in entries {
+        zip.start_file(name.as_str(), options).map_err(|e| err(&e))?;
+        let mut reader = BufReader::new(File::open(path).map_err(|e| format!("{}: {}", path.display(), e))?);
+        io::copy(&mut reader, &mut zip).map_err(|e| err(&e))?;
+    }
+    zip.finish().map_err(|e| err(&e))?;
+    Ok(())
+}
@@ -0,0 +1,109 @@
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use image::{DynamicImage, Rgb, RgbImage};
+use librusimg::{Extension, RusimgError};
+use librusimg::tempdir::TempDir;
+use colored::*;
+
+/// Size of the synthetic image used when no image is specified.
+const FIXTURE_WIDTH: u32 = 2048;
+const FIXTURE_HEIGHT: u32 = 1536;
+/// Formats measured by the benchmark.
+const FORMATS: [Extension; 4] = [Extension::Jpeg, Extension::Png, Extension::Webp, Extension::Bmp];
+
+/// BenchOptions is the options of the hidden bench subcommand.
+/// - iterations: Number of iterations of each measurement.
+/// - image: Image used for the measurement. If None, a synthetic image is generated.
+#[derive(Debug, Clone)]
+pub struct BenchOptions {
+    pub iterations: u32,
+    pub image: Option<PathBuf>,
+}
+
+/// Generate a synthetic photo-like image (gradients with noise-like patterns).
+fn generate_fixture() -> DynamicImage {
+    let image = RgbImage::from_fn(FIXTURE_WIDTH, FIXTURE_HEIGHT, |x, y| {
+        let r = (x * 255 / FIXTURE_WIDTH) as u8;
+        let g = (y * 255 / FIXTURE_HEIGHT) as u8;
+        let b = ((x ^ y).wrapping_mul(2654435761) >> 24) as u8;
+        Rgb([r, g, b])
+    });
+    DynamicImage::ImageRgb8(image)
+}
+
+/// Run `setup` and `f` `iterations` times and return the average time of `f`.
+/// The time of `setup` (e.g. opening the image) is not included.
+fn measure<T, S, F>(iterations: u32, mut setup: S, mut f: F) -> Result<Duration, RusimgError>
+where
+    S: FnMut() -> Result<T, RusimgError>,
+    F: FnMut(T) -> Result<(), RusimgError>,
+{
+    let mut total = Duration::ZERO;
+    for _ in 0..iterations {
+        let input = setup()?;
+        let start = Instant::now();
+        f(input)?;
+        total += start.elapsed();
+    }
+    Ok(total / iterations.max(1))
+}
+
+/// Print a line of the result.
+fn print_result(name: &str, format: &Extension, elapsed: Duration) {
+    println!("{:<10} {:<6} {:>10.1} ms", name, format.to_string(), elapsed.as_secs_f64() * 1000.0);
+}
+
+/// Write the fixture in each format to the working directory.
+fn write_fixtures(source: &Path, dir: &Path) -> Result<Vec<(Extension, PathBuf)>, RusimgError> {
+    let mut ret = Vec::new();
+    for format in FORMATS {
+        let path = dir.join(format!("fixture.{}", format));
+        let mut image = librusimg::open_image(source)?;
+        if image.get_extension() != format {
+            image.convert(&format)?;
+        }
+        image.save_image(path.to_str())?;
+        ret.push((format, path));
+    }
+    Ok(ret)
+}
+
+/// Measure decode, resize, compress and save on this machine.
+/// The fixtures are written to a temporary directory (--temp-dir), which is removed after the measurement.
+pub fn run(options: &BenchOptions) -> Result<(), String> {
+    let dir = TempDir::new().map_err(|e| e.to_string())?;
+    run_in(options, dir.path())
+}
+
+/// Write the fixtures to dir and print the results.
+fn run_in(options: &BenchOptions, dir: &Path) -> Result<(), String> {
+    let iterations = options.iterations;
+    let source = match &options.image {
+        Some(image) => image.clone(),
+        None => {
+            let path = dir.join("source.png");
+            generate_fixture().save(&path).map_err(|e| e.to_string())?;
+            path
+        },
+    };
+    let fixtures = write_fixtures(&source, dir).map_err(|e| e.to_string())?;
+    let size = librusimg::open_image(&source).and_then(|i| i.get_image_size()).map_err(|e| e.to_string())?;
+
+    println!("{}", format!("Benchmark: {}x{}, {} iterations", size.width, size.height, iterations).bold());
+    for (format, path) in &fixtures {
+        let decode = measure(iterations, || Ok(()), |_| librusimg::open_image(path).map(|_| ()));
+        let resize = measure(iterations, || librusimg::open_image(path), |mut image| image.resize(50.0).map(|_| ()));
+        let compress = measure(iterations, || librusimg::open_image(path), |mut image| image.compress(Some(75.0)));
+        let save_path = dir.join(format!("output.{}", format));
+        let save = measure(iterations, || librusimg::open_image(path), |mut image| image.save_image(save_path.to_str()).map(|_| ()));
+
+        for (name, result) in [("decode", decode), ("resize", resize), ("compress", compress), ("save", save)] {
+            match result {
+                Ok(elapsed) => print_result(name, format, elapsed),
+                // BMP cannot be compressed
+                Err(e) => println!("{:<10} {:<6} {:>13}", name, format.to_string(), e.to_string().yellow()),
+            }
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,263 @@
+use std::fs;
+use std::sync::{Arc, Mutex};
+use image::DynamicImage;
+use librusimg::{RusImg, RusimgError, SaveOptions};
+use librusimg::tempdir::TempDir;
+
+/// Lowest quality tried by the quality search.
+const MIN_QUALITY: f32 = 5.0;
+/// Highest quality tried by the quality search (if --quality is not specified).
+pub const MAX_QUALITY: f32 = 95.0;
+/// Maximum number of trial encodes after the first one.
+const SEARCH_STEPS: usize = 6;
+
+/// Budget is the size budget of the whole batch (--total-budget), shared by the threads.
+/// Each task reserves a share of the remaining budget in proportion to its source size (see Reservation), and settles it after saving.
+/// The shares are reserved largest first before the tasks are started, so they do not depend on the order in which the threads finish.
+/// - total_bytes: The budget of the whole batch.
+/// - remaining_bytes: The budget not reserved yet.
+/// - remaining_source_bytes: The total source size of the files that have not reserved yet.
+/// - used_bytes: The total size of the saved files.
+pub struct Budget {
+    pub total_bytes: u64,
+    remaining_bytes: u64,
+    remaining_source_bytes: u64,
+    pub used_bytes: u64,
+}
+
+impl Budget {
+    pub fn new(total_bytes: u64, source_bytes: u64) -> Self {
+        Self {
+            total_bytes: total_bytes,
+            remaining_bytes: total_bytes,
+            remaining_source_bytes: source_bytes,
+            used_bytes: 0,
+        }
+    }
+
+    /// Reserve the target size of a file.
+    pub fn reserve(&mut self, source_bytes: u64) -> u64 {
+        let target = if self.remaining_source_bytes <= source_bytes {
+            self.remaining_bytes
+        }
+        else {
+            (self.remaining_bytes as u128 * source_bytes as u128 / self.remaining_source_bytes as u128) as u64
+        };
+        self.remaining_source_bytes = self.remaining_source_bytes.saturating_sub(source_bytes);
+        self.remaining_bytes -= target;
+        target
+    }
+
+    /// Settle the reservation with the actual size of the saved file.
+    /// The unused part goes back to the remaining budget, and an overrun is taken from it.
+    pub fn settle(&mut self, target: u64, actual: u64) {
+        self.remaining_bytes = (self.remaining_bytes + target).saturating_sub(actual);
+        self.used_bytes += actual;
+    }
+}
+
+/// Reservation is the share of the budget reserved for a task.
+/// It is settled when it is dropped, so the share goes back to the budget even if the task fails or the file is skipped.
+/// - budget: The budget the share is reserved from.
+/// - target: The reserved size in bytes.
+/// - written: The size of the saved file (None: nothing was written).
+pub struct Reservation {
+    budget: Arc<Mutex<Budget>>,
+    pub target: u64,
+    written: Option<u64>,
+}
+
+impl Reservation {
+    /// Reserve the share of a file from the budget.
+    pub fn new(budget: &Arc<Mutex<Budget>>, source_bytes: u64) -> Self {
+        let target = budget.lock().unwrap().reserve(source_bytes);
+        Self {
+            budget: Arc::clone(budget),
+            target: target,
+            written: None,
+        }
+    }
+
+    /// Record the size of the saved file, which is used when the reservation is settled.
+    pub fn set_written(&mut self, bytes: u64) {
+        self.written = Some(bytes);
+    }
+}
+
+impl Drop for Reservation {
+    fn drop(&mut self) {
+        // パニック中に Mutex が poison されていても、予算の精算は行う
+        let mut budget = self.budget.lock().unwrap_or_else(|e| e.into_inner());
+        budget.settle(self.target, self.written.unwrap_or(0));
+    }
+}
+
+/// Parse a size such as 200MB, 1.5GB, 500KiB or 1048576 (bytes).
+/// KB, MB and GB are powers of 1000, and KiB, MiB and GiB are powers of 1024 (case-insensitive).
+pub fn parse_size(size: &str) -> Option<u64> {
+    let size = size.trim().to_ascii_lowercase();
+    let split = size.find(|c: char| !(c.is_ascii_digit() || c == '.')).unwrap_or(size.len());
+    let (number, unit) = size.split_at(split);
+    let number = number.parse::<f64>().ok()?;
+    let multiplier = match unit.trim() {
+        "" | "b" => 1.0,
+        "k" | "kb" => 1e3,
+        "m" | "mb" => 1e6,
+        "g" | "gb" => 1e9,
+        "kib" => 1024.0,
+        "mib" => 1024.0 * 1024.0,
+        "gib" => 1024.0 * 1024.0 * 1024.0,
+        _ => return None,
+    };
+    if number <= 0.0 {
+        return None;
+    }
+    Some((number * multiplier) as u64)
+}
+
+/// Check if the quality of the format changes the output size (lossy formats).
+pub fn supports(extension: &librusimg::Extension) -> bool {
+    matches!(extension, librusimg::Extension::Jpeg | librusimg::Extension::Webp)
+}
+
+/// Encode the image at the quality and get the output size.
+/// A copy of the image is compressed and saved to a temporary directory of its own, which is removed after measuring.
+/// If decode is true, the output is also read back, so that it can be compared with the source.
+pub fn trial_encode(image: &RusImg, quality: f32, options: &SaveOptions, decode: bool) -> Result<(u64, Option<DynamicImage>), RusimgError> {
+    let mut copy = image.clone_image();
+    copy.compress(Some(quality))?;
+
+    let temp_dir = TempDir::new().map_err(|e| RusimgError::FailedToCreateFile(e.to_string()))?;
+    let temp_path = temp_dir.file(format!("trial.{}", image.extension));
+    let temp_path_str = temp_path.to_str().ok_or(RusimgError::FailedToConvertPathToString)?;
+    let save_options = SaveOptions {
+        overwrite: true,
+        ..options.clone()
+    };
+    copy.save_image_with(Some(temp_path_str), &save_options)?;
+    let size = fs::metadata(&temp_path).map_err(|e| RusimgError::FailedToGetMetadata(e.to_string()))?.len();
+    let decoded = if decode {
+        Some(librusimg::open_image(&temp_path)?.as_dynamic_image().clone())
+    }
+    else {
+        None
+    };
+    Ok((size, decoded))
+}
+
+/// Get the output size of the image at the quality.
+fn encoded_size(image: &RusImg, quality: f32, options: &SaveOptions) -> Result<u64, RusimgError> {
+    trial_encode(image, quality, options, false).map(|(size, _)| size)
+}
+
+/// Find the highest quality (up to max_quality) whose output fits in target bytes, with a binary search.
+/// If even the lowest quality does not fit, the lowest quality is used.
+pub fn search_quality(image: &RusImg, target: u64, max_quality: f32, options: &SaveOptions) -> Result<f32, RusimgError> {
+    let (mut low, mut high) = (MIN_QUALITY, max_quality.max(MIN_QUALITY));
+    if encoded_size(image, high, options)? <= target {
+        return Ok(high);
+    }
+
+    let mut best = MIN_QUALITY;
+    for _ in 0..SEARCH_STEPS {
+        if high - low <= 1.0 {
+            break;
+        }
+        let quality = ((low + high) / 2.0).round();
+        if encoded_size(image, quality, options)? <= target {
+            best = quality;
+            low = quality;
+        }
+        else {
+            high = quality;
+        }
+    }
+    Ok(best)
+}
+
+/// Parse a quality search target such as ssim:0.95.
+/// The threshold must be 0.0 < threshold <= 1.0.
+pub fn parse_quality_search(target: &str) -> Option<f64> {
+    let (metric, threshold) = target.split_once(':')?;
+    if !metric.trim().eq_ignore_ascii_case("ssim") {
+        return None;
+    }
+    let threshold = threshold.trim().parse::<f64>().ok()?;
+    if threshold <= 0.0 || threshold > 1.0 {
+        return None;
+    }
+    Some(threshold)
+}
+
+/// Get the SSIM of the output at the quality, compared with the image before encoding.
+fn encoded_ssim(image: &RusImg, quality: f32, options: &SaveOptions) -> Result<f64, RusimgError> {
+    let (_, decoded) = trial_encode(image, quality, options, true)?;
+    Ok(decoded.and_then(|decoded| librusimg::metrics::ssim(image.as_dynamic_image(), &decoded)).unwrap_or(0.0))
+}
+
+/// Find the lowest quality (up to max_quality) whose output has an SSIM of at least threshold, with a binary search.
+/// The lowest quality gives the smallest file, so it is the best quality that meets the threshold.
+/// If even max_quality does not meet the threshold, max_quality is used.
+/// Returns the quality and its SSIM.
+pub fn search_ssim(image: &RusImg, threshold: f64, max_quality: f32, options: &SaveOptions) -> Result<(f32, f64), RusimgError> {
+    let (mut low, mut high) = (MIN_QUALITY, max_quality.max(MIN_QUALITY));
+    let ssim = encoded_ssim(image, high, options)?;
+    let mut best = (high, ssim);
+    if ssim < threshold {
+        return Ok(best);
+    }
+
+    for _ in 0..SEARCH_STEPS {
+        if high - low <= 1.0 {
+            break;
+        }
+        let quality = ((low + high) / 2.0).round();
+        let ssim = encoded_ssim(image, quality, options)?;
+        if ssim >= threshold {
+            best = (quality, ssim);
+            high = quality;
+        }
+        else {
+            low = quality;
+        }
+    }
+    Ok(best)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Make a budget shared by the tasks.
+    fn shared(total_bytes: u64, source_bytes: u64) -> Arc<Mutex<Budget>> {
+        Arc::new(Mutex::new(Budget::new(total_bytes, source_bytes)))
+    }
+
+    #[test]
+    fn written_reservation_settles_the_actual_size() {
+        let budget = shared(1000, 400);
+        let mut reservation = Reservation::new(&budget, 200);
+        assert_eq!(reservation.target, 500);
+        reservation.set_written(300);
+        drop(reservation);
+
+        let budget = budget.lock().unwrap();
+        assert_eq!(budget.used_bytes, 300);
+        assert_eq!(budget.remaining_bytes, 700);
+    }
+
+    #[test]
+    fn skipped_or_failed_file_returns_its_share() {
+        let budget = shared(1000, 400);
+        let skipped = Reservation::new(&budget, 200);
+        let remaining = Reservation::new(&budget, 200);
+        assert_eq!(budget.lock().unwrap().remaining_bytes, 0);
+
+        // Nothing was written (the file was skipped, or processing failed) -> The whole share goes back to the budget.
+        drop(skipped);
+        drop(remaining);
+        let budget = budget.lock().unwrap();
+        assert_eq!(budget.used_bytes, 0);
+        assert_eq!(budget.remaining_bytes, 1000);
+    }
+}
@@ -0,0 +1,320 @@
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter, Cursor};
+use std::path::{Path, PathBuf};
+use ignore::WalkBuilder;
+use image::{AnimationDecoder, DynamicImage, ImageDecoder, ImageError, ImageFormat, ImageReader, Limits, Rgb, RgbImage};
+use image::codecs::gif::GifDecoder;
+use image::codecs::jpeg::JpegEncoder;
+use image::codecs::png::PngDecoder;
+use image::codecs::webp::WebPDecoder;
+use rayon::prelude::*;
+
+use crate::split;
+
+/// Default memory limit of decoding one image in MiB (--memory-limit).
+pub const DEFAULT_MEMORY_LIMIT: u64 = 512;
+/// Quality of the salvaged jpeg files (high, so that re-encoding loses as little as possible).
+const SALVAGE_QUALITY: u8 = 95;
+/// Color of the missing rows with --salvage pad.
+const PADDING: Rgb<u8> = Rgb([128, 128, 128]);
+
+/// How the missing rows of a salvaged jpeg are handled (--salvage).
+/// - Crop: Keep only the rows decoded from the data.
+/// - Pad: Keep the size of the image, and fill the missing rows with gray.
+#[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq)]
+pub enum SalvageMode {
+    Crop,
+    Pad,
+}
+
+/// CheckOptions is the options of the check subcommand.
+/// - paths: Directories (searched recursively), images or wildcard patterns.
+/// - threads: Number of images decoded at the same time.
+/// - memory_limit: Maximum memory used to decode one image in MiB. The peak memory is about threads * memory_limit.
+/// - salvage: If specified, the decodable part of each corrupt or truncated jpeg is saved as <name>_salvaged.jpg.
+/// - output: Directory of the salvaged files. If not specified, the directory of each source.
+#[derive(Debug, Clone)]
+pub struct CheckOptions {
+    pub paths: Vec<PathBuf>,
+    pub threads: usize,
+    pub memory_limit: u64,
+    pub salvage: Option<SalvageMode>,
+    pub output: Option<PathBuf>,
+}
+
+/// Problem found in an image.
+/// - Corrupt: The decoder failed (e.g. broken data or a damaged header).
+/// - Truncated: The pixels were decoded, but the end of the file is missing (decoders fill the missing part of a truncated jpeg with gray).
+/// - Skipped: The image was not checked (e.g. it needs more memory than --memory-limit, or its format has no decoder in this build).
+#[derive(Debug, Clone, PartialEq)]
+enum Problem {
+    Corrupt(String),
+    Truncated,
+    Skipped(String),
+}
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Problem::Corrupt(e) => write!(f, "corrupt ({})", e),
+            Problem::Truncated => write!(f, "truncated (the end of the file is missing)"),
+            Problem::Skipped(reason) => write!(f, "skipped ({})", reason),
+        }
+    }
+}
+
+/// Convert a decoding error to a problem. Errors that do not mean a broken file are skips.
+fn problem(e: ImageError) -> Problem {
+    match e {
+        ImageError::Limits(_) => Problem::Skipped("exceeds --memory-limit".to_string()),
+        ImageError::Unsupported(e) => Problem::Skipped(e.to_string()),
+        e => Problem::Corrupt(e.to_string()),
+    }
+}
+
+/// Get the images in a directory tree (files with an image extension, including hidden and ignored files).
+fn image_files(dir: &Path) -> Vec<PathBuf> {
+    WalkBuilder::new(dir)
+        .standard_filters(false)
+        .build()
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_type().is_some_and(|t| t.is_file()))
+        .map(|entry| entry.into_path())
+        .filter(|path| ImageFormat::from_path(path).is_ok())
+        .collect()
+}
+
+/// Decode an image fully: all frames of animations and all pages of tiff files, and check that the file is complete.
+/// Each frame is dropped after it is decoded, so the memory is bounded by the limit of one frame.
+fn check_file(path: &Path, memory_limit: u64) -> Result<(), Problem> {
+    let max_alloc = memory_limit.saturating_mul(1024 * 1024);
+    let limits = || {
+        let mut limits = Limits::default();
+        limits.max_alloc = Some(max_alloc);
+        limits
+    };
+    let ioerr = |e: std::io::Error| Problem::Corrupt(e.to_string());
+
+    let mut reader = ImageReader::open(path).map_err(ioerr)?.with_guessed_format().map_err(ioerr)?;
+    let format = reader.format();
+    reader.limits(limits());
+    reader.decode().map_err(problem)?;
+
+    // 1 フレーム目 (1 ページ目) 以外も展開する
+    match format {
+        Some(ImageFormat::Gif) | Some(ImageFormat::Png) | Some(ImageFormat::WebP) => check_frames(path, format, limits())?,
+        Some(ImageFormat::Tiff) => check_tiff_pages(path, max_alloc)?,
+        _ => {},
+    }
+
+    let complete = match format {
+        Some(ImageFormat::Jpeg) => jpeg_is_complete(&fs::read(path).map_err(ioerr)?),
+        Some(ImageFormat::Png) => png_is_complete(&fs::read(path).map_err(ioerr)?),
+        Some(ImageFormat::Gif) => gif_is_complete(&fs::read(path).map_err(ioerr)?),
+        _ => true,
+    };
+    if !complete {
+        return Err(Problem::Truncated);
+    }
+    Ok(())
+}
+
+/// Decode the frames of an animated gif, png (APNG) or webp one by one.
+fn check_frames(path: &Path, format: Option<ImageFormat>, limits: Limits) -> Result<(), Problem> {
+    let file = BufReader::new(File::open(path).map_err(|e| Problem::Corrupt(e.to_string()))?);
+    let frames = match format {
+        Some(ImageFormat::Gif) => {
+            let mut decoder = GifDecoder::new(file).map_err(problem)?;
+            decoder.set_limits(limits).map_err(problem)?;
+            decoder.into_frames()
+        },
+        Some(ImageFormat::Png) => {
+            let decoder = PngDecoder::with_limits(file, limits).map_err(problem)?;
+            if !decoder.is_apng().map_err(problem)? {
+                return Ok(());
+            }
+            decoder.apng().map_err(problem)?.into_frames()
+        },
+        Some(ImageFormat::WebP) => {
+            let mut decoder = WebPDecoder::new(file).map_err(problem)?;
+            if !decoder.has_animation() {
+                return Ok(());
+            }
+            decoder.set_limits(limits).map_err(problem)?;
+            decoder.into_frames()
+        },
+        _ => return Ok(()),
+    };
+    for frame in frames {
+        frame.map_err(problem)?;
+    }
+    Ok(())
+}
+
+/// Decode the pages of a multi-page tiff after the first one (decoded by the image crate) one by one.
+fn check_tiff_pages(path: &Path, max_alloc: u64) -> Result<(), Problem> {
+    let tiff_err = |e: tiff::TiffError| match e {
+        tiff::TiffError::LimitsExceeded => Problem::Skipped("exceeds --memory-limit".to_string()),
+        e => Problem::Corrupt(e.to_string()),
+    };
+    let file = BufReader::new(File::open(path).map_err(|e| Problem::Corrupt(e.to_string()))?);
+    let mut limits = tiff::decoder::Limits::default();
+    limits.decoding_buffer_size = max_alloc as usize;
+    limits.intermediate_buffer_size = max_alloc as usize;
+    let mut decoder = tiff::decoder::Decoder::new(file).map_err(tiff_err)?.with_limits(limits);
+    while decoder.more_images() {
+        decoder.next_image().map_err(tiff_err)?;
+        decoder.read_image().map_err(tiff_err)?;
+    }
+    Ok(())
+}
+
+/// Check that a jpeg has the end of image marker (FF D9) after its last scan.
+/// The markers of an embedded thumbnail come before the scans of the image, so they are not taken for the end of the image.
+fn jpeg_is_complete(data: &[u8]) -> bool {
+    let last_scan = data.windows(2).rposition(|w| w == [0xFF, 0xDA]).unwrap_or(0);
+    data[last_scan..].windows(2).any(|w| w == [0xFF, 0xD9])
+}
+
+/// Check that a png has the IEND chunk.
+fn png_is_complete(data: &[u8]) -> bool {
+    data.windows(8).rev().any(|w| w == [0, 0, 0, 0, b'I', b'E', b'N', b'D'])
+}
+
+/// Check that a gif ends with the trailer (3B).
+fn gif_is_complete(data: &[u8]) -> bool {
+    data.last() == Some(&0x3B)
+}
+
+/// Count the rows decoded from the data of a salvaged jpeg.
+/// After the end of the data, the decoder repeats the last DC value, so the missing rows at the bottom are filled with one color.
+fn recovered_rows(image: &RgbImage) -> u32 {
+    (0..image.height()).rev()
+        .find(|&y| {
+            let first = image.get_pixel(0, y);
+            (1..image.width()).any(|x| image.get_pixel(x, y) != first)
+        })
+        .map_or(0, |y| y + 1)
+}
+
+/// Salvage a corrupt or truncated jpeg: decode as much as possible, and save the rows decoded from the data as <name>_salvaged.jpg.
+/// The end marker is appended to the data, so the decoder stops at the end of the data instead of failing.
+/// Returns the path of the salvaged file, the number of the recovered rows and the height of the image.
+fn salvage_jpeg(path: &Path, mode: SalvageMode, output: Option<&Path>, memory_limit: u64) -> Result<(PathBuf, u32, u32), String> {
+    let mut data = fs::read(path).map_err(|e| e.to_string())?;
+    data.extend_from_slice(&[0xFF, 0xD9]);
+    let mut reader = ImageReader::with_format(Cursor::new(data), ImageFormat::Jpeg);
+    let mut limits = Limits::default();
+    limits.max_alloc = Some(memory_limit.saturating_mul(1024 * 1024));
+    reader.limits(limits);
+    let mut image = reader.decode().map_err(|e| e.to_string())?.to_rgb8();
+    let height = image.height();
+
+    let rows = recovered_rows(&image);
+    if rows == 0 {
+        return Err("no rows could be recovered".to_string());
+    }
+    let image = match mode {
+        SalvageMode::Crop => {
+            let width = image.width();
+            DynamicImage::ImageRgb8(image).crop_imm(0, 0, width, rows)
+        },
+        SalvageMode::Pad => {
+            for y in rows..height {
+                for x in 0..image.width() {
+                    image.put_pixel(x, y, PADDING);
+                }
+            }
+            DynamicImage::ImageRgb8(image)
+        },
+    };
+
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let directory = output.or(path.parent()).unwrap_or(Path::new(""));
+    fs::create_dir_all(directory).map_err(|e| format!("{}: {}", directory.display(), e))?;
+    let salvaged_path = directory.join(format!("{}_salvaged.jpg", stem));
+    let file = File::create(&salvaged_path).map_err(|e| format!("{}: {}", salvaged_path.display(), e))?;
+    image.write_with_encoder(JpegEncoder::new_with_quality(BufWriter::new(file), SALVAGE_QUALITY))
+        .map_err(|e| format!("{}: {}", salvaged_path.display(), e))?;
+    Ok((salvaged_path, rows, height))
+}
+
+/// Check if the file is a jpeg (by its content).
+fn is_jpeg(path: &Path) -> bool {
+    ImageReader::open(path).ok()
+        .and_then(|reader| reader.with_guessed_format().ok())
+        .is_some_and(|reader| reader.format() == Some(ImageFormat::Jpeg))
+}
+
+/// Check the images and report the corrupt and truncated files. Nothing is written, except the salvaged files with --salvage.
+/// Returns an error if any image is corrupt or truncated, so that scripts can check the exit status.
+pub fn run(options: &CheckOptions) -> Result<(), String> {
+    let mut files = Vec::new();
+    for path in &options.paths {
+        if path.is_dir() {
+            files.append(&mut image_files(path));
+        }
+        else {
+            files.append(&mut split::expand(path)?);
+        }
+    }
+    files.sort();
+
+    let pool = rayon::ThreadPoolBuilder::new().num_threads(options.threads).build().map_err(|e| e.to_string())?;
+    let problems = pool.install(|| {
+        files.par_iter().filter_map(|path| {
+            let found = check_file(path, options.memory_limit).err()?;
+            println!("{}: {}", path.display(), found);
+            // --salvage -> Save the decodable part of a broken jpeg.
+            if let Some(mode) = options.salvage.filter(|_| !matches!(found, Problem::Skipped(_)) && is_jpeg(path)) {
+                match salvage_jpeg(path, mode, options.output.as_deref(), options.memory_limit) {
+                    Ok((salvaged_path, rows, height)) => println!("{}: salvaged {} of {} rows ({:.0}%) -> {}", path.display(), rows, height, rows as f64 / height as f64 * 100.0, salvaged_path.display()),
+                    Err(e) => println!("{}: could not be salvaged ({})", path.display(), e),
+                }
+            }
+            Some(found)
+        }).collect::<Vec<_>>()
+    });
+
+    let skipped = problems.iter().filter(|problem| matches!(problem, Problem::Skipped(_))).count();
+    let failed = problems.len() - skipped;
+    println!("{} images checked: {} corrupt or truncated, {} skipped", files.len(), failed, skipped);
+    if failed > 0 {
+        return Err(format!("{} of {} images are corrupt or truncated", failed, files.len()));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn jpeg_needs_the_end_marker_after_the_last_scan() {
+        let thumbnail: [u8; 11] = [0xFF, 0xD8, 0xFF, 0xE1, 0xFF, 0xD8, 0xFF, 0xDA, 0x12, 0xFF, 0xD9];
+        let image: [u8; 6] = [0xFF, 0xDA, 0x34, 0xFF, 0x00, 0x56];
+        let complete = [&thumbnail[..], &image[..], &[0xFF, 0xD9]].concat();
+        assert!(jpeg_is_complete(&complete));
+        // サムネイルの EOI があっても本体の EOI がなければ不完全
+        let truncated = [&thumbnail[..], &image[..]].concat();
+        assert!(!jpeg_is_complete(&truncated));
+    }
+
+    #[test]
+    fn png_and_gif_need_their_trailers() {
+        let png = [&b"\x89PNG\r\n\x1a\n"[..], &[0, 0, 0, 0], b"IEND", &[0xAE, 0x42, 0x60, 0x82]].concat();
+        assert!(png_is_complete(&png));
+        assert!(!png_is_complete(&png[..png.len() - 8]));
+        assert!(gif_is_complete(b"GIF89a\x00\x3B"));
+        assert!(!gif_is_complete(b"GIF89a\x00"));
+    }
+
+    #[test]
+    fn flat_rows_at_the_bottom_are_missing() {
+        let mut image = RgbImage::from_pixel(4, 6, Rgb([90, 60, 30]));
+        image.put_pixel(2, 1, Rgb([200, 200, 200]));
+        image.put_pixel(3, 3, Rgb([10, 10, 10]));
+        assert_eq!(recovered_rows(&image), 4);
+        assert_eq!(recovered_rows(&RgbImage::from_pixel(4, 6, Rgb([128, 128, 128]))), 0);
+    }
+}
@@ -0,0 +1,55 @@
+use std::fs::File;
+use std::io::{self, BufReader, Read, Write};
+use std::path::{Path, PathBuf};
+use sha2::{Digest, Sha256};
+
+/// Checksum algorithm of --checksum.
+/// - Sha256: SHA-256 (compatible with sha256sum).
+#[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq)]
+pub enum ChecksumAlgorithm {
+    Sha256,
+}
+
+impl ChecksumAlgorithm {
+    /// Extension of the checksum files (e.g. image.webp.sha256).
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ChecksumAlgorithm::Sha256 => "sha256",
+        }
+    }
+}
+
+/// Compute the checksum of the file as lowercase hex digits.
+pub fn file_checksum(path: &Path, algorithm: ChecksumAlgorithm) -> io::Result<String> {
+    let mut reader = BufReader::new(File::open(path)?);
+    let mut buf = [0u8; 64 * 1024];
+    match algorithm {
+        ChecksumAlgorithm::Sha256 => {
+            let mut hasher = Sha256::new();
+            loop {
+                let n = reader.read(&mut buf)?;
+                if n == 0 {
+                    break;
+                }
+                hasher.update(&buf[..n]);
+            }
+            Ok(hasher.finalize().iter().map(|b| format!("{:02x}", b)).collect())
+        },
+    }
+}
+
+/// Format a line of a checksum file ("<checksum>  <file name>", the format of sha256sum -c).
+pub fn line(checksum: &str, path: &Path) -> String {
+    format!("{}  {}\n", checksum, path.display())
+}
+
+/// Write the checksum next to the file (e.g. image.webp.sha256), with the file name relative to it.
+pub fn write_sidecar(path: &Path, checksum: &str, algorithm: ChecksumAlgorithm) -> io::Result<PathBuf> {
+    let mut sidecar = path.as_os_str().to_os_string();
+    sidecar.push(".");
+    sidecar.push(algorithm.extension());
+    let sidecar = PathBuf::from(sidecar);
+    let file_name = Path::new(path.file_name().unwrap_or_default());
+    File::create(&sidecar)?.write_all(line(checksum, file_name).as_bytes())?;
+    Ok(sidecar)
+}
@@ -0,0 +1,99 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use librusimg::tempdir::TempDir;
+
+use crate::archive;
+
+/// File extensions of comic archives: CBZ (zip) and CBR (rar).
+const COMIC_EXTENSIONS: [&str; 2] = ["cbz", "cbr"];
+
+/// Whether the file is a comic archive.
+pub fn is_comic(path: &Path) -> bool {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| COMIC_EXTENSIONS.iter().any(|comic| e.eq_ignore_ascii_case(comic)))
+}
+
+/// Get the output path of a comic archive.
+/// - destination: -o. A path ending with .cbz is the output archive itself, and any other path is a directory.
+/// - append: -a. Appended to the file name (e.g. volume1.cbz -> volume1_small.cbz).
+///
+/// Without them, the archive is replaced. The outputs are always CBZ, so a CBR source gets a new file.
+pub fn output_path(archive: &Path, destination: Option<&Path>, append: Option<&str>) -> PathBuf {
+    if let Some(destination) = destination.filter(|d| d.extension().is_some_and(|e| e.eq_ignore_ascii_case("cbz"))) {
+        return destination.to_path_buf();
+    }
+    let stem = archive.file_stem().unwrap_or_default().to_string_lossy();
+    let name = format!("{}{}.cbz", stem, append.unwrap_or(""));
+    match destination {
+        Some(dir) => dir.join(name),
+        None => archive.with_file_name(name),
+    }
+}
+
+/// Comic is a comic archive extracted to a temporary directory.
+/// The pages are processed as ordinary files from pages_dir to output_dir, and save() puts them back into an archive.
+/// - pages_dir: The entries of the archive, named by archive::entry_file_name().
+/// - output_dir: The processed pages. Their file stems are the ones of the entries in pages_dir.
+pub struct Comic {
+    temp_dir: TempDir,
+    names: Vec<String>,
+    pub pages_dir: PathBuf,
+    pub output_dir: PathBuf,
+}
+
+impl Comic {
+    /// Extract a comic archive.
+    pub fn open(archive: &Path) -> Result<Comic, String> {
+        if archive.extension().is_some_and(|e| e.eq_ignore_ascii_case("cbr")) {
+            return Err(format!("{}: CBR (rar) archives are not supported. Convert them to CBZ (zip) first.", archive.display()));
+        }
+        let temp_dir = TempDir::new().map_err(|e| e.to_string())?;
+        let (pages_dir, output_dir) = (temp_dir.file("pages"), temp_dir.file("output"));
+        fs::create_dir(&pages_dir).map_err(|e| e.to_string())?;
+        fs::create_dir(&output_dir).map_err(|e| e.to_string())?;
+
+        let names = archive::extract_entries(archive, &pages_dir)?;
+        Ok(Comic {
+            temp_dir: temp_dir,
+            names: names,
+            pages_dir: pages_dir,
+            output_dir: output_dir,
+        })
+    }
+
+    /// Write the comic to a CBZ archive, in the order of the source archive.
+    /// Processed pages replace their entries (with the extension of the output, e.g. 001.png -> 001.webp),
+    /// and the other entries (e.g. ComicInfo.xml, or pages that failed) are copied as they are.
+    /// Returns the number of the processed pages.
+    pub fn save(&self, output: &Path) -> Result<usize, String> {
+        let mut processed = Vec::new();
+        for entry in fs::read_dir(&self.output_dir).map_err(|e| e.to_string())? {
+            processed.push(entry.map_err(|e| e.to_string())?.path());
+        }
+
+        let mut replaced = 0;
+        let mut entries = Vec::new();
+        for (index, name) in self.names.iter().enumerate() {
+            let stem = format!("{:05}", index);
+            match processed.iter().find(|p| p.file_stem().is_some_and(|s| s.to_string_lossy() == stem)) {
+                Some(page) => {
+                    let extension = page.extension().unwrap_or_default().to_string_lossy();
+                    let name = Path::new(name).with_extension(extension.as_ref()).to_string_lossy().replace('\\', "/");
+                    entries.push((name, page.clone()));
+                    replaced += 1;
+                },
+                None => entries.push((name.clone(), self.pages_dir.join(archive::entry_file_name(index, name)))),
+            }
+        }
+
+        // 書き込みに失敗しても元のアーカイブが残るように、一時ディレクトリに書いてからコピーする
+        let temp_output = self.temp_dir.file("output.cbz");
+        archive::write_entries(&temp_output, &entries)?;
+        if let Some(parent) = output.parent().filter(|p| !p.as_os_str().is_empty()) {
+            fs::create_dir_all(parent).map_err(|e| format!("{}: {}", parent.display(), e))?;
+        }
+        fs::copy(&temp_output, output).map_err(|e| format!("{}: {}", output.display(), e))?;
+        Ok(replaced)
+    }
+}
@@ -0,0 +1,68 @@
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+use std::time::UNIX_EPOCH;
+
+/// Date of a photo (year, month and day).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Date {
+    pub year: i32,
+    pub month: u32,
+    pub day: u32,
+}
+
+/// Read the capture date from the EXIF data (DateTimeOriginal, or DateTime if it is not recorded).
+fn exif_date(path: &Path) -> Option<Date> {
+    let file = File::open(path).ok()?;
+    let exif = exif::Reader::new().read_from_container(&mut BufReader::new(file)).ok()?;
+    let field = exif.get_field(exif::Tag::DateTimeOriginal, exif::In::PRIMARY)
+        .or(exif.get_field(exif::Tag::DateTime, exif::In::PRIMARY))?;
+    let exif::Value::Ascii(ref values) = field.value else {
+        return None;
+    };
+    let datetime = exif::DateTime::from_ascii(values.first()?).ok()?;
+    // 日付が未設定のカメラは 0000:00:00 を書き込む
+    if datetime.year == 0 || datetime.month == 0 || datetime.day == 0 {
+        return None;
+    }
+    Some(Date {
+        year: datetime.year as i32,
+        month: datetime.month as u32,
+        day: datetime.day as u32,
+    })
+}
+
+/// Convert days since 1970-01-01 to a date (proleptic Gregorian calendar).
+fn civil_from_days(days: i64) -> Date {
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = (yoe + era * 400 + if month <= 2 { 1 } else { 0 }) as i32;
+    Date { year, month, day }
+}
+
+/// Get the modification date of the file (UTC).
+fn mtime_date(path: &Path) -> Option<Date> {
+    let modified = path.metadata().ok()?.modified().ok()?;
+    let seconds = modified.duration_since(UNIX_EPOCH).ok()?.as_secs() as i64;
+    Some(civil_from_days(seconds.div_euclid(86400)))
+}
+
+/// Get the capture date of the photo: the EXIF date, or the modification date if the file has no EXIF date.
+pub fn capture_date(path: &Path) -> Option<Date> {
+    exif_date(path).or_else(|| mtime_date(path))
+}
+
+/// Render the --organize-by-date template.
+/// "{year}" is replaced with the 4-digit year, "{month}" with the 2-digit month and "{day}" with the 2-digit day (e.g. "{year}/{month}" -> "2024/07").
+pub fn render(template: &str, date: Date) -> String {
+    template
+        .replace("{year}", &format!("{:04}", date.year))
+        .replace("{month}", &format!("{:02}", date.month))
+        .replace("{day}", &format!("{:02}", date.day))
+}
@@ -0,0 +1,121 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// Inches per meter. PNG and BMP store the density in pixels per meter.
+const METERS_PER_INCH: f64 = 0.0254;
+
+/// Convert DPI to pixels per meter.
+fn dpi_to_ppm(dpi: u32) -> u32 {
+    (dpi as f64 / METERS_PER_INCH).round() as u32
+}
+
+/// Calculate the CRC-32 of a PNG chunk (chunk type + chunk data).
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = 0xffffffffu32;
+    for byte in data {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 { 0xedb88320 ^ (crc >> 1) } else { crc >> 1 };
+        }
+    }
+    !crc
+}
+
+/// Set the pHYs chunk of a PNG image.
+/// An existing pHYs chunk is replaced, and a new one is inserted right after IHDR.
+fn set_png_density(data: &[u8], dpi: u32) -> Option<Vec<u8>> {
+    const SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+    if !data.starts_with(SIGNATURE) {
+        return None;
+    }
+
+    let ppm = dpi_to_ppm(dpi);
+    let mut phys = Vec::with_capacity(21);
+    phys.extend_from_slice(&9u32.to_be_bytes());
+    let mut body = b"pHYs".to_vec();
+    body.extend_from_slice(&ppm.to_be_bytes());
+    body.extend_from_slice(&ppm.to_be_bytes());
+    body.push(1);   // unit: meter
+    phys.extend_from_slice(&body);
+    phys.extend_from_slice(&crc32(&body).to_be_bytes());
+
+    let mut ret = SIGNATURE.to_vec();
+    let mut pos = SIGNATURE.len();
+    while pos + 8 <= data.len() {
+        let length = u32::from_be_bytes(data[pos..pos + 4].try_into().ok()?) as usize;
+        let chunk_end = pos + 12 + length;
+        if chunk_end > data.len() {
+            return None;
+        }
+        let chunk_type = &data[pos + 4..pos + 8];
+        // 既存の pHYs は削除
+        if chunk_type != b"pHYs" {
+            ret.extend_from_slice(&data[pos..chunk_end]);
+        }
+        // IHDR の直後に pHYs を挿入
+        if chunk_type == b"IHDR" {
+            ret.extend_from_slice(&phys);
+        }
+        pos = chunk_end;
+    }
+    Some(ret)
+}
+
+/// Set the density of the JFIF APP0 segment of a JPEG image.
+/// If the JPEG image has no JFIF segment, a new one is inserted right after SOI.
+fn set_jpeg_density(data: &[u8], dpi: u32) -> Option<Vec<u8>> {
+    if !data.starts_with(&[0xff, 0xd8]) {
+        return None;
+    }
+    let dpi = dpi.min(u16::MAX as u32) as u16;
+
+    let mut ret = data.to_vec();
+    if data.len() >= 18 && data[2..4] == [0xff, 0xe0] && &data[6..11] == b"JFIF\0" {
+        ret[13] = 1;    // unit: dots per inch
+        ret[14..16].copy_from_slice(&dpi.to_be_bytes());
+        ret[16..18].copy_from_slice(&dpi.to_be_bytes());
+    }
+    else {
+        let mut app0 = vec![0xff, 0xe0, 0x00, 0x10];
+        app0.extend_from_slice(b"JFIF\0");
+        app0.extend_from_slice(&[1, 1, 1]);     // version 1.01, unit: dots per inch
+        app0.extend_from_slice(&dpi.to_be_bytes());
+        app0.extend_from_slice(&dpi.to_be_bytes());
+        app0.extend_from_slice(&[0, 0]);        // no thumbnail
+        ret.splice(2..2, app0);
+    }
+    Some(ret)
+}
+
+/// Set biXPelsPerMeter and biYPelsPerMeter of a BMP image.
+fn set_bmp_density(data: &[u8], dpi: u32) -> Option<Vec<u8>> {
+    if data.len() < 46 || !data.starts_with(b"BM") {
+        return None;
+    }
+    let ppm = dpi_to_ppm(dpi);
+    let mut ret = data.to_vec();
+    ret[38..42].copy_from_slice(&ppm.to_le_bytes());
+    ret[42..46].copy_from_slice(&ppm.to_le_bytes());
+    Some(ret)
+}
+
+/// Set the density (DPI) metadata of a saved image file.
+/// Supported formats: jpeg (JFIF), png (pHYs), bmp.
+/// Returns Ok(false) if the format does not support density metadata (e.g. webp).
+pub fn set_density(path: &Path, extension: &librusimg::Extension, dpi: u32) -> Result<bool, io::Error> {
+    let data = fs::read(path)?;
+    let new_data = match extension {
+        librusimg::Extension::Jpeg => set_jpeg_density(&data, dpi),
+        librusimg::Extension::Png => set_png_density(&data, dpi),
+        librusimg::Extension::Bmp => set_bmp_density(&data, dpi),
+        _ => return Ok(false),
+    };
+    match new_data {
+        Some(new_data) => {
+            fs::write(path, new_data)?;
+            Ok(true)
+        },
+        None => Err(io::Error::new(io::ErrorKind::InvalidData, format!("failed to set DPI: unexpected file structure ({})", path.display()))),
+    }
+}
@@ -0,0 +1,79 @@
+use std::path::Path;
+use image::DynamicImage;
+use librusimg::{ImgSize, Rect};
+
+/// Whether face detection is compiled into this build (face-detect feature).
+pub const AVAILABLE: bool = cfg!(feature = "face-detect");
+
+/// Images are downscaled to this size before the face detection to keep it fast.
+#[cfg(feature = "face-detect")]
+const FACE_ANALYSIS_SIZE: u32 = 1024;
+
+/// Contents of the face model, read once and shared by all threads.
+#[cfg(feature = "face-detect")]
+static MODEL: std::sync::OnceLock<Result<Vec<u8>, String>> = std::sync::OnceLock::new();
+
+/// Detect the faces in an image with rustface (SeetaFace).
+/// model is the path of the SeetaFace model (seeta_fd_frontal_v1.0.bin), read on the first call.
+/// Returns the areas of the faces in pixels of the image.
+#[cfg(feature = "face-detect")]
+pub fn detect(image: &DynamicImage, model: &Path) -> Result<Vec<Rect>, String> {
+    let bytes = MODEL.get_or_init(|| std::fs::read(model).map_err(|e| format!("{}: {}", model.display(), e)));
+    let bytes = bytes.as_ref().map_err(|e| e.clone())?;
+    let model = rustface::read_model(std::io::Cursor::new(bytes)).map_err(|e| format!("{}: {}", model.display(), e))?;
+    let mut detector = rustface::create_detector_with_model(model);
+    detector.set_min_face_size(20);
+    detector.set_score_thresh(2.0);
+    detector.set_pyramid_scale_factor(0.8);
+    detector.set_slide_window_step(4, 4);
+
+    let luma = image.thumbnail(FACE_ANALYSIS_SIZE, FACE_ANALYSIS_SIZE).to_luma8();
+    let scale = image.width() as f64 / luma.width().max(1) as f64;
+    let faces = detector.detect(&rustface::ImageData::new(luma.as_raw(), luma.width(), luma.height()));
+    Ok(faces.iter().map(|face| {
+        let bbox = face.bbox();
+        let (x, y) = (bbox.x().max(0) as f64 * scale, bbox.y().max(0) as f64 * scale);
+        Rect {
+            x: x as u32,
+            y: y as u32,
+            w: (bbox.width() as f64 * scale).round() as u32,
+            h: (bbox.height() as f64 * scale).round() as u32,
+        }
+    }).collect())
+}
+
+/// Face detection is not compiled into this build.
+#[cfg(not(feature = "face-detect"))]
+pub fn detect(_image: &DynamicImage, _model: &Path) -> Result<Vec<Rect>, String> {
+    Err("rusimg was built without face detection. Build it with --features face-detect.".to_string())
+}
+
+/// Get the bounding box (left, top, right, bottom) of the areas, or None if there are no areas.
+fn union<'a>(areas: impl Iterator<Item = &'a Rect>) -> Option<(u32, u32, u32, u32)> {
+    areas.fold(None, |bounds, area| {
+        let (left, top, right, bottom) = bounds.unwrap_or((area.x, area.y, area.x + area.w, area.y + area.h));
+        Some((left.min(area.x), top.min(area.y), right.max(area.x + area.w), bottom.max(area.y + area.h)))
+    })
+}
+
+/// Place a crop of the given size in the image, so that the faces are in the frame.
+/// The crop is centered on the bounding box of all faces and moved inside the image.
+/// If the faces do not fit in the crop, it is centered on the largest face. Without faces, the crop is centered on the image.
+pub fn place(size: ImgSize, crop: ImgSize, faces: &[Rect]) -> Rect {
+    let (width, height) = (crop.width.min(size.width) as u32, crop.height.min(size.height) as u32);
+    let target = match union(faces.iter()) {
+        Some((left, top, right, bottom)) if right - left <= width && bottom - top <= height => Some((left, top, right, bottom)),
+        // 全員が収まらない場合は一番大きい顔を優先
+        _ => union(faces.iter().max_by_key(|face| face.w as u64 * face.h as u64).into_iter()),
+    };
+    let (center_x, center_y) = match target {
+        Some((left, top, right, bottom)) => ((left + right) / 2, (top + bottom) / 2),
+        None => (size.width as u32 / 2, size.height as u32 / 2),
+    };
+    Rect {
+        x: center_x.saturating_sub(width / 2).min(size.width as u32 - width),
+        y: center_y.saturating_sub(height / 2).min(size.height as u32 - height),
+        w: width,
+        h: height,
+    }
+}
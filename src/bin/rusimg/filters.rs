@@ -0,0 +1,245 @@
+use image::{DynamicImage, GenericImageView, Rgba, RgbaImage};
+use image::imageops::FilterType;
+
+use librusimg::{ImgSize, Rect};
+use librusimg::rect::TrimPolicy;
+
+use crate::parse::{UpscaleFilter, WhiteBalance, RedactStyle};
+
+/// Redacted areas are blurred by shrinking them to at most this many pixels on the long side and scaling them back.
+/// Unlike a gaussian blur, no detail smaller than 1/8 of the area (e.g. the modules of a QR code) survives.
+const REDACT_BLUR_SIZE: u32 = 8;
+
+/// Sample a pixel with bilinear interpolation.
+/// Returns None if the position is outside of the image.
+fn sample_bilinear(image: &RgbaImage, x: f32, y: f32) -> Option<Rgba<u8>> {
+    let (width, height) = image.dimensions();
+    if x < 0.0 || y < 0.0 || x > (width - 1) as f32 || y > (height - 1) as f32 {
+        return None;
+    }
+    let (x0, y0) = (x.floor() as u32, y.floor() as u32);
+    let (x1, y1) = ((x0 + 1).min(width - 1), (y0 + 1).min(height - 1));
+    let (fx, fy) = (x - x0 as f32, y - y0 as f32);
+
+    let (p00, p10, p01, p11) = (image.get_pixel(x0, y0), image.get_pixel(x1, y0), image.get_pixel(x0, y1), image.get_pixel(x1, y1));
+    let pixel = std::array::from_fn(|c| {
+        let top = p00.0[c] as f32 * (1.0 - fx) + p10.0[c] as f32 * fx;
+        let bottom = p01.0[c] as f32 * (1.0 - fx) + p11.0[c] as f32 * fx;
+        (top * (1.0 - fy) + bottom * fy).round() as u8
+    });
+    Some(Rgba(pixel))
+}
+
+/// Convert an RGBA buffer back to the color type of the original image.
+/// Backends encode the DynamicImage as-is, so the color type must not change unexpectedly (e.g. RGB JPEG -> RGBA).
+pub fn to_original_color(original: &DynamicImage, rgba: RgbaImage) -> DynamicImage {
+    let rgba = DynamicImage::ImageRgba8(rgba);
+    match original {
+        DynamicImage::ImageLuma8(_) => DynamicImage::ImageLuma8(rgba.to_luma8()),
+        DynamicImage::ImageLumaA8(_) => DynamicImage::ImageLumaA8(rgba.to_luma_alpha8()),
+        DynamicImage::ImageRgb8(_) => DynamicImage::ImageRgb8(rgba.to_rgb8()),
+        _ => rgba,
+    }
+}
+
+/// Rotate the image counterclockwise around its center by the given angle in degrees.
+/// The canvas size is kept, and the uncovered area is filled with the fill color.
+pub fn rotate(image: &DynamicImage, degrees: f32, fill: Rgba<u8>) -> DynamicImage {
+    let source = image.to_rgba8();
+    let (width, height) = source.dimensions();
+    if width == 0 || height == 0 {
+        return image.clone();
+    }
+    let (sin, cos) = degrees.to_radians().sin_cos();
+    let (cx, cy) = ((width - 1) as f32 / 2.0, (height - 1) as f32 / 2.0);
+
+    let rotated = RgbaImage::from_fn(width, height, |x, y| {
+        let (u, v) = (x as f32 - cx, y as f32 - cy);
+        let sx = u * cos - v * sin + cx;
+        let sy = u * sin + v * cos + cy;
+        sample_bilinear(&source, sx, sy).unwrap_or(fill)
+    });
+    to_original_color(image, rotated)
+}
+
+/// Get the color of the top-left pixel.
+/// This is used as the fill color for operations that uncover the canvas (e.g. deskew).
+pub fn background_color(image: &DynamicImage) -> Rgba<u8> {
+    if image.width() == 0 || image.height() == 0 {
+        Rgba([255, 255, 255, 255])
+    }
+    else {
+        image.get_pixel(0, 0)
+    }
+}
+
+/// Find the range of a channel, excluding clip percent of the pixels on each side.
+fn levels_range(histogram: &[u64; 256], clip: f32) -> (u8, u8) {
+    let total = histogram.iter().sum::<u64>();
+    let clip_count = (total as f64 * clip as f64 / 100.0) as u64;
+    let mut sum = 0;
+    let low = histogram.iter().position(|c| {
+        sum += c;
+        sum > clip_count
+    }).unwrap_or(0);
+    let mut sum = 0;
+    let high = histogram.iter().rposition(|c| {
+        sum += c;
+        sum > clip_count
+    }).unwrap_or(255);
+    (low as u8, high as u8)
+}
+
+/// Stretch the histogram of each color channel to the full range (0-255).
+/// clip percent of the darkest and the brightest pixels of each channel are clipped, so that a few outliers do not limit the stretch.
+/// The alpha channel is not changed.
+pub fn auto_levels(image: &DynamicImage, clip: f32) -> DynamicImage {
+    let mut rgba = image.to_rgba8();
+    let mut histograms = [[0u64; 256]; 3];
+    for pixel in rgba.pixels() {
+        for (histogram, value) in histograms.iter_mut().zip(pixel.0.iter()) {
+            histogram[*value as usize] += 1;
+        }
+    }
+
+    // チャンネルごとの変換テーブル
+    let luts = histograms.map(|histogram| {
+        let (low, high) = levels_range(&histogram, clip);
+        let mut lut = [0u8; 256];
+        for (v, out) in lut.iter_mut().enumerate() {
+            *out = if high <= low {
+                v as u8
+            }
+            else {
+                ((v as f32 - low as f32) * 255.0 / (high - low) as f32).round().clamp(0.0, 255.0) as u8
+            };
+        }
+        lut
+    });
+    for pixel in rgba.pixels_mut() {
+        for (value, lut) in pixel.0.iter_mut().zip(luts.iter()) {
+            *value = lut[*value as usize];
+        }
+    }
+    to_original_color(image, rgba)
+}
+
+/// Darken or brighten the image toward the corners.
+/// The gain at the distance r from the center (r = 1.0 at the corners) is 1.0 - amount * r^2,
+/// so a positive amount adds a vignette and a negative amount removes one. The alpha channel is not changed.
+pub fn vignette(image: &DynamicImage, amount: f32) -> DynamicImage {
+    let mut rgba = image.to_rgba8();
+    let (width, height) = rgba.dimensions();
+    let (cx, cy) = (width as f32 / 2.0, height as f32 / 2.0);
+    let max_distance2 = (cx * cx + cy * cy).max(1.0);
+
+    for (x, y, pixel) in rgba.enumerate_pixels_mut() {
+        let (dx, dy) = (x as f32 + 0.5 - cx, y as f32 + 0.5 - cy);
+        let gain = (1.0 - amount * (dx * dx + dy * dy) / max_distance2).max(0.0);
+        for value in pixel.0.iter_mut().take(3) {
+            *value = (*value as f32 * gain).round().clamp(0.0, 255.0) as u8;
+        }
+    }
+    to_original_color(image, rgba)
+}
+
+/// Reference color temperature of a neutral white (D65).
+const REFERENCE_KELVIN: u32 = 6500;
+
+/// Approximate the RGB color of a black body at the given color temperature (Tanner Helland's fit, 1000K-40000K).
+fn kelvin_to_rgb(kelvin: u32) -> [f32; 3] {
+    let t = kelvin as f32 / 100.0;
+    let r = if t <= 66.0 { 255.0 } else { 329.69873 * (t - 60.0).powf(-0.13320476) };
+    let g = if t <= 66.0 { 99.4708 * t.ln() - 161.11957 } else { 288.12217 * (t - 60.0).powf(-0.07551485) };
+    let b = if t >= 66.0 { 255.0 } else if t <= 19.0 { 0.0 } else { 138.51773 * (t - 10.0).ln() - 305.0448 };
+    [r.clamp(1.0, 255.0), g.clamp(1.0, 255.0), b.clamp(1.0, 255.0)]
+}
+
+/// Calculate the gain of each channel (R, G, B) for the white balance correction.
+/// The gains are normalized so that the brightness is kept.
+pub fn white_balance_gains(image: &DynamicImage, white_balance: WhiteBalance) -> [f32; 3] {
+    let gains = match white_balance {
+        WhiteBalance::Auto => {
+            // Gray world: 平均色が灰色になるように補正
+            let rgb = image.to_rgb8();
+            let mut sum = [0u64; 3];
+            for pixel in rgb.pixels() {
+                for (s, v) in sum.iter_mut().zip(pixel.0.iter()) {
+                    *s += *v as u64;
+                }
+            }
+            let mean = sum.map(|s| s.max(1) as f32);
+            let gray = mean.iter().sum::<f32>() / 3.0;
+            mean.map(|m| gray / m)
+        },
+        WhiteBalance::Temperature { kelvin, tint } => {
+            let light = kelvin_to_rgb(kelvin);
+            let reference = kelvin_to_rgb(REFERENCE_KELVIN);
+            let mut gains = [reference[0] / light[0], reference[1] / light[1], reference[2] / light[2]];
+            gains[1] *= 1.0 - tint as f32 / 400.0;
+            gains
+        },
+    };
+    // 明るさ (Rec. 601) を保つように正規化
+    let luma = gains[0] * 0.299 + gains[1] * 0.587 + gains[2] * 0.114;
+    gains.map(|g| g / luma)
+}
+
+/// Multiply each color channel by the gain. The alpha channel is not changed.
+pub fn apply_gains(image: &DynamicImage, gains: [f32; 3]) -> DynamicImage {
+    let mut rgba = image.to_rgba8();
+    let luts = gains.map(|gain| {
+        let mut lut = [0u8; 256];
+        for (v, out) in lut.iter_mut().enumerate() {
+            *out = (v as f32 * gain).round().clamp(0.0, 255.0) as u8;
+        }
+        lut
+    });
+    for pixel in rgba.pixels_mut() {
+        for (value, lut) in pixel.0.iter_mut().zip(luts.iter()) {
+            *value = lut[*value as usize];
+        }
+    }
+    to_original_color(image, rgba)
+}
+
+/// Upscale the image to the given size.
+/// - Lanczos: Lanczos3 filter.
+/// - Sharp: Lanczos3 filter followed by an unsharp mask to restore the edges blurred by the interpolation.
+/// - PixelArt: Nearest neighbor filter.
+pub fn upscale(image: &DynamicImage, width: u32, height: u32, filter: UpscaleFilter) -> DynamicImage {
+    match filter {
+        UpscaleFilter::Lanczos => image.resize_exact(width, height, FilterType::Lanczos3),
+        UpscaleFilter::Sharp => {
+            // 拡大率に応じてシャープの半径を調整
+            let scale = width as f32 / image.width().max(1) as f32;
+            image.resize_exact(width, height, FilterType::Lanczos3).unsharpen(scale.max(1.0) * 0.5, 2)
+        },
+        UpscaleFilter::PixelArt => image.resize_exact(width, height, FilterType::Nearest),
+    }
+}
+
+/// Redact the areas of an image by compositing a blurred or black patch over each of them.
+/// The areas exceeding the image are clamped, and the areas outside of it are ignored.
+pub fn redact(image: &DynamicImage, areas: &[Rect], style: RedactStyle) -> DynamicImage {
+    let mut rgba = image.to_rgba8();
+    let size = ImgSize::new(rgba.width() as usize, rgba.height() as usize);
+    for area in areas {
+        let area = match area.clamp_to(size, TrimPolicy::Clamp) {
+            Ok(area) => area,
+            Err(_) => continue,
+        };
+        let patch = match style {
+            RedactStyle::Blur => {
+                let region = image::imageops::crop_imm(&rgba, area.x, area.y, area.w, area.h).to_image();
+                let scale = (area.w.max(area.h) as f32 / REDACT_BLUR_SIZE as f32).max(1.0);
+                let (small_w, small_h) = (((area.w as f32 / scale) as u32).max(1), ((area.h as f32 / scale) as u32).max(1));
+                let small = image::imageops::resize(&region, small_w, small_h, FilterType::Triangle);
+                image::imageops::resize(&small, area.w, area.h, FilterType::Triangle)
+            },
+            RedactStyle::Black => RgbaImage::from_pixel(area.w, area.h, Rgba([0, 0, 0, 255])),
+        };
+        image::imageops::replace(&mut rgba, &patch, area.x as i64, area.y as i64);
+    }
+    to_original_color(image, rgba)
+}
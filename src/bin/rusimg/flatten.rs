@@ -0,0 +1,68 @@
+use std::collections::HashSet;
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+
+use crate::checksum::{self, ChecksumAlgorithm};
+
+/// How to make the names unique when a recursive tree is flattened into one directory.
+/// - Parent: Append the name of the parent directory (e.g. trip/IMG_0001.jpg -> IMG_0001_trip.webp).
+/// - Hash: Append the first 8 hex digits of the SHA-256 of the source file (e.g. IMG_0001_3fa2c4d1.webp).
+#[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq)]
+pub enum DedupeMode {
+    Parent,
+    Hash,
+}
+
+/// Get the suffix that distinguishes the file.
+fn suffix(input_path: &Path, mode: DedupeMode) -> Option<String> {
+    match mode {
+        DedupeMode::Parent => input_path.parent()?.file_name().map(|n| n.to_string_lossy().to_string()),
+        DedupeMode::Hash => {
+            let checksum = checksum::file_checksum(input_path, ChecksumAlgorithm::Sha256).ok()?;
+            Some(checksum[..8].to_string())
+        },
+    }
+}
+
+/// Append a suffix to the file stem (e.g. IMG_0001.webp + trip -> IMG_0001_trip.webp).
+fn with_suffix(file_name: &Path, suffix: &str) -> OsString {
+    let mut name = file_name.file_stem().unwrap_or_default().to_os_string();
+    name.push("_");
+    name.push(suffix);
+    if let Some(extension) = file_name.extension() {
+        name.push(".");
+        name.push(extension);
+    }
+    name
+}
+
+/// Names already given to the outputs of the flattened tree.
+#[derive(Default)]
+pub struct Names {
+    used: HashSet<PathBuf>,
+}
+
+impl Names {
+    /// Get a unique output path in the output directory.
+    /// If the name is already used by another file of the batch, the suffix of the mode is appended (and then a number if it is still used).
+    /// Returns None if the name is used and no mode is specified; the file should be skipped instead of overwriting the earlier output.
+    pub fn unique(&mut self, output_path: PathBuf, input_path: &Path, mode: Option<DedupeMode>) -> Option<PathBuf> {
+        if self.used.insert(output_path.clone()) {
+            return Some(output_path);
+        }
+        let mode = mode?;
+
+        let file_name = PathBuf::from(output_path.file_name().unwrap_or_default());
+        let base = match suffix(input_path, mode).filter(|s| !s.is_empty()) {
+            Some(suffix) => PathBuf::from(with_suffix(&file_name, &suffix)),
+            None => file_name,
+        };
+        let mut candidate = output_path.with_file_name(&base);
+        let mut number = 2;
+        while !self.used.insert(candidate.clone()) {
+            candidate = output_path.with_file_name(with_suffix(&base, &number.to_string()));
+            number += 1;
+        }
+        Some(candidate)
+    }
+}
@@ -0,0 +1,27 @@
+use std::path::Path;
+use librusimg::{RusImg, RusimgError};
+use librusimg::tempdir::TempDir;
+use librusimg::tonemap::{self, ToneMap};
+
+/// File extensions of the HDR sources (OpenEXR, Radiance HDR).
+/// The library cannot open them, so they are decoded with the image crate and tone mapped to 8-bit.
+const HDR_EXTENSIONS: [&str; 2] = ["exr", "hdr"];
+
+/// Whether the file is an HDR source.
+pub fn is_hdr(path: &Path) -> bool {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| HDR_EXTENSIONS.iter().any(|hdr| e.eq_ignore_ascii_case(hdr)))
+}
+
+/// Open an HDR source: decode it, tone map it to 8-bit, and open the result as a png image.
+/// The library opens images from files, so the tone mapped image is passed through a lossless png in a temporary directory.
+pub fn open(path: &Path, operator: ToneMap, exposure: f32) -> Result<RusImg, RusimgError> {
+    let image = image::open(path).map_err(|e| RusimgError::FailedToOpenImage(e.to_string()))?;
+    let mapped = tonemap::tonemap(&image, operator, exposure);
+
+    let temp_dir = TempDir::new().map_err(|e| RusimgError::FailedToCreateFile(e.to_string()))?;
+    let temp_path = temp_dir.file("tonemapped.png");
+    mapped.save(&temp_path).map_err(|e| RusimgError::FailedToSaveImage(e.to_string()))?;
+    librusimg::open_image(&temp_path)
+}
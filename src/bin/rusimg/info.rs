@@ -0,0 +1,71 @@
+use colored::*;
+
+/// FormatInfo is a structure that represents an image format supported by this build.
+/// - name: The name of the format (value of --convert).
+/// - extensions: File extensions recognized as the format.
+/// - decoder: The library used to decode the format.
+/// - encoder: The library used to encode the format.
+struct FormatInfo {
+    name: String,
+    extensions: String,
+    decoder: String,
+    encoder: String,
+}
+
+/// Get the name and the version of a library compiled into this build.
+fn library(name: &str) -> String {
+    let version = match name {
+        "librusimg" => env!("RUSIMG_VERSION_LIBRUSIMG"),
+        "image" => env!("RUSIMG_VERSION_IMAGE"),
+        "mozjpeg" => env!("RUSIMG_VERSION_MOZJPEG"),
+        "oxipng" => env!("RUSIMG_VERSION_OXIPNG"),
+        "webp" => env!("RUSIMG_VERSION_WEBP"),
+        _ => return name.to_string(),
+    };
+    format!("{} {}", name, version)
+}
+
+/// Get the list of image formats compiled into this build with the versions of the encoders/decoders.
+/// The encoders are the ones active in this build (e.g. the image crate instead of mozjpeg with the jpeg-pure feature).
+fn supported_formats() -> Vec<FormatInfo> {
+    librusimg::Extension::builtin().iter().map(|format| {
+        let mut encoder = format.encoder().map(library).unwrap_or_default();
+        // png は圧縮時に oxipng で最適化される
+        if *format == librusimg::Extension::Png {
+            encoder.push_str(&format!(", {}", library("oxipng")));
+        }
+        FormatInfo {
+            name: format.preferred_extension(),
+            extensions: format.aliases().join(", "),
+            decoder: format.decoder().map(library).unwrap_or_default(),
+            encoder: encoder,
+        }
+    }).collect()
+}
+
+/// Show the list of supported image formats (--formats).
+pub fn print_formats() {
+    println!("{}", format!("librusimg {}", env!("RUSIMG_VERSION_LIBRUSIMG")).bold());
+    println!("{:<6} {:<22} {:<20} {}", "Format".bold(), "Extensions".bold(), "Decoder".bold(), "Encoder".bold());
+    for format in supported_formats() {
+        println!("{:<6} {:<22} {:<20} {}", format.name, format.extensions, format.decoder, format.encoder);
+    }
+}
+
+/// Show the version (--version). With verbose (--version --verbose), also show the build information for bug reports:
+/// the git commit, the target, the compiler, the enabled features and the encoders/decoders compiled into this build.
+/// The output is plain text (without colors), so that it can be pasted into an issue as it is.
+pub fn print_version(verbose: bool) {
+    println!("rusimg {}", env!("CARGO_PKG_VERSION"));
+    if !verbose {
+        return;
+    }
+    println!("commit: {}", env!("RUSIMG_GIT_HASH"));
+    println!("target: {} ({})", env!("RUSIMG_TARGET"), env!("RUSIMG_PROFILE"));
+    println!("rustc: {}", env!("RUSIMG_RUSTC_VERSION"));
+    println!("features: {}", if env!("RUSIMG_FEATURES").is_empty() { "(none)" } else { env!("RUSIMG_FEATURES") });
+    println!("{}", library("librusimg"));
+    for format in supported_formats() {
+        println!("{}: decoder: {}, encoder: {}", format.name, format.decoder, format.encoder);
+    }
+}
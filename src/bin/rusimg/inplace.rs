@@ -0,0 +1,121 @@
+use std::fs::{self, File, FileTimes, Metadata};
+use std::io;
+use std::path::{Path, PathBuf};
+use librusimg::Extension;
+
+use crate::hdr;
+use crate::pages;
+
+/// Check if saving the source in place (--in-place) keeps its format.
+/// HDR and multi-page sources are saved as png, and --convert may name another format; the source would then no longer match its extension.
+pub fn keeps_format(source: &Path, source_extension: &Extension, destination_extension: Option<&str>) -> bool {
+    if hdr::is_hdr(source) || pages::is_multi_page(source) {
+        return false;
+    }
+    match destination_extension {
+        Some(extension) => Extension::from_extension(extension).is_ok_and(|extension| &extension == source_extension),
+        None => true,
+    }
+}
+
+/// Path of the temporary file the source is saved to before it is replaced.
+/// It is a hidden file next to the source with the same extension, so the rename stays on the same file system.
+pub fn temp_path(source: &Path) -> PathBuf {
+    let stem = source.file_stem().map(|s| s.to_string_lossy().into_owned()).unwrap_or_default();
+    let name = match source.extension() {
+        Some(extension) => format!(".{}.rusimg-tmp.{}", stem, extension.to_string_lossy()),
+        None => format!(".{}.rusimg-tmp", stem),
+    };
+    source.with_file_name(name)
+}
+
+/// Replace the source with the temporary file atomically.
+/// The permissions and the access and modification times of the source (read before saving) are copied to the temporary file first,
+/// so the source is either untouched or fully replaced. The temporary file is removed if the replacement fails.
+pub fn replace(temp: &Path, source: &Path, metadata: &Metadata) -> io::Result<()> {
+    let result = copy_metadata(temp, metadata).and_then(|_| fs::rename(temp, source));
+    if result.is_err() {
+        let _ = fs::remove_file(temp);
+    }
+    result
+}
+
+/// Copy the timestamps and the permissions to a file.
+fn copy_metadata(path: &Path, metadata: &Metadata) -> io::Result<()> {
+    let times = FileTimes::new().set_accessed(metadata.accessed()?).set_modified(metadata.modified()?);
+    File::options().write(true).open(path)?.set_times(times)?;
+    // 読み取り専用だと時刻を設定できないので、パーミッションは最後に設定する
+    fs::set_permissions(path, metadata.permissions())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{Duration, SystemTime};
+
+    /// Make an empty directory for a test in the temporary directory.
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("rusimg-inplace-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn format_changes_are_refused() {
+        assert!(keeps_format(Path::new("photo.jpg"), &Extension::Jpeg, None));
+        assert!(keeps_format(Path::new("photo.jpg"), &Extension::Jpeg, Some("jpeg")));
+        assert!(!keeps_format(Path::new("photo.jpg"), &Extension::Jpeg, Some("webp")));
+        assert!(!keeps_format(Path::new("scan.tiff"), &Extension::Png, None));
+        assert!(!keeps_format(Path::new("sky.exr"), &Extension::Png, Some("png")));
+    }
+
+    #[test]
+    fn temp_file_is_next_to_the_source() {
+        assert_eq!(temp_path(Path::new("photos/photo.jpg")), Path::new("photos/.photo.rusimg-tmp.jpg"));
+        assert_eq!(temp_path(Path::new("scan")), Path::new(".scan.rusimg-tmp"));
+    }
+
+    #[test]
+    fn replace_keeps_timestamps_and_permissions() {
+        let dir = test_dir("replace");
+        let source = dir.join("photo.jpg");
+        fs::write(&source, b"original").unwrap();
+        let modified = SystemTime::UNIX_EPOCH + Duration::from_secs(1_600_000_000);
+        File::options().write(true).open(&source).unwrap().set_times(FileTimes::new().set_modified(modified)).unwrap();
+        let mut permissions = fs::metadata(&source).unwrap().permissions();
+        permissions.set_readonly(true);
+        fs::set_permissions(&source, permissions).unwrap();
+        let metadata = fs::metadata(&source).unwrap();
+
+        let temp = temp_path(&source);
+        fs::write(&temp, b"saved").unwrap();
+        replace(&temp, &source, &metadata).unwrap();
+
+        assert_eq!(fs::read(&source).unwrap(), b"saved");
+        assert!(!temp.exists());
+        let replaced = fs::metadata(&source).unwrap();
+        assert_eq!(replaced.modified().unwrap(), modified);
+        assert!(replaced.permissions().readonly());
+
+        let mut permissions = replaced.permissions();
+        #[allow(clippy::permissions_set_readonly_false)]
+        permissions.set_readonly(false);
+        fs::set_permissions(&source, permissions).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn failed_replace_keeps_the_source() {
+        let dir = test_dir("failed");
+        let source = dir.join("photo.jpg");
+        fs::write(&source, b"original").unwrap();
+        let metadata = fs::metadata(&source).unwrap();
+
+        // 一時ファイルが書き込まれていない場合
+        let temp = temp_path(&source);
+        assert!(replace(&temp, &source, &metadata).is_err());
+        assert_eq!(fs::read(&source).unwrap(), b"original");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
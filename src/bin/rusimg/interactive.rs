@@ -0,0 +1,142 @@
+use std::io::{stdin, stdout, Write};
+use std::path::PathBuf;
+use colored::*;
+
+/// InteractiveResult is the result of the interactive mode.
+/// - files: The selected files.
+/// - quality: The quality adjusted in the interactive mode.
+pub struct InteractiveResult {
+    pub files: Vec<PathBuf>,
+    pub quality: Option<f32>,
+}
+
+/// Show an image with its selection status.
+fn print_item(files: &[PathBuf], selected: &[bool], i: usize) {
+    let mark = if selected[i] { "[x]".green().bold() } else { "[ ]".normal() };
+    println!("{} {:>4}: {}", mark, i + 1, files[i].display());
+}
+
+/// Show the list of detected images with their selection status.
+fn print_list(files: &[PathBuf], selected: &[bool]) {
+    for i in 0..files.len() {
+        print_item(files, selected, i);
+    }
+}
+
+/// Show the help of the interactive mode.
+fn print_help() {
+    println!("{}", "Commands:".bold());
+    println!("  l              List images");
+    println!("  <n>[-<m>]      Toggle image n (or images n to m)");
+    println!("  a / none       Select all / none");
+    println!("  p <n>          Preview image n");
+    println!("  q <quality>    Set quality (0.0 <= q <= 100.0)");
+    println!("  r              Show summary and run");
+    println!("  x              Exit without processing");
+    println!("  h              Show this help");
+}
+
+/// Read a line from stdin.
+/// Returns None at the end of input.
+fn read_line(prompt: &str) -> Option<String> {
+    print!("{}", prompt);
+    stdout().flush().unwrap();
+    let mut input = String::new();
+    match stdin().read_line(&mut input) {
+        Ok(0) | Err(_) => None,
+        Ok(_) => Some(input.trim().to_string()),
+    }
+}
+
+/// Parse an image number or a range of image numbers (1-origin).
+fn parse_range(s: &str, len: usize) -> Option<(usize, usize)> {
+    let (start, end) = match s.split_once('-') {
+        Some((start, end)) => (start.trim().parse::<usize>().ok()?, end.trim().parse::<usize>().ok()?),
+        None => {
+            let n = s.parse::<usize>().ok()?;
+            (n, n)
+        },
+    };
+    if start < 1 || end > len || start > end {
+        return None;
+    }
+    Some((start - 1, end - 1))
+}
+
+/// Run the interactive mode.
+/// Lets the user select the images to be processed, preview them and adjust the quality before execution.
+/// Returns None if the user exits without processing.
+pub fn run(files: Vec<PathBuf>, quality: Option<f32>) -> Option<InteractiveResult> {
+    let mut selected = vec![true; files.len()];
+    let mut quality = quality;
+
+    print_list(&files, &selected);
+    print_help();
+    loop {
+        let input = read_line(&format!("{} ", "rusimg>".cyan().bold()))?;
+        let (command, argument) = match input.split_once(' ') {
+            Some((command, argument)) => (command, argument.trim()),
+            None => (input.as_str(), ""),
+        };
+        match command {
+            "" => {},
+            "l" => print_list(&files, &selected),
+            "h" => print_help(),
+            "a" => selected.iter_mut().for_each(|s| *s = true),
+            "none" => selected.iter_mut().for_each(|s| *s = false),
+            "x" => return None,
+            "p" => {
+                match parse_range(argument, files.len()) {
+                    Some((n, _)) => {
+                        match image::open(&files[n]) {
+                            Ok(image) => {
+                                if let Err(e) = super::viewer::view(&image) {
+                                    println!("{}: {}", "Error".red(), e);
+                                }
+                            },
+                            Err(e) => println!("{}: {}", "Error".red(), e),
+                        }
+                    },
+                    None => println!("Please enter an image number (1-{}).", files.len()),
+                }
+            },
+            "q" => {
+                match argument.parse::<f32>() {
+                    Ok(q) if (0.0..=100.0).contains(&q) => {
+                        quality = Some(q);
+                        println!("Quality: {}", q);
+                    },
+                    _ => println!("Quality must be 0.0 <= q <= 100.0"),
+                }
+            },
+            "r" => {
+                let count = selected.iter().filter(|s| **s).count();
+                println!("{}", "Summary:".bold());
+                println!("  Images: {} / {}", count, files.len());
+                match quality {
+                    Some(q) => println!("  Quality: {}", q),
+                    None => println!("  Quality: (not specified)"),
+                }
+                let answer = read_line(" Do you want to run? [y/N]: ")?;
+                if answer.eq_ignore_ascii_case("y") || answer.eq_ignore_ascii_case("yes") {
+                    let files = files.into_iter().zip(selected).filter(|(_, s)| *s).map(|(f, _)| f).collect();
+                    return Some(InteractiveResult {
+                        files,
+                        quality,
+                    });
+                }
+            },
+            _ => {
+                match parse_range(command, files.len()) {
+                    Some((start, end)) => {
+                        for i in start..=end {
+                            selected[i] = !selected[i];
+                            print_item(&files, &selected, i);
+                        }
+                    },
+                    None => println!("Unknown command: {} (enter h for help)", input),
+                }
+            },
+        }
+    }
+}
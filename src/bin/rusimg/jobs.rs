@@ -0,0 +1,99 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Job is one row of a mapping file.
+/// - path: The source image file. Relative paths are resolved from the directory of the mapping file.
+/// - args: Command line options for this file (e.g. ["--quality", "60"]). They are parsed after the global options, so they override them.
+#[derive(Debug, Clone)]
+pub struct Job {
+    pub path: PathBuf,
+    pub args: Vec<String>,
+}
+
+/// Column (or key) that contains the source file path.
+const FILE_KEY: &str = "file";
+
+/// Convert a column name and a value to command line options.
+/// The column name is the option name (e.g. quality -> --quality, q -> -q, auto_levels -> --auto-levels).
+/// Empty values, "false" and "no" are skipped, and "true" and "yes" are flags without a value.
+fn to_args(key: &str, value: &str) -> Vec<String> {
+    let key = key.trim().trim_start_matches('-').replace('_', "-");
+    let option = if key.len() == 1 { format!("-{}", key) } else { format!("--{}", key) };
+    match value.trim() {
+        "" | "false" | "no" => Vec::new(),
+        "true" | "yes" => vec![option],
+        value => vec![option, value.to_string()],
+    }
+}
+
+/// Read a CSV mapping file.
+/// The first row is the header: a "file" column and one column per option.
+fn load_csv(path: &Path) -> Result<Vec<(String, Vec<String>)>, String> {
+    let mut reader = csv::Reader::from_path(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    let headers = reader.headers().map_err(|e| format!("{}: {}", path.display(), e))?.clone();
+    let file_column = headers.iter().position(|h| h.trim() == FILE_KEY)
+        .ok_or(format!("{}: no '{}' column", path.display(), FILE_KEY))?;
+
+    let mut rows = Vec::new();
+    for record in reader.records() {
+        let record = record.map_err(|e| format!("{}: {}", path.display(), e))?;
+        let file = record.get(file_column).unwrap_or("").trim().to_string();
+        let args = headers.iter().zip(record.iter()).enumerate()
+            .filter(|(i, _)| *i != file_column)
+            .flat_map(|(_, (key, value))| to_args(key, value))
+            .collect();
+        rows.push((file, args));
+    }
+    Ok(rows)
+}
+
+/// Convert a key and a value of a JSON mapping file to command line options.
+/// An array repeats the option for each value (e.g. "trim": ["0x0+600x400", "600x0+600x400"] -> two trim areas).
+fn json_to_args(key: &str, value: &serde_json::Value) -> Vec<String> {
+    match value {
+        serde_json::Value::String(s) => to_args(key, s),
+        serde_json::Value::Null => Vec::new(),
+        serde_json::Value::Array(values) => values.iter().flat_map(|value| json_to_args(key, value)).collect(),
+        other => to_args(key, &other.to_string()),
+    }
+}
+
+/// Read a JSON mapping file.
+/// The file is an array of objects with a "file" key and one key per option (e.g. [{"file": "a.jpg", "quality": 60, "grayscale": true}]).
+fn load_json(path: &Path) -> Result<Vec<(String, Vec<String>)>, String> {
+    let text = fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    let value: serde_json::Value = serde_json::from_str(&text).map_err(|e| format!("{}: {}", path.display(), e))?;
+    let entries = value.as_array().ok_or(format!("{}: the mapping file must be an array of objects", path.display()))?;
+
+    let mut rows = Vec::new();
+    for entry in entries {
+        let object = entry.as_object().ok_or(format!("{}: the mapping file must be an array of objects", path.display()))?;
+        let file = object.get(FILE_KEY).and_then(|f| f.as_str()).unwrap_or("").trim().to_string();
+        let args = object.iter()
+            .filter(|(key, _)| key.as_str() != FILE_KEY)
+            .flat_map(|(key, value)| json_to_args(key, value))
+            .collect();
+        rows.push((file, args));
+    }
+    Ok(rows)
+}
+
+/// Load the jobs from a mapping file (.csv or .json).
+pub fn load(path: &Path) -> Result<Vec<Job>, String> {
+    let extension = path.extension().and_then(|e| e.to_str()).map(|e| e.to_ascii_lowercase());
+    let rows = match extension.as_deref() {
+        Some("json") => load_json(path)?,
+        _ => load_csv(path)?,
+    };
+
+    let base = path.parent().unwrap_or(Path::new(""));
+    rows.into_iter().enumerate().map(|(i, (file, args))| {
+        if file.is_empty() {
+            return Err(format!("{}: row {}: '{}' is empty", path.display(), i + 1, FILE_KEY));
+        }
+        Ok(Job {
+            path: base.join(file),
+            args,
+        })
+    }).collect()
+}
@@ -2,19 +2,69 @@ use std::path::{Path, PathBuf};
 use std::fs;
 use std::fmt;
 use std::io::{stdout, Write};
-use glob::glob;
+use std::time::Instant;
+use glob::{glob_with, MatchOptions};
+use ignore::{WalkBuilder, WalkState};
+use rand::{SeedableRng, rngs::StdRng};
 use image::DynamicImage;
-use parse::ArgStruct;
+use messages::Msg;
+use budget::{Budget, Reservation};
+use progress::Progress;
+use paths::OutputNaming;
+use parse::{ArgStruct, Mode, TrimSpec, TrimOffset, Gravity, RedactStyle, ResizeSpec, UpscaleFilter, SortOrder, WhiteBalance};
 use colored::*;
 use std::sync::{Arc, Mutex};
 use tokio::sync::mpsc;
 use futures::stream::FuturesUnordered;
 
-use librusimg::{RusImg, RusimgError};
+use librusimg::{RusImg, RusimgError, SaveOptions, OpenOptions, PngOptions, BmpOptions, BmpFormat};
+use librusimg::warning::Warning;
+use librusimg::colorspace::ColorSpace;
+use librusimg::filters::{Curve, Threshold};
 mod parse;
+mod analysis;
+mod filters;
+mod density;
+mod interactive;
+mod info;
+mod messages;
+mod bench;
+mod montage;
+mod preview;
+mod viewer;
+mod notify;
+mod progress;
+mod shell;
+mod appicon;
+mod dates;
+mod flatten;
+mod checksum;
+mod archive;
+mod pdf;
+mod pages;
+mod animation;
+mod comic;
+mod paths;
+mod jobs;
+mod regions;
+mod faces;
+mod hdr;
+mod xattrs;
+mod inplace;
+mod picture;
+mod check;
+mod qr;
+mod split;
+mod watermark;
+mod budget;
+
+/// Name of the ignore file specific to rusimg (same syntax as .gitignore).
+const IGNORE_FILE_NAME: &str = ".rusimgignore";
+/// Maximum width/height of the image kept for --view.
+const VIEW_MAX_SIZE: u32 = 1024;
 
 // Error types
-type ErrorOccuredFilePath = String;
+type ErrorOccuredFilePath = PathBuf;
 type ErrorMessage = std::io::Error;
 /// Error structure containing the error and the file path where the error occurred.
 struct ErrorStruct<T> {
@@ -26,6 +76,8 @@ enum ProcessingError {
     RusimgError(ErrorStruct<RusimgError>),
     IOError(ErrorStruct<ErrorMessage>),
     FailedToViewImage(String),
+    FailedToOpenImage(String),
+    OperationError(ErrorStruct<String>),
 }
 impl fmt::Display for ProcessingError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
@@ -33,10 +85,20 @@ impl fmt::Display for ProcessingError {
             ProcessingError::RusimgError(e) => write!(f, "{}", e.error),
             ProcessingError::IOError(e) => write!(f, "{}", e.error),
             ProcessingError::FailedToViewImage(s) => write!(f, "Failed to view image: {}", s),
+            ProcessingError::FailedToOpenImage(s) => write!(f, "Failed to open image: {}", s),
+            ProcessingError::OperationError(e) => write!(f, "{}", e.error),
         }
     }
 }
 
+/// Summary is the result of processing all images.
+/// - processed: The number of images processed without errors.
+/// - failed: The number of images failed to process.
+pub struct Summary {
+    pub processed: usize,
+    pub failed: usize,
+}
+
 // result status
 /// FileOverwriteAsk is an enum that represents the status of whether to overwrite a file.
 /// This is used to determine whether to overwrite a file when it already exists.
@@ -73,11 +135,24 @@ enum AskResult {
 /// - Success: The processing was successful.
 /// - Cancel: The processing was canceled.
 /// - NotNeeded: The processing was not needed. This is used when no processing is required.
+/// - SkippedBlank: The image was skipped because it is blank (--skip-blank).
 #[derive(Debug, Clone, PartialEq)]
 enum RusimgStatus {
     Success,
     Cancel,
     NotNeeded,
+    SkippedBlank,
+}
+impl RusimgStatus {
+    /// Name of the status in the progress events.
+    fn name(&self) -> &'static str {
+        match self {
+            RusimgStatus::Success => "success",
+            RusimgStatus::Cancel => "canceled",
+            RusimgStatus::NotNeeded => "not_needed",
+            RusimgStatus::SkippedBlank => "skipped_blank",
+        }
+    }
 }
 
 /// ThreadTask is a structure that represents the task to be executed by each thread.
@@ -86,12 +161,30 @@ enum RusimgStatus {
 /// - output_path: The path to the output image file.
 /// - extension: The extension of the output image file.
 /// - ask_result: The result of asking whether to overwrite the file.
+/// - budget: The share of the size budget reserved for this task (--total-budget). It is settled when the task ends.
+/// - trim: The trim area of this task. With multiple trim areas, each area of a source is a task of its own.
+/// - page: The page of a multi-page source (tiff, pdf) to process, starting from 1. Each page is a task of its own.
+/// - line_art_output: --convert auto -> The output used if the image is line art (png). output_path, extension and ask_result are the output for photos (jpeg).
 struct ThreadTask {
     args: ArgStruct,
     input_path: PathBuf,
     output_path: Option<PathBuf>,
     extension: Option<librusimg::Extension>,
     ask_result: AskResult,
+    budget: Option<Reservation>,
+    trim: Option<TrimSpec>,
+    page: Option<usize>,
+    line_art_output: Option<TaskOutput>,
+}
+
+/// TaskOutput is the output of a task that is not saved in place.
+/// - path: The path to the output image file.
+/// - extension: The extension of the output image file.
+/// - ask_result: The result of asking whether to overwrite the file.
+struct TaskOutput {
+    path: PathBuf,
+    extension: librusimg::Extension,
+    ask_result: AskResult,
 }
 
 /// ConvertResult is a structure that represents the result of converting an image.
@@ -102,6 +195,28 @@ struct ConvertResult {
     before_extension: librusimg::Extension,
     after_extension: librusimg::Extension,
 }
+/// ColorSpaceResult is a structure that represents the result of converting the colors of an image.
+/// This structure will be used to display the result of the color space conversion.
+/// - from: The color space of the source. None if its ICC profile is of an unsupported color space (the colors are not converted).
+/// - to: The color space of the output.
+struct ColorSpaceResult {
+    from: Option<ColorSpace>,
+    to: ColorSpace,
+}
+/// RedactResult is a structure that represents the result of hiding areas of an image.
+/// This structure will be used to display the result of the redaction.
+/// - areas: The number of the hidden areas (--redact areas and detected QR codes).
+struct RedactResult {
+    areas: usize,
+}
+/// RegionsResult is a structure that represents the result of pixelating and blurring areas of an image.
+/// This structure will be used to display the result of the region operations.
+/// - pixelated: The number of the pixelated areas.
+/// - blurred: The number of the blurred areas.
+struct RegionsResult {
+    pixelated: usize,
+    blurred: usize,
+}
 /// TrimResult is a structure that represents the result of trimming an image.
 /// This structure will be used to display the result of the trimming.
 /// - before_size: The size of the image before trimming.
@@ -110,6 +225,20 @@ struct TrimResult {
     before_size: librusimg::ImgSize,
     after_size: librusimg::ImgSize,
 }
+/// AutocropResult is a structure that represents the result of removing the borders of an image.
+/// This structure will be used to display the result of the autocrop.
+/// - before_size: The size of the image before autocrop.
+/// - after_size: The size of the image after autocrop.
+struct AutocropResult {
+    before_size: librusimg::ImgSize,
+    after_size: librusimg::ImgSize,
+}
+/// DeskewResult is a structure that represents the result of straightening an image.
+/// This structure will be used to display the result of the deskew.
+/// - angle: The detected skew angle in degrees.
+struct DeskewResult {
+    angle: f32,
+}
 /// ResizeResult is a structure that represents the result of resizing an image.
 /// This structure will be used to display the result of the resizing.
 /// - before_size: The size of the image before resizing.
@@ -118,17 +247,83 @@ struct ResizeResult {
     before_size: librusimg::ImgSize,
     after_size: librusimg::ImgSize,
 }
+/// VignetteResult is a structure that represents the result of the vignette adjustment.
+/// This structure will be used to display the result of the vignette adjustment.
+/// - amount: The amount of the adjustment (negative: removed, positive: added).
+struct VignetteResult {
+    amount: f32,
+}
+/// WhiteBalanceResult is a structure that represents the result of the white balance correction.
+/// This structure will be used to display the result of the white balance correction.
+/// - gains: The gain of each channel (R, G, B).
+struct WhiteBalanceResult {
+    gains: [f32; 3],
+}
+/// AutoLevelsResult is a structure that represents the result of stretching the histogram of an image.
+/// This structure will be used to display the result of the auto levels.
+/// - clip: The percentage of the clipped pixels on each side.
+struct AutoLevelsResult {
+    clip: f32,
+}
+/// GammaResult is a structure that represents the result of the gamma correction.
+/// This structure will be used to display the result of the gamma correction.
+/// - gamma: The gamma.
+struct GammaResult {
+    gamma: f32,
+}
+/// CurvesResult is a structure that represents the result of applying tonal curves.
+/// This structure will be used to display the result of the curves.
+/// - channels: The channels with a curve (e.g. "RGB", "R", "B").
+struct CurvesResult {
+    channels: Vec<&'static str>,
+}
+/// LutResult is a structure that represents the result of applying a 3D LUT.
+/// This structure will be used to display the result of the color grading.
+/// - size: The size of the LUT (entries on each axis).
+struct LutResult {
+    size: usize,
+}
 /// GrayscaleResult is a structure that represents the result of converting an image to grayscale.
 /// This structure will be used to display the result of the grayscale conversion.
 /// - status: The status of the grayscale conversion.
 struct GrayscaleResult {
     status: bool,
 }
+/// EdgesResult is a structure that represents the result of detecting the edges of an image.
+/// This structure will be used to display the result of the edge detection.
+/// - status: The status of the edge detection.
+struct EdgesResult {
+    status: bool,
+}
+/// ThresholdResult is a structure that represents the result of binarizing an image.
+/// This structure will be used to display the result of the threshold.
+/// - method: The method of the threshold.
+/// - level: The level separating black from white (None for the adaptive threshold).
+struct ThresholdResult {
+    method: Threshold,
+    level: Option<u8>,
+}
+/// WatermarkResult is a structure that represents the result of embedding a watermark.
+/// This structure will be used to display the result of the watermark.
+/// - message: The embedded message.
+struct WatermarkResult {
+    message: String,
+}
+/// DepthResult is a structure that represents the result of reducing the bit depth of an image.
+/// This structure will be used to display the result of the bit depth reduction.
+/// - depth: The bit depth of the output.
+struct DepthResult {
+    depth: u8,
+}
 /// CompressResult is a structure that represents the result of compressing an image.
 /// This structure will be used to display the result of the compression.
 /// - status: The status of the compression.
+/// - budget: The quality chosen for the size budget and the target size in bytes (--total-budget).
+/// - search: The quality chosen by the quality search and its SSIM (--quality-search).
 struct CompressResult {
     status: bool,
+    budget: Option<(f32, u64)>,
+    search: Option<(f32, f64)>,
 }
 /// SaveResult is a structure that represents the result of saving an image.
 /// This structure will be used to display the result of the saving.
@@ -137,23 +332,46 @@ struct CompressResult {
 /// - output_path: The path to the output image file.
 /// - before_filesize: The size of the image before saving.
 /// - after_filesize: The size of the image after saving. If the image was not saved, this value will be None.
+/// - elapsed: The time taken to encode and write the image. If the image was not saved, this value will be None.
 /// - delete: Whether to delete the original file.
+/// - dpi: The density set to the output file. If the format does not support density metadata, this value will be None.
+/// - checksum: The checksum of the output file (--checksum).
+/// - warnings: Non-fatal issues found while processing the image (e.g. a clamped trim area).
 struct SaveResult {
     status: RusimgStatus,
     input_path: PathBuf,
     output_path: Option<PathBuf>,
     before_filesize: u64,
     after_filesize: Option<u64>,
+    elapsed: Option<std::time::Duration>,
     delete: bool,
+    dpi: Option<u32>,
+    checksum: Option<String>,
+    warnings: Vec<Warning>,
 }
 /// ProcessResult is a structure that represents the result of processing an image.
 /// This structure contains the results of each processing step.
 struct ProcessResult {
     viuer_image: Option<DynamicImage>,
     convert_result: Option<ConvertResult>,
+    colorspace_result: Option<ColorSpaceResult>,
+    redact_result: Option<RedactResult>,
+    regions_result: Option<RegionsResult>,
     trim_result: Option<TrimResult>,
+    autocrop_result: Option<AutocropResult>,
+    deskew_result: Option<DeskewResult>,
     resize_result: Option<ResizeResult>,
+    vignette_result: Option<VignetteResult>,
+    white_balance_result: Option<WhiteBalanceResult>,
+    auto_levels_result: Option<AutoLevelsResult>,
+    gamma_result: Option<GammaResult>,
+    curves_result: Option<CurvesResult>,
+    lut_result: Option<LutResult>,
     grayscale_result: Option<GrayscaleResult>,
+    edges_result: Option<EdgesResult>,
+    threshold_result: Option<ThresholdResult>,
+    watermark_result: Option<WatermarkResult>,
+    depth_result: Option<DepthResult>,
     compress_result: Option<CompressResult>,
     save_result: SaveResult,
 }
@@ -166,43 +384,78 @@ struct ThreadResult {
 
 /// Get the list of files in the directory.
 /// This function used to get the list of image files in the directory when the --source option is specified with a directory path.
+/// The directory tree is traversed in parallel, so the order of the files is not deterministic (use --sort to fix it).
+/// Files matched by .gitignore, .ignore and .rusimgignore are excluded unless --no-ignore is specified.
 /// - dir_path: The path to the directory.
 /// - recursive: Whether to search recursively.
-fn get_files_in_dir(dir_path: &PathBuf, recursive: bool) -> Result<Vec<PathBuf>, String> {
-    let mut files = fs::read_dir(&dir_path).expect("cannot read directory");
-    let mut ret = Vec::new();
+/// - use_ignore_files: Whether to respect ignore files.
+fn get_files_in_dir(dir_path: &PathBuf, recursive: bool, use_ignore_files: bool) -> Result<Vec<PathBuf>, String> {
+    let files = Mutex::new(Vec::new());
+    let mut builder = WalkBuilder::new(dir_path);
+    builder.standard_filters(false)
+        .git_ignore(use_ignore_files)
+        .git_exclude(use_ignore_files)
+        .ignore(use_ignore_files)
+        .parents(use_ignore_files)
+        .require_git(false)
+        .follow_links(true)
+        .max_depth(if recursive { None } else { Some(1) });
+    if use_ignore_files {
+        builder.add_custom_ignore_filename(IGNORE_FILE_NAME);
+    }
+    let walker = builder.build_parallel();
 
-    while let Some(file) = files.next() {
-        let dir_entry = file;
-        match dir_entry {
-            Ok(dir_entry) => {
-                let path = dir_entry.path();
-                // recursive に探索
-                if path.is_dir() && recursive {
-                    let mut files = get_files_in_dir(&path, recursive)?;
-                    ret.append(&mut files);
-                }
-                else {
-                    let file_name = dir_entry.file_name().into_string().expect("cannot convert file name");
-                    if get_extension(&Path::new(&file_name)).is_ok() {
-                        ret.push(Path::new(&dir_path).join(&file_name));
+    walker.run(|| {
+        let files = &files;
+        Box::new(move |entry| {
+            match entry {
+                Ok(entry) => {
+                    // 画像形式のファイルであればファイルリストに追加
+                    let is_file = entry.file_type().map(|t| t.is_file()).unwrap_or(false);
+                    if is_file && get_extension(entry.path()).is_ok() {
+                        files.lock().unwrap().push(entry.into_path());
                     }
-                }
-            },
-            Err(e) => {
-                println!("cannot read a directory entry: {}", e.to_string());
-                continue;
-            },
-        }
+                },
+                Err(e) => {
+                    eprintln!("{}", messages::format(Msg::DirectoryEntryError, &[&e]).yellow());
+                },
+            }
+            WalkState::Continue
+        })
+    });
+
+    files.into_inner().map_err(|e| e.to_string())
+}
+
+/// Normalize a wildcard pattern for the glob crate.
+/// On Windows, the shell does not expand wildcards and paths are given with backslashes, so they are converted to slashes.
+fn normalize_glob_pattern(pattern: &str) -> String {
+    if cfg!(windows) {
+        pattern.replace('\\', "/")
+    }
+    else {
+        pattern.to_string()
     }
-    Ok(ret)
 }
 
 /// Get the list of files by wildcard.
 /// This function used to get the list of image files by wildcard when the --source option is specified with a wildcard pattern.
-fn get_files_by_wildcard(source_path: &PathBuf) -> Result<Vec<PathBuf>, String> {
+/// The pattern is always expanded internally (the shell does not expand it on Windows), and is matched case-insensitively so that "*.PNG" and "*.png" behave the same on all platforms.
+fn get_files_by_wildcard(source_path: &Path) -> Result<Vec<PathBuf>, String> {
     let mut ret = Vec::new();
-    for entry in glob(source_path.to_str().unwrap()).expect("Failed to read glob pattern") {
+    // glob only accepts UTF-8 patterns; a non-UTF-8 path cannot contain wildcards we could expand, so treat it as a plain file.
+    let pattern = match source_path.to_str() {
+        Some(pattern) => normalize_glob_pattern(pattern),
+        None => {
+            return Ok(if source_path.is_file() && get_extension(source_path).is_ok() { vec![source_path.to_path_buf()] } else { Vec::new() });
+        },
+    };
+    let options = MatchOptions {
+        case_sensitive: false,
+        require_literal_separator: false,
+        require_literal_leading_dot: false,
+    };
+    for entry in glob_with(&pattern, options).map_err(|e| e.to_string())? {
         match entry {
             Ok(path) => {
                 // 画像形式であればファイルリストに追加
@@ -210,65 +463,124 @@ fn get_files_by_wildcard(source_path: &PathBuf) -> Result<Vec<PathBuf>, String>
                     ret.push(path);
                 }
             },
-            Err(e) => println!("{:?}", e),
+            Err(e) => eprintln!("{}", messages::format(Msg::WildcardEntryError, &[&e.path().display(), e.error()]).yellow()),
         }
     }
     Ok(ret)
 }
 
-/// Convert a string to an image extension.
-fn convert_str_to_extension(extension_str: &str) -> Result<librusimg::Extension, RusimgError> {
-    match extension_str {
-        "bmp" => Ok(librusimg::Extension::Bmp),
-        "jpg" | "jpeg" | "jfif" => Ok(librusimg::Extension::Jpeg),
-        "png" => Ok(librusimg::Extension::Png),
-        "webp" => Ok(librusimg::Extension::Webp),
-        _ => Err(RusimgError::UnsupportedFileExtension),
+/// Select the indices of the files to process for --limit and --sample.
+/// --limit keeps the first N files, and --sample keeps N files at random. The order of the files is kept in both cases.
+fn select_files(count: usize, limit: Option<usize>, sample: Option<usize>, seed: Option<u64>) -> Vec<usize> {
+    if let Some(limit) = limit {
+        return (0..count.min(limit)).collect();
+    }
+    match sample {
+        Some(sample) => {
+            let mut rng = match seed {
+                Some(seed) => StdRng::seed_from_u64(seed),
+                None => StdRng::from_entropy(),
+            };
+            let mut indices = rand::seq::index::sample(&mut rng, count, sample.min(count)).into_vec();
+            indices.sort_unstable();
+            indices
+        },
+        None => (0..count).collect(),
+    }
+}
+
+/// Read the list of files for --files-from.
+/// The list is separated by newlines, or by NUL characters if null is true. '-' reads the list from stdin.
+/// Files whose extension is not supported are skipped, in the same way as wildcards.
+fn read_file_list(list_path: &Path, null: bool) -> Result<Vec<PathBuf>, String> {
+    let data = if list_path == Path::new("-") {
+        let mut data = Vec::new();
+        std::io::Read::read_to_end(&mut std::io::stdin(), &mut data).map_err(|e| e.to_string())?;
+        data
+    }
+    else {
+        fs::read(list_path).map_err(|e| format!("{}: {}", list_path.display(), e))?
+    };
+
+    let separator = if null { b'\0' } else { b'\n' };
+    let files = data.split(|b| *b == separator)
+        .map(|entry| if null { entry } else { entry.strip_suffix(b"\r").unwrap_or(entry) })
+        .filter(|entry| !entry.is_empty())
+        .map(path_from_bytes)
+        .filter(|path| get_extension(path).is_ok())
+        .collect();
+    Ok(files)
+}
+
+/// Convert the bytes of a file list entry to a path.
+/// On Unix, any bytes are a valid path (e.g. file names that are not UTF-8); on other platforms the entry must be UTF-8.
+#[cfg(unix)]
+fn path_from_bytes(bytes: &[u8]) -> PathBuf {
+    use std::os::unix::ffi::OsStrExt;
+    PathBuf::from(std::ffi::OsStr::from_bytes(bytes))
+}
+#[cfg(not(unix))]
+fn path_from_bytes(bytes: &[u8]) -> PathBuf {
+    PathBuf::from(String::from_utf8_lossy(bytes).into_owned())
+}
+
+/// Sort the list of files.
+/// Files whose metadata cannot be read are placed last for mtime and size.
+fn sort_files(files: &mut [PathBuf], sort: SortOrder) {
+    match sort {
+        SortOrder::Name => files.sort(),
+        SortOrder::Mtime => files.sort_by_cached_key(|f| {
+            let mtime = fs::metadata(f).and_then(|m| m.modified()).ok();
+            (mtime.is_none(), mtime)
+        }),
+        SortOrder::Size => files.sort_by_cached_key(|f| {
+            let size = fs::metadata(f).map(|m| std::cmp::Reverse(m.len())).ok();
+            (size.is_none(), size)
+        }),
+        SortOrder::None => {},
     }
 }
 
 /// Get the extension of the file.
+/// A file without an extension (e.g. "scan" or ".image") is detected from its content.
+/// HDR sources are processed as png after tone mapping (see hdr::open()), and the pages of multi-page sources as png (see pages::open()).
 fn get_extension(path: &Path) -> Result<librusimg::Extension, RusimgError> {
-    let path = path.to_str().ok_or(RusimgError::FailedToConvertPathToString)?.to_ascii_lowercase();
-    match Path::new(&path).extension().and_then(|s| s.to_str()) {
-        Some("bmp") => Ok(librusimg::Extension::Bmp),
-        Some("jpg") | Some("jpeg") | Some("jfif") => Ok(librusimg::Extension::Jpeg),
-        Some("png") => Ok(librusimg::Extension::Png),
-        Some("webp") => Ok(librusimg::Extension::Webp),
-        _ => {
-            Err(RusimgError::UnsupportedFileExtension)
-        },
+    if hdr::is_hdr(path) || pages::is_multi_page(path) {
+        return Ok(librusimg::Extension::Png);
+    }
+    match path.extension() {
+        Some(_) => librusimg::Extension::from_path(path),
+        None => sniff_extension(path),
     }
 }
 
-/// Determine the output path.
-fn get_output_path(args: &ArgStruct, input_path: &PathBuf, extension: &librusimg::Extension) -> PathBuf {
-    let extension = if args.double_extension {
-        format!("{}.{}", input_path.extension().unwrap().to_str().unwrap(), extension.to_string())
+/// Detect the format of the file from its first bytes.
+fn sniff_extension(path: &Path) -> Result<librusimg::Extension, RusimgError> {
+    if !path.is_file() {
+        return Err(RusimgError::UnsupportedFileExtension);
     }
-    else {
-        extension.to_string()
-    };
-    let mut output_path = match &args.destination_path {
-        Some(path) => path.clone(),                                                             // If --output is specified, use it
-        None => Path::new(input_path).with_extension(&extension),       // If not, use the input filepath as the input file
-    };
-    // If append_name is specified, add it to the file name.
-    if let Some(append_name) = &args.destination_append_name {
-        let mut output_path_tmp = output_path.file_stem().unwrap().to_str().unwrap().to_string();
-        output_path_tmp.push_str(append_name);
-        output_path_tmp.push_str(".");
-        output_path_tmp.push_str(&extension);
-        output_path = PathBuf::from(output_path_tmp);
+    let reader = image::ImageReader::open(path).map_err(|e| RusimgError::FailedToOpenFile(e.to_string()))?
+        .with_guessed_format().map_err(|e| RusimgError::FailedToReadFile(e.to_string()))?;
+    match reader.format() {
+        Some(image::ImageFormat::Bmp) => Ok(librusimg::Extension::Bmp),
+        Some(image::ImageFormat::Jpeg) => Ok(librusimg::Extension::Jpeg),
+        Some(image::ImageFormat::Png) => Ok(librusimg::Extension::Png),
+        Some(image::ImageFormat::WebP) => Ok(librusimg::Extension::Webp),
+        _ => Err(RusimgError::UnsupportedFileExtension),
     }
-    output_path
+}
+
+/// Get the file name for display.
+/// Non-UTF-8 characters are replaced with U+FFFD (only for display; the path itself is kept as is).
+fn display_file_name(path: &Path) -> String {
+    path.file_name().unwrap_or(path.as_os_str()).to_string_lossy().to_string()
 }
 
 /// Check if the file exists.
 /// If the file exists, check if it should be overwritten.
 fn check_file_exists(path: &PathBuf, file_overwrite_ask: &FileOverwriteAsk) -> ExistsCheckResult {
     if Path::new(path).exists() {
-        println!("The image file \"{}\" already exists.", path.display().to_string().yellow().bold());
+        println!("{}", messages::format(Msg::AlreadyExists, &[&path.display().to_string().yellow().bold()]));
         match file_overwrite_ask {
             FileOverwriteAsk::YesToAll => {
                 return ExistsCheckResult::AllOverwrite;
@@ -281,72 +593,46 @@ fn check_file_exists(path: &PathBuf, file_overwrite_ask: &FileOverwriteAsk) -> E
             },
         }
     }
-    return ExistsCheckResult::NoProblem;
+    ExistsCheckResult::NoProblem
 }
 
 /// Ask if the file should be overwritten.
 fn ask_file_exists() -> bool {
-    print!(" Do you want to overwrite it? [y/N]: ");
+    print!("{}", messages::text(Msg::AskOverwrite));
     loop {
         stdout().flush().unwrap();
 
         let mut input = String::new();
         std::io::stdin().read_line(&mut input).unwrap();
-        if input.trim().to_ascii_lowercase() == "y" || input.trim().to_ascii_lowercase() == "yes" {
-            println!(" => The file will be overwritten.");
+        if input.trim().eq_ignore_ascii_case("y") || input.trim().eq_ignore_ascii_case("yes") {
+            println!("{}", messages::text(Msg::WillOverwrite));
             return true;
         }
-        else if input.trim().to_ascii_lowercase() == "n" || input.trim().to_ascii_lowercase() == "no" || input.trim() == "" {
-            println!(" => The file will be skipped.");
+        else if input.trim().eq_ignore_ascii_case("n") || input.trim().eq_ignore_ascii_case("no") || input.trim().is_empty() {
+            println!("{}", messages::text(Msg::WillSkip));
             return false;
         }
         else {
-            print!(" Please enter y or n [y/N]: ");
+            print!("{}", messages::text(Msg::EnterYesOrNo));
         }
     }
 }
 
 /// Show the result of saving the image.
 fn save_print(before_path: &PathBuf, after_path: &Option<PathBuf>, before_size: u64, after_size: Option<u64>) {
-    match (after_path, after_size) {
-        (Some(after_path), Some(after_size)) => {
-            if before_path == after_path {
-                println!("{}: {}", "Overwrite", before_path.display());
-                println!("File Size: {} -> {} ({:.1}%)", before_size, after_size, (after_size as f64 / before_size as f64) * 100.0);
-            }
-            else if get_extension(before_path.as_path()) != get_extension(after_path.as_path()) {
-                println!("{}: {} -> {}", "Rename", before_path.display(), after_path.display());
-                println!("File Size: {} -> {} ({:.1}%)", before_size, after_size, (after_size as f64 / before_size as f64) * 100.0);
-            }
-            else {
-                println!("{}: {} -> {}", "Move", before_path.display(), after_path.display());
-                println!("File Size: {} -> {} ({:.1}%)", before_size, after_size, (after_size as f64 / before_size as f64) * 100.0);
-            }
-        },
-        (_, _) => {
-            return;
-        },
-    }
-}
-
-/// Show the image in the terminal using viuer.
-/// Read the image data from memory and display it.
-fn view(image: &DynamicImage) -> Result<(), ProcessingError> {
-    let width = image.width();
-    let height = image.height();
-    let conf_width = width as f64 / std::cmp::max(width, height) as f64 * 100 as f64;
-    let conf_height = height as f64 / std::cmp::max(width, height) as f64 as f64 * 50 as f64;
-    let conf = viuer::Config {
-        absolute_offset: false,
-        width: Some(conf_width as u32),
-        height: Some(conf_height as u32),    
-        ..Default::default()
-    };
-    
-    let result = viuer::print(&image, &conf);
-    match result {
-        Ok(_) => Ok(()),
-        Err(e) => Err(ProcessingError::FailedToViewImage(e.to_string())),
+    if let (Some(after_path), Some(after_size)) = (after_path, after_size) {
+        if before_path == after_path {
+            println!("{}: {}", messages::text(Msg::Overwrite), before_path.display());
+            println!("{}: {} -> {} ({:.1}%)", messages::text(Msg::FileSize), before_size, after_size, (after_size as f64 / before_size as f64) * 100.0);
+        }
+        else if get_extension(before_path.as_path()) != get_extension(after_path.as_path()) {
+            println!("{}: {} -> {}", messages::text(Msg::Rename), before_path.display(), after_path.display());
+            println!("{}: {} -> {} ({:.1}%)", messages::text(Msg::FileSize), before_size, after_size, (after_size as f64 / before_size as f64) * 100.0);
+        }
+        else {
+            println!("{}: {} -> {}", messages::text(Msg::Move), before_path.display(), after_path.display());
+            println!("{}: {} -> {} ({:.1}%)", messages::text(Msg::FileSize), before_size, after_size, (after_size as f64 / before_size as f64) * 100.0);
+        }
     }
 }
 
@@ -356,7 +642,7 @@ fn process_convert<C: Fn(RusimgError) -> ProcessingError>(extension: &Option<lib
         let before_extension = image.extension.clone();
 
         // 変換
-        image.convert(&extension).map_err(rierr)?;
+        image.convert(extension).map_err(rierr)?;
 
         Ok(Some(ConvertResult {
             before_extension: before_extension,
@@ -368,11 +654,61 @@ fn process_convert<C: Fn(RusimgError) -> ProcessingError>(extension: &Option<lib
     }
 }
 
+/// Convert the colors of an image from the color space of its ICC profile.
+/// Images with a profile of an unsupported color space (e.g. Adobe RGB) are not converted.
+fn process_colorspace<C: Fn(RusimgError) -> ProcessingError>(image: &mut RusImg, path: &Path, colorspace: ColorSpace, rierr: C) -> Result<Option<ColorSpaceResult>, ProcessingError> {
+    let from = ColorSpace::of_file(path).map_err(&rierr)?;
+    if let Some(from) = from {
+        image.convert_colorspace(from, colorspace).map_err(&rierr)?;
+    }
+
+    Ok(Some(ColorSpaceResult {
+        from: from,
+        to: colorspace,
+    }))
+}
+
+/// Hide the areas of an image (--redact) and the detected QR codes (--redact-qr).
+fn process_redact<C: Fn(RusimgError) -> ProcessingError, O: Fn(String) -> ProcessingError>(image: &mut RusImg, redact: &[TrimSpec], redact_qr: bool, style: RedactStyle, rierr: C, operr: O) -> Result<Option<RedactResult>, ProcessingError> {
+    let size = image.get_image_size().map_err(&rierr)?;
+    let mut areas = redact.iter().map(|area| area.resolve(size)).collect::<Vec<_>>();
+    if redact_qr {
+        areas.append(&mut qr::detect(image.as_dynamic_image()).map_err(&operr)?);
+    }
+
+    // 隠す領域がなければ画像はそのまま
+    if !areas.is_empty() {
+        let redacted = filters::redact(image.as_dynamic_image(), &areas, style);
+        image.set_dynamic_image(redacted).map_err(&rierr)?;
+    }
+
+    Ok(Some(RedactResult {
+        areas: areas.len(),
+    }))
+}
+
+/// Pixelate (--pixelate) and blur (--blur-region) the areas of an image.
+fn process_regions<C: Fn(RusimgError) -> ProcessingError>(image: &mut RusImg, pixelate: &[(TrimSpec, u32)], blur_regions: &[(TrimSpec, f32)], rierr: C) -> Result<Option<RegionsResult>, ProcessingError> {
+    let size = image.get_image_size().map_err(&rierr)?;
+    for (area, block_size) in pixelate {
+        image.pixelate_region(area.resolve(size), *block_size).map_err(&rierr)?;
+    }
+    for (area, sigma) in blur_regions {
+        image.blur_region(area.resolve(size), *sigma).map_err(&rierr)?;
+    }
+
+    Ok(Some(RegionsResult {
+        pixelated: pixelate.len(),
+        blurred: blur_regions.len(),
+    }))
+}
+
 /// Trim an image.
-fn process_trim<C: Fn(RusimgError) -> ProcessingError>(image: &mut RusImg, trim: librusimg::Rect, rierr: C) -> Result<Option<TrimResult>, ProcessingError> {
+/// The trim specification is resolved against the size of each image.
+fn process_trim<C: Fn(RusimgError) -> ProcessingError>(image: &mut RusImg, trim: TrimSpec, rierr: C) -> Result<Option<TrimResult>, ProcessingError> {
     // トリミング
     let before_size = image.get_image_size().map_err(&rierr)?;
-    let after_size = image.trim_rect(trim).map_err(&rierr)?;
+    let after_size = image.trim_rect(trim.resolve(before_size)).map_err(&rierr)?;
 
     Ok(Some(TrimResult {
         before_size: before_size,
@@ -380,33 +716,313 @@ fn process_trim<C: Fn(RusimgError) -> ProcessingError>(image: &mut RusImg, trim:
     }))
 }
 
+/// Crop the largest area with the aspect ratio (width, height) of an image.
+/// The crop is centered, or placed on the detected faces with Gravity::Faces.
+fn process_ratio_crop<C: Fn(RusimgError) -> ProcessingError, O: Fn(String) -> ProcessingError>(image: &mut RusImg, ratio: (u32, u32), gravity: Gravity, face_model: Option<&Path>, rierr: C, operr: O) -> Result<Option<TrimResult>, ProcessingError> {
+    let size = image.get_image_size().map_err(&rierr)?;
+    let crop = parse::crop_size(size, ratio);
+    let trim = match (gravity, face_model) {
+        (Gravity::Faces, Some(model)) => {
+            let faces = faces::detect(image.as_dynamic_image(), model).map_err(&operr)?;
+            let area = faces::place(size, crop, &faces);
+            TrimSpec::Pixels {
+                x: TrimOffset::Start(area.x),
+                y: TrimOffset::Start(area.y),
+                w: area.w,
+                h: area.h,
+            }
+        },
+        _ => TrimSpec::Pixels {
+            x: TrimOffset::Center,
+            y: TrimOffset::Center,
+            w: crop.width as u32,
+            h: crop.height as u32,
+        },
+    };
+    process_trim(image, trim, rierr)
+}
+
+/// Remove the uniform-color borders of an image.
+/// The detected area is passed to the trim operation.
+fn process_autocrop<C: Fn(RusimgError) -> ProcessingError>(image: &mut RusImg, tolerance: u8, rierr: C) -> Result<Option<AutocropResult>, ProcessingError> {
+    let before_size = image.get_image_size().map_err(&rierr)?;
+    let border = analysis::detect_border(image.as_dynamic_image(), tolerance);
+
+    // 余白が検出されなければ何もしない
+    let after_size = match border {
+        Some(rect) => image.trim_rect(rect).map_err(&rierr)?,
+        None => before_size,
+    };
+
+    Ok(Some(AutocropResult {
+        before_size: before_size,
+        after_size: after_size,
+    }))
+}
+
+/// Straighten a skewed image.
+/// The image is rotated around its center, and the uncovered corners are filled with the background color.
+fn process_deskew<C: Fn(RusimgError) -> ProcessingError>(image: &mut RusImg, rierr: C) -> Result<Option<DeskewResult>, ProcessingError> {
+    let angle = analysis::detect_skew_angle(image.as_dynamic_image());
+
+    // 傾きがなければ何もしない
+    if angle != 0.0 {
+        let dynamic_image = image.as_dynamic_image();
+        let fill = filters::background_color(dynamic_image);
+        let rotated = filters::rotate(dynamic_image, angle, fill);
+        image.set_dynamic_image(rotated).map_err(&rierr)?;
+    }
+
+    Ok(Some(DeskewResult {
+        angle: angle,
+    }))
+}
+
+/// Remove or add a vignette to an image.
+fn process_vignette<C: Fn(RusimgError) -> ProcessingError>(image: &mut RusImg, amount: f32, rierr: C) -> Result<Option<VignetteResult>, ProcessingError> {
+    let adjusted = filters::vignette(image.as_dynamic_image(), amount);
+    image.set_dynamic_image(adjusted).map_err(&rierr)?;
+
+    Ok(Some(VignetteResult {
+        amount: amount,
+    }))
+}
+
+/// Correct the white balance of an image.
+fn process_white_balance<C: Fn(RusimgError) -> ProcessingError>(image: &mut RusImg, white_balance: WhiteBalance, rierr: C) -> Result<Option<WhiteBalanceResult>, ProcessingError> {
+    let gains = filters::white_balance_gains(image.as_dynamic_image(), white_balance);
+    let corrected = filters::apply_gains(image.as_dynamic_image(), gains);
+    image.set_dynamic_image(corrected).map_err(&rierr)?;
+
+    Ok(Some(WhiteBalanceResult {
+        gains: gains,
+    }))
+}
+
+/// Stretch the histogram of each channel of an image.
+fn process_auto_levels<C: Fn(RusimgError) -> ProcessingError>(image: &mut RusImg, clip: f32, rierr: C) -> Result<Option<AutoLevelsResult>, ProcessingError> {
+    let adjusted = filters::auto_levels(image.as_dynamic_image(), clip);
+    image.set_dynamic_image(adjusted).map_err(&rierr)?;
+
+    Ok(Some(AutoLevelsResult {
+        clip: clip,
+    }))
+}
+
+/// Embed an invisible watermark into an image.
+fn process_watermark<C: Fn(RusimgError) -> ProcessingError, O: Fn(String) -> ProcessingError>(image: &mut RusImg, message: &str, key: &str, rierr: C, operr: O) -> Result<Option<WatermarkResult>, ProcessingError> {
+    let watermarked = watermark::embed(image.as_dynamic_image(), message, key).map_err(&operr)?;
+    image.set_dynamic_image(watermarked).map_err(&rierr)?;
+
+    Ok(Some(WatermarkResult {
+        message: message.to_string(),
+    }))
+}
+
+/// Reduce the bit depth of an image with dithering.
+/// Only png and bmp can store paletted images; the pixels are reduced here and the encoder writes the palette when saving.
+fn process_depth<C: Fn(RusimgError) -> ProcessingError, O: Fn(String) -> ProcessingError>(image: &mut RusImg, depth: u8, rierr: C, operr: O) -> Result<Option<DepthResult>, ProcessingError> {
+    if !matches!(image.extension, librusimg::Extension::Png | librusimg::Extension::Bmp) {
+        return Err(operr(format!("--depth is only supported for png and bmp output ({})", image.extension)));
+    }
+    let reduced = librusimg::quantize::quantize(image.as_dynamic_image(), 1 << depth, true);
+    image.set_dynamic_image(reduced).map_err(&rierr)?;
+
+    Ok(Some(DepthResult {
+        depth: depth,
+    }))
+}
+
+/// Resize an image.
+/// If the resize specification is an exact size (e.g. from a physical size and DPI), the DynamicImage is resized directly.
+/// A Fit specification (e.g. the screen of --device) shrinks the image to fit within the size, and leaves smaller images as they are.
+/// Upscaling is done with the filter specified by --upscale-filter, and only if --allow-upscale is specified.
+fn process_resize<C: Fn(RusimgError) -> ProcessingError, O: Fn(String) -> ProcessingError>(image: &mut RusImg, resize: ResizeSpec, allow_upscale: bool, upscale_filter: UpscaleFilter, rierr: C, operr: O) -> Result<Option<ResizeResult>, ProcessingError> {
+    let before_size = image.get_image_size().map_err(&rierr)?;
+    let (width, height) = match resize {
+        ResizeSpec::Ratio(ratio) => ((before_size.width as f32 * (ratio / 100.0)) as u32, (before_size.height as f32 * (ratio / 100.0)) as u32),
+        ResizeSpec::Pixels { width, height } => (width, height),
+        ResizeSpec::Fit { width, height } => {
+            let scale = (width as f32 / before_size.width as f32).min(height as f32 / before_size.height as f32).min(1.0);
+            (((before_size.width as f32 * scale).round() as u32).max(1), ((before_size.height as f32 * scale).round() as u32).max(1))
+        },
+    };
+    let upscale = !librusimg::ImgSize::new(width as usize, height as usize).fits_within(before_size);
+    if upscale && !allow_upscale {
+        return Err(operr(format!("Resizing {}x{} -> {}x{} enlarges the image. Specify --allow-upscale to upscale.", before_size.width, before_size.height, width, height)));
+    }
+
+    let after_size = match resize {
+        ResizeSpec::Ratio(ratio) if !upscale => image.resize(ratio).map_err(&rierr)?,
+        _ => {
+            let dynamic_image = image.as_dynamic_image();
+            let resized = if upscale {
+                filters::upscale(dynamic_image, width, height, upscale_filter)
+            }
+            else {
+                dynamic_image.resize_exact(width, height, image::imageops::FilterType::Lanczos3)
+            };
+            image.set_dynamic_image(resized).map_err(&rierr)?;
+            image.get_image_size().map_err(&rierr)?
+        },
+    };
+
+    Ok(Some(ResizeResult {
+        before_size: before_size,
+        after_size: after_size,
+    }))
+}
+
+/// Build the options for saving from the arguments.
+fn build_save_options(args: &ArgStruct) -> SaveOptions {
+    SaveOptions {
+        progressive: args.progressive,
+        // --depth: oxipng (at least level 1) reduces a png to the palette and bit depth of its colors
+        effort: args.effort.or(args.depth.map(|_| 0)),
+        encoder_threads: args.encoder_threads,
+        bmp: BmpOptions {
+            format: match args.depth {
+                Some(8) => BmpFormat::Indexed8,
+                Some(4) => BmpFormat::Indexed4,
+                Some(1) => BmpFormat::Mono1,
+                _ => BmpFormat::Rgb24,
+            },
+            ..Default::default()
+        },
+        png: PngOptions {
+            interlace: args.interlace,
+        },
+        ..Default::default()
+    }
+}
+
 /// Process the image in a thread.
 async fn process(thread_task: ThreadTask, file_io_lock: Arc<Mutex<i32>>) -> Result<ProcessResult, ProcessingError> {
     let args = thread_task.args;
     let image_file_path = thread_task.input_path;
     let output_file_path = thread_task.output_path;
     let ask_result = thread_task.ask_result;
+    let mut budget = thread_task.budget;
+    let save_options = build_save_options(&args);
 
-    let rierr = |e: RusimgError| ProcessingError::RusimgError(ErrorStruct { error: e, filepath: image_file_path.to_str().unwrap().to_string() });
-    let ioerr = |e: std::io::Error| ProcessingError::IOError(ErrorStruct { error: e, filepath: image_file_path.to_str().unwrap().to_string() });
+    let rierr = |e: RusimgError| ProcessingError::RusimgError(ErrorStruct { error: e, filepath: image_file_path.clone() });
+    let ioerr = |e: std::io::Error| ProcessingError::IOError(ErrorStruct { error: e, filepath: image_file_path.clone() });
+    let operr = |e: String| ProcessingError::OperationError(ErrorStruct { error: e, filepath: image_file_path.clone() });
 
     // Open the image
-    let mut image = librusimg::open_image(&image_file_path).map_err(rierr)?;
+    // --mmap -> Map large files into memory (falls back to reading if mapping is not available).
+    let open_options = OpenOptions {
+        mmap: args.mmap,
+        ..Default::default()
+    };
+    // HDR sources (exr, hdr) -> Tone map to 8-bit with --tonemap and --exposure.
+    let mut image = if hdr::is_hdr(&image_file_path) {
+        hdr::open(&image_file_path, args.tonemap, args.exposure).map_err(rierr)?
+    }
+    // Multi-page sources (tiff, pdf) -> Open the page of the task.
+    else if let Some(page) = thread_task.page {
+        pages::open(&image_file_path, page).map_err(rierr)?
+    }
+    else {
+        librusimg::open_image_with(&image_file_path, &open_options).map_err(rierr)?
+    };
+
+    // --skip-blank -> Skip the image if it is blank.
+    if let Some(threshold) = args.skip_blank {
+        if image.stats().luma.stddev < threshold {
+            return Ok(ProcessResult {
+                viuer_image: None,
+                convert_result: None,
+                colorspace_result: None,
+                redact_result: None,
+                regions_result: None,
+                trim_result: None,
+                autocrop_result: None,
+                deskew_result: None,
+                resize_result: None,
+                vignette_result: None,
+                white_balance_result: None,
+                auto_levels_result: None,
+                gamma_result: None,
+                curves_result: None,
+                lut_result: None,
+                grayscale_result: None,
+                edges_result: None,
+                threshold_result: None,
+                watermark_result: None,
+                depth_result: None,
+                compress_result: None,
+                save_result: SaveResult {
+                    status: RusimgStatus::SkippedBlank,
+                    input_path: image.get_input_filepath(),
+                    output_path: None,
+                    before_filesize: 0,
+                    after_filesize: None,
+                    elapsed: None,
+                    delete: false,
+                    dpi: None,
+                    checksum: None,
+                    warnings: image.warnings(),
+                },
+            });
+        }
+    }
+
+    // --convert auto -> Save as png if the decoded image is line art or text, and as jpeg if it is a photo.
+    let (output_file_path, extension, ask_result) = match thread_task.line_art_output {
+        Some(line_art_output) if analysis::is_line_art(image.as_dynamic_image()) => (Some(line_art_output.path), Some(line_art_output.extension), line_art_output.ask_result),
+        _ => (output_file_path, thread_task.extension, ask_result),
+    };
 
     // Is saving the image required? (default: false)
-    let mut save_required = false;
+    // HDR sources are always saved, because they are converted to 8-bit, and so are the pages of multi-page sources.
+    let mut save_required = hdr::is_hdr(&image_file_path) || thread_task.page.is_some();
 
     // --convert -> Convert the image.
     let convert_result = if let Some(_c) = args.destination_extension {
         save_required = true;
-        process_convert(&thread_task.extension, &mut image, rierr)?
+        process_convert(&extension, &mut image, rierr)?
+    }
+    else {
+        None
+    };
+
+    // --colorspace -> Convert the colors from the color space of the source.
+    // This is done first, so that the other operations work on the colors of the output.
+    let colorspace_result = if let Some(colorspace) = args.colorspace {
+        let result = process_colorspace(&mut image, &image_file_path, colorspace, rierr)?;
+        // 変換しなかった場合は保存不要
+        if matches!(&result, Some(ColorSpaceResult { from: Some(from), to }) if from != to) {
+            save_required = true;
+        }
+        result
+    }
+    else {
+        None
+    };
+
+    // --redact, --redact-qr -> Hide the areas.
+    // This is done first, so that the areas are in the coordinates of the source image and nothing is left in the trimmed outputs.
+    let redact_result = if !args.redact.is_empty() || args.redact_qr {
+        save_required = true;
+        process_redact(&mut image, &args.redact, args.redact_qr, args.redact_style, rierr, operr)?
     }
     else {
         None
     };
 
-    // --trim -> Trim the image.
-    let trim_result = if let Some(trim) = args.trim {
+    // --pixelate, --blur-region -> Pixelate or blur the areas.
+    // Like redaction, this is done on the source image.
+    let regions_result = if !args.pixelate.is_empty() || !args.blur_regions.is_empty() {
+        save_required = true;
+        process_regions(&mut image, &args.pixelate, &args.blur_regions, rierr)?
+    }
+    else {
+        None
+    };
+
+    // --trim, --trim-file -> Trim the image.
+    let trim_result = if let Some(trim) = thread_task.trim {
         save_required = true;
         process_trim(&mut image, trim, rierr)?
     }
@@ -414,15 +1030,112 @@ async fn process(thread_task: ThreadTask, file_io_lock: Arc<Mutex<i32>>) -> Resu
         None
     };
 
+    // --deskew -> Straighten the image.
+    // Deskew is done before autocrop so that the straightened borders can be removed.
+    let deskew_result = if args.deskew {
+        save_required = true;
+        process_deskew(&mut image, rierr)?
+    }
+    else {
+        None
+    };
+
+    // --autocrop -> Remove the uniform-color borders.
+    let autocrop_result = if let Some(tolerance) = args.autocrop {
+        save_required = true;
+        process_autocrop(&mut image, tolerance, rierr)?
+    }
+    else {
+        None
+    };
+
+    // --social -> Crop to the aspect ratio of the preset.
+    // --crop-ratio -> Crop the largest area with the aspect ratio.
+    // The crop is done after autocrop, so that the removed borders do not change the aspect ratio.
+    let ratio = args.social.map(|social| social.size()).or(args.crop_ratio);
+    let trim_result = if let Some(ratio) = ratio {
+        save_required = true;
+        process_ratio_crop(&mut image, ratio, args.gravity, args.face_model.as_deref(), rierr, operr)?
+    }
+    else {
+        trim_result
+    };
+
     // --resize -> Resize the image.
-    let resize_result = if let Some(resize) = args.resize {
-        let before_size = image.get_image_size().map_err(rierr)?;
-        let after_size = image.resize(resize).map_err(rierr)?;
+    // --social -> Resize to the exact size of the preset.
+    let resize = args.social.map(|social| social.resize()).or(args.resize);
+    let resize_result = if let Some(resize) = resize {
+        save_required = true;
+        process_resize(&mut image, resize, args.allow_upscale, args.upscale_filter, rierr, operr)?
+    }
+    else {
+        None
+    };
+
+    // --vignette -> Remove or add a vignette.
+    // Vignetting is corrected before the color corrections so that the corners do not skew the statistics.
+    let vignette_result = if let Some(amount) = args.vignette {
+        save_required = true;
+        process_vignette(&mut image, amount, rierr)?
+    }
+    else {
+        None
+    };
+
+    // --wb -> Correct the white balance.
+    // White balance is corrected before auto levels, because auto levels stretches each channel independently.
+    let white_balance_result = if let Some(white_balance) = args.white_balance {
         save_required = true;
+        process_white_balance(&mut image, white_balance, rierr)?
+    }
+    else {
+        None
+    };
 
-        Some(ResizeResult {
-            before_size: before_size,
-            after_size: after_size,
+    // --auto-levels -> Stretch the histogram of each channel.
+    let auto_levels_result = if let Some(clip) = args.auto_levels {
+        save_required = true;
+        process_auto_levels(&mut image, clip, rierr)?
+    }
+    else {
+        None
+    };
+
+    // --gamma -> Brighten or darken the midtones.
+    let gamma_result = if let Some(gamma) = args.gamma {
+        image.apply_gamma(gamma).map_err(rierr)?;
+        save_required = true;
+        Some(GammaResult {
+            gamma: gamma,
+        })
+    }
+    else {
+        None
+    };
+
+    // --curve -> Apply the tonal curves.
+    let curves_result = if let Some(curves) = &args.curves {
+        image.apply_curves(curves).map_err(rierr)?;
+        save_required = true;
+
+        let identity = Curve::default();
+        let channels = [("RGB", &curves.rgb), ("R", &curves.red), ("G", &curves.green), ("B", &curves.blue)];
+        Some(CurvesResult {
+            channels: channels.iter().filter(|(_, curve)| **curve != identity).map(|(name, _)| *name).collect(),
+        })
+    }
+    else {
+        None
+    };
+
+    // --lut -> Apply the 3D LUT.
+    // It is applied after the corrections, as the LUTs for color grading expect neutral images.
+    let lut_result = if let Some(lut) = &args.lut {
+        image.apply_lut(lut).map_err(rierr)?;
+        save_required = true;
+
+        Some(LutResult {
+            size: lut.size,
         })
     }
     else {
@@ -442,31 +1155,125 @@ async fn process(thread_task: ThreadTask, file_io_lock: Arc<Mutex<i32>>) -> Resu
         None
     };
 
+    // --edges -> Replace the image with its edges.
+    let edges_result = if args.edges {
+        image.detect_edges().map_err(rierr)?;
+        save_required = true;
+
+        Some(EdgesResult {
+            status: true,
+        })
+    }
+    else {
+        None
+    };
+
+    // --threshold -> Binarize the image.
+    // This is done after the edge detection, so that the edges are binarized to lines.
+    let threshold_result = if let Some(threshold) = args.threshold {
+        let level = image.threshold(threshold).map_err(rierr)?;
+        save_required = true;
+
+        Some(ThresholdResult {
+            method: threshold,
+            level: level,
+        })
+    }
+    else {
+        None
+    };
+
+    // --watermark -> Embed the invisible watermark.
+    // This is done after the other pixel operations, which would destroy it, and before the bit depth reduction.
+    let watermark_result = if let Some((message, key)) = &args.watermark {
+        save_required = true;
+        process_watermark(&mut image, message, key, rierr, operr)?
+    }
+    else {
+        None
+    };
+
+    // --depth -> Reduce the bit depth.
+    // This is done after all pixel operations, so that the palette is built from the final image.
+    let depth_result = if let Some(depth) = args.depth {
+        save_required = true;
+        process_depth(&mut image, depth, rierr, operr)?
+    }
+    else {
+        None
+    };
+
+    // --total-budget -> The share of the budget reserved for this task.
+    let budget_target = budget.as_ref().map(|reservation| reservation.target);
+
+    // --total-budget -> Compress with the highest quality that fits in the share of the budget (jpeg, webp).
     // --quality -> Compress the image.
-    let compress_result = if let Some(q) = args.quality {
+    let compress_result = if let (Some(target), true) = (budget_target, budget::supports(&image.extension)) {
+        let quality = budget::search_quality(&image, target, args.quality.unwrap_or(budget::MAX_QUALITY), &save_options).map_err(rierr)?;
+        image.compress(Some(quality)).map_err(rierr)?;
+        save_required = true;
+
+        Some(CompressResult {
+            status: true,
+            budget: Some((quality, target)),
+            search: None,
+        })
+    }
+    // --quality-search -> Compress with the lowest quality whose SSIM meets the threshold (jpeg, webp).
+    else if let (Some(threshold), true) = (args.quality_search, budget::supports(&image.extension)) {
+        let (quality, ssim) = budget::search_ssim(&image, threshold, args.quality.unwrap_or(budget::MAX_QUALITY), &save_options).map_err(rierr)?;
+        image.compress(Some(quality)).map_err(rierr)?;
+        save_required = true;
+
+        Some(CompressResult {
+            status: true,
+            budget: None,
+            search: Some((quality, ssim)),
+        })
+    }
+    else if let Some(q) = args.quality {
         image.compress(Some(q)).map_err(rierr)?;
         save_required = true;
 
         Some(CompressResult {
             status: true,
+            budget: None,
+            search: None,
         })
     }
     else {
         None
     };
 
+    // --dpi, --progressive, --effort, --interlace -> These options are applied when saving.
+    if args.dpi.is_some() || args.progressive || args.effort.is_some() || args.interlace {
+        save_required = true;
+    }
+
     // --view -> View the image in the terminal.
     // Viuer will be called after all processing is complete.
     // So, store the image data in memory.
+    // The terminal cannot show more pixels than this, so large images are stored as a thumbnail instead of a full copy.
     let viuer_image = if args.view {
-        Some(image.get_dynamic_image().map_err(rierr)?)
+        let dynamic_image = image.as_dynamic_image();
+        if dynamic_image.width() > VIEW_MAX_SIZE || dynamic_image.height() > VIEW_MAX_SIZE {
+            Some(dynamic_image.thumbnail(VIEW_MAX_SIZE, VIEW_MAX_SIZE))
+        }
+        else {
+            Some(dynamic_image.clone())
+        }
     }
     else {
         None
     };
 
     // Save the image if necessary.
-    let save_status = if save_required == true {
+    let save_status = if save_required {
+        // Without --in-place, the source is not overwritten implicitly.
+        if output_file_path.is_none() && !args.in_place {
+            return Err(operr(messages::text(Msg::InPlaceRequired).to_string()));
+        }
+
         // Check if the file exists and ask if it should be overwritten.
         match ask_result {
             AskResult::Overwrite => {
@@ -477,9 +1284,24 @@ async fn process(thread_task: ThreadTask, file_io_lock: Arc<Mutex<i32>>) -> Resu
                 return Ok(ProcessResult {
                     viuer_image: viuer_image,
                     convert_result: convert_result,
+                    colorspace_result: colorspace_result,
+                    redact_result: redact_result,
+                    regions_result: regions_result,
                     trim_result: trim_result,
+                    autocrop_result: autocrop_result,
+                    deskew_result: deskew_result,
                     resize_result: resize_result,
+                    vignette_result: vignette_result,
+                    white_balance_result: white_balance_result,
+                    auto_levels_result: auto_levels_result,
+                    gamma_result: gamma_result,
+                    curves_result: curves_result,
+                    lut_result: lut_result,
                     grayscale_result: grayscale_result,
+                    edges_result: edges_result,
+                    threshold_result: threshold_result,
+                    watermark_result: watermark_result,
+                    depth_result: depth_result,
                     compress_result: compress_result,
                     save_result: SaveResult {
                         status: RusimgStatus::Cancel,
@@ -487,7 +1309,11 @@ async fn process(thread_task: ThreadTask, file_io_lock: Arc<Mutex<i32>>) -> Resu
                         output_path: None,
                         before_filesize: 0,
                         after_filesize: None,
+                        elapsed: None,
                         delete: false,
+                        dpi: None,
+                        checksum: None,
+                        warnings: image.warnings(),
                     },
                 });
             },
@@ -496,17 +1322,88 @@ async fn process(thread_task: ThreadTask, file_io_lock: Arc<Mutex<i32>>) -> Resu
             },
         }
 
-        // Get the output path
-        let output_path = output_file_path.unwrap();
+        // --preserve-xattrs -> Read the attributes of the source before it is overwritten (saving in place) or deleted (--delete).
+        // HDR sources and the pages of multi-page sources are opened from a temporary png, so the attributes are read from the original file.
+        let source_attributes = if args.preserve_xattrs {
+            Some(xattrs::FileAttributes::read(&image_file_path).map_err(ioerr)?)
+        }
+        else {
+            None
+        };
+
+        // --in-place -> Save to a temporary file next to the source, and replace the source with it after the metadata is written.
+        // The timestamps and the permissions of the source are read before it is replaced.
+        let in_place_source = if output_file_path.is_none() {
+            Some(fs::metadata(&image_file_path).map_err(ioerr)?)
+        }
+        else {
+            None
+        };
+        let output_path = output_file_path.clone().unwrap_or_else(|| inplace::temp_path(&image_file_path));
 
         // Save the image
         // Saving images at the same time can be a heavy load, so we need to lock the file I/O.
         // *lock is used to lock the file I/O.
-        let save_status = {
+        let mut save_status = {
             let mut lock = file_io_lock.lock().unwrap();
             *lock += 1;
-            let ret = image.save_image(output_path.to_str()).map_err(rierr)?;
-            ret
+            // --output, --organize-by-date, --out-per-format, --flatten -> Create the output directories.
+            if let Some(parent) = output_path.parent().filter(|p| !p.as_os_str().is_empty()) {
+                fs::create_dir_all(parent).map_err(ioerr)?;
+            }
+            // save_image_with() takes the path as &str, so a non-UTF-8 path is an error rather than silently falling back to the default path.
+            let output_path_str = output_path.to_str().ok_or(RusimgError::FailedToConvertPathToString).map_err(rierr)?;
+            let ret = image.save_image_with(Some(output_path_str), &save_options);
+            // 保存に失敗した場合は一時ファイルを残さない
+            if ret.is_err() && in_place_source.is_some() {
+                let _ = fs::remove_file(&output_path);
+            }
+            ret.map_err(rierr)?
+        };
+
+        let mut warnings = save_status.warnings.clone();
+
+        // --dpi -> Set the density metadata to the output file.
+        let dpi = match (args.dpi, &save_status.output_path) {
+            (Some(dpi), Some(saved_filepath)) => {
+                let extension = get_extension(saved_filepath).map_err(rierr)?;
+                if density::set_density(saved_filepath, &extension, dpi).map_err(ioerr)? {
+                    Some(dpi)
+                }
+                else {
+                    // webp には密度のメタデータがない
+                    warnings.push(Warning::MetadataDropped("density".to_string()));
+                    None
+                }
+            },
+            _ => None,
+        };
+
+        // --preserve-xattrs -> Copy the attributes of the source to the output file.
+        if let (Some(attributes), Some(saved_filepath)) = (&source_attributes, &save_status.output_path) {
+            for name in attributes.apply(saved_filepath) {
+                warnings.push(Warning::MetadataDropped(format!("extended attribute {}", name)));
+            }
+        }
+
+        // --in-place -> Replace the source with the saved file.
+        if let (Some(metadata), Some(saved_filepath)) = (&in_place_source, &save_status.output_path) {
+            inplace::replace(saved_filepath, &image_file_path, metadata).map_err(ioerr)?;
+            save_status.output_path = Some(image_file_path.clone());
+        }
+
+        // --checksum -> Compute the checksum of the output file.
+        // The written file is hashed rather than the encoded buffer, because --dpi rewrites the header after saving.
+        let checksum = match (args.checksum, &save_status.output_path) {
+            (Some(algorithm), Some(saved_filepath)) => {
+                let checksum = checksum::file_checksum(saved_filepath, algorithm).map_err(ioerr)?;
+                // --checksum-manifest -> The checksums are written to one file after all images are processed.
+                if args.checksum_manifest.is_none() {
+                    checksum::write_sidecar(saved_filepath, &checksum, algorithm).map_err(ioerr)?;
+                }
+                Some(checksum)
+            },
+            _ => None,
         };
 
         // --delete -> Delete the original file. 
@@ -530,7 +1427,11 @@ async fn process(thread_task: ThreadTask, file_io_lock: Arc<Mutex<i32>>) -> Resu
             output_path: save_status.output_path,
             before_filesize: save_status.before_filesize,
             after_filesize: save_status.after_filesize,
+            elapsed: save_status.elapsed,
             delete: delete,
+            dpi: dpi,
+            checksum: checksum,
+            warnings: warnings,
         }
     }
     else {
@@ -541,17 +1442,42 @@ async fn process(thread_task: ThreadTask, file_io_lock: Arc<Mutex<i32>>) -> Resu
             output_path: None,
             before_filesize: 0,
             after_filesize: None,
+            elapsed: None,
             delete: false,
+            dpi: None,
+            checksum: None,
+            warnings: image.warnings(),
         }
     };
 
+    // --total-budget -> Settle the share with the size of the saved file. The unused part goes back to the budget when the reservation is dropped.
+    if let (Some(reservation), Some(after_filesize)) = (budget.as_mut(), save_status.after_filesize) {
+        reservation.set_written(after_filesize);
+    }
+    drop(budget);
+
     // Return the processing result.
     let thread_results = ProcessResult {
         viuer_image: viuer_image,
         convert_result: convert_result,
+        colorspace_result: colorspace_result,
+        redact_result: redact_result,
+        regions_result: regions_result,
         trim_result: trim_result,
+        autocrop_result: autocrop_result,
+        deskew_result: deskew_result,
         resize_result: resize_result,
+        vignette_result: vignette_result,
+        white_balance_result: white_balance_result,
+        auto_levels_result: auto_levels_result,
+        gamma_result: gamma_result,
+        curves_result: curves_result,
+        lut_result: lut_result,
         grayscale_result: grayscale_result,
+        edges_result: edges_result,
+        threshold_result: threshold_result,
+        watermark_result: watermark_result,
+        depth_result: depth_result,
         compress_result: compress_result,
         save_result: save_status,
     };
@@ -563,6 +1489,131 @@ async fn main() -> Result<(), String> {
     // Parse the arguments.
     let args = parse::parser().map_err(|e| e.to_string())?;
 
+    // --lang -> Set the language of the messages.
+    messages::init(args.lang.as_deref());
+
+    let notify = args.notify;
+    let start = Instant::now();
+    let result = run(args).await;
+
+    // --notify -> Send a desktop notification when the batch completes or fails.
+    if notify {
+        match &result {
+            Ok(Some(summary)) => notify::finished(summary, start.elapsed()),
+            Ok(None) => {},
+            Err(e) => notify::failed(e),
+        }
+    }
+    result.map(|_| ())
+}
+
+/// Process the pages of comic archives and write them to CBZ archives.
+/// The pages of each archive are processed as a batch by run(), and put back with the names and in the order of the source archive.
+async fn run_comics(args: &ArgStruct, archives: &[PathBuf], file_overwrite_ask: &FileOverwriteAsk) -> Result<Summary, String> {
+    let mut summary = Summary {
+        processed: 0,
+        failed: 0,
+    };
+    for archive in archives {
+        let output_path = comic::output_path(archive, args.destination_path.as_deref(), args.destination_append_name.as_deref());
+        let overwrite = match check_file_exists(&output_path, file_overwrite_ask) {
+            ExistsCheckResult::AllOverwrite | ExistsCheckResult::NoProblem => true,
+            ExistsCheckResult::AllSkip => false,
+            ExistsCheckResult::NeedToAsk => ask_file_exists(),
+        };
+        if !overwrite {
+            continue;
+        }
+
+        let comic = match comic::Comic::open(archive) {
+            Ok(comic) => comic,
+            Err(e) => {
+                println!("{}: {}", messages::text(Msg::Error).red(), e);
+                summary.failed += 1;
+                continue;
+            },
+        };
+
+        // ページは一時ディレクトリ内で処理するので、出力先やバッチ全体に関わるオプションは外す
+        let mut page_args = args.clone();
+        page_args.souce_path = Some(vec![comic.pages_dir.clone()]);
+        page_args.destination_path = Some(comic.output_dir.clone());
+        page_args.destination_append_name = None;
+        page_args.sequence = None;
+        page_args.organize_by_date = None;
+        page_args.out_per_format = None;
+        page_args.flatten = false;
+        page_args.recursive = false;
+        page_args.sort = SortOrder::Name;
+        page_args.limit = None;
+        page_args.sample = None;
+        page_args.interactive = false;
+        page_args.delete = false;
+        page_args.in_place = false;
+        page_args.checksum = None;
+        page_args.checksum_manifest = None;
+        page_args.zip = None;
+        page_args.pdf = None;
+        page_args.picture_manifest = None;
+        page_args.open = None;
+        page_args.yes = true;
+        page_args.no = false;
+        if let Some(pages) = Box::pin(run(page_args)).await? {
+            summary.processed += pages.processed;
+            summary.failed += pages.failed;
+        }
+
+        let replaced = comic.save(&output_path)?;
+        println!("{}", messages::format(Msg::ComicWritten, &[&replaced, &output_path.display()]).green().bold());
+    }
+    Ok(summary)
+}
+
+/// Process the images with the arguments.
+/// Returns the summary of the batch, or None if no images were processed (e.g. --formats, subcommands or canceled).
+async fn run(mut args: ArgStruct) -> Result<Option<Summary>, String> {
+
+    // --view-size, --view-protocol -> Set the configuration of the terminal preview.
+    viewer::init(args.view_size, args.view_protocol);
+
+    // --temp-dir -> Put the intermediate files in the directory.
+    if let Some(temp_dir) = &args.temp_dir {
+        librusimg::tempdir::set_location(Some(temp_dir.clone()));
+    }
+
+    // --version (--verbose) -> Show the version (and the build information) and exit.
+    if args.version {
+        info::print_version(args.verbose);
+        return Ok(None);
+    }
+
+    // --formats -> Show the supported formats and exit.
+    if args.formats {
+        info::print_formats();
+        return Ok(None);
+    }
+
+    // Subcommands -> Run the subcommand and exit.
+    match &args.mode {
+        // bench (hidden subcommand) -> Measure the processing speed.
+        Mode::Bench(bench_options) => return bench::run(bench_options).map(|_| None),
+        // preview-quality -> Write the comparison sheet of the qualities.
+        Mode::PreviewQuality(preview_options) => return preview::run(preview_options).map(|_| None),
+        // install-shell-integration -> Register (or remove) the context-menu entries.
+        Mode::ShellIntegration(action) => return shell::run(*action).map(|_| None),
+        // appicon -> Generate the icon set.
+        Mode::Appicon(appicon_options) => return appicon::run(appicon_options).map(|_| None),
+        // split-scan -> Save the photos on the scans.
+        Mode::SplitScan(split_options) => return split::run(split_options).map(|_| None),
+        // verify-watermark -> Read the watermarks.
+        Mode::VerifyWatermark(verify_options) => return watermark::run(verify_options).map(|_| None),
+        // to-gif -> Assemble the frames into an animation.
+        Mode::ToGif(to_gif_options) => return animation::run(to_gif_options).map(|_| None),
+        // check -> Decode the images to find corrupt files.
+        Mode::Check(check_options) => return check::run(check_options).map(|_| None),
+        Mode::Process => {},
+    }
+
     // Number of threads.
     let threads = args.threads;
 
@@ -580,85 +1631,272 @@ async fn main() -> Result<(), String> {
         FileOverwriteAsk::AskEverytime
     };
 
-    // Specify the source path.
-    // Default: current directory
-    let source_paths = args.souce_path.clone().or(Some(vec![PathBuf::from(".")])).unwrap();
+    // .cbz sources -> Process the pages of each comic archive and write them to a new archive.
+    if let Some(sources) = args.souce_path.as_ref().filter(|sources| sources.iter().any(|p| comic::is_comic(p))) {
+        if !sources.iter().all(|p| comic::is_comic(p)) {
+            return Err("Comic archives (.cbz) cannot be processed together with images. Process them in separate commands.".to_string());
+        }
+        return run_comics(&args, sources, &file_overwrite_ask).await.map(Some);
+    }
+
+    let mut image_files = Vec::new();
+    // Options for each file of the mapping file (same order as image_files).
+    let mut job_args = Vec::new();
+    if let Some(map_path) = &args.map {
+        // --map -> The files and their options are listed in the mapping file.
+        // The files are processed in the order of the rows, so --sort is not applied.
+        for job in jobs::load(map_path)? {
+            let job_arg = parse::parser_with(&job.args).map_err(|e| format!("{}: {}", job.path.display(), e))?;
+            image_files.push(job.path);
+            job_args.push(job_arg);
+        }
+    }
+    else {
+        match &args.files_from {
+            // --files-from -> Read the list of files (-0: NUL-separated).
+            Some(list_path) => image_files = read_file_list(list_path, args.null)?,
+            None => {
+                // Specify the source path.
+                // Default: current directory
+                let source_paths = args.souce_path.clone().unwrap_or(vec![PathBuf::from(".")]);
+                for source_path in source_paths {
+                    let mut image_files_list = if source_path.is_dir() {
+                        get_files_in_dir(&source_path, args.recursive, !args.no_ignore)?
+                    }
+                    else {
+                        get_files_by_wildcard(&source_path)?
+                    };
+                    image_files.append(&mut image_files_list);
+                }
+            },
+        }
+
+        // --sort -> Sort the files.
+        // --sequence -> Output files are numbered in sorted order (by name if --sort is not specified).
+        // --total-budget -> The largest files are processed first.
+        let sort = if args.total_budget.is_some() {
+            SortOrder::Size
+        }
+        else if args.sequence.is_some() && args.sort == SortOrder::None {
+            SortOrder::Name
+        }
+        else {
+            args.sort
+        };
+        sort_files(&mut image_files, sort);
+    }
+
+    // --limit, --sample -> Process only some of the files.
+    if args.limit.is_some() || args.sample.is_some() {
+        let selected = select_files(image_files.len(), args.limit, args.sample, args.seed);
+        image_files = selected.iter().map(|i| image_files[*i].clone()).collect();
+        if !job_args.is_empty() {
+            job_args = selected.iter().map(|i| job_args[*i].clone()).collect();
+        }
+    }
+
+    // --interactive -> Select the images and adjust the quality before processing.
+    if args.interactive {
+        match interactive::run(image_files, args.quality) {
+            Some(result) => {
+                image_files = result.files;
+                args.quality = result.quality;
+            },
+            None => {
+                println!("{}", messages::text(Msg::Canceled).yellow().bold());
+                return Ok(None);
+            },
+        }
+    }
+
     let mut thread_tasks = Vec::new();
-    for source_path in source_paths {
-        let image_files_list = if source_path.is_dir() {
-            get_files_in_dir(&source_path, args.recursive)?
+    let mut flattened_names = flatten::Names::default();
+    for (index, image_file) in image_files.into_iter().enumerate() {
+        // --map -> Use the options of the row instead of the global options.
+        let args = job_args.get(index).cloned().unwrap_or_else(|| args.clone());
+        // Multi-page sources (tiff, pdf) -> One task per page selected by --pages. The outputs of sources with multiple pages are numbered.
+        let (page_numbers, numbered_pages) = if pages::is_multi_page(&image_file) {
+            let (selected, count) = match pages::select(&image_file, args.pages.as_ref()) {
+                Ok(pages) => pages,
+                Err(e) => {
+                    println!("{}: {}", messages::text(Msg::Error).red(), e);
+                    continue;
+                },
+            };
+            if selected.is_empty() {
+                println!("{}", messages::format(Msg::NoPagesSelected, &[&image_file.display()]).yellow());
+                continue;
+            }
+            (selected.into_iter().map(Some).collect::<Vec<_>>(), count > 1)
         }
         else {
-            get_files_by_wildcard(&source_path)?
+            (vec![None], false)
         };
-        for image_file in image_files_list {
-            let thread_task = if let Some(extension_str) = &args.destination_extension {
-                // Determine the output path.
-                let extension = convert_str_to_extension(&extension_str.clone());
-                let extension = match extension {
-                    Ok(e) => e,
-                    Err(e) => {
-                        println!("{}: {}", "Error".red(), e.to_string());
-                        continue;
-                    },
+        // --trim, --trim-file -> Multiple trim areas make one task per area, and the outputs are numbered.
+        let areas = if args.trims.is_empty() { vec![None] } else { args.trims.iter().copied().map(Some).collect() };
+        let parts = page_numbers.iter().flat_map(|page| areas.iter().enumerate().map(move |(area_index, trim)| (*page, area_index, *trim))).collect::<Vec<_>>();
+        'parts: for (page, area_index, trim) in parts {
+            let mut naming = OutputNaming::from_args(&args);
+            if numbered_pages {
+                naming.page = page;
+            }
+            if args.trims.len() > 1 {
+                naming.region = Some(area_index + 1);
+            }
+            // --in-place -> Overwrite the source, unless its format would change.
+            // --out-extension-case lower renames a source with an uppercase extension, so it is not saved in place.
+            // HDR sources and the pages of multi-page sources are saved as new files, because they are opened from a temporary png.
+            let in_place = if args.in_place {
+                if !get_extension(&image_file).map_or(true, |source| inplace::keeps_format(&image_file, &source, args.destination_extension.as_deref())) {
+                    println!("{}", messages::format(Msg::InPlaceFormatChange, &[&image_file.display()]).yellow());
+                    break;
+                }
+                true
+            }
+            else {
+                args.destination_extension.is_none() && naming.is_in_place() && !naming.extension_case.renames(&image_file) && !hdr::is_hdr(&image_file) && page.is_none()
+            };
+            let thread_task = if !in_place {
+                // --convert auto -> The format is chosen by process() from the decoded image: png for line art and text, jpeg for photos.
+                // The outputs of both formats are determined here, so that overwriting is asked before the tasks are started.
+                let formats = if args.destination_extension.as_deref() == Some(parse::AUTO_FORMAT) {
+                    vec![Some("jpeg"), Some("png")]
+                }
+                else {
+                    vec![args.destination_extension.as_deref()]
                 };
-                let output_path = get_output_path(&args, &image_file, &extension);
-
-                // If the output file already exists, check if it should be overwritten.
-                let ask_result = match check_file_exists(&output_path, &file_overwrite_ask) {
-                    // Print the result of checking if the file exists.
-                    ExistsCheckResult::AllOverwrite => {
-                        println!("{}", " => Overwrite (default: yes)".bold());
-                        AskResult::Overwrite
-                    },
-                    ExistsCheckResult::AllSkip => {
-                        println!("{}", " => Skip (default: no)".bold());
-                        AskResult::Skip
-                    },
-                    ExistsCheckResult::NeedToAsk => {
-                        // If the file exists, ask if it should be overwritten.
-                        if ask_file_exists() {
-                            AskResult::Overwrite
+                let mut outputs = Vec::new();
+                for format in formats {
+                    // Determine the output path.
+                    // The extension is the one of --convert, or the one of the source.
+                    let extensions = get_extension(&image_file).and_then(|source| {
+                        let extension = match format {
+                            Some(extension_str) => librusimg::Extension::from_extension(extension_str)?,
+                            None => source.clone(),
+                        };
+                        Ok((source, extension))
+                    });
+                    let (source_extension, extension) = match extensions {
+                        Ok(e) => e,
+                        Err(e) => {
+                            // 他の領域も同じエラーになるので、ファイルごとスキップする
+                            println!("{}: {}", messages::text(Msg::Error).red(), e);
+                            break 'parts;
+                        },
+                    };
+                    let output_extension = paths::output_extension(&image_file, &source_extension, format);
+                    let output_path = naming.output_path(&image_file, &source_extension, &output_extension, index + 1);
+
+                    // --flatten -> Put the outputs directly into the output directory, with unique names.
+                    let output_path = if args.flatten {
+                        match flattened_names.unique(output_path, &image_file, args.dedupe_names) {
+                            Some(output_path) => output_path,
+                            None => {
+                                println!("{}", messages::format(Msg::DuplicateName, &[&image_file.display()]).yellow());
+                                continue 'parts;
+                            },
                         }
-                        else {
+                    }
+                    else {
+                        output_path
+                    };
+
+                    // If the output file already exists, check if it should be overwritten.
+                    let ask_result = match check_file_exists(&output_path, &file_overwrite_ask) {
+                        // Print the result of checking if the file exists.
+                        ExistsCheckResult::AllOverwrite => {
+                            println!("{}", messages::text(Msg::OverwriteByDefault).bold());
+                            AskResult::Overwrite
+                        },
+                        ExistsCheckResult::AllSkip => {
+                            println!("{}", messages::text(Msg::SkipByDefault).bold());
                             AskResult::Skip
-                        }
-                    },
-                    ExistsCheckResult::NoProblem => {
-                        AskResult::NoProblem
-                    },
-                };
+                        },
+                        ExistsCheckResult::NeedToAsk => {
+                            // If the file exists, ask if it should be overwritten.
+                            if ask_file_exists() {
+                                AskResult::Overwrite
+                            }
+                            else {
+                                AskResult::Skip
+                            }
+                        },
+                        ExistsCheckResult::NoProblem => {
+                            AskResult::NoProblem
+                        },
+                    };
+
+                    outputs.push(TaskOutput {
+                        path: output_path,
+                        extension: extension,
+                        ask_result: ask_result,
+                    });
+                }
+                let mut outputs = outputs.into_iter();
+                let output = outputs.next().expect("at least one output format");
 
                 // Make a thread task.
                 ThreadTask {
                     args: args.clone(),
-                    input_path: image_file,
-                    output_path: Some(output_path),
-                    extension: Some(extension),
-                    ask_result: ask_result,
+                    input_path: image_file.clone(),
+                    output_path: Some(output.path),
+                    extension: Some(output.extension),
+                    ask_result: output.ask_result,
+                    budget: None,
+                    trim: trim,
+                    page: page,
+                    line_art_output: outputs.next(),
                 }
             }
             else {
-                // If saving is not required, create a thread task without an output path.
+                // --in-place, or without --convert and the naming options -> The image is saved in place (if saving is required).
+                // Without --in-place, process() refuses to overwrite the source.
                 ThreadTask {
                     args: args.clone(),
-                    input_path: image_file,
+                    input_path: image_file.clone(),
                     output_path: None,
                     extension: None,
                     ask_result: AskResult::NoProblem,
+                    budget: None,
+                    trim: trim,
+                    page: page,
+                    line_art_output: None,
                 }
             };
-            
+        
             // Add the thread task to the thread_tasks.
             thread_tasks.push(thread_task);
         }
     }
 
+    // --total-budget -> Share the size budget between the tasks, in proportion to the source sizes.
+    // The shares are reserved in the sorted order (largest first) before the tasks are started.
+    // With multiple trim areas (or pages), each area of a source is a task that reserves a share for the whole source.
+    let budget = args.total_budget.map(|total_bytes| {
+        let source_sizes = thread_tasks.iter().map(|task| fs::metadata(&task.input_path).map(|m| m.len()).unwrap_or(0)).collect::<Vec<_>>();
+        let budget = Arc::new(Mutex::new(Budget::new(total_bytes, source_sizes.iter().sum())));
+        for (task, source_size) in thread_tasks.iter_mut().zip(source_sizes) {
+            task.budget = Some(Reservation::new(&budget, source_size));
+        }
+        budget
+    });
+
     // Display the number of images detected.
     let total_image_count = thread_tasks.len();
-    println!("{}", format!("🔎 {} images are detected.", total_image_count).bold());
+    println!("{}", messages::format(Msg::ImagesDetected, &[&total_image_count]).bold());
+
+    // --progress-file, --progress-fd -> Write the progress events as JSON lines.
+    let progress = match &args.progress {
+        Some(target) => Some(Arc::new(Progress::open(target)?)),
+        None => None,
+    };
+    if let Some(progress) = &progress {
+        progress.batch_started(total_image_count);
+    }
 
     // Share thread_tasks between threads.
+    // Tasks are popped from the end, so reverse the order to process them in the sorted order.
+    thread_tasks.reverse();
     let thread_tasks = Arc::new(Mutex::new(thread_tasks));
 
     // Processing for each image..
@@ -678,6 +1916,7 @@ async fn main() -> Result<(), String> {
         let count = Arc::clone(&count);
         let tx = tx.clone();
         let file_io_lock = Arc::clone(&file_io_lock);
+        let progress = progress.clone();
         
         let thread = tokio::spawn(async move {
             loop {
@@ -692,16 +1931,19 @@ async fn main() -> Result<(), String> {
                     }).await {
                         Ok(_) => {},
                         Err(e) => {
-                            println!("Send error: {}", e.to_string());
+                            eprintln!("{}", messages::format(Msg::SendError, &[&e]).red());
                         }
                     }
                     break;
                 }
                 let thread_task = thread_task.unwrap();
                 /*
-                let processing_str = format!("[{}/{}] Processing: {}", count, total_image_count, &Path::new(&thread_task.input_path).file_name().unwrap().to_str().unwrap());
+                let processing_str = format!("[{}/{}] Processing: {}", count, total_image_count, display_file_name(&thread_task.input_path));
                 println!("{}", processing_str.yellow().bold());
                 */
+                if let Some(progress) = &progress {
+                    progress.started(&thread_task.input_path);
+                }
                 let process_result = process(thread_task, file_io_lock.clone()).await;
                 match tx.send(ThreadResult {
                     process_result: Some(process_result),
@@ -709,7 +1951,9 @@ async fn main() -> Result<(), String> {
                 }).await {
                     Ok(_) => {},
                     Err(e) => {
-                        println!("Send error: {}", e.to_string());
+                        // 受信側が終了しているので、残りのタスクは処理しない
+                        eprintln!("{}", messages::format(Msg::SendError, &[&e]).red());
+                        break;
                     }
                 }
 
@@ -724,39 +1968,120 @@ async fn main() -> Result<(), String> {
     // Display the results of the threads.
     let mut count = 0;
     let mut thread_finished = 0;
+    let mut opened = 0;
+    let mut checksum_lines = Vec::new();
+    let mut archive_files = Vec::new();
+    let mut picture_outputs = Vec::new();
     while let Some(rx_result) = rx.recv().await {
         if let Some(process_result) = rx_result.process_result {
             match process_result {
                 // If the processing is successful, display the result.
                 Ok(thread_results) => {
-                    count = count + 1;
-                    let processing_str = format!("[{}/{}] Finish: {}", count + error_count, total_image_count, &Path::new(&thread_results.save_result.input_path).file_name().unwrap().to_str().unwrap());
+                    count += 1;
+                    let processing_str = messages::format(Msg::Finish, &[&(count + error_count), &total_image_count, &display_file_name(&thread_results.save_result.input_path)]);
                     println!("{}", processing_str.yellow().bold());
 
+                    if let Some(progress) = &progress {
+                        let save_result = &thread_results.save_result;
+                        progress.finished(&save_result.input_path, save_result.output_path.as_deref(), save_result.status.name(), save_result.before_filesize, save_result.after_filesize, &save_result.warnings);
+                    }
+
                     if let Some(convert_result) = thread_results.convert_result {
-                        println!("Convert: {} -> {}", convert_result.before_extension.to_string(), convert_result.after_extension.to_string());
+                        println!("{}: {} -> {}", messages::text(Msg::Convert), convert_result.before_extension, convert_result.after_extension);
+                    }
+                    if let Some(colorspace_result) = thread_results.colorspace_result {
+                        match colorspace_result.from {
+                            Some(from) => println!("{}: {} -> {}", messages::text(Msg::ColorSpace), from, colorspace_result.to),
+                            None => println!("{}: {}", messages::text(Msg::ColorSpace), messages::text(Msg::UnknownColorSpace)),
+                        }
+                    }
+                    if let Some(redact_result) = thread_results.redact_result {
+                        println!("{}: {}", messages::text(Msg::Redact), messages::format(Msg::Areas, &[&redact_result.areas]));
+                    }
+                    if let Some(regions_result) = thread_results.regions_result {
+                        if regions_result.pixelated > 0 {
+                            println!("{}: {}", messages::text(Msg::Pixelate), messages::format(Msg::Areas, &[&regions_result.pixelated]));
+                        }
+                        if regions_result.blurred > 0 {
+                            println!("{}: {}", messages::text(Msg::Blur), messages::format(Msg::Areas, &[&regions_result.blurred]));
+                        }
                     }
                     if let Some(trim_result) = thread_results.trim_result {
-                        println!("Trim: {}x{} -> {}x{}", trim_result.before_size.width, trim_result.before_size.height, trim_result.after_size.width, trim_result.after_size.height);
+                        println!("{}: {}x{} -> {}x{}", messages::text(Msg::Trim), trim_result.before_size.width, trim_result.before_size.height, trim_result.after_size.width, trim_result.after_size.height);
+                    }
+                    if let Some(deskew_result) = thread_results.deskew_result {
+                        println!("{}: {:.2}°", messages::text(Msg::Deskew), deskew_result.angle);
+                    }
+                    if let Some(autocrop_result) = thread_results.autocrop_result {
+                        println!("{}: {}x{} -> {}x{}", messages::text(Msg::Autocrop), autocrop_result.before_size.width, autocrop_result.before_size.height, autocrop_result.after_size.width, autocrop_result.after_size.height);
                     }
                     if let Some(resize_result) = thread_results.resize_result {
-                        println!("Resize: {}x{} -> {}x{}", resize_result.before_size.width, resize_result.before_size.height, resize_result.after_size.width, resize_result.after_size.height);
+                        println!("{}: {}x{} -> {}x{}", messages::text(Msg::Resize), resize_result.before_size.width, resize_result.before_size.height, resize_result.after_size.width, resize_result.after_size.height);
+                    }
+                    if let Some(vignette_result) = thread_results.vignette_result {
+                        println!("{}: {:+.2}", messages::text(Msg::Vignette), vignette_result.amount);
+                    }
+                    if let Some(white_balance_result) = thread_results.white_balance_result {
+                        let [r, g, b] = white_balance_result.gains;
+                        println!("{}: R x{:.2}, G x{:.2}, B x{:.2}", messages::text(Msg::WhiteBalance), r, g, b);
+                    }
+                    if let Some(auto_levels_result) = thread_results.auto_levels_result {
+                        println!("{}: {}: {}%", messages::text(Msg::AutoLevels), messages::text(Msg::Clip), auto_levels_result.clip);
+                    }
+                    if let Some(gamma_result) = thread_results.gamma_result {
+                        println!("{}: {}", messages::text(Msg::Gamma), gamma_result.gamma);
+                    }
+                    if let Some(curves_result) = thread_results.curves_result {
+                        println!("{}: {}", messages::text(Msg::Curves), curves_result.channels.join(", "));
+                    }
+                    if let Some(lut_result) = thread_results.lut_result {
+                        println!("{}: {}x{}x{}", messages::text(Msg::Lut), lut_result.size, lut_result.size, lut_result.size);
                     }
                     if let Some(grayscale_result) = thread_results.grayscale_result {
                         if grayscale_result.status {
-                            println!("Grayscale: Done.");
+                            println!("{}: {}", messages::text(Msg::Grayscale), messages::text(Msg::Done));
+                        }
+                    }
+                    if let Some(edges_result) = thread_results.edges_result {
+                        if edges_result.status {
+                            println!("{}: {}", messages::text(Msg::Edges), messages::text(Msg::Done));
+                        }
+                    }
+                    if let Some(threshold_result) = thread_results.threshold_result {
+                        match (threshold_result.method, threshold_result.level) {
+                            (Threshold::Otsu, Some(level)) => println!("{}: {} (Otsu)", messages::text(Msg::Threshold), level),
+                            (_, Some(level)) => println!("{}: {}", messages::text(Msg::Threshold), level),
+                            (_, None) => println!("{}: {}", messages::text(Msg::Threshold), messages::text(Msg::Adaptive)),
                         }
                     }
+                    if let Some(watermark_result) = thread_results.watermark_result {
+                        println!("{}: \"{}\"", messages::text(Msg::Watermark), watermark_result.message);
+                    }
+                    if let Some(depth_result) = thread_results.depth_result {
+                        println!("{}: {}-bit", messages::text(Msg::Depth), depth_result.depth);
+                    }
                     if let Some(compress_result) = thread_results.compress_result {
-                        if compress_result.status {
-                            println!("Compress: Done.");
+                        if let Some((quality, target)) = compress_result.budget {
+                            println!("{}: {}", messages::text(Msg::Compress), messages::format(Msg::BudgetQuality, &[&quality, &target]));
+                        }
+                        else if let Some((quality, ssim)) = compress_result.search {
+                            println!("{}: {}", messages::text(Msg::Compress), messages::format(Msg::SearchQuality, &[&quality, &format!("{:.4}", ssim)]));
+                        }
+                        else if compress_result.status {
+                            println!("{}: {}", messages::text(Msg::Compress), messages::text(Msg::Done));
                         }
                     }
 
                     // Show the image in the terminal.
                     // Use viuer crate to display the image.
                     if let Some(viuer_image) = thread_results.viuer_image {
-                        view(&viuer_image).map_err(|e| e.to_string()).unwrap();
+                        if let Err(e) = viewer::view(&viuer_image) {
+                            println!("{}: {}", messages::text(Msg::Error).red(), e);
+                        }
+                    }
+
+                    for warning in &thread_results.save_result.warnings {
+                        println!("{}: {}", messages::text(Msg::Warning).yellow(), warning);
                     }
 
                     match thread_results.save_result.status {
@@ -764,32 +2089,73 @@ async fn main() -> Result<(), String> {
                             // Print the result of saving the image.
                             save_print(&thread_results.save_result.input_path, &thread_results.save_result.output_path,
                                 thread_results.save_result.before_filesize, thread_results.save_result.after_filesize);
+                            if let Some(elapsed) = thread_results.save_result.elapsed {
+                                println!("{}: {:.1} ms", messages::text(Msg::SaveTime), elapsed.as_secs_f64() * 1000.0);
+                            }
 
+                            if let Some(dpi) = thread_results.save_result.dpi {
+                                println!("DPI: {}", dpi);
+                            }
+                            if let (Some(checksum), Some(output_path)) = (&thread_results.save_result.checksum, &thread_results.save_result.output_path) {
+                                println!("{}: {}", messages::text(Msg::Checksum), checksum);
+                                checksum_lines.push(checksum::line(checksum, output_path));
+                            }
+                            if let Some(output_path) = thread_results.save_result.output_path.as_ref().filter(|_| args.zip.is_some() || args.pdf.is_some()) {
+                                archive_files.push(output_path.clone());
+                            }
+                            if let Some(output_path) = thread_results.save_result.output_path.as_ref().filter(|_| args.picture_manifest.is_some()) {
+                                picture_outputs.push((thread_results.save_result.input_path.clone(), output_path.clone()));
+                            }
                             if thread_results.save_result.delete {
-                                println!("Delete source file: {}", thread_results.save_result.input_path.display());
+                                println!("{}", messages::format(Msg::DeleteSourceFile, &[&thread_results.save_result.input_path.display()]));
+                            }
+                            println!("{}", messages::text(Msg::Success).green().bold());
+
+                            // --open -> Open the first N outputs in the default image viewer.
+                            if let (Some(open), Some(output_path)) = (args.open, &thread_results.save_result.output_path) {
+                                if opened < open {
+                                    opened += 1;
+                                    if let Err(e) = viewer::open(output_path) {
+                                        println!("{}: {}", messages::text(Msg::Error).red(), e);
+                                    }
+                                }
                             }
-                            println!("{}", "Success.".green().bold())
                         },
-                        RusimgStatus::Cancel => println!("{}", "Canceled.".yellow().bold()),
-                        RusimgStatus::NotNeeded => println!("{}", "Nothing to do.".yellow().bold()),
+                        RusimgStatus::Cancel => println!("{}", messages::text(Msg::Canceled).yellow().bold()),
+                        RusimgStatus::NotNeeded => println!("{}", messages::text(Msg::NothingToDo).yellow().bold()),
+                        RusimgStatus::SkippedBlank => println!("{}", messages::text(Msg::SkippedBlank).yellow().bold()),
                     };
                 }
                 // If an error occurs during processing, display the error.
                 Err(e) => {
-                    error_count = error_count + 1;
+                    error_count += 1;
+                    if let Some(progress) = &progress {
+                        let file = match &e {
+                            ProcessingError::RusimgError(e) => Some(e.filepath.as_path()),
+                            ProcessingError::IOError(e) => Some(e.filepath.as_path()),
+                            ProcessingError::OperationError(e) => Some(e.filepath.as_path()),
+                            ProcessingError::FailedToViewImage(_) | ProcessingError::FailedToOpenImage(_) => None,
+                        };
+                        progress.error(file, &e.to_string());
+                    }
                     match e {
                         ProcessingError::RusimgError(e) => {
-                            let processing_str = format!("[{}/{}] Failed: {}", count + error_count, total_image_count, &Path::new(&e.filepath).file_name().unwrap().to_str().unwrap());
+                            let processing_str = messages::format(Msg::Failed, &[&(count + error_count), &total_image_count, &display_file_name(&e.filepath)]);
                             println!("{}", processing_str.red().bold());
-                            println!("{}: {}", "Error".red(), e.error);
+                            println!("{}: {}", messages::text(Msg::Error).red(), e.error);
                         },
                         ProcessingError::IOError(e) => {
-                            let processing_str = format!("[{}/{}] Failed: {}", count + error_count, total_image_count, &Path::new(&e.filepath).file_name().unwrap().to_str().unwrap());
+                            let processing_str = messages::format(Msg::Failed, &[&(count + error_count), &total_image_count, &display_file_name(&e.filepath)]);
                             println!("{}", processing_str.red().bold());
-                            println!("{}: {}", "Error".red(), e.error);
+                            println!("{}: {}", messages::text(Msg::Error).red(), e.error);
                         },
-                        ProcessingError::FailedToViewImage(s) => {
-                            println!("{}: {}", "Error".red(), s);
+                        ProcessingError::OperationError(e) => {
+                            let processing_str = messages::format(Msg::Failed, &[&(count + error_count), &total_image_count, &display_file_name(&e.filepath)]);
+                            println!("{}", processing_str.red().bold());
+                            println!("{}: {}", messages::text(Msg::Error).red(), e.error);
+                        },
+                        ProcessingError::FailedToViewImage(s) | ProcessingError::FailedToOpenImage(s) => {
+                            println!("{}: {}", messages::text(Msg::Error).red(), s);
                         },
                     }
                 }
@@ -797,7 +2163,7 @@ async fn main() -> Result<(), String> {
         }
 
         if rx_result.finish {
-            thread_finished = thread_finished + 1;
+            thread_finished += 1;
         }
         // If all threads are finished, break the loop.
         if thread_finished == threads {
@@ -807,12 +2173,53 @@ async fn main() -> Result<(), String> {
 
     // Show the result of processing all images.
     if error_count > 0 {
-        println!("\n✅ {} images are processed.", total_image_count - error_count);
-        println!("❌ {} images are failed to process.", error_count);
+        println!("\n{}", messages::format(Msg::ImagesProcessed, &[&(total_image_count - error_count)]));
+        println!("{}", messages::format(Msg::ImagesFailed, &[&error_count]));
     }
     else {
-        println!("\n✅ All images are processed.");
+        println!("\n{}", messages::text(Msg::AllImagesProcessed));
+    }
+
+    // --total-budget -> Show the total size of the outputs.
+    if let Some(budget) = &budget {
+        let budget = budget.lock().unwrap();
+        println!("{}", messages::format(Msg::BudgetUsed, &[&budget.used_bytes, &budget.total_bytes]));
     }
 
-    Ok(())
+    // --checksum-manifest -> Write the checksums of all outputs to one file.
+    if let Some(manifest) = &args.checksum_manifest {
+        checksum_lines.sort();
+        fs::write(manifest, checksum_lines.concat()).map_err(|e| format!("{}: {}", manifest.display(), e))?;
+        println!("{}", messages::format(Msg::ChecksumManifest, &[&manifest.display()]));
+    }
+
+    // --zip, --pdf -> Archive the outputs in the order of their paths.
+    archive_files.sort();
+    if let Some(zip) = &args.zip {
+        archive::write_zip(zip, &archive_files, args.zip_password.as_deref())?;
+        println!("{}", messages::format(Msg::ZipWritten, &[&archive_files.len(), &zip.display()]));
+        if args.zip_password.is_some() {
+            println!("{}", messages::text(Msg::ZipEncrypted));
+        }
+    }
+    if let Some(pdf) = &args.pdf {
+        pdf::write_pdf(pdf, &archive_files, args.dpi.unwrap_or(pdf::DEFAULT_DPI))?;
+        println!("{}", messages::format(Msg::PdfWritten, &[&archive_files.len(), &pdf.display()]));
+    }
+
+    // --picture-manifest -> Write the outputs of each source for <picture> elements.
+    if let Some(manifest) = &args.picture_manifest {
+        picture::write(manifest, &picture_outputs)?;
+        let sources = picture_outputs.iter().map(|(source, _)| source).collect::<std::collections::HashSet<_>>().len();
+        println!("{}", messages::format(Msg::PictureManifest, &[&sources, &manifest.display()]));
+    }
+
+    if let Some(progress) = &progress {
+        progress.batch_finished(total_image_count - error_count, error_count);
+    }
+
+    Ok(Some(Summary {
+        processed: total_image_count - error_count,
+        failed: error_count,
+    }))
 }
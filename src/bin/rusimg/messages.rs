@@ -0,0 +1,278 @@
+use std::fmt;
+use std::sync::OnceLock;
+
+/// Language of the CLI messages.
+/// - En: English (default)
+/// - Ja: Japanese
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Lang {
+    En,
+    Ja,
+}
+
+static LANG: OnceLock<Lang> = OnceLock::new();
+
+/// Set the language of the CLI messages.
+/// The language is determined by --lang, or the LANG environment variable if --lang is not specified.
+pub fn init(lang: Option<&str>) {
+    let lang = lang.map(|l| l.to_string()).or(std::env::var("LANG").ok()).unwrap_or_default();
+    let lang = if lang.to_ascii_lowercase().starts_with("ja") {
+        Lang::Ja
+    }
+    else {
+        Lang::En
+    };
+    let _ = LANG.set(lang);
+}
+
+/// Get the language of the CLI messages.
+fn lang() -> Lang {
+    *LANG.get().unwrap_or(&Lang::En)
+}
+
+/// Msg is the key of the message catalog.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Msg {
+    ImagesDetected,
+    AlreadyExists,
+    AskOverwrite,
+    WillOverwrite,
+    WillSkip,
+    EnterYesOrNo,
+    OverwriteByDefault,
+    SkipByDefault,
+    Overwrite,
+    Rename,
+    Move,
+    FileSize,
+    Finish,
+    Failed,
+    Error,
+    Convert,
+    ColorSpace,
+    UnknownColorSpace,
+    Redact,
+    Areas,
+    Pixelate,
+    Blur,
+    Trim,
+    Deskew,
+    Autocrop,
+    Resize,
+    Vignette,
+    WhiteBalance,
+    AutoLevels,
+    Gamma,
+    Curves,
+    Lut,
+    Edges,
+    Threshold,
+    Adaptive,
+    Clip,
+    Grayscale,
+    Watermark,
+    Depth,
+    Compress,
+    Done,
+    SaveTime,
+    DeleteSourceFile,
+    Success,
+    Canceled,
+    NothingToDo,
+    SkippedBlank,
+    ImagesProcessed,
+    ImagesFailed,
+    AllImagesProcessed,
+    BudgetQuality,
+    BudgetUsed,
+    SearchQuality,
+    NotifyFinished,
+    NotifyFailed,
+    NotifyBody,
+    DuplicateName,
+    NoPagesSelected,
+    InPlaceFormatChange,
+    InPlaceRequired,
+    Checksum,
+    ChecksumManifest,
+    ZipWritten,
+    PdfWritten,
+    PictureManifest,
+    ComicWritten,
+    ZipEncrypted,
+    Warning,
+    DirectoryEntryError,
+    WildcardEntryError,
+    SendError,
+}
+
+/// Get the message template.
+/// "{}" in the template is replaced with the arguments by format().
+fn template(msg: Msg) -> &'static str {
+    match (lang(), msg) {
+        (Lang::En, Msg::ImagesDetected) => "🔎 {} images are detected.",
+        (Lang::Ja, Msg::ImagesDetected) => "🔎 {} 個の画像が見つかりました。",
+        (Lang::En, Msg::AlreadyExists) => "The image file \"{}\" already exists.",
+        (Lang::Ja, Msg::AlreadyExists) => "画像ファイル \"{}\" は既に存在します。",
+        (Lang::En, Msg::AskOverwrite) => " Do you want to overwrite it? [y/N]: ",
+        (Lang::Ja, Msg::AskOverwrite) => " 上書きしますか？ [y/N]: ",
+        (Lang::En, Msg::WillOverwrite) => " => The file will be overwritten.",
+        (Lang::Ja, Msg::WillOverwrite) => " => ファイルを上書きします。",
+        (Lang::En, Msg::WillSkip) => " => The file will be skipped.",
+        (Lang::Ja, Msg::WillSkip) => " => ファイルをスキップします。",
+        (Lang::En, Msg::EnterYesOrNo) => " Please enter y or n [y/N]: ",
+        (Lang::Ja, Msg::EnterYesOrNo) => " y または n を入力してください [y/N]: ",
+        (Lang::En, Msg::OverwriteByDefault) => " => Overwrite (default: yes)",
+        (Lang::Ja, Msg::OverwriteByDefault) => " => 上書き (既定: yes)",
+        (Lang::En, Msg::SkipByDefault) => " => Skip (default: no)",
+        (Lang::Ja, Msg::SkipByDefault) => " => スキップ (既定: no)",
+        (Lang::En, Msg::Overwrite) => "Overwrite",
+        (Lang::Ja, Msg::Overwrite) => "上書き",
+        (Lang::En, Msg::Rename) => "Rename",
+        (Lang::Ja, Msg::Rename) => "名前変更",
+        (Lang::En, Msg::Move) => "Move",
+        (Lang::Ja, Msg::Move) => "移動",
+        (Lang::En, Msg::FileSize) => "File Size",
+        (Lang::Ja, Msg::FileSize) => "ファイルサイズ",
+        (Lang::En, Msg::Finish) => "[{}/{}] Finish: {}",
+        (Lang::Ja, Msg::Finish) => "[{}/{}] 完了: {}",
+        (Lang::En, Msg::Failed) => "[{}/{}] Failed: {}",
+        (Lang::Ja, Msg::Failed) => "[{}/{}] 失敗: {}",
+        (Lang::En, Msg::Error) => "Error",
+        (Lang::Ja, Msg::Error) => "エラー",
+        (Lang::En, Msg::Convert) => "Convert",
+        (Lang::Ja, Msg::Convert) => "変換",
+        (Lang::En, Msg::ColorSpace) => "Color space",
+        (Lang::Ja, Msg::ColorSpace) => "色空間",
+        (Lang::En, Msg::UnknownColorSpace) => "unsupported ICC profile, not converted",
+        (Lang::Ja, Msg::UnknownColorSpace) => "未対応の ICC プロファイルのため変換なし",
+        (Lang::En, Msg::Redact) => "Redact",
+        (Lang::Ja, Msg::Redact) => "墨消し",
+        (Lang::En, Msg::Areas) => "{} areas",
+        (Lang::Ja, Msg::Areas) => "{} 箇所",
+        (Lang::En, Msg::Pixelate) => "Pixelate",
+        (Lang::Ja, Msg::Pixelate) => "モザイク",
+        (Lang::En, Msg::Blur) => "Blur",
+        (Lang::Ja, Msg::Blur) => "ぼかし",
+        (Lang::En, Msg::Trim) => "Trim",
+        (Lang::Ja, Msg::Trim) => "トリミング",
+        (Lang::En, Msg::Deskew) => "Deskew",
+        (Lang::Ja, Msg::Deskew) => "傾き補正",
+        (Lang::En, Msg::Autocrop) => "Autocrop",
+        (Lang::Ja, Msg::Autocrop) => "余白除去",
+        (Lang::En, Msg::Resize) => "Resize",
+        (Lang::Ja, Msg::Resize) => "リサイズ",
+        (Lang::En, Msg::Vignette) => "Vignette",
+        (Lang::Ja, Msg::Vignette) => "周辺光量",
+        (Lang::En, Msg::WhiteBalance) => "White balance",
+        (Lang::Ja, Msg::WhiteBalance) => "ホワイトバランス",
+        (Lang::En, Msg::AutoLevels) => "Auto levels",
+        (Lang::Ja, Msg::AutoLevels) => "自動レベル補正",
+        (Lang::En, Msg::Gamma) => "Gamma",
+        (Lang::En, Msg::Curves) => "Curves",
+        (Lang::Ja, Msg::Gamma) => "ガンマ補正",
+        (Lang::Ja, Msg::Curves) => "トーンカーブ",
+        (Lang::En, Msg::Lut) => "LUT",
+        (Lang::Ja, Msg::Lut) => "LUT",
+        (Lang::En, Msg::Edges) => "Edges",
+        (Lang::Ja, Msg::Edges) => "エッジ検出",
+        (Lang::En, Msg::Threshold) => "Threshold",
+        (Lang::Ja, Msg::Threshold) => "二値化",
+        (Lang::En, Msg::Adaptive) => "adaptive",
+        (Lang::Ja, Msg::Adaptive) => "適応的",
+        (Lang::En, Msg::Clip) => "clip",
+        (Lang::Ja, Msg::Clip) => "クリップ",
+        (Lang::En, Msg::Grayscale) => "Grayscale",
+        (Lang::Ja, Msg::Grayscale) => "グレースケール",
+        (Lang::En, Msg::Watermark) => "Watermark",
+        (Lang::Ja, Msg::Watermark) => "透かし",
+        (Lang::En, Msg::Depth) => "Bit depth",
+        (Lang::Ja, Msg::Depth) => "ビット深度",
+        (Lang::En, Msg::Compress) => "Compress",
+        (Lang::Ja, Msg::Compress) => "圧縮",
+        (Lang::En, Msg::Done) => "Done.",
+        (Lang::Ja, Msg::Done) => "完了。",
+        (Lang::En, Msg::SaveTime) => "Save Time",
+        (Lang::Ja, Msg::SaveTime) => "保存時間",
+        (Lang::En, Msg::DeleteSourceFile) => "Delete source file: {}",
+        (Lang::Ja, Msg::DeleteSourceFile) => "元ファイルを削除: {}",
+        (Lang::En, Msg::Success) => "Success.",
+        (Lang::Ja, Msg::Success) => "成功。",
+        (Lang::En, Msg::Canceled) => "Canceled.",
+        (Lang::Ja, Msg::Canceled) => "キャンセルしました。",
+        (Lang::En, Msg::NothingToDo) => "Nothing to do.",
+        (Lang::Ja, Msg::NothingToDo) => "処理は不要です。",
+        (Lang::En, Msg::SkippedBlank) => "Skipped (blank image).",
+        (Lang::Ja, Msg::SkippedBlank) => "スキップしました（空白の画像）。",
+        (Lang::En, Msg::ImagesProcessed) => "✅ {} images are processed.",
+        (Lang::Ja, Msg::ImagesProcessed) => "✅ {} 個の画像を処理しました。",
+        (Lang::En, Msg::ImagesFailed) => "❌ {} images are failed to process.",
+        (Lang::Ja, Msg::ImagesFailed) => "❌ {} 個の画像の処理に失敗しました。",
+        (Lang::En, Msg::AllImagesProcessed) => "✅ All images are processed.",
+        (Lang::Ja, Msg::AllImagesProcessed) => "✅ すべての画像を処理しました。",
+        (Lang::En, Msg::BudgetQuality) => "quality {} (target: {} bytes)",
+        (Lang::Ja, Msg::BudgetQuality) => "品質 {} (目標: {} バイト)",
+        (Lang::En, Msg::BudgetUsed) => "Total size: {} / {} bytes (budget)",
+        (Lang::Ja, Msg::BudgetUsed) => "合計サイズ: {} / {} バイト (予算)",
+        (Lang::En, Msg::SearchQuality) => "quality {} (SSIM: {})",
+        (Lang::Ja, Msg::SearchQuality) => "品質 {} (SSIM: {})",
+        (Lang::En, Msg::NotifyFinished) => "rusimg: batch finished",
+        (Lang::Ja, Msg::NotifyFinished) => "rusimg: 処理が完了しました",
+        (Lang::En, Msg::NotifyFailed) => "rusimg: batch failed",
+        (Lang::Ja, Msg::NotifyFailed) => "rusimg: 処理に失敗しました",
+        (Lang::En, Msg::NotifyBody) => "{} images processed, {} failed ({})",
+        (Lang::Ja, Msg::NotifyBody) => "{} 個の画像を処理、{} 個が失敗 ({})",
+        (Lang::En, Msg::DuplicateName) => "Skip {}: the output name is already used by another file (use --dedupe-names parent|hash).",
+        (Lang::Ja, Msg::DuplicateName) => "{} をスキップ: 出力ファイル名が他のファイルと重複しています (--dedupe-names parent|hash を指定してください)。",
+        (Lang::En, Msg::NoPagesSelected) => "Skip {}: no pages are selected by --pages.",
+        (Lang::Ja, Msg::NoPagesSelected) => "{} をスキップ: --pages で選択されたページがありません。",
+        (Lang::En, Msg::InPlaceFormatChange) => "Skip {}: --in-place cannot change the format of the source (save it with -o or -a instead).",
+        (Lang::Ja, Msg::InPlaceFormatChange) => "{} をスキップ: --in-place では元ファイルの形式を変更できません (-o または -a で保存してください)。",
+        (Lang::En, Msg::InPlaceRequired) => "The source would be overwritten. Specify --in-place to overwrite it, or -o / -a to save to a new file.",
+        (Lang::Ja, Msg::InPlaceRequired) => "元ファイルが上書きされます。上書きする場合は --in-place を、新しいファイルに保存する場合は -o / -a を指定してください。",
+        (Lang::En, Msg::Checksum) => "Checksum",
+        (Lang::Ja, Msg::Checksum) => "チェックサム",
+        (Lang::En, Msg::ChecksumManifest) => "Checksums are written to {}",
+        (Lang::Ja, Msg::ChecksumManifest) => "チェックサムを {} に書き込みました",
+        (Lang::En, Msg::ZipWritten) => "{} files are archived to {}",
+        (Lang::Ja, Msg::ZipWritten) => "{} 個のファイルを {} にアーカイブしました",
+        (Lang::En, Msg::PdfWritten) => "{} pages are written to {}",
+        (Lang::Ja, Msg::PdfWritten) => "{} ページを {} に書き出しました",
+        (Lang::En, Msg::PictureManifest) => "The variants of {} sources are written to {}",
+        (Lang::Ja, Msg::PictureManifest) => "{} 個の元画像の出力一覧を {} に書き込みました",
+        (Lang::En, Msg::ComicWritten) => "{} pages are written to {}",
+        (Lang::Ja, Msg::ComicWritten) => "{} ページを {} に書き出しました",
+        (Lang::En, Msg::ZipEncrypted) => "The archive is encrypted with AES-256",
+        (Lang::Ja, Msg::ZipEncrypted) => "アーカイブは AES-256 で暗号化されています",
+        (Lang::En, Msg::Warning) => "Warning",
+        (Lang::Ja, Msg::Warning) => "警告",
+        (Lang::En, Msg::DirectoryEntryError) => "Cannot read a directory entry: {}",
+        (Lang::Ja, Msg::DirectoryEntryError) => "ディレクトリのエントリを読み込めません: {}",
+        (Lang::En, Msg::WildcardEntryError) => "Cannot read {} matched by the wildcard: {}",
+        (Lang::Ja, Msg::WildcardEntryError) => "ワイルドカードに一致した {} を読み込めません: {}",
+        (Lang::En, Msg::SendError) => "Internal error: cannot send the result of a task: {}",
+        (Lang::Ja, Msg::SendError) => "内部エラー: タスクの結果を送信できません: {}",
+    }
+}
+
+/// Get the message.
+pub fn text(msg: Msg) -> &'static str {
+    template(msg)
+}
+
+/// Get the message with the arguments.
+/// Each "{}" in the message is replaced with the arguments in order.
+pub fn format(msg: Msg, args: &[&dyn fmt::Display]) -> String {
+    let mut ret = String::new();
+    let mut args = args.iter();
+    let mut parts = template(msg).split("{}").peekable();
+    while let Some(part) = parts.next() {
+        ret.push_str(part);
+        if parts.peek().is_some() {
+            if let Some(arg) = args.next() {
+                ret.push_str(&arg.to_string());
+            }
+        }
+    }
+    ret
+}
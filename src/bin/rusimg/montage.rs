@@ -0,0 +1,83 @@
+use image::{Rgba, RgbaImage, imageops};
+
+/// Space between the tiles and around the sheet.
+const GAP: u32 = 8;
+/// Scale of the label font (the glyphs are 5x7 pixels).
+const FONT_SCALE: u32 = 2;
+const GLYPH_WIDTH: u32 = 5;
+const GLYPH_HEIGHT: u32 = 7;
+/// Height of the label bar above each tile.
+const LABEL_HEIGHT: u32 = GLYPH_HEIGHT * FONT_SCALE + GAP;
+
+const BACKGROUND: Rgba<u8> = Rgba([48, 48, 48, 255]);
+const LABEL_COLOR: Rgba<u8> = Rgba([255, 255, 255, 255]);
+
+/// Rows of a 5x7 glyph (the highest of the 5 bits is the left pixel).
+/// Only the characters used by the labels are defined; others are drawn as blanks.
+fn glyph(c: char) -> [u8; 7] {
+    match c.to_ascii_uppercase() {
+        '0' => [0x0e, 0x11, 0x13, 0x15, 0x19, 0x11, 0x0e],
+        '1' => [0x04, 0x0c, 0x04, 0x04, 0x04, 0x04, 0x0e],
+        '2' => [0x0e, 0x11, 0x01, 0x02, 0x04, 0x08, 0x1f],
+        '3' => [0x1f, 0x02, 0x04, 0x02, 0x01, 0x11, 0x0e],
+        '4' => [0x02, 0x06, 0x0a, 0x12, 0x1f, 0x02, 0x02],
+        '5' => [0x1f, 0x10, 0x1e, 0x01, 0x01, 0x11, 0x0e],
+        '6' => [0x06, 0x08, 0x10, 0x1e, 0x11, 0x11, 0x0e],
+        '7' => [0x1f, 0x01, 0x02, 0x04, 0x08, 0x08, 0x08],
+        '8' => [0x0e, 0x11, 0x11, 0x0e, 0x11, 0x11, 0x0e],
+        '9' => [0x0e, 0x11, 0x11, 0x0f, 0x01, 0x02, 0x0c],
+        'B' => [0x1e, 0x11, 0x11, 0x1e, 0x11, 0x11, 0x1e],
+        'C' => [0x0e, 0x11, 0x10, 0x10, 0x10, 0x11, 0x0e],
+        'K' => [0x11, 0x12, 0x14, 0x18, 0x14, 0x12, 0x11],
+        'M' => [0x11, 0x1b, 0x15, 0x15, 0x11, 0x11, 0x11],
+        'Q' => [0x0e, 0x11, 0x11, 0x11, 0x15, 0x12, 0x0d],
+        'R' => [0x1e, 0x11, 0x11, 0x1e, 0x14, 0x12, 0x11],
+        'S' => [0x0f, 0x10, 0x10, 0x0e, 0x01, 0x01, 0x1e],
+        '.' => [0x00, 0x00, 0x00, 0x00, 0x00, 0x0c, 0x0c],
+        ':' => [0x00, 0x0c, 0x0c, 0x00, 0x0c, 0x0c, 0x00],
+        _ => [0; 7],
+    }
+}
+
+/// Draw a label with the built-in font at (x, y).
+fn draw_label(canvas: &mut RgbaImage, text: &str, x: u32, y: u32) {
+    for (i, c) in text.chars().enumerate() {
+        let left = x + i as u32 * (GLYPH_WIDTH + 1) * FONT_SCALE;
+        for (row, bits) in glyph(c).iter().enumerate() {
+            for column in 0..GLYPH_WIDTH {
+                if bits & (0x10 >> column) == 0 {
+                    continue;
+                }
+                for dy in 0..FONT_SCALE {
+                    for dx in 0..FONT_SCALE {
+                        let (px, py) = (left + column * FONT_SCALE + dx, y + row as u32 * FONT_SCALE + dy);
+                        if px < canvas.width() && py < canvas.height() {
+                            canvas.put_pixel(px, py, LABEL_COLOR);
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Compose the tiles into a sheet with `columns` columns.
+/// Each tile is placed in a cell of the size of the largest tile, with its label above it.
+pub fn compose(tiles: &[(RgbaImage, String)], columns: u32) -> RgbaImage {
+    let columns = columns.clamp(1, tiles.len().max(1) as u32);
+    let rows = (tiles.len() as u32).div_ceil(columns);
+    let cell_width = tiles.iter().map(|(tile, _)| tile.width()).max().unwrap_or(0);
+    let cell_height = tiles.iter().map(|(tile, _)| tile.height()).max().unwrap_or(0) + LABEL_HEIGHT;
+
+    let mut sheet = RgbaImage::from_pixel(
+        GAP + columns * (cell_width + GAP),
+        GAP + rows * (cell_height + GAP),
+        BACKGROUND,
+    );
+    for (i, (tile, label)) in tiles.iter().enumerate() {
+        let (x, y) = (GAP + (i as u32 % columns) * (cell_width + GAP), GAP + (i as u32 / columns) * (cell_height + GAP));
+        draw_label(&mut sheet, label, x, y);
+        imageops::overlay(&mut sheet, tile, x as i64, (y + LABEL_HEIGHT) as i64);
+    }
+    sheet
+}
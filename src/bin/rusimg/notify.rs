@@ -0,0 +1,47 @@
+use std::time::Duration;
+use notify_rust::Notification;
+
+use crate::Summary;
+use crate::messages::{self, Msg};
+
+/// Application name shown in the notifications.
+const APP_NAME: &str = "rusimg";
+
+/// Show a desktop notification.
+/// A failure to notify (e.g. no notification daemon) is reported but does not fail the run.
+fn show(summary: &str, body: &str) {
+    let result = Notification::new()
+        .appname(APP_NAME)
+        .summary(summary)
+        .body(body)
+        .show();
+    if let Err(e) = result {
+        println!("{}: {}", messages::text(Msg::Error), e);
+    }
+}
+
+/// Format the elapsed time (e.g. 2h 03m 04s, 5m 06s, 7s).
+fn format_elapsed(elapsed: Duration) -> String {
+    let seconds = elapsed.as_secs();
+    let (hours, minutes, seconds) = (seconds / 3600, seconds / 60 % 60, seconds % 60);
+    if hours > 0 {
+        format!("{}h {:02}m {:02}s", hours, minutes, seconds)
+    }
+    else if minutes > 0 {
+        format!("{}m {:02}s", minutes, seconds)
+    }
+    else {
+        format!("{}s", seconds)
+    }
+}
+
+/// Notify that the batch has completed, with the numbers of processed and failed images.
+pub fn finished(summary: &Summary, elapsed: Duration) {
+    let body = messages::format(Msg::NotifyBody, &[&summary.processed, &summary.failed, &format_elapsed(elapsed)]);
+    show(messages::text(Msg::NotifyFinished), &body);
+}
+
+/// Notify that the run has failed.
+pub fn failed(error: &str) {
+    show(messages::text(Msg::NotifyFailed), error);
+}
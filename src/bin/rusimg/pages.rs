@@ -0,0 +1,58 @@
+use std::path::Path;
+use librusimg::{RusImg, RusimgError};
+use librusimg::frames::Frames;
+use librusimg::tempdir::TempDir;
+
+use crate::parse::PageSelection;
+use crate::pdf;
+
+/// File extensions of the multi-page sources (TIFF, PDF).
+/// The library cannot open them, so each page is decoded here and processed as png.
+const MULTI_PAGE_EXTENSIONS: [&str; 3] = ["tif", "tiff", "pdf"];
+
+/// Whether the file is a multi-page source (it may also have one page).
+pub fn is_multi_page(path: &Path) -> bool {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| MULTI_PAGE_EXTENSIONS.iter().any(|multi_page| e.eq_ignore_ascii_case(multi_page)))
+}
+
+/// Whether the file is a PDF document.
+fn is_pdf(path: &Path) -> bool {
+    path.extension().is_some_and(|e| e.eq_ignore_ascii_case("pdf"))
+}
+
+/// Count the pages of a multi-page source.
+pub fn page_count(path: &Path) -> Result<usize, String> {
+    if is_pdf(path) {
+        pdf::page_count(path)
+    }
+    else {
+        Frames::count(path).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+}
+
+/// Get the pages of a multi-page source selected by --pages (all pages if None), and the number of its pages.
+pub fn select(path: &Path, selection: Option<&PageSelection>) -> Result<(Vec<usize>, usize), String> {
+    let count = page_count(path)?;
+    let selected = (1..=count).filter(|page| selection.is_none_or(|selection| selection.contains(*page))).collect();
+    Ok((selected, count))
+}
+
+/// Open a page of a multi-page source (page numbers start from 1) as a png image.
+/// The library opens images from files, so the page is passed through a lossless png in a temporary directory (as hdr::open()).
+pub fn open(path: &Path, page: usize) -> Result<RusImg, RusimgError> {
+    let image = if is_pdf(path) {
+        pdf::read_page(path, page).map_err(RusimgError::FailedToOpenImage)?
+    }
+    else {
+        let frames = Frames::decode_selected(path, |index| index + 1 == page)?;
+        let frames = frames.ok_or(RusimgError::FailedToOpenImage(format!("{}: page {} not found", path.display(), page)))?;
+        frames.into_vec().swap_remove(0).image
+    };
+
+    let temp_dir = TempDir::new().map_err(|e| RusimgError::FailedToCreateFile(e.to_string()))?;
+    let temp_path = temp_dir.file(format!("page{}.png", page));
+    image.save(&temp_path).map_err(|e| RusimgError::FailedToSaveImage(e.to_string()))?;
+    librusimg::open_image(&temp_path)
+}
@@ -1,69 +1,942 @@
 use std::path::PathBuf;
+use std::sync::Arc;
 use clap::Parser;
 use regex::Regex;
-use librusimg::Rect;
+use librusimg::{Rect, ImgSize};
+use librusimg::colorspace::ColorSpace;
+use librusimg::filters::{Curve, Curves, Lut3d, Threshold};
+use librusimg::tonemap::ToneMap;
+use crate::bench::BenchOptions;
+use crate::preview::{self, PreviewOptions};
+use crate::progress::ProgressTarget;
+use crate::shell::ShellAction;
+use crate::appicon::{AppIconOptions, IconTarget};
+use crate::flatten::DedupeMode;
+use crate::regions;
+use crate::faces;
+use crate::qr;
+use crate::split::{self, SplitScanOptions};
+use crate::watermark::{self, VerifyWatermarkOptions};
+use crate::animation::{self, AnimationFormat, ToGifOptions};
+use crate::check::{self, CheckOptions, SalvageMode};
+use crate::paths::ExtensionCase;
+use crate::checksum::ChecksumAlgorithm;
 use std::fmt;
 
 const DEFAULT_THREADS: u8 = 4;
+const DEFAULT_BENCH_ITERATIONS: u32 = 5;
+const DEFAULT_AUTOCROP_TOLERANCE: &str = "10";
+const DEFAULT_BLANK_THRESHOLD: &str = "2.0";
+const DEFAULT_AUTO_LEVELS_CLIP: &str = "0.5";
+const DEFAULT_PIXELATE_BLOCK_SIZE: u32 = 16;
+const DEFAULT_BLUR_SIGMA: f32 = 8.0;
+const DEFAULT_THRESHOLD: &str = "otsu";
+/// Threshold and compression effort of the --document preset.
+const DOCUMENT_THRESHOLD: &str = "adaptive";
+const DOCUMENT_EFFORT: u8 = 100;
+/// Gamma of the --device preset. E-ink screens show the midtones darker than LCDs.
+const DEVICE_GAMMA: f32 = 1.2;
+/// --convert auto: png for line art and text, jpeg for photos (chosen for each image).
+pub const AUTO_FORMAT: &str = "auto";
+/// Names accepted by --convert: the file extensions of the formats built into the library.
+fn supported_formats() -> Vec<&'static str> {
+    librusimg::Extension::builtin().iter().flat_map(|format| format.aliases().iter().copied()).collect()
+}
 
 /// Argument errors
 pub enum ArgError {
+    UnsupportedFormat(String, Option<String>),
     InvalidTrimFormat,
     FailedToParseTrim(String),
     InvalidQuality,
     InvalidResize,
+    InvalidDpi,
+    InvalidEffort,
+    InvalidSequence,
+    UpscaleNotAllowed,
     InvalidThreads,
+    InvalidMemoryLimit,
+    InvalidAutoLevels,
+    InvalidWhiteBalance,
+    InvalidVignette,
+    InvalidEncoderThreads,
+    InvalidDepth,
+    InvalidMapOptions(String),
+    InvalidTotalBudget,
+    InvalidQualitySearch,
+    InvalidViewSize,
+    InvalidOrganizeByDate,
+    InvalidOutPerFormat,
+    FailedToReadTrimFile(String),
+    InvalidMinArea,
+    InvalidCropRatio,
+    FaceDetectUnavailable,
+    MissingFaceModel,
+    QrDetectUnavailable,
+    InvalidBlockSize,
+    InvalidExposure,
+    WatermarkTooLong,
+    InvalidSigma,
+    DeleteWithMultipleTrims,
+    InPlaceWithMultipleTrims,
+    FailedToReadLut(String),
+    InvalidCurve(String),
+    InvalidThreshold,
+    InvalidGamma,
+    InvalidFps,
+    InvalidPages,
+    UnsupportedAnimationFormat(PathBuf),
 }
 impl fmt::Display for ArgError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
-            ArgError::InvalidTrimFormat => write!(f, "Invalid trim format. Please use 'XxY+W+H' (e.g.100x100+50x50)."),
+            ArgError::UnsupportedFormat(format, Some(suggestion)) => write!(f, "Unsupported format: '{}'. Did you mean '{}'? (supported: {})", format, suggestion, supported_formats().join(", ")),
+            ArgError::UnsupportedFormat(format, None) => write!(f, "Unsupported format: '{}' (supported: {})", format, supported_formats().join(", ")),
+            ArgError::InvalidTrimFormat => write!(f, "Invalid trim format. Please use 'XxY+W+H' (e.g.100x100+50x50, -200x-200+200x200), 'X%xY%+W%xH%' (e.g.10%x10%+50%x50%) or 'center:WxH' (e.g.center:800x600)."),
             ArgError::FailedToParseTrim(e) => write!(f, "Failed to parse trim format: \n\t{}", e),
             ArgError::InvalidQuality => write!(f, "Quality must be 0.0 <= q <= 100.0"),
-            ArgError::InvalidResize => write!(f, "Resize must be size > 0"),
+            ArgError::InvalidResize => write!(f, "Resize must be size > 0.0 or a physical size with DPI (e.g. 10cmx15cm@300dpi)"),
+            ArgError::InvalidDpi => write!(f, "DPI must be dpi > 0"),
+            ArgError::InvalidEffort => write!(f, "Effort must be 0 <= effort <= 100"),
+            ArgError::InvalidSequence => write!(f, "Sequence must contain '{{n}}' or '{{n:WIDTH}}' (e.g. IMG_{{n:04}})."),
+            ArgError::UpscaleNotAllowed => write!(f, "Resize ratio > 100 enlarges images. Specify --allow-upscale to upscale."),
             ArgError::InvalidThreads => write!(f, "Threads must be threads => 1"),
+            ArgError::InvalidMemoryLimit => write!(f, "Memory limit must be >= 1 (MiB)"),
+            ArgError::InvalidAutoLevels => write!(f, "Auto levels clip must be 0.0 <= clip < 50.0"),
+            ArgError::InvalidVignette => write!(f, "Vignette must be -1.0 <= amount <= 1.0"),
+            ArgError::InvalidWhiteBalance => write!(f, "Invalid white balance. Please use 'auto' or a color temperature 1000K-40000K with an optional tint -100 to 100 (e.g. 5500K, 5500K:+10)."),
+            ArgError::InvalidEncoderThreads => write!(f, "Encoder threads must be encoder_threads => 1"),
+            ArgError::InvalidDepth => write!(f, "Depth must be 8, 4 or 1"),
+            ArgError::InvalidTotalBudget => write!(f, "Total budget must be a size > 0 (e.g. 200MB, 1.5GB, 500KiB)"),
+            ArgError::InvalidOrganizeByDate => write!(f, "Organize by date must contain '{{year}}', '{{month}}' or '{{day}}' (e.g. {{year}}/{{month}})."),
+            ArgError::InvalidOutPerFormat => write!(f, "Out per format must contain '{{format}}' (e.g. dist/{{format}}/)."),
+            ArgError::InvalidViewSize => write!(f, "View size must be COLUMNSxROWS or COLUMNS with sizes > 0 (e.g. 80x24, 80)"),
+            ArgError::InvalidQualitySearch => write!(f, "Quality search must be 'ssim:THRESHOLD' with 0.0 < threshold <= 1.0 (e.g. ssim:0.95)"),
+            ArgError::InvalidMapOptions(e) => write!(f, "Invalid options in the mapping file: \n\t{}", e),
+            ArgError::FailedToReadTrimFile(e) => write!(f, "Failed to read the trim file: \n\t{}", e),
+            ArgError::InvalidMinArea => write!(f, "Min area must be 0.0 <= min_area < 100.0"),
+            ArgError::InvalidCropRatio => write!(f, "Crop ratio must be W:H with W, H > 0 (e.g. 1:1, 16:9)"),
+            ArgError::FaceDetectUnavailable => write!(f, "--gravity faces is not available: rusimg was built without the face-detect feature."),
+            ArgError::WatermarkTooLong => write!(f, "Watermark message must be at most {} bytes", watermark::MAX_MESSAGE_LEN),
+            ArgError::InvalidExposure => write!(f, "Exposure must be > 0.0"),
+            ArgError::InvalidBlockSize => write!(f, "Block size of --pixelate must be >= 2"),
+            ArgError::InvalidSigma => write!(f, "Sigma of --blur-region must be > 0.0"),
+            ArgError::QrDetectUnavailable => write!(f, "--redact-qr is not available: rusimg was built without the qr-detect feature."),
+            ArgError::MissingFaceModel => write!(f, "--gravity faces requires the face model. Specify --face-model or RUSIMG_FACE_MODEL (seeta_fd_frontal_v1.0.bin)."),
+            ArgError::DeleteWithMultipleTrims => write!(f, "--delete cannot be used with multiple trim areas, because every area is cropped from the source."),
+            ArgError::InPlaceWithMultipleTrims => write!(f, "--in-place cannot be used with multiple trim areas, because every area would overwrite the source."),
+            ArgError::FailedToReadLut(e) => write!(f, "Failed to read the LUT file: \n\t{}", e),
+            ArgError::InvalidFps => write!(f, "FPS must be > 0.0"),
+            ArgError::InvalidPages => write!(f, "Pages must be page numbers and ranges separated by commas, starting from 1 (e.g. 1-3,7 or 5-)"),
+            ArgError::UnsupportedAnimationFormat(path) => write!(f, "Unsupported animation format: '{}' (supported: gif, webp, png, apng)", path.display()),
+            ArgError::InvalidGamma => write!(f, "Gamma must be > 0.0"),
+            ArgError::InvalidThreshold => write!(f, "Threshold must be 'otsu', 'adaptive' or a level 0-255"),
+            ArgError::InvalidCurve(e) => write!(f, "Curve must be [CHANNEL:]INPUT,OUTPUT ... with CHANNEL r, g or b and values 0.0-1.0 (e.g. \"0,0 0.5,0.58 1,1\", \"b:0,0.05 1,0.95\"): \n\t{}", e),
         }
     }
 
 }
 
+/// Social media presets (recommended image sizes of each platform).
+/// - Og: Open Graph image for link previews (1200x630).
+/// - Twitter: Image in a post on X/Twitter (1600x900, 16:9).
+/// - Instagram: Square Instagram post (1080x1080).
+#[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq)]
+pub enum SocialPreset {
+    Og,
+    Twitter,
+    Instagram,
+}
+impl SocialPreset {
+    /// Size of the output in pixels.
+    pub fn size(&self) -> (u32, u32) {
+        match self {
+            SocialPreset::Og => (1200, 630),
+            SocialPreset::Twitter => (1600, 900),
+            SocialPreset::Instagram => (1080, 1080),
+        }
+    }
+
+    /// Resize specification to the size of the preset.
+    pub fn resize(&self) -> ResizeSpec {
+        let (width, height) = self.size();
+        ResizeSpec::Pixels { width, height }
+    }
+}
+
+/// E-reader device profiles (screen resolutions in portrait orientation).
+/// - KindlePaperwhite: Kindle Paperwhite (2021-, 1236x1648).
+/// - KindleOasis: Kindle Oasis (1264x1680).
+/// - KindleScribe: Kindle Scribe (1860x2480).
+/// - Kindle: Kindle (2022-, 1072x1448).
+/// - KoboClara: Kobo Clara HD/2E/BW (1072x1448).
+/// - KoboLibra: Kobo Libra 2 (1264x1680).
+/// - KoboSage: Kobo Sage (1440x1920).
+#[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq)]
+pub enum DeviceProfile {
+    KindlePaperwhite,
+    KindleOasis,
+    KindleScribe,
+    Kindle,
+    KoboClara,
+    KoboLibra,
+    KoboSage,
+}
+impl DeviceProfile {
+    /// Size of the screen in pixels (width, height).
+    pub fn size(&self) -> (u32, u32) {
+        match self {
+            DeviceProfile::KindlePaperwhite => (1236, 1648),
+            DeviceProfile::KindleOasis | DeviceProfile::KoboLibra => (1264, 1680),
+            DeviceProfile::KindleScribe => (1860, 2480),
+            DeviceProfile::Kindle | DeviceProfile::KoboClara => (1072, 1448),
+            DeviceProfile::KoboSage => (1440, 1920),
+        }
+    }
+
+    /// Resize specification to fit the screen.
+    pub fn resize(&self) -> ResizeSpec {
+        let (width, height) = self.size();
+        ResizeSpec::Fit { width, height }
+    }
+}
+
+/// How the areas of --redact and --redact-qr are hidden.
+/// - Blur: Blur the area beyond recognition.
+/// - Black: Fill the area with black.
+#[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq, Default)]
+pub enum RedactStyle {
+    #[default]
+    Blur,
+    Black,
+}
+
+/// Placement of a crop with an aspect ratio (--crop-ratio, --social).
+/// - Center: Centered on the image.
+/// - Faces: Keep the detected faces in the frame (face-detect feature).
+#[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq, Default)]
+pub enum Gravity {
+    #[default]
+    Center,
+    Faces,
+}
+
+/// Get the size of the largest crop of the image with the aspect ratio (width, height).
+pub fn crop_size(size: ImgSize, ratio: (u32, u32)) -> ImgSize {
+    let ratio = ImgSize::new(ratio.0 as usize, ratio.1 as usize);
+    // 幅に合わせると高さが足りない場合は高さに合わせる
+    let crop = if size.aspect_ratio() <= ratio.aspect_ratio() {
+        ratio.scaled_to_width(size.width)
+    }
+    else {
+        ratio.scaled_to_height(size.height)
+    };
+    ImgSize::new(crop.width.max(1), crop.height.max(1))
+}
+
+/// Trim offset of one axis.
+/// - Start: Offset from the left/top edge.
+/// - End: Offset from the right/bottom edge (negative value in the trim option).
+/// - Center: Centered on the axis.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TrimOffset {
+    Start(u32),
+    End(u32),
+    Center,
+}
+impl TrimOffset {
+    /// Resolve the offset to an absolute coordinate for the given image length and trim length.
+    fn resolve(&self, image_len: usize, trim_len: u32) -> u32 {
+        let image_len = image_len as u32;
+        match self {
+            TrimOffset::Start(v) => *v,
+            TrimOffset::End(v) => image_len.saturating_sub(*v),
+            TrimOffset::Center => image_len.saturating_sub(trim_len) / 2,
+        }
+    }
+}
+
+/// Trim specification.
+/// The trim area is resolved to a librusimg::Rect for each image, because relative offsets and percentages depend on the image size.
+/// - Pixels: Offsets (x, y) and size (w, h) of the trim area in pixels.
+/// - Percent: Offsets and size of the trim area in percent of the image width/height, so that the same area is trimmed from images of different sizes.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TrimSpec {
+    Pixels { x: TrimOffset, y: TrimOffset, w: u32, h: u32 },
+    Percent { x: f32, y: f32, w: f32, h: f32 },
+}
+impl TrimSpec {
+    /// Resolve the trim specification to a librusimg::Rect for the given image size.
+    pub fn resolve(&self, size: ImgSize) -> Rect {
+        match *self {
+            TrimSpec::Pixels { x, y, w, h } => Rect {
+                x: x.resolve(size.width, w),
+                y: y.resolve(size.height, h),
+                w: w,
+                h: h,
+            },
+            TrimSpec::Percent { x, y, w, h } => Rect::from_percent(x, y, w, h, size),
+        }
+    }
+}
+
+/// Parse an offset of the trim option.
+/// A negative value means an offset from the right/bottom edge.
+fn parse_trim_offset(s: &str) -> Result<TrimOffset, String> {
+    if let Some(v) = s.strip_prefix('-') {
+        Ok(TrimOffset::End(v.parse().map_err(|e: std::num::ParseIntError| e.to_string())?))
+    }
+    else {
+        Ok(TrimOffset::Start(s.parse().map_err(|e: std::num::ParseIntError| e.to_string())?))
+    }
+}
+
+/// Parse a percentage of the trim option (0.0 <= percent <= 100.0).
+fn parse_trim_percent(s: &str) -> Result<f32, ArgError> {
+    let percent = s.parse::<f32>().map_err(|e| ArgError::FailedToParseTrim(e.to_string()))?;
+    if !(0.0..=100.0).contains(&percent) {
+        return Err(ArgError::FailedToParseTrim(format!("{}% is out of range (0% - 100%)", s)));
+    }
+    Ok(percent)
+}
+
+/// Parse the trim option.
+/// Accepted formats:
+/// - 'XxY+W+H' (e.g. 100x100+50x50)
+/// - 'XxY+W+H' with negative offsets from the right/bottom edges (e.g. -200x-200+200x200)
+/// - 'X%xY%+W%xH%' in percent of the image size (e.g. 10%x10%+50%x50%)
+/// - 'center:WxH' (e.g. center:800x600)
+fn parse_trim(trim: &str) -> Result<TrimSpec, ArgError> {
+    let re_center = Regex::new(r"^center:(\d+)x(\d+)$").unwrap();
+    let re_percent = Regex::new(r"^(\d+(?:\.\d+)?)%x(\d+(?:\.\d+)?)%\+(\d+(?:\.\d+)?)%x(\d+(?:\.\d+)?)%$").unwrap();
+    let re_rect = Regex::new(r"(-?\d+)x(-?\d+)\+(\d+)x(\d+)").unwrap();
+    if let Some(captures) = re_center.captures(trim) {
+        let w = captures.get(1).unwrap().as_str().parse().map_err(|e: std::num::ParseIntError| ArgError::FailedToParseTrim(e.to_string()))?;
+        let h = captures.get(2).unwrap().as_str().parse().map_err(|e: std::num::ParseIntError| ArgError::FailedToParseTrim(e.to_string()))?;
+        Ok(TrimSpec::Pixels { x: TrimOffset::Center, y: TrimOffset::Center, w, h })
+    }
+    else if let Some(captures) = re_percent.captures(trim) {
+        let x = parse_trim_percent(captures.get(1).unwrap().as_str())?;
+        let y = parse_trim_percent(captures.get(2).unwrap().as_str())?;
+        let w = parse_trim_percent(captures.get(3).unwrap().as_str())?;
+        let h = parse_trim_percent(captures.get(4).unwrap().as_str())?;
+        Ok(TrimSpec::Percent { x, y, w, h })
+    }
+    else if let Some(captures) = re_rect.captures(trim) {
+        let x = parse_trim_offset(captures.get(1).unwrap().as_str()).map_err(ArgError::FailedToParseTrim)?;
+        let y = parse_trim_offset(captures.get(2).unwrap().as_str()).map_err(ArgError::FailedToParseTrim)?;
+        let w = captures.get(3).unwrap().as_str().parse().map_err(|e: std::num::ParseIntError| ArgError::FailedToParseTrim(e.to_string()))?;
+        let h = captures.get(4).unwrap().as_str().parse().map_err(|e: std::num::ParseIntError| ArgError::FailedToParseTrim(e.to_string()))?;
+        Ok(TrimSpec::Pixels { x, y, w, h })
+    }
+    else {
+        Err(ArgError::InvalidTrimFormat)
+    }
+}
+
+/// Parse an area with an optional strength (--pixelate, --blur-region): 'AREA[:N]', where AREA is in the format of the trim option.
+/// Returns the area and the strength, or the default strength if it is omitted.
+/// 'center:WxH' also contains ':', so the suffix is the strength only if it is a number.
+fn parse_area_with<T: std::str::FromStr>(value: &str, default: T) -> Result<(TrimSpec, T), ArgError> {
+    match value.rsplit_once(':').map(|(area, strength)| (area, strength.parse::<T>())) {
+        Some((area, Ok(strength))) => Ok((parse_trim(area)?, strength)),
+        _ => Ok((parse_trim(value)?, default)),
+    }
+}
+
+/// Parse the crop ratio option ('W:H', e.g. 1:1, 16:9).
+fn parse_crop_ratio(ratio: &str) -> Result<(u32, u32), ArgError> {
+    let (width, height) = ratio.split_once(':').ok_or(ArgError::InvalidCropRatio)?;
+    let width = width.trim().parse::<u32>().map_err(|_| ArgError::InvalidCropRatio)?;
+    let height = height.trim().parse::<u32>().map_err(|_| ArgError::InvalidCropRatio)?;
+    if width == 0 || height == 0 {
+        return Err(ArgError::InvalidCropRatio);
+    }
+    Ok((width, height))
+}
+
+/// Pages of multi-page sources selected by --pages.
+/// - ranges: Ranges of page numbers starting from 1 (first, last). The last page of an open range (e.g. 5-) is None.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PageSelection {
+    pub ranges: Vec<(usize, Option<usize>)>,
+}
+
+impl PageSelection {
+    /// Check if the page is selected.
+    pub fn contains(&self, page: usize) -> bool {
+        self.ranges.iter().any(|(first, last)| page >= *first && last.is_none_or(|last| page <= last))
+    }
+}
+
+/// Parse the pages option (page numbers and ranges separated by commas, e.g. 1-3,7 or 5-).
+fn parse_pages(pages: &str) -> Result<PageSelection, ArgError> {
+    let number = |n: &str| n.trim().parse::<usize>().ok().filter(|n| *n >= 1).ok_or(ArgError::InvalidPages);
+    let mut ranges = Vec::new();
+    for range in pages.split(',') {
+        let (first, last) = match range.split_once('-') {
+            Some((first, last)) if last.trim().is_empty() => (number(first)?, None),
+            Some((first, last)) => (number(first)?, Some(number(last)?)),
+            None => (number(range)?, Some(number(range)?)),
+        };
+        if last.is_some_and(|last| last < first) {
+            return Err(ArgError::InvalidPages);
+        }
+        ranges.push((first, last));
+    }
+    Ok(PageSelection { ranges: ranges })
+}
+
+/// Resize specification.
+/// - Ratio: Resize ratio in percent.
+/// - Pixels: Exact size in pixels, computed from a physical size and DPI.
+/// - Fit: Shrink to fit within the size in pixels, keeping the aspect ratio (e.g. the screen of a device). Smaller images are not enlarged.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ResizeSpec {
+    Ratio(f32),
+    Pixels { width: u32, height: u32 },
+    Fit { width: u32, height: u32 },
+}
+
+/// Filter used for upscaling.
+/// - Lanczos: Lanczos3 filter.
+/// - Sharp: Lanczos3 filter followed by a sharpening pass.
+/// - PixelArt: Nearest neighbor filter that keeps hard pixel edges.
+#[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq)]
+pub enum UpscaleFilter {
+    Lanczos,
+    Sharp,
+    PixelArt,
+}
+
+/// Tone mapping operator of HDR sources (see librusimg::tonemap::ToneMap).
+#[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq)]
+pub enum Tonemap {
+    Reinhard,
+    Aces,
+}
+impl Tonemap {
+    /// Get the operator of the library.
+    pub fn operator(&self) -> ToneMap {
+        match self {
+            Tonemap::Reinhard => ToneMap::Reinhard,
+            Tonemap::Aces => ToneMap::Aces,
+        }
+    }
+}
+
+/// Order of processing files.
+/// - Name: By file path.
+/// - Mtime: By modification time (oldest first).
+/// - Size: By file size (largest first).
+/// - None: In the order of discovery.
+#[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq)]
+pub enum SortOrder {
+    Name,
+    Mtime,
+    Size,
+    None,
+}
+
+/// Graphics protocol of the terminal preview.
+/// - Auto: The kitty or iTerm graphics protocol if the terminal supports it, otherwise colored half blocks.
+/// - Blocks: Always colored half blocks.
+#[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq)]
+pub enum ViewProtocol {
+    Auto,
+    Blocks,
+}
+
+/// White balance correction.
+/// - Auto: Gray-world assumption (the average color of the image is neutral gray).
+/// - Temperature: Color temperature of the light in Kelvin, and the tint (-100 to 100; positive removes green, negative removes magenta).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum WhiteBalance {
+    Auto,
+    Temperature { kelvin: u32, tint: i32 },
+}
+
+/// Parse the white balance option.
+/// Accepted formats: 'auto', '5500K', '5500K:+10'
+fn parse_white_balance(wb: &str) -> Result<WhiteBalance, ArgError> {
+    let wb = wb.trim().to_ascii_lowercase();
+    if wb == "auto" {
+        return Ok(WhiteBalance::Auto);
+    }
+    let re = Regex::new(r"^(\d+)k(?::([+-]?\d+))?$").unwrap();
+    let captures = re.captures(&wb).ok_or(ArgError::InvalidWhiteBalance)?;
+    let kelvin = captures.get(1).unwrap().as_str().parse::<u32>().map_err(|_| ArgError::InvalidWhiteBalance)?;
+    let tint = match captures.get(2) {
+        Some(t) => t.as_str().parse::<i32>().map_err(|_| ArgError::InvalidWhiteBalance)?,
+        None => 0,
+    };
+    if !(1000..=40000).contains(&kelvin) || !(-100..=100).contains(&tint) {
+        return Err(ArgError::InvalidWhiteBalance);
+    }
+    Ok(WhiteBalance::Temperature { kelvin, tint })
+}
+
+/// Convert a physical length to inches.
+fn length_to_inch(value: f64, unit: &str) -> f64 {
+    match unit {
+        "cm" => value / 2.54,
+        "mm" => value / 25.4,
+        _ => value,     // in
+    }
+}
+
+/// Parse the specs of --curve into the curves of all channels and of each channel.
+/// A channel specified twice is an error, as the curves would silently replace each other.
+fn parse_curves(specs: &[String]) -> Result<Curves, ArgError> {
+    let mut curves = Curves::default();
+    let mut specified = Vec::new();
+    for spec in specs {
+        let (channel, points) = match spec.split_once(':') {
+            Some((channel, points)) => (channel.trim().to_lowercase(), points),
+            None => ("rgb".to_string(), spec.as_str()),
+        };
+        if specified.contains(&channel) {
+            return Err(ArgError::InvalidCurve(format!("the curve of '{}' is specified twice", channel)));
+        }
+        let curve = Curve::parse(points).map_err(ArgError::InvalidCurve)?;
+        match channel.as_str() {
+            "rgb" => curves.rgb = curve,
+            "r" => curves.red = curve,
+            "g" => curves.green = curve,
+            "b" => curves.blue = curve,
+            _ => return Err(ArgError::InvalidCurve(format!("unknown channel '{}'", channel))),
+        }
+        specified.push(channel);
+    }
+    Ok(curves)
+}
+
+/// Parse the size of the terminal preview (e.g. 80x24, 80).
+fn parse_view_size(size: &str) -> Result<(u32, Option<u32>), ArgError> {
+    let (columns, rows) = match size.split_once(['x', 'X']) {
+        Some((columns, rows)) => (columns, Some(rows)),
+        None => (size, None),
+    };
+    let columns = columns.trim().parse::<u32>().map_err(|_| ArgError::InvalidViewSize)?;
+    let rows = match rows {
+        Some(rows) => Some(rows.trim().parse::<u32>().map_err(|_| ArgError::InvalidViewSize)?),
+        None => None,
+    };
+    if columns == 0 || rows == Some(0) {
+        return Err(ArgError::InvalidViewSize);
+    }
+    Ok((columns, rows))
+}
+
+/// Parse the resize option.
+/// Returns the resize specification and the DPI used for the physical size.
+/// Accepted formats:
+/// - Ratio in percent (e.g. 50, 12.5)
+/// - Physical size with DPI (e.g. '10cmx15cm@300dpi', '4inx6in'). Units: cm, mm, in.
+///   If '@DPI' is omitted, the value of --dpi is used.
+fn parse_resize(resize: &str, dpi: Option<u32>) -> Result<(ResizeSpec, Option<u32>), ArgError> {
+    let resize = resize.split_whitespace().collect::<String>().to_ascii_lowercase();
+    if let Ok(ratio) = resize.parse::<f32>() {
+        if !ratio.is_finite() || ratio <= 0.0 {
+            return Err(ArgError::InvalidResize);
+        }
+        return Ok((ResizeSpec::Ratio(ratio), None));
+    }
+
+    let re = Regex::new(r"^(\d+(?:\.\d+)?)(cm|mm|in)x(\d+(?:\.\d+)?)(cm|mm|in)(?:@(\d+)dpi)?$").unwrap();
+    let captures = re.captures(&resize).ok_or(ArgError::InvalidResize)?;
+    let dpi = match captures.get(5) {
+        Some(d) => d.as_str().parse::<u32>().map_err(|_| ArgError::InvalidDpi)?,
+        None => dpi.ok_or(ArgError::InvalidResize)?,
+    };
+    if dpi == 0 {
+        return Err(ArgError::InvalidDpi);
+    }
+    let width = length_to_inch(captures.get(1).unwrap().as_str().parse().map_err(|_| ArgError::InvalidResize)?, captures.get(2).unwrap().as_str());
+    let height = length_to_inch(captures.get(3).unwrap().as_str().parse().map_err(|_| ArgError::InvalidResize)?, captures.get(4).unwrap().as_str());
+    let (width, height) = ((width * dpi as f64).round() as u32, (height * dpi as f64).round() as u32);
+    if width == 0 || height == 0 {
+        return Err(ArgError::InvalidResize);
+    }
+    Ok((ResizeSpec::Pixels { width, height }, Some(dpi)))
+}
+
+/// Calculate the edit distance between two strings.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b = b.chars().collect::<Vec<_>>();
+    let mut prev = (0..=b.len()).collect::<Vec<_>>();
+    for (i, ca) in a.chars().enumerate() {
+        let mut cur = vec![i + 1; b.len() + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let cost = if ca == *cb { 0 } else { 1 };
+            cur[j + 1] = (prev[j] + cost).min(prev[j + 1] + 1).min(cur[j] + 1);
+        }
+        prev = cur;
+    }
+    prev[b.len()]
+}
+
+/// Check the format of the convert option.
+/// The format is returned as written (without the leading dot), so that --out-extension-case keep can keep its case.
+/// If the format is not supported, the closest supported format is suggested.
+fn parse_format(format: &str) -> Result<String, ArgError> {
+    let format = format.trim_start_matches('.');
+    if librusimg::Extension::from_extension(format).is_ok() {
+        return Ok(format.to_string());
+    }
+    let format = format.to_ascii_lowercase();
+    let suggestion = supported_formats().into_iter()
+        .map(|f| (edit_distance(&format, f), f))
+        .filter(|(d, _)| *d <= 2)
+        .min_by_key(|(d, _)| *d)
+        .map(|(_, f)| f.to_string());
+    Err(ArgError::UnsupportedFormat(format, suggestion))
+}
+
 /// Argument structure
 /// souce_path: Option<Vec<PathBuf>>: Source file path (file name or directory path)
 /// destination_path: Option<PathBuf>: Destination file path (file name or directory path)
 /// destination_extension: Option<String>: Destination file extension (e.g. jpeg, png, webp, bmp)
 /// destination_append_name: Option<String>: Name to be appended to the source file name (e.g. image.jpg -> image_output.jpg)
+/// sequence: Option<String>: Name template to number output files sequentially (e.g. IMG_{n:04})
+/// organize_by_date: Option<String>: Template of the subdirectories of the capture date (e.g. {year}/{month})
+/// out_per_format: Option<String>: Template of the output directory of each format (e.g. dist/{format}/)
+/// flatten: bool: Put all outputs directly into the output directory (default: false)
+/// out_extension_case: ExtensionCase: Case of the extensions of the output files (default: keep)
+/// dedupe_names: Option<DedupeMode>: Make duplicate output names unique when flattening (parent or hash)
+/// in_place: bool: Overwrite the source files with an atomic replace (default: false)
+/// sort: SortOrder: Order of processing files (default: none)
+/// limit: Option<usize>: Process only the first N files
+/// sample: Option<usize>: Process a random subset of N files
+/// seed: Option<u64>: Seed of the random subset (for reproducible samples)
 /// recursive: bool: Recusive search (default: false)
+/// no_ignore: bool: Do not respect .gitignore, .ignore and .rusimgignore (default: false)
+/// mmap: bool: Map large source files into memory instead of reading them (default: false)
 /// quality: Option<f32>: Image quality (for compress, must be 0.0 <= q <= 100.0)
+/// progressive: bool: Save as progressive JPEG (default: false)
+/// effort: Option<u8>: Compression effort for png and webp (must be 0 <= effort <= 100)
+/// total_budget: Option<u64>: Total size of the outputs in bytes; the quality of each jpeg/webp file is chosen to fit in it
+/// quality_search: Option<f64>: Minimum SSIM of jpeg/webp output; the lowest quality that meets it is chosen
+/// interlace: bool: Save as interlaced (Adam7) PNG (default: false)
 /// delete: bool: Delete source file (default: false)
-/// resize: Option<u8>: Resize images in parcent (must be 0 < size)
-/// trim: Option<Rect>: Trim image. trim: librusimg::Rect { x: u32, y: u32, w: u32, h: u32 }
+/// preserve_xattrs: bool: Copy the extended attributes and the ACL of the source file to the output (default: false)
+/// checksum: Option<ChecksumAlgorithm>: Compute the checksums of the outputs
+/// checksum_manifest: Option<PathBuf>: Write the checksums to one manifest file instead of a file next to each output
+/// zip: Option<PathBuf>: Write the outputs to a zip archive as well
+/// zip_password: Option<String>: Encrypt the zip archive with AES-256 using the password
+/// pdf: Option<PathBuf>: Write the outputs to a PDF document as well
+/// picture_manifest: Option<PathBuf>: Write a manifest of the outputs of each source for <picture> elements (JSON or HTML)
+/// resize: Option<ResizeSpec>: Resize images in parcent (must be 0.0 < size) or to a physical size
+/// allow_upscale: bool: Allow resizing to a larger size (default: false)
+/// upscale_filter: UpscaleFilter: Filter used for upscaling (default: lanczos)
+/// dpi: Option<u32>: Output density in DPI (must be 0 < dpi)
+/// social: Option<SocialPreset>: Crop and resize to the recommended size of a social media platform
+/// crop_ratio: Option<(u32, u32)>: Crop the largest area with the aspect ratio (width, height)
+/// gravity: Gravity: Placement of the crops of --crop-ratio and --social (default: center)
+/// face_model: Option<PathBuf>: SeetaFace model file for Gravity::Faces
+/// trims: Vec<TrimSpec>: Trim areas (--trim, --trim-file). Resolved to librusimg::Rect { x: u32, y: u32, w: u32, h: u32 } for each image. With multiple areas, one output is written per area
+/// tonemap: ToneMap: Tone mapping operator of HDR sources (default: aces)
+/// exposure: f32: Multiplier of the light of HDR sources before tone mapping (default: 1.0)
+/// colorspace: Option<ColorSpace>: Convert the colors from the color space of the source to this color space
+/// redact: Vec<TrimSpec>: Areas to hide before the other operations (--redact)
+/// redact_qr: bool: Hide the detected QR codes (default: false)
+/// redact_style: RedactStyle: How the areas are hidden (default: blur)
+/// pixelate: Vec<(TrimSpec, u32)>: Areas to pixelate and their block sizes (--pixelate)
+/// blur_regions: Vec<(TrimSpec, f32)>: Areas to blur and their sigmas (--blur-region)
+/// autocrop: Option<u8>: Remove uniform-color borders with the given tolerance (0-255)
+/// skip_blank: Option<f64>: Skip images whose brightness standard deviation is below the threshold (blank scans)
+/// deskew: bool: Straighten skewed scans (default: false)
+/// vignette: Option<f32>: Radial gain adjustment (-1.0 <= amount <= 1.0; negative removes, positive adds a vignette)
+/// white_balance: Option<WhiteBalance>: White balance correction (auto or color temperature)
+/// auto_levels: Option<f32>: Stretch the histogram of each channel, clipping the given percentage of pixels (0.0 <= clip < 50.0)
+/// gamma: Option<f32>: Gamma correction (> 0.0)
+/// pages: Option<PageSelection>: Pages of multi-page sources to process. If None, all pages
+/// curves: Option<Curves>: Tonal curves of all channels and of each channel (--curve)
+/// lut: Option<Arc<Lut3d>>: 3D LUT (.cube) to apply to the colors. Loaded once and shared by the threads
 /// grayscale: bool: Grayscale image (default: false)
+/// edges: bool: Replace the image with its edges (default: false)
+/// threshold: Option<Threshold>: Binarize the image to black and white by Otsu's method, the adaptive threshold or a level
+/// watermark: Option<(String, String)>: Message and key of the invisible watermark
+/// depth: Option<u8>: Reduce the bit depth of png and bmp output with dithering (8, 4 or 1)
 /// view: bool: View result in the comand line (default: false)
+/// open: Option<usize>: Open the first N outputs in the default image viewer of the OS
+/// notify: bool: Send a desktop notification when the batch completes or fails (default: false)
+/// progress: Option<ProgressTarget>: Write progress events as JSON lines to a file or a file descriptor
+/// view_size: Option<(u32, Option<u32>)>: Maximum size of the preview in terminal cells (columns, rows). If None, the terminal size is used
+/// view_protocol: ViewProtocol: Graphics protocol of the preview (default: auto)
+/// interactive: bool: Select images and adjust the quality interactively before processing (default: false)
+/// files_from: Option<PathBuf>: Read the list of source files from a file ('-' for stdin)
+/// null: bool: The file list is separated by NUL characters instead of newlines (default: false)
+/// map: Option<PathBuf>: Mapping file (csv or json) listing the files to process with per-file options
 /// yes: bool: Yes to all (default: false) to overwrite files
 /// no: bool: No to all (default: false) to overwrite files
 /// threads: u8: Number of threads (default: 4)
+/// encoder_threads: Option<usize>: Maximum number of threads used by each encoder (png, webp)
+/// temp_dir: Option<PathBuf>: Directory for the intermediate files. If None, the temporary directory of the OS is used
+/// formats: bool: Show the supported formats and exit (default: false)
+/// version: bool: Show the version and exit (default: false)
+/// verbose: bool: With version, also show the build information (default: false)
+/// mode: Mode: What to do, processing images or running a subcommand (default: Process)
+/// lang: Option<String>: Language of the messages (en, ja). If not specified, the LANG environment variable is used.
 #[derive(Debug, Clone)]
 pub struct ArgStruct {
     pub souce_path: Option<Vec<PathBuf>>,
     pub destination_path: Option<PathBuf>,
     pub destination_extension: Option<String>,
     pub destination_append_name: Option<String>,
+    pub sequence: Option<String>,
+    pub organize_by_date: Option<String>,
+    pub out_per_format: Option<String>,
+    pub flatten: bool,
+    pub out_extension_case: ExtensionCase,
+    pub dedupe_names: Option<DedupeMode>,
+    pub in_place: bool,
+    pub sort: SortOrder,
+    pub limit: Option<usize>,
+    pub sample: Option<usize>,
+    pub seed: Option<u64>,
     pub recursive: bool,
+    pub no_ignore: bool,
+    pub mmap: bool,
     pub quality: Option<f32>,
+    pub progressive: bool,
+    pub effort: Option<u8>,
+    pub total_budget: Option<u64>,
+    pub quality_search: Option<f64>,
+    pub interlace: bool,
     pub delete: bool,
-    pub resize: Option<u8>,
-    pub trim: Option<Rect>,
+    pub preserve_xattrs: bool,
+    pub checksum: Option<ChecksumAlgorithm>,
+    pub checksum_manifest: Option<PathBuf>,
+    pub zip: Option<PathBuf>,
+    pub zip_password: Option<String>,
+    pub pdf: Option<PathBuf>,
+    pub picture_manifest: Option<PathBuf>,
+    pub resize: Option<ResizeSpec>,
+    pub allow_upscale: bool,
+    pub upscale_filter: UpscaleFilter,
+    pub dpi: Option<u32>,
+    pub trims: Vec<TrimSpec>,
+    pub tonemap: ToneMap,
+    pub exposure: f32,
+    pub colorspace: Option<ColorSpace>,
+    pub redact: Vec<TrimSpec>,
+    pub redact_qr: bool,
+    pub redact_style: RedactStyle,
+    pub pixelate: Vec<(TrimSpec, u32)>,
+    pub blur_regions: Vec<(TrimSpec, f32)>,
+    pub social: Option<SocialPreset>,
+    pub crop_ratio: Option<(u32, u32)>,
+    pub gravity: Gravity,
+    pub face_model: Option<PathBuf>,
+    pub autocrop: Option<u8>,
+    pub skip_blank: Option<f64>,
+    pub deskew: bool,
+    pub vignette: Option<f32>,
+    pub white_balance: Option<WhiteBalance>,
+    pub auto_levels: Option<f32>,
+    pub gamma: Option<f32>,
+    pub pages: Option<PageSelection>,
+    pub curves: Option<Curves>,
+    pub lut: Option<Arc<Lut3d>>,
     pub grayscale: bool,
+    pub edges: bool,
+    pub threshold: Option<Threshold>,
+    pub watermark: Option<(String, String)>,
+    pub depth: Option<u8>,
     pub view: bool,
+    pub view_size: Option<(u32, Option<u32>)>,
+    pub open: Option<usize>,
+    pub notify: bool,
+    pub progress: Option<ProgressTarget>,
+    pub view_protocol: ViewProtocol,
+    pub interactive: bool,
+    pub files_from: Option<PathBuf>,
+    pub null: bool,
+    pub map: Option<PathBuf>,
     pub yes: bool,
     pub no: bool,
     pub double_extension: bool,
     pub threads: u8,
+    pub encoder_threads: Option<usize>,
+    pub temp_dir: Option<PathBuf>,
+    pub formats: bool,
+    pub version: bool,
+    pub verbose: bool,
+    pub mode: Mode,
+    pub lang: Option<String>,
+}
+
+/// Mode of a run: processing images, or one of the subcommands.
+/// - Process: Process the source images with the options (no subcommand).
+/// - Bench: Run the benchmark (hidden subcommand).
+/// - PreviewQuality: Write a comparison sheet of qualities (preview-quality subcommand).
+/// - ShellIntegration: Install or uninstall the context-menu entries (install-shell-integration subcommand).
+/// - Appicon: Generate an icon set (appicon subcommand).
+/// - SplitScan: Split scans into the photos on them (split-scan subcommand).
+/// - VerifyWatermark: Read the watermarks of images (verify-watermark subcommand).
+/// - ToGif: Assemble images into an animation (to-gif subcommand).
+/// - Check: Decode images to find corrupt files (check subcommand).
+#[derive(Debug, Clone)]
+pub enum Mode {
+    Process,
+    Bench(BenchOptions),
+    PreviewQuality(PreviewOptions),
+    ShellIntegration(ShellAction),
+    Appicon(AppIconOptions),
+    SplitScan(SplitScanOptions),
+    VerifyWatermark(VerifyWatermarkOptions),
+    ToGif(ToGifOptions),
+    Check(CheckOptions),
+}
+
+/// Subcommands
+/// - Bench: Measure the processing speed on this machine (hidden).
+/// - PreviewQuality: Compare the output of an image at several qualities.
+/// - InstallShellIntegration: Register the context-menu entries of the file manager.
+/// - Appicon: Generate the icon set of an app.
+/// - SplitScan: Save the photos on flatbed scans.
+/// - VerifyWatermark: Read the invisible watermarks of images.
+/// - ToGif: Assemble images into an animation.
+/// - Check: Find corrupt and truncated images.
+#[derive(clap::Subcommand, Debug)]
+enum Command {
+    /// Measure decode, resize, compress and save speed on this machine.
+    #[command(hide = true)]
+    Bench {
+        /// Image used for the measurement. If not specified, a synthetic image is used.
+        image: Option<PathBuf>,
+
+        /// Number of iterations of each measurement.
+        #[arg(long, default_value_t = DEFAULT_BENCH_ITERATIONS)]
+        iterations: u32,
+    },
+    /// Write a labeled comparison sheet of an image encoded at several qualities, to pick a quality before batch processing.
+    PreviewQuality {
+        /// Image to be compared.
+        image: PathBuf,
+
+        /// Qualities to be compared, separated by commas (e.g. 60,70,80,90).
+        #[arg(long, value_delimiter = ',', default_values_t = preview::DEFAULT_QUALITIES)]
+        qualities: Vec<f32>,
+
+        /// Path of the sheet (png). If not specified, <image name>_qualities.png next to the image.
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+
+        /// Show the sheet in the command line.
+        #[arg(short, long)]
+        view: bool,
+    },
+    /// Register "Convert to WebP" and "Compress" context-menu entries in the file manager (Explorer, Finder, Dolphin, Nautilus).
+    InstallShellIntegration {
+        /// Remove the entries instead.
+        #[arg(long)]
+        uninstall: bool,
+    },
+    /// Generate the full set of app icons (sizes, file names and Contents.json / manifest) for a platform.
+    Appicon {
+        /// Source image (square, 1024x1024 or larger is recommended).
+        image: PathBuf,
+
+        /// Platform of the icon set.
+        #[arg(long, value_enum)]
+        target: IconTarget,
+
+        /// Output directory. If not specified, <image name>-<target>-icons next to the image.
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Detect the photos on flatbed scans and save each of them, straightened and cropped, as <scan name>_<n>.
+    SplitScan {
+        /// Scans (file paths or wildcard patterns, e.g. "*.tiff").
+        #[arg(required = true)]
+        images: Vec<PathBuf>,
+
+        /// Output directory. If not specified, the directory of each scan.
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+
+        /// Format of the outputs. If not specified, the format of the scan (png for formats other than bmp, jpeg, png and webp, e.g. tiff).
+        #[arg(short, long)]
+        convert: Option<String>,
+
+        /// Allowed difference of each channel from the background color (0-255).
+        #[arg(long, default_value_t = split::DEFAULT_TOLERANCE)]
+        tolerance: u8,
+
+        /// Minimum area of a photo in percent of the scan. Smaller regions (e.g. dust) are ignored.
+        #[arg(long, default_value_t = split::DEFAULT_MIN_AREA)]
+        min_area: f32,
+
+        /// Do not straighten the photos.
+        #[arg(long)]
+        no_deskew: bool,
+    },
+    /// Read the invisible watermarks (--watermark) of images. Exits with an error if any image has no watermark.
+    VerifyWatermark {
+        /// Images to verify (file paths or wildcard patterns).
+        #[arg(required = true)]
+        images: Vec<PathBuf>,
+
+        /// Key of the watermark.
+        #[arg(long, env = "RUSIMG_WATERMARK_KEY", hide_env_values = true)]
+        key: String,
+
+        /// Also require the message of the watermark to be this message.
+        #[arg(long)]
+        expect: Option<String>,
+    },
+    /// Assemble images (e.g. processed frames) into an animation. The format is chosen by the extension of the output: gif, webp or png (APNG).
+    ToGif {
+        /// Frames (file paths or wildcard patterns, e.g. "frames/*.png"). They are ordered by name, with numbers compared by value (frame2 before frame10).
+        #[arg(required = true)]
+        frames: Vec<PathBuf>,
+
+        /// Path of the animation (.gif, .webp, .png or .apng).
+        #[arg(short, long)]
+        output: PathBuf,
+
+        /// Frames per second.
+        #[arg(long, default_value_t = animation::DEFAULT_FPS)]
+        fps: f32,
+
+        /// Number of times to play the animation. 0 is forever.
+        #[arg(long, default_value_t = 0)]
+        loops: u16,
+    },
+    /// Decode every image fully (all frames and pages) and report corrupt and truncated files, without writing anything.
+    /// Exits with an error if any image is corrupt.
+    Check {
+        /// Directories (searched recursively), images or wildcard patterns.
+        #[arg(required = true)]
+        paths: Vec<PathBuf>,
+
+        /// Number of images decoded at the same time.
+        #[arg(short='T', long, default_value_t = DEFAULT_THREADS as usize)]
+        threads: usize,
+
+        /// Maximum memory used to decode one image in MiB. Larger images are reported as skipped.
+        #[arg(long, default_value_t = check::DEFAULT_MEMORY_LIMIT)]
+        memory_limit: u64,
+
+        /// Save the decodable part of each corrupt or truncated jpeg as <name>_salvaged.jpg.
+        /// crop keeps only the recovered rows, pad keeps the size and fills the missing rows with gray.
+        #[arg(long, value_enum, num_args = 0..=1, default_missing_value = "crop")]
+        salvage: Option<SalvageMode>,
+
+        /// Directory of the salvaged files. If not specified, the directory of each source.
+        #[arg(short, long, requires = "salvage")]
+        output: Option<PathBuf>,
+    },
 }
 
 #[derive(clap::Parser, Debug)]
-#[command(author, version, about, long_about = None)]
+#[command(author, version, about, long_about = None, args_conflicts_with_subcommands = true, args_override_self = true, disable_version_flag = true)]
 struct Args {
+    #[command(subcommand)]
+    command: Option<Command>,
+
     /// Source file path (file name or directory path)
     source: Option<Vec<PathBuf>>,
 
@@ -71,6 +944,15 @@ struct Args {
     #[arg(long)]
     recursive: bool,
 
+    /// Do not exclude files matched by .gitignore, .ignore and .rusimgignore.
+    #[arg(long)]
+    no_ignore: bool,
+
+    /// Map large source files into memory instead of reading them.
+    /// Reduces the peak memory when many threads open large files at the same time.
+    #[arg(long)]
+    mmap: bool,
+
     /// Specify output directory or output file name. 
     #[arg(short, long)]
     output: Option<PathBuf>,
@@ -80,26 +962,264 @@ struct Args {
     #[arg(short, long)]
     append: Option<String>,
 
+    /// Rename output files sequentially in sorted order.
+    /// '{n}' is replaced with the sequence number, '{n:04}' with the zero-padded number (e.g. IMG_{n:04} -> IMG_0001.jpeg)
+    #[arg(long)]
+    sequence: Option<String>,
+
+    /// Put the outputs into subdirectories of the capture date (EXIF, or the modification time if not recorded).
+    /// '{year}', '{month}' and '{day}' are replaced with the date (e.g. {year}/{month} -> 2024/07/image.webp)
+    #[arg(long)]
+    organize_by_date: Option<String>,
+
+    /// Put the outputs into a directory for each output format, e.g. for web build pipelines that serve each format from its own folder.
+    /// '{format}' is replaced with the format (e.g. dist/{format}/ -> dist/webp/image.webp, dist/jpeg/image.jpg)
+    #[arg(long, conflicts_with = "output")]
+    out_per_format: Option<String>,
+
+    /// Put all outputs of a recursive tree directly into the output directory (-o).
+    /// Files with a name already used by another file are skipped, unless --dedupe-names is specified.
+    #[arg(long, requires = "output")]
+    flatten: bool,
+
+    /// Case of the extensions of the output files (lower: Photo.JPG -> Photo.jpg, keep: the source extension and -c as written)
+    #[arg(long, value_enum, default_value_t = ExtensionCase::Keep)]
+    out_extension_case: ExtensionCase,
+
+    /// Make duplicate names unique when flattening: parent appends the parent directory name, hash appends a hash of the content
+    #[arg(long, value_enum, requires = "flatten")]
+    dedupe_names: Option<DedupeMode>,
+
+    /// Overwrite the source files. Each file is saved next to the source and renamed over it, keeping its timestamps and permissions.
+    /// Without --in-place, rusimg refuses to overwrite a source when no output is given (-o, -a, --sequence, --organize-by-date or -c).
+    /// Converting a source to another format is refused.
+    #[arg(long, conflicts_with_all = ["output", "append", "sequence", "organize_by_date", "out_per_format", "out_extension_case", "delete"])]
+    in_place: bool,
+
+    /// Order of processing files (name, mtime, size, none).
+    /// size processes the largest files first. If --sequence is specified, none is treated as name.
+    #[arg(long, value_enum, default_value_t = SortOrder::None)]
+    sort: SortOrder,
+
+    /// Process only the first N files (after sorting), to try the options on a few files before the full run.
+    #[arg(long)]
+    limit: Option<usize>,
+
+    /// Process a random subset of N files. The files are processed in the same order as without --sample.
+    #[arg(long, conflicts_with = "limit")]
+    sample: Option<usize>,
+
+    /// Seed of the random subset of --sample, to pick the same files again.
+    #[arg(long, requires = "sample")]
+    seed: Option<u64>,
+
     /// Destination file extension (e.g. jpeg, png, webp, bmp).
+    /// 'auto' chooses png for line art and text (e.g. manga, document scans) and jpeg for photos, for each image.
     #[arg(short, long)]
     convert: Option<String>,
 
-    /// Resize images in parcent (must be 0 < size)
+    /// Resize images in parcent (must be 0.0 < size, e.g. 12.5)
+    /// or to a physical size with DPI (e.g. 10cmx15cm@300dpi; units: cm, mm, in)
     #[arg(short, long)]
-    resize: Option<u8>,
+    resize: Option<String>,
+
+    /// Allow resizing to a larger size (e.g. resize ratio > 100)
+    #[arg(long)]
+    allow_upscale: bool,
+
+    /// Filter used for upscaling (lanczos, sharp, pixel-art)
+    #[arg(long, value_enum, default_value_t = UpscaleFilter::Lanczos)]
+    upscale_filter: UpscaleFilter,
+
+    /// Set the output density in DPI (jpeg, png, bmp)
+    #[arg(long)]
+    dpi: Option<u32>,
 
     /// Trim image. Input format: 'XxY+W+H' (e.g.100x100+50x50)
-    #[arg(short, long)]
-    trim: Option<String>,
+    /// Negative X/Y are offsets from the right/bottom edges (e.g. -200x-200+200x200).
+    /// 'X%xY%+W%xH%' trims the same part of images of different sizes (e.g. 10%x10%+50%x50%).
+    /// 'center:WxH' trims the center of the image (e.g. center:800x600).
+    /// Specify it multiple times to write one output per area (e.g. -t 0x0+1200x900 -t 1200x0+1200x900 -> image_1, image_2).
+    #[arg(short, long, allow_hyphen_values = true)]
+    trim: Option<Vec<String>>,
+
+    /// Read the trim areas from a JSON file: an array of trim strings or {"x", "y", "w", "h"} objects in pixels.
+    /// One output is written per area.
+    #[arg(long, conflicts_with = "trim")]
+    trim_file: Option<PathBuf>,
+
+    /// Pages of multi-page sources (tiff, pdf) to process, e.g. 1-3,7 or 5- (default: all pages).
+    /// Each page is a separate output named <name>_p<page>.
+    #[arg(long)]
+    pages: Option<String>,
+
+    /// Crop the center and resize to the recommended size of a platform (og: 1200x630, twitter: 1600x900, instagram: 1080x1080)
+    /// The crop is placed by --gravity.
+    #[arg(long, value_enum, conflicts_with_all = ["trim", "trim_file", "resize"])]
+    social: Option<SocialPreset>,
+
+    /// Crop the largest area with an aspect ratio W:H (e.g. 1:1 for avatars, 16:9). The crop is placed by --gravity.
+    #[arg(long, conflicts_with_all = ["trim", "trim_file", "social"])]
+    crop_ratio: Option<String>,
+
+    /// Placement of the --crop-ratio and --social crops (center, faces).
+    /// 'faces' keeps the detected faces in the frame (requires the face-detect feature and --face-model).
+    #[arg(long, value_enum, default_value_t = Gravity::Center)]
+    gravity: Gravity,
+
+    /// SeetaFace model file for --gravity faces (seeta_fd_frontal_v1.0.bin).
+    #[arg(long, env = "RUSIMG_FACE_MODEL")]
+    face_model: Option<PathBuf>,
+
+    /// Tone mapping operator of HDR sources (exr, hdr): reinhard or aces.
+    /// HDR sources are tone mapped to 8-bit before the other operations, and saved as png unless --convert is specified.
+    #[arg(long, value_enum, default_value_t = Tonemap::Aces)]
+    tonemap: Tonemap,
+
+    /// Exposure of HDR sources: the light is multiplied by it before tone mapping (e.g. 2.0 is one stop brighter).
+    #[arg(long, default_value_t = 1.0)]
+    exposure: f32,
+
+    /// Convert the colors to the color space before the other operations.
+    /// The color space of the source is read from its ICC profile (e.g. Display P3 photos of phones), and images without a profile are sRGB.
+    #[arg(long, value_parser = ["srgb"])]
+    colorspace: Option<String>,
+
+    /// Hide an area of the source image before the other operations, in the format of --trim (e.g. 0x0+400x80, 80%x0%+20%x10%).
+    /// Specify it multiple times to hide multiple areas.
+    #[arg(long, allow_hyphen_values = true)]
+    redact: Option<Vec<String>>,
+
+    /// Hide the detected QR codes (requires the qr-detect feature).
+    #[arg(long)]
+    redact_qr: bool,
+
+    /// How --redact and --redact-qr hide the areas (blur, black).
+    #[arg(long, value_enum, default_value_t = RedactStyle::Blur)]
+    redact_style: RedactStyle,
+
+    /// Pixelate an area of the source image, e.g. to anonymize faces or license plates: 'AREA[:BLOCK]'.
+    /// AREA is in the format of --trim, and BLOCK is the size of the blocks in pixels (default: 16). e.g. 120x40+200x80:24
+    /// Specify it multiple times to pixelate multiple areas.
+    #[arg(long, allow_hyphen_values = true)]
+    pixelate: Option<Vec<String>>,
+
+    /// Blur an area of the source image with a gaussian blur: 'AREA[:SIGMA]'.
+    /// AREA is in the format of --trim, and SIGMA is the strength of the blur (default: 8.0). e.g. 10%x80%+30%x15%:12
+    /// Specify it multiple times to blur multiple areas.
+    #[arg(long, allow_hyphen_values = true)]
+    blur_region: Option<Vec<String>>,
+
+    /// Remove uniform-color borders (e.g. scan margins, letterbox bars).
+    /// Optionally specify the color tolerance (0-255, default: 10).
+    #[arg(long, num_args = 0..=1, default_missing_value = DEFAULT_AUTOCROP_TOLERANCE)]
+    autocrop: Option<u8>,
+
+    /// Skip blank images (e.g. empty pages in scan batches).
+    /// An image is blank if the standard deviation of its brightness is below the threshold (default: 2.0).
+    #[arg(long, num_args = 0..=1, default_missing_value = DEFAULT_BLANK_THRESHOLD)]
+    skip_blank: Option<f64>,
+
+    /// Straighten skewed scans (up to ±15 degrees).
+    #[arg(long)]
+    deskew: bool,
+
+    /// Preset for e-readers: fit the screen of the device, --grayscale, --gamma 1.2 and --convert auto.
+    /// Options specified explicitly take precedence (e.g. --resize). Use --no-grayscale to keep the colors (e.g. for color e-ink screens).
+    #[arg(long, value_enum)]
+    device: Option<DeviceProfile>,
+
+    /// Preset for receipts and paperwork (e.g. before OCR): --deskew, --autocrop, --grayscale and --threshold adaptive,
+    /// saved as 1-bit png (--convert png --depth 1) with --effort 100. Options specified explicitly take precedence. Combine it with --pdf to get one document.
+    #[arg(long)]
+    document: bool,
+
+    /// Darken (positive) or brighten (negative) the corners of the image (-1.0 <= amount <= 1.0).
+    /// Use a negative value to remove the vignetting of lenses and scanners (e.g. --vignette -0.3).
+    #[arg(long, allow_hyphen_values = true)]
+    vignette: Option<f32>,
+
+    /// White balance correction: 'auto' (gray world) or the color temperature of the light with an optional tint (e.g. 5500K, 5500K:+10).
+    #[arg(long)]
+    wb: Option<String>,
+
+    /// Stretch the histogram of each channel to the full range (for faded scans and underexposed photos).
+    /// Optionally specify the percentage of the darkest/brightest pixels to clip (0.0 <= clip < 50.0, default: 0.5).
+    #[arg(long, num_args = 0..=1, default_missing_value = DEFAULT_AUTO_LEVELS_CLIP)]
+    auto_levels: Option<f32>,
+
+    /// Gamma correction: a gamma above 1.0 brightens the midtones, and below 1.0 darkens them (e.g. 1.2 for e-ink screens).
+    #[arg(long)]
+    gamma: Option<f32>,
+
+    /// Apply a tonal curve through the control points INPUT,OUTPUT (0.0-1.0), e.g. "0,0 0.5,0.58 1,1" to brighten the midtones.
+    /// Prefix it with r:, g: or b: to apply it to one channel (e.g. "b:0,0.05 1,0.95"). Specify it multiple times to combine the curves.
+    #[arg(long)]
+    curve: Option<Vec<String>>,
+
+    /// Apply a 3D LUT file (.cube) to the colors for color grading (e.g. teal_orange.cube).
+    /// It is applied after the white balance and auto levels corrections.
+    #[arg(long)]
+    lut: Option<PathBuf>,
 
     /// Grayscale image
     #[arg(short, long)]
     grayscale: bool,
 
+    /// Keep the colors with --device, which converts the images to grayscale by default.
+    #[arg(long, conflicts_with = "grayscale")]
+    no_grayscale: bool,
+
+    /// Replace the image with its edges (Sobel operator): edges are white, flat areas black.
+    /// Combine it with --threshold to get a black and white line drawing.
+    #[arg(long)]
+    edges: bool,
+
+    /// Binarize the image to black and white for document scans: 'otsu' chooses the level from the histogram,
+    /// 'adaptive' compares each pixel with its surroundings (for uneven lighting), or specify the level (0-255).
+    /// Pixels brighter than the level become white. Combine it with --depth 1 to save 1-bit png files.
+    #[arg(long, num_args = 0..=1, default_missing_value = DEFAULT_THRESHOLD)]
+    threshold: Option<String>,
+
+    /// Embed an invisible watermark with this message (up to 32 bytes), e.g. the name of a client.
+    /// It is embedded after the other pixel operations, and survives JPEG compression at quality 75 and above. Read it with verify-watermark.
+    #[arg(long, requires = "watermark_key")]
+    watermark: Option<String>,
+
+    /// Key of --watermark. Only the same key can read the watermark.
+    #[arg(long, env = "RUSIMG_WATERMARK_KEY", hide_env_values = true)]
+    watermark_key: Option<String>,
+
+    /// Reduce the bit depth of png and bmp output with dithering (8: 256 colors, 4: 16 colors, 1: black and white)
+    #[arg(long)]
+    depth: Option<u8>,
+
     /// Image quality (for compress, must be 0.0 <= q <= 100.0)
     #[arg(short, long)]
     quality: Option<f32>,
 
+    /// Save as progressive JPEG
+    #[arg(long)]
+    progressive: bool,
+
+    /// Compression effort for png and webp (0 <= effort <= 100). Higher is smaller but slower.
+    #[arg(long)]
+    effort: Option<u8>,
+
+    /// Total size of all outputs (e.g. 200MB, 1.5GB, 500KiB). Files are processed largest first,
+    /// and the quality of each jpeg/webp output is chosen so that the whole batch fits in the budget. --quality is used as the maximum quality.
+    #[arg(long)]
+    total_budget: Option<String>,
+
+    /// Search the quality of each jpeg/webp output: the smallest file whose SSIM against the source meets the threshold is kept (e.g. ssim:0.95).
+    /// --quality is used as the maximum quality.
+    #[arg(long, conflicts_with = "total_budget")]
+    quality_search: Option<String>,
+
+    /// Save as interlaced (Adam7) PNG for progressive rendering
+    #[arg(long)]
+    interlace: bool,
+
     /// Set output file extension to double extension (e.g. image.jpg -> image.jpg.webp)
     #[arg(short, long)]
     double_extension: bool,
@@ -108,6 +1228,48 @@ struct Args {
     #[arg(short, long)]
     view: bool,
 
+    /// Open the first N outputs (default: 1) in the default image viewer of the OS
+    #[arg(long, num_args = 0..=1, default_missing_value = "1")]
+    open: Option<usize>,
+
+    /// Send a desktop notification with the summary when the batch completes or fails
+    #[arg(long)]
+    notify: bool,
+
+    /// Write progress events (started, finished, error) as JSON lines to the file
+    #[arg(long, conflicts_with = "progress_fd")]
+    progress_file: Option<PathBuf>,
+
+    /// Write progress events as JSON lines to the inherited file descriptor (e.g. 3, unix only)
+    #[arg(long)]
+    progress_fd: Option<i32>,
+
+    /// Maximum size of the preview in terminal cells: COLUMNSxROWS or COLUMNS (e.g. 80x24). Default: the terminal size
+    #[arg(long)]
+    view_size: Option<String>,
+
+    /// Graphics protocol of the preview (auto: kitty/iTerm if supported, blocks: colored characters)
+    #[arg(long, value_enum, default_value_t = ViewProtocol::Auto)]
+    view_protocol: ViewProtocol,
+
+    /// Select images, preview them and adjust the quality interactively before processing
+    #[arg(short, long)]
+    interactive: bool,
+
+    /// Read the list of source files from a file, one path per line ('-' for stdin).
+    /// The source paths on the command line are ignored. (e.g. find . -name '*.png' -print0 | rusimg --files-from - -0 -c webp)
+    #[arg(long, conflicts_with = "map")]
+    files_from: Option<PathBuf>,
+
+    /// The file list of --files-from is separated by NUL characters instead of newlines (like xargs -0).
+    #[arg(short = '0', long, requires = "files_from")]
+    null: bool,
+
+    /// Mapping file (csv or json) listing the files to process with per-file options (e.g. a different trim or quality per file).
+    /// Each row has a 'file' column and one column per option; the row options override the other options on the command line.
+    #[arg(long, conflicts_with = "interactive")]
+    map: Option<PathBuf>,
+
     /// Yes to all to overwrite files
     #[arg(short, long)]
     yes: bool,
@@ -120,65 +1282,449 @@ struct Args {
     #[arg(short='D', long)]
     delete: bool,
 
+    /// Copy the extended attributes (and the POSIX ACLs on Linux) or the ACL (Windows) of the source file to the output,
+    /// e.g. for permissioned workflows on shared drives.
+    #[arg(long)]
+    preserve_xattrs: bool,
+
+    /// Compute the checksum of each output and write it to <output>.sha256 (sha256sum format)
+    #[arg(long, value_enum)]
+    checksum: Option<ChecksumAlgorithm>,
+
+    /// Write the checksums of all outputs to one manifest file instead of a file next to each output
+    #[arg(long, requires = "checksum")]
+    checksum_manifest: Option<PathBuf>,
+
+    /// Write the outputs to a zip archive after all images are processed
+    #[arg(long)]
+    zip: Option<PathBuf>,
+
+    /// Write the outputs to a PDF document (one image per page) after all images are processed.
+    /// The size of the pages is taken from --dpi (default: 300).
+    #[arg(long)]
+    pdf: Option<PathBuf>,
+
+    /// Write a manifest mapping each source to its outputs with their widths, for <picture> elements and srcset attributes of static-site generators.
+    /// JSON by default (an existing manifest is merged, so the sizes and formats of several runs are collected); <picture> snippets if FILE ends with .html.
+    #[arg(long)]
+    picture_manifest: Option<PathBuf>,
+
+    /// Encrypt the zip archive with AES-256 (the RUSIMG_ZIP_PASSWORD environment variable keeps it out of the shell history)
+    #[arg(long, env = "RUSIMG_ZIP_PASSWORD", hide_env_values = true, requires = "zip")]
+    zip_password: Option<String>,
+
     /// Number of threads.
     #[arg(short='T', long, default_value_t = DEFAULT_THREADS)]
     threads: u8,
+
+    /// Maximum number of threads used by each encoder (png: oxipng, webp).
+    /// A single large PNG can use multiple cores even if only one file is processed.
+    #[arg(long)]
+    encoder_threads: Option<usize>,
+
+    /// Directory for the intermediate files (e.g. the trial encodes of --total-budget and --quality-search).
+    /// If not specified, the temporary directory of the OS is used.
+    #[arg(long)]
+    temp_dir: Option<PathBuf>,
+
+    /// Show the supported formats with the versions of the encoders/decoders, and exit.
+    #[arg(long)]
+    formats: bool,
+
+    /// Print version.
+    #[arg(short = 'V', long)]
+    version: bool,
+
+    /// With --version, also print the build information (git commit, target, features and encoder versions) for bug reports.
+    #[arg(long, requires = "version")]
+    verbose: bool,
+
+    /// Language of the messages (en, ja). If not specified, the LANG environment variable is used.
+    #[arg(long)]
+    lang: Option<String>,
 }
 
 pub fn parser() -> Result<ArgStruct, ArgError> {
     // Parse arguments.
-    let args = Args::parse();
+    check_args(Args::parse())
+}
 
-    // If trim option is specified, check the format.
-    let trim: Result<Option<librusimg::Rect>, String> = if args.trim.is_some() {
-        let re = Regex::new(r"(\d+)x(\d+)\+(\d+)x(\d+)").unwrap();
-        if let Some(captures) = re.captures(&args.trim.unwrap()) {
-            let x = captures.get(1).unwrap().as_str().parse().map_err(|e: std::num::ParseIntError| e.to_string()).unwrap();
-            let y = captures.get(2).unwrap().as_str().parse().map_err(|e: std::num::ParseIntError| e.to_string()).unwrap();
-            let w = captures.get(3).unwrap().as_str().parse().map_err(|e: std::num::ParseIntError| e.to_string()).unwrap();
-            let h = captures.get(4).unwrap().as_str().parse().map_err(|e: std::num::ParseIntError| e.to_string()).unwrap();
-            Ok(Some(Rect{x, y, w, h}))
-        }
-        else {
-            return Err(ArgError::InvalidTrimFormat);
-        }
+/// Parse the arguments with additional options for one file of a mapping file.
+/// The additional options are appended to the command line, so they override the options given on the command line.
+/// The trim areas of the file replace the ones on the command line, instead of being added to them.
+pub fn parser_with(extra_args: &[String]) -> Result<ArgStruct, ArgError> {
+    let command_line = std::env::args_os().chain(extra_args.iter().map(std::ffi::OsString::from));
+    let mut args = Args::try_parse_from(command_line).map_err(|e| ArgError::InvalidMapOptions(e.to_string()))?;
+
+    // --trim は複数指定で追加されるので、ファイルごとの指定だけを使う
+    let file_args = Args::try_parse_from(std::env::args_os().take(1).chain(extra_args.iter().map(std::ffi::OsString::from)))
+        .map_err(|e| ArgError::InvalidMapOptions(e.to_string()))?;
+    if file_args.trim.is_some() || file_args.trim_file.is_some() {
+        args.trim = file_args.trim;
+        args.trim_file = file_args.trim_file;
     }
-    else {
-        Ok(None)
+    check_args(args)
+}
+
+/// Fill the options of the --document preset that are not specified.
+/// The preset is a pipeline of the existing operations: deskew -> autocrop -> grayscale -> adaptive threshold -> 1-bit png.
+fn apply_document_preset(args: &mut Args) {
+    args.deskew = true;
+    args.autocrop.get_or_insert_with(|| DEFAULT_AUTOCROP_TOLERANCE.parse().unwrap());
+    args.grayscale = true;
+    args.threshold.get_or_insert_with(|| DOCUMENT_THRESHOLD.to_string());
+    let convert = args.convert.get_or_insert_with(|| "png".to_string());
+    // --depth は png と bmp の出力のみ (--convert jpeg などでは 8-bit のまま)
+    if ["png", "bmp"].iter().any(|format| convert.trim_start_matches('.').eq_ignore_ascii_case(format)) {
+        args.depth.get_or_insert(1);
+    }
+    args.effort.get_or_insert(DOCUMENT_EFFORT);
+}
+
+/// Fill the options of the --device preset that are not specified.
+/// The size of the screen is filled after --resize is parsed, as it is not a --resize string.
+fn apply_device_preset(args: &mut Args) {
+    // --no-grayscale -> Keep the colors (e.g. color e-ink screens).
+    args.grayscale = !args.no_grayscale;
+    args.gamma.get_or_insert(DEVICE_GAMMA);
+    args.convert.get_or_insert_with(|| AUTO_FORMAT.to_string());
+}
+
+/// Check the parsed arguments and convert them to ArgStruct.
+fn check_args(mut args: Args) -> Result<ArgStruct, ArgError> {
+    // --device -> Fill the options of the preset before checking them.
+    if args.device.is_some() {
+        apply_device_preset(&mut args);
+    }
+    // --document -> Fill the options of the preset before checking them.
+    if args.document {
+        apply_document_preset(&mut args);
+    }
+
+    // If trim options are specified, check the format.
+    // --trim-file -> Read the trim areas from the file.
+    let trims = match (&args.trim, &args.trim_file) {
+        (Some(trims), _) => trims.iter().map(|trim| parse_trim(trim)).collect::<Result<Vec<_>, _>>()?,
+        (None, Some(trim_file)) => regions::load(trim_file).map_err(ArgError::FailedToReadTrimFile)?
+            .iter().map(|trim| parse_trim(trim)).collect::<Result<Vec<_>, _>>()?,
+        (None, None) => Vec::new(),
     };
-    let trim = if let Err(e) = trim {
-        return Err(ArgError::FailedToParseTrim(e));
+    // Every area is cropped from the source, so the source must be kept until all of them are saved.
+    if trims.len() > 1 && args.delete {
+        return Err(ArgError::DeleteWithMultipleTrims);
     }
-    else {
-        trim.unwrap()
+    if trims.len() > 1 && args.in_place {
+        return Err(ArgError::InPlaceWithMultipleTrims);
+    }
+
+    // --exposure -> Must be positive.
+    if !args.exposure.is_finite() || args.exposure <= 0.0 {
+        return Err(ArgError::InvalidExposure);
+    }
+
+    // --colorspace -> Only sRGB outputs are supported, because the outputs carry no ICC profile.
+    let colorspace = match args.colorspace.as_deref() {
+        Some("srgb") => Some(ColorSpace::Srgb),
+        _ => None,
+    };
+
+    // --redact -> The areas are in the format of --trim.
+    let redact = match &args.redact {
+        Some(areas) => areas.iter().map(|area| parse_trim(area)).collect::<Result<Vec<_>, _>>()?,
+        None => Vec::new(),
+    };
+    // --pixelate -> The block size must be at least 2 pixels.
+    let pixelate = match &args.pixelate {
+        Some(areas) => areas.iter().map(|area| parse_area_with(area, DEFAULT_PIXELATE_BLOCK_SIZE)).collect::<Result<Vec<_>, _>>()?,
+        None => Vec::new(),
+    };
+    if pixelate.iter().any(|(_, block_size)| *block_size < 2) {
+        return Err(ArgError::InvalidBlockSize);
+    }
+    // --blur-region -> The sigma must be positive.
+    let blur_regions = match &args.blur_region {
+        Some(areas) => areas.iter().map(|area| parse_area_with(area, DEFAULT_BLUR_SIGMA)).collect::<Result<Vec<_>, _>>()?,
+        None => Vec::new(),
+    };
+    if blur_regions.iter().any(|(_, sigma)| !sigma.is_finite() || *sigma <= 0.0) {
+        return Err(ArgError::InvalidSigma);
+    }
+    // --redact-qr -> QR code detection must be compiled in.
+    if args.redact_qr && !qr::AVAILABLE {
+        return Err(ArgError::QrDetectUnavailable);
+    }
+
+    // --watermark -> The message must fit in the watermark.
+    let watermark = match (args.watermark, args.watermark_key) {
+        (Some(message), _) if message.len() > watermark::MAX_MESSAGE_LEN => return Err(ArgError::WatermarkTooLong),
+        (Some(message), Some(key)) => Some((message, key)),
+        _ => None,
+    };
+
+    // --crop-ratio -> Check the ratio.
+    let crop_ratio = match &args.crop_ratio {
+        Some(ratio) => Some(parse_crop_ratio(ratio)?),
+        None => None,
+    };
+    // --gravity faces -> Face detection must be compiled in, and the model must be given.
+    if args.gravity == Gravity::Faces {
+        if !faces::AVAILABLE {
+            return Err(ArgError::FaceDetectUnavailable);
+        }
+        if args.face_model.is_none() {
+            return Err(ArgError::MissingFaceModel);
+        }
+    }
+
+    // If convert option is specified, check the format.
+    // --convert auto -> The format is chosen for each image when the tasks are made.
+    let convert = match &args.convert {
+        Some(convert) if convert.eq_ignore_ascii_case(AUTO_FORMAT) => Some(AUTO_FORMAT.to_string()),
+        Some(convert) => Some(parse_format(convert)?),
+        None => None,
     };
 
     if (args.quality < Some(0.0) || args.quality > Some(100.0)) && args.quality.is_some() {
         return Err(ArgError::InvalidQuality);
     }
-    if args.resize < Some(0) && args.resize.is_some() {
-        return Err(ArgError::InvalidResize);
+    if args.effort > Some(100) {
+        return Err(ArgError::InvalidEffort);
+    }
+    if let Some(vignette) = args.vignette {
+        if !(-1.0..=1.0).contains(&vignette) {
+            return Err(ArgError::InvalidVignette);
+        }
+    }
+    let white_balance = match &args.wb {
+        Some(wb) => Some(parse_white_balance(wb)?),
+        None => None,
+    };
+    if let Some(clip) = args.auto_levels {
+        if !(0.0..50.0).contains(&clip) {
+            return Err(ArgError::InvalidAutoLevels);
+        }
+    }
+    // --pages -> Page numbers and ranges.
+    let pages = match &args.pages {
+        Some(pages) => Some(parse_pages(pages)?),
+        None => None,
+    };
+    // --gamma -> Must be positive.
+    if let Some(gamma) = args.gamma {
+        if !gamma.is_finite() || gamma <= 0.0 {
+            return Err(ArgError::InvalidGamma);
+        }
+    }
+    // --threshold -> 'otsu' or a level.
+    let threshold = match args.threshold.as_deref() {
+        Some(threshold) if threshold.eq_ignore_ascii_case("otsu") => Some(Threshold::Otsu),
+        Some(threshold) if threshold.eq_ignore_ascii_case("adaptive") => Some(Threshold::Adaptive),
+        Some(threshold) => Some(Threshold::Value(threshold.parse().map_err(|_| ArgError::InvalidThreshold)?)),
+        None => None,
+    };
+    // --curve -> The curve without a channel applies to all channels.
+    let curves = match &args.curve {
+        Some(specs) => Some(parse_curves(specs)?),
+        None => None,
+    };
+    // --lut -> Load the LUT once here, so that a broken file is reported before processing.
+    let lut = match &args.lut {
+        Some(path) => Some(Arc::new(Lut3d::load(path).map_err(|e| ArgError::FailedToReadLut(e.to_string()))?)),
+        None => None,
+    };
+    let total_budget = match &args.total_budget {
+        Some(size) => Some(crate::budget::parse_size(size).ok_or(ArgError::InvalidTotalBudget)?),
+        None => None,
+    };
+    let quality_search = match &args.quality_search {
+        Some(target) => Some(crate::budget::parse_quality_search(target).ok_or(ArgError::InvalidQualitySearch)?),
+        None => None,
+    };
+    let view_size = match &args.view_size {
+        Some(size) => Some(parse_view_size(size)?),
+        None => None,
+    };
+    if let Some(depth) = args.depth {
+        if ![8, 4, 1].contains(&depth) {
+            return Err(ArgError::InvalidDepth);
+        }
+    }
+    if args.dpi == Some(0) {
+        return Err(ArgError::InvalidDpi);
+    }
+    // If the resize option is a physical size, its DPI is also used as the output density.
+    let (resize, dpi) = match &args.resize {
+        Some(resize) => {
+            let (resize, resize_dpi) = parse_resize(resize, args.dpi)?;
+            (Some(resize), args.dpi.or(resize_dpi))
+        },
+        None => (None, args.dpi),
+    };
+    // --device -> Fit the screen of the device, unless --resize is specified.
+    let resize = resize.or(args.device.map(|device| device.resize()));
+    if let Some(ResizeSpec::Ratio(ratio)) = resize {
+        if ratio > 100.0 && !args.allow_upscale {
+            return Err(ArgError::UpscaleNotAllowed);
+        }
+    }
+
+    if let Some(sequence) = &args.sequence {
+        if !Regex::new(r"\{n(?::0?\d+)?\}").unwrap().is_match(sequence) {
+            return Err(ArgError::InvalidSequence);
+        }
+    }
+
+    if let Some(template) = &args.organize_by_date {
+        if !["{year}", "{month}", "{day}"].iter().any(|p| template.contains(p)) {
+            return Err(ArgError::InvalidOrganizeByDate);
+        }
+    }
+
+    if let Some(template) = &args.out_per_format {
+        if !template.contains("{format}") {
+            return Err(ArgError::InvalidOutPerFormat);
+        }
     }
 
     if args.threads < 1 {
         return Err(ArgError::InvalidThreads);
     }
+    if args.encoder_threads == Some(0) {
+        return Err(ArgError::InvalidEncoderThreads);
+    }
+
+    let mode = match args.command {
+        Some(Command::Bench { image, iterations }) => Mode::Bench(BenchOptions { iterations: iterations.max(1), image }),
+        Some(Command::PreviewQuality { image, qualities, output, view }) => {
+            if qualities.iter().any(|q| !(0.0..=100.0).contains(q)) {
+                return Err(ArgError::InvalidQuality);
+            }
+            Mode::PreviewQuality(PreviewOptions { image, qualities, output, view })
+        },
+        Some(Command::InstallShellIntegration { uninstall }) => {
+            let action = if uninstall { ShellAction::Uninstall } else { ShellAction::Install };
+            Mode::ShellIntegration(action)
+        },
+        Some(Command::Appicon { image, target, output }) => Mode::Appicon(AppIconOptions { image, target, output }),
+        Some(Command::SplitScan { images, output, convert, tolerance, min_area, no_deskew }) => {
+            if !(0.0..100.0).contains(&min_area) {
+                return Err(ArgError::InvalidMinArea);
+            }
+            let convert = match convert {
+                Some(convert) => Some(parse_format(&convert)?),
+                None => None,
+            };
+            Mode::SplitScan(SplitScanOptions { images, output, convert, tolerance, min_area, deskew: !no_deskew })
+        },
+        Some(Command::VerifyWatermark { images, key, expect }) => Mode::VerifyWatermark(VerifyWatermarkOptions { images, key, expect }),
+        Some(Command::ToGif { frames, output, fps, loops }) => {
+            if !fps.is_finite() || fps <= 0.0 {
+                return Err(ArgError::InvalidFps);
+            }
+            let format = AnimationFormat::from_path(&output).ok_or_else(|| ArgError::UnsupportedAnimationFormat(output.clone()))?;
+            Mode::ToGif(ToGifOptions { frames, output, format, fps, loops })
+        },
+        Some(Command::Check { paths, threads, memory_limit, salvage, output }) => {
+            if threads < 1 {
+                return Err(ArgError::InvalidThreads);
+            }
+            if memory_limit < 1 {
+                return Err(ArgError::InvalidMemoryLimit);
+            }
+            Mode::Check(CheckOptions { paths, threads, memory_limit, salvage, output })
+        },
+        None => Mode::Process,
+    };
 
     Ok(ArgStruct {
         souce_path: args.source,
         destination_path: args.output,
-        destination_extension: args.convert,
+        destination_extension: convert,
         destination_append_name: args.append,
+        sequence: args.sequence,
+        organize_by_date: args.organize_by_date,
+        out_per_format: args.out_per_format,
+        flatten: args.flatten,
+        out_extension_case: args.out_extension_case,
+        dedupe_names: args.dedupe_names,
+        in_place: args.in_place,
+        sort: args.sort,
+        limit: args.limit,
+        sample: args.sample,
+        seed: args.seed,
         recursive: args.recursive,
+        no_ignore: args.no_ignore,
+        mmap: args.mmap,
         quality: args.quality,
+        progressive: args.progressive,
+        effort: args.effort,
+        total_budget,
+        quality_search,
+        interlace: args.interlace,
         delete: args.delete,
-        resize: args.resize,
-        trim,
+        preserve_xattrs: args.preserve_xattrs,
+        checksum: args.checksum,
+        checksum_manifest: args.checksum_manifest,
+        zip: args.zip,
+        zip_password: args.zip_password,
+        pdf: args.pdf,
+        picture_manifest: args.picture_manifest,
+        resize,
+        allow_upscale: args.allow_upscale,
+        upscale_filter: args.upscale_filter,
+        dpi,
+        trims,
+        social: args.social,
+        crop_ratio: crop_ratio,
+        gravity: args.gravity,
+        face_model: args.face_model,
+        tonemap: args.tonemap.operator(),
+        exposure: args.exposure,
+        colorspace: colorspace,
+        redact: redact,
+        redact_qr: args.redact_qr,
+        redact_style: args.redact_style,
+        pixelate: pixelate,
+        blur_regions: blur_regions,
+        autocrop: args.autocrop,
+        skip_blank: args.skip_blank,
+        deskew: args.deskew,
+        vignette: args.vignette,
+        white_balance,
+        auto_levels: args.auto_levels,
+        gamma: args.gamma,
+        pages: pages,
+        curves: curves,
+        lut: lut,
         grayscale: args.grayscale,
+        edges: args.edges,
+        threshold: threshold,
+        watermark: watermark,
+        depth: args.depth,
         view: args.view,
+        view_size,
+        open: args.open,
+        notify: args.notify,
+        progress: match (args.progress_file, args.progress_fd) {
+            (Some(path), _) => Some(ProgressTarget::File(path)),
+            (None, Some(fd)) => Some(ProgressTarget::Fd(fd)),
+            (None, None) => None,
+        },
+        view_protocol: args.view_protocol,
+        interactive: args.interactive,
+        files_from: args.files_from,
+        null: args.null,
+        map: args.map,
         yes: args.yes,
         no: args.no,
         double_extension: args.double_extension,
         threads: args.threads,
+        encoder_threads: args.encoder_threads,
+        temp_dir: args.temp_dir,
+        formats: args.formats,
+        version: args.version,
+        verbose: args.verbose,
+        mode: mode,
+        lang: args.lang,
     })
 }
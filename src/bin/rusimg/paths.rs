@@ -0,0 +1,425 @@
+use std::ffi::{OsStr, OsString};
+use std::path::{Path, PathBuf};
+use regex::Regex;
+
+use librusimg::Extension;
+
+use crate::dates;
+use crate::parse::ArgStruct;
+
+/// Case of the extensions of the output files.
+/// - Lower: Lowercase all extensions (e.g. Photo.JPG -> Photo.jpg, -c WEBP -> .webp).
+/// - Keep: Keep the extensions as written: the source extension as in the file name, and the --convert extension as given on the command line.
+#[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq, Default)]
+pub enum ExtensionCase {
+    Lower,
+    #[default]
+    Keep,
+}
+
+impl ExtensionCase {
+    /// Apply the policy to an extension.
+    pub fn apply(&self, extension: &OsStr) -> OsString {
+        match self {
+            ExtensionCase::Lower => extension.to_ascii_lowercase(),
+            ExtensionCase::Keep => extension.to_os_string(),
+        }
+    }
+
+    /// Check if the policy renames the source when it is saved in place (e.g. Photo.JPG with lower).
+    pub fn renames(&self, input_path: &Path) -> bool {
+        match input_path.extension() {
+            Some(extension) => self.apply(extension) != extension,
+            None => false,
+        }
+    }
+}
+
+/// OutputNaming is the part of the arguments that determines the output path.
+/// - destination: --output (a file or a directory).
+/// - append: --append (appended to the file stem).
+/// - sequence: --sequence (template of the file name).
+/// - double_extension: --double-extension (keep the source extension before the new one).
+/// - organize_by_date: --organize-by-date (template of the date directories).
+/// - per_format: --out-per-format (template of the output directory of each format).
+/// - flatten: --flatten (the destination is a directory, even if it does not exist yet).
+/// - extension_case: --out-extension-case (case of the generated extensions).
+/// - page: Page number of a multi-page source (tiff, pdf) with multiple pages (appended to the stem as _pN).
+/// - region: Number of the trim area (starting from 1) when multiple areas are cropped from each source (appended to the stem as _N).
+#[derive(Debug, Clone, Default)]
+pub struct OutputNaming {
+    pub destination: Option<PathBuf>,
+    pub append: Option<String>,
+    pub sequence: Option<String>,
+    pub double_extension: bool,
+    pub organize_by_date: Option<String>,
+    pub per_format: Option<String>,
+    pub flatten: bool,
+    pub extension_case: ExtensionCase,
+    pub page: Option<usize>,
+    pub region: Option<usize>,
+}
+
+impl OutputNaming {
+    pub fn from_args(args: &ArgStruct) -> Self {
+        Self {
+            destination: args.destination_path.clone(),
+            append: args.destination_append_name.clone(),
+            sequence: args.sequence.clone(),
+            double_extension: args.double_extension,
+            organize_by_date: args.organize_by_date.clone(),
+            per_format: args.out_per_format.clone(),
+            flatten: args.flatten,
+            extension_case: args.out_extension_case,
+            page: None,
+            region: None,
+        }
+    }
+
+    /// Check if the output is the source itself (no --output, --append, --sequence, --organize-by-date, --out-per-format, --flatten, pages and multiple trim areas).
+    /// --double-extension alone does not rename the output unless the format is converted.
+    pub fn is_in_place(&self) -> bool {
+        self.destination.is_none() && self.append.is_none() && self.sequence.is_none() && self.organize_by_date.is_none() && self.per_format.is_none() && !self.flatten && self.page.is_none() && self.region.is_none()
+    }
+
+    /// Check if the destination is a directory.
+    /// An existing directory, a path ending with a separator (e.g. out/) and the destination of --flatten are directories.
+    fn destination_dir(&self) -> Option<&Path> {
+        let destination = self.destination.as_deref()?;
+        let trailing_separator = destination.as_os_str().to_string_lossy().ends_with(std::path::is_separator);
+        if self.flatten || trailing_separator || destination.is_dir() {
+            Some(destination)
+        }
+        else {
+            None
+        }
+    }
+
+    /// Determine the output path.
+    /// sequence_number is used for the file name if --sequence is specified (starting from 1).
+    ///
+    /// The path is composed of the directory, the date directories and the file name:
+    /// - directory: The -o directory, the directory of the -o file, or the directory of the source.
+    /// - date directories: The capture date rendered with --organize-by-date.
+    /// - file name: The stem (the --sequence name, the stem of the -o file, or the stem of the source), --append, the page number, the number of the trim area and the extension.
+    ///   The extension is the new one (after the source extension with --double-extension), in the case of --out-extension-case.
+    ///   A -o file keeps its own extension as written.
+    ///
+    /// A -o file without --sequence, --append, pages and multiple trim areas is used as is.
+    /// extension is the extension of the output (see output_extension()).
+    /// source is the format of the source. It stands in for the source extension if the source has none (e.g. "scan" or ".image").
+    pub fn output_path(&self, input_path: &Path, source: &Extension, extension: &str, sequence_number: usize) -> PathBuf {
+        // --out-per-format -> The directory of the output format is the output directory.
+        if let Some(template) = &self.per_format {
+            let naming = OutputNaming { destination: Some(render_format_directory(template, extension)), per_format: None, ..self.clone() };
+            return naming.output_path(input_path, source, extension, sequence_number);
+        }
+
+        let mut new_extension = self.extension_case.apply(OsStr::new(extension));
+        if self.double_extension {
+            let mut double_extension = match input_path.extension() {
+                Some(input_extension) => self.extension_case.apply(input_extension),
+                None => OsString::from(source.preferred_extension()),
+            };
+            double_extension.push(".");
+            double_extension.push(&new_extension);
+            new_extension = double_extension;
+        }
+
+        let (directory, stem, extension) = match (self.destination_dir(), &self.destination) {
+            (Some(directory), _) => (directory.to_path_buf(), input_path.file_stem(), new_extension),
+            (None, Some(file)) => {
+                // -o のファイル名はそのまま使う
+                if self.sequence.is_none() && self.append.is_none() && self.page.is_none() && self.region.is_none() {
+                    return self.with_date_directories(file.clone(), input_path);
+                }
+                let extension = file.extension().map(|e| e.to_os_string()).unwrap_or(new_extension);
+                (parent(file), file.file_stem(), extension)
+            },
+            (None, None) => (parent(input_path), input_path.file_stem(), new_extension),
+        };
+
+        // If --sequence is specified, replace the stem with the sequence name.
+        let mut file_name = match &self.sequence {
+            Some(template) => OsString::from(render_sequence_name(template, sequence_number)),
+            None => stem.unwrap_or_default().to_os_string(),
+        };
+        // If append_name is specified, add it to the stem.
+        if let Some(append_name) = &self.append {
+            file_name.push(append_name);
+        }
+        // Multi-page sources -> Number the outputs of the pages.
+        if let Some(page) = self.page {
+            file_name.push(format!("_p{}", page));
+        }
+        // Multiple trim areas -> Number the outputs of each source.
+        if let Some(region) = self.region {
+            file_name.push(format!("_{}", region));
+        }
+        file_name.push(".");
+        file_name.push(&extension);
+        self.with_date_directories(directory.join(file_name), input_path)
+    }
+
+    /// If --organize-by-date is specified, put the file into the subdirectories of its capture date.
+    fn with_date_directories(&self, output_path: PathBuf, input_path: &Path) -> PathBuf {
+        let (Some(template), Some(file_name)) = (&self.organize_by_date, output_path.file_name()) else {
+            return output_path;
+        };
+        match dates::capture_date(input_path) {
+            Some(date) => parent(&output_path).join(dates::render(template, date)).join(file_name),
+            None => output_path,
+        }
+    }
+}
+
+/// Get the extension of the output file.
+/// The extension given to --convert is used as written (e.g. -c jpg -> .jpg, -c jpeg -> .jpeg).
+/// Without --convert, the source keeps its extension (e.g. .jpe stays .jpe), and a source without an extension gets the preferred extension of its format.
+/// The name of the source is kept exactly when the format is not changed: -c jpg on Photo.JPG keeps .JPG, so that overwriting does not create a second file on case-sensitive file systems.
+/// The case of the result is changed by --out-extension-case in OutputNaming::output_path().
+pub fn output_extension(input_path: &Path, source: &Extension, convert: Option<&str>) -> String {
+    let source_extension = source.extension_for(input_path);
+    match convert.map(|c| c.trim_start_matches('.')) {
+        Some(convert) if !convert.eq_ignore_ascii_case(&source_extension) => convert.to_string(),
+        _ => source_extension,
+    }
+}
+
+/// Render the --out-per-format template.
+/// "{format}" is replaced with the name of the output format, so the outputs of one format share a directory whatever their extensions are (e.g. -c jpg -> jpeg).
+/// The result ends with a separator, so it is a directory even if it does not exist yet.
+pub fn render_format_directory(template: &str, extension: &str) -> PathBuf {
+    let format = Extension::from_extension(extension).map(|format| format.to_string()).unwrap_or_else(|_| extension.to_ascii_lowercase());
+    PathBuf::from(template.replace("{format}", &format)).join("")
+}
+
+/// Get the directory of the file ("" for a file name without a directory).
+fn parent(path: &Path) -> PathBuf {
+    path.parent().unwrap_or(Path::new("")).to_path_buf()
+}
+
+/// Render the name of the --sequence template.
+/// "{n}" is replaced with the sequence number, and "{n:04}" with the zero-padded sequence number.
+pub fn render_sequence_name(template: &str, sequence_number: usize) -> String {
+    let re = Regex::new(r"\{n(?::(0)?(\d+))?\}").unwrap();
+    re.replace_all(template, |captures: &regex::Captures| {
+        let width = captures.get(2).and_then(|w| w.as_str().parse::<usize>().ok()).unwrap_or(0);
+        if captures.get(1).is_some() {
+            format!("{:0width$}", sequence_number, width = width)
+        }
+        else {
+            format!("{:width$}", sequence_number, width = width)
+        }
+    }).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use librusimg::tempdir::TempDir;
+    use proptest::prelude::*;
+
+    fn extension() -> impl Strategy<Value = Extension> {
+        prop_oneof![Just(Extension::Bmp), Just(Extension::Jpeg), Just(Extension::Png), Just(Extension::Webp)]
+    }
+
+    /// A relative input path that does not exist (e.g. photos/2024/IMG_0001.jpg).
+    fn input_path() -> impl Strategy<Value = PathBuf> {
+        (prop::collection::vec("[a-z0-9_-]{1,8}", 0..3), "[A-Za-z0-9_-]{1,8}", prop::sample::select(vec!["jpg", "png", "webp", "bmp"]))
+            .prop_map(|(directories, stem, extension)| {
+                let mut path = PathBuf::from("rusimg-proptest");
+                path.extend(directories);
+                path.join(format!("{}.{}", stem, extension))
+            })
+    }
+
+    /// A relative input path without an extension, or a hidden file (e.g. photos/scan, photos/.image).
+    fn extensionless_path() -> impl Strategy<Value = PathBuf> {
+        (prop::collection::vec("[a-z0-9_-]{1,8}", 0..3), "\\.?[A-Za-z0-9_-]{1,8}")
+            .prop_map(|(directories, name)| {
+                let mut path = PathBuf::from("rusimg-proptest");
+                path.extend(directories);
+                path.join(name)
+            })
+    }
+
+    proptest! {
+        #[test]
+        fn without_output_the_input_directory_is_kept(input in input_path(), extension in extension(), append in proptest::option::of("_[a-z]{1,5}")) {
+            let naming = OutputNaming { append: append, ..Default::default() };
+            let output = naming.output_path(&input, &Extension::Png, &extension.to_string(), 1);
+            prop_assert_eq!(output.parent(), input.parent());
+        }
+
+        #[test]
+        fn append_keeps_the_output_directory(input in input_path(), extension in extension(), append in "_[a-z]{1,5}") {
+            let dir = TempDir::new().unwrap();
+            let naming = OutputNaming { destination: Some(dir.path().to_path_buf()), append: Some(append.clone()), ..Default::default() };
+            let output = naming.output_path(&input, &Extension::Png, &extension.to_string(), 1);
+            prop_assert_eq!(output.parent(), Some(dir.path()));
+            let expected = format!("{}{}.{}", input.file_stem().unwrap().to_string_lossy(), append, extension);
+            prop_assert_eq!(output.file_name().unwrap().to_string_lossy(), expected);
+        }
+
+        #[test]
+        fn append_keeps_the_directory_of_the_output_file(input in input_path(), output in input_path(), extension in extension(), append in "_[a-z]{1,5}") {
+            let naming = OutputNaming { destination: Some(output.clone()), append: Some(append.clone()), ..Default::default() };
+            let result = naming.output_path(&input, &Extension::Png, &extension.to_string(), 1);
+            prop_assert_eq!(result.parent(), output.parent());
+            let expected = format!("{}{}.{}", output.file_stem().unwrap().to_string_lossy(), append, output.extension().unwrap().to_string_lossy());
+            prop_assert_eq!(result.file_name().unwrap().to_string_lossy(), expected);
+        }
+
+        #[test]
+        fn output_file_is_used_as_is(input in input_path(), output in input_path(), extension in extension()) {
+            let naming = OutputNaming { destination: Some(output.clone()), ..Default::default() };
+            prop_assert_eq!(naming.output_path(&input, &Extension::Png, &extension.to_string(), 1), output);
+        }
+
+        #[test]
+        fn sequence_names_the_file(input in input_path(), extension in extension(), number in 1usize..100000) {
+            let dir = TempDir::new().unwrap();
+            let naming = OutputNaming { destination: Some(dir.path().to_path_buf()), sequence: Some("img_{n:05}".to_string()), ..Default::default() };
+            let output = naming.output_path(&input, &Extension::Png, &extension.to_string(), number);
+            prop_assert_eq!(output, dir.file(format!("img_{:05}.{}", number, extension)));
+        }
+
+        #[test]
+        fn double_extension_keeps_the_source_extension(input in input_path(), extension in extension()) {
+            let naming = OutputNaming { double_extension: true, ..Default::default() };
+            let output = naming.output_path(&input, &Extension::Png, &extension.to_string(), 1);
+            let expected = format!("{}.{}", input.file_name().unwrap().to_string_lossy(), extension);
+            prop_assert_eq!(output.file_name().unwrap().to_string_lossy(), expected);
+        }
+
+        #[test]
+        fn append_goes_before_the_double_extension(input in input_path(), extension in extension(), append in "_[a-z]{1,5}") {
+            let naming = OutputNaming { append: Some(append.clone()), double_extension: true, ..Default::default() };
+            let output = naming.output_path(&input, &Extension::Png, &extension.to_string(), 1);
+            let expected = format!("{}{}.{}.{}", input.file_stem().unwrap().to_string_lossy(), append, input.extension().unwrap().to_string_lossy(), extension);
+            prop_assert_eq!(output.file_name().unwrap().to_string_lossy(), expected);
+        }
+
+        #[test]
+        fn sequence_and_append_compose(input in input_path(), extension in extension(), number in 1usize..1000, append in "_[a-z]{1,5}") {
+            let naming = OutputNaming { sequence: Some("{n:03}".to_string()), append: Some(append.clone()), ..Default::default() };
+            let output = naming.output_path(&input, &Extension::Png, &extension.to_string(), number);
+            prop_assert_eq!(output.parent(), input.parent());
+            prop_assert_eq!(output.file_name().unwrap().to_string_lossy(), format!("{:03}{}.{}", number, append, extension));
+        }
+
+        #[test]
+        fn missing_directories_are_directories_with_a_separator_or_flatten(input in input_path(), output in "[a-z]{1,8}", extension in extension(), flatten in any::<bool>()) {
+            let destination = if flatten { PathBuf::from(&output) } else { PathBuf::from(format!("{}/", output)) };
+            let naming = OutputNaming { destination: Some(destination), flatten: flatten, ..Default::default() };
+            let result = naming.output_path(&input, &Extension::Png, &extension.to_string(), 1);
+            prop_assert_eq!(result, Path::new(&output).join(format!("{}.{}", input.file_stem().unwrap().to_string_lossy(), extension)));
+        }
+
+        #[test]
+        fn extensionless_input_gets_the_new_extension(input in extensionless_path(), extension in extension(), append in proptest::option::of("_[a-z]{1,5}")) {
+            let naming = OutputNaming { append: append.clone(), ..Default::default() };
+            let output = naming.output_path(&input, &Extension::Jpeg, &extension.to_string(), 1);
+            let expected = format!("{}{}.{}", input.file_name().unwrap().to_string_lossy(), append.unwrap_or_default(), extension);
+            prop_assert_eq!(output.parent(), input.parent());
+            prop_assert_eq!(output.file_name().unwrap().to_string_lossy(), expected);
+        }
+
+        #[test]
+        fn double_extension_of_extensionless_input_uses_the_source_format(input in extensionless_path(), source in extension(), extension in extension()) {
+            let naming = OutputNaming { double_extension: true, ..Default::default() };
+            let output = naming.output_path(&input, &source, &extension.to_string(), 1);
+            let expected = format!("{}.{}.{}", input.file_name().unwrap().to_string_lossy(), source, extension);
+            prop_assert_eq!(output.file_name().unwrap().to_string_lossy(), expected);
+        }
+    }
+
+    #[test]
+    fn hidden_files_keep_their_name() {
+        let naming = OutputNaming::default();
+        assert_eq!(naming.output_path(Path::new("photos/.image"), &Extension::Png, "webp", 1), Path::new("photos/.image.webp"));
+        assert_eq!(naming.output_path(Path::new(".image.png"), &Extension::Png, "webp", 1), Path::new(".image.webp"));
+        let naming = OutputNaming { append: Some("_small".to_string()), double_extension: true, ..Default::default() };
+        assert_eq!(naming.output_path(Path::new(".image"), &Extension::Jpeg, "webp", 1), Path::new(".image_small.jpeg.webp"));
+    }
+
+    #[test]
+    fn source_extension_is_kept_without_convert() {
+        assert_eq!(output_extension(Path::new("photo.jpg"), &Extension::Jpeg, None), "jpg");
+        assert_eq!(output_extension(Path::new("photo.jpe"), &Extension::Jpeg, None), "jpe");
+        assert_eq!(output_extension(Path::new("scan"), &Extension::Jpeg, None), "jpeg");
+        // 拡張子と中身が異なる場合は中身の形式
+        assert_eq!(output_extension(Path::new("photo.png"), &Extension::Jpeg, None), "jpeg");
+    }
+
+    #[test]
+    fn convert_extension_is_used_as_written() {
+        assert_eq!(output_extension(Path::new("photo.jpeg"), &Extension::Jpeg, Some("jpg")), "jpg");
+        assert_eq!(output_extension(Path::new("photo.jpg"), &Extension::Jpeg, Some("jpeg")), "jpeg");
+        assert_eq!(output_extension(Path::new("photo.png"), &Extension::Png, Some("webp")), "webp");
+    }
+
+    #[test]
+    fn lower_case_policy_lowercases_generated_extensions() {
+        let naming = OutputNaming { extension_case: ExtensionCase::Lower, ..Default::default() };
+        assert_eq!(naming.output_path(Path::new("Photo.JPG"), &Extension::Jpeg, "JPG", 1), Path::new("Photo.jpg"));
+        assert_eq!(naming.output_path(Path::new("Photo.JPG"), &Extension::Jpeg, "WEBP", 1), Path::new("Photo.webp"));
+        let naming = OutputNaming { extension_case: ExtensionCase::Lower, double_extension: true, ..Default::default() };
+        assert_eq!(naming.output_path(Path::new("Photo.JPG"), &Extension::Jpeg, "webp", 1), Path::new("Photo.jpg.webp"));
+        // -o のファイル名はそのまま
+        let naming = OutputNaming { destination: Some(PathBuf::from("OUT.PNG")), extension_case: ExtensionCase::Lower, ..Default::default() };
+        assert_eq!(naming.output_path(Path::new("Photo.JPG"), &Extension::Jpeg, "png", 1), Path::new("OUT.PNG"));
+    }
+
+    #[test]
+    fn keep_case_policy_keeps_the_extensions_as_written() {
+        let naming = OutputNaming { double_extension: true, ..Default::default() };
+        assert_eq!(naming.output_path(Path::new("Photo.JPG"), &Extension::Jpeg, "WebP", 1), Path::new("Photo.JPG.WebP"));
+        assert!(!ExtensionCase::Keep.renames(Path::new("Photo.JPG")));
+        assert!(ExtensionCase::Lower.renames(Path::new("Photo.JPG")));
+        assert!(!ExtensionCase::Lower.renames(Path::new("photo.jpg")));
+        assert!(!ExtensionCase::Lower.renames(Path::new("SCAN")));
+    }
+
+    #[test]
+    fn case_only_differences_keep_the_source_name() {
+        let naming = OutputNaming::default();
+        for (source, convert, expected) in [("Photo.JPG", None, "Photo.JPG"), ("Photo.JPG", Some("jpg"), "Photo.JPG"), ("Photo.Jpeg", Some("JPEG"), "Photo.Jpeg"), ("Photo.JPG", Some("jpeg"), "Photo.jpeg"), ("Photo.JPG", Some("png"), "Photo.png"), ("IMAGE.PNG", None, "IMAGE.PNG")] {
+            let input = Path::new(source);
+            let format = Extension::from_path(input).unwrap();
+            let extension = output_extension(input, &format, convert);
+            assert_eq!(naming.output_path(input, &format, &extension, 1), Path::new(expected), "{} -c {:?}", source, convert);
+        }
+    }
+
+    #[test]
+    fn trim_areas_number_the_outputs() {
+        let naming = OutputNaming { append: Some("_crop".to_string()), region: Some(2), ..Default::default() };
+        assert_eq!(naming.output_path(Path::new("scans/scan.png"), &Extension::Png, "png", 1), Path::new("scans/scan_crop_2.png"));
+        let naming = OutputNaming { destination: Some(PathBuf::from("out/photo.jpg")), region: Some(1), ..Default::default() };
+        assert_eq!(naming.output_path(Path::new("scan.png"), &Extension::Png, "png", 1), Path::new("out/photo_1.jpg"));
+        assert!(!OutputNaming { region: Some(1), ..Default::default() }.is_in_place());
+    }
+
+    #[test]
+    fn pages_number_the_outputs() {
+        let naming = OutputNaming { page: Some(3), ..Default::default() };
+        assert_eq!(naming.output_path(Path::new("docs/contract.pdf"), &Extension::Png, "png", 1), Path::new("docs/contract_p3.png"));
+        let naming = OutputNaming { destination: Some(PathBuf::from("out/")), page: Some(12), region: Some(2), ..Default::default() };
+        assert_eq!(naming.output_path(Path::new("scan.tiff"), &Extension::Png, "jpg", 1), Path::new("out/scan_p12_2.jpg"));
+        assert!(!naming.is_in_place());
+    }
+
+    #[test]
+    fn per_format_directories_are_named_after_the_format() {
+        let naming = OutputNaming { per_format: Some("dist/{format}/".to_string()), ..Default::default() };
+        assert_eq!(naming.output_path(Path::new("src/hero.png"), &Extension::Png, "webp", 1), Path::new("dist/webp/hero.webp"));
+        // 拡張子の表記に関わらず形式名のディレクトリ
+        assert_eq!(naming.output_path(Path::new("src/hero.png"), &Extension::Png, "jpg", 1), Path::new("dist/jpeg/hero.jpg"));
+        assert_eq!(naming.output_path(Path::new("src/logo.png"), &Extension::Png, "png", 1), Path::new("dist/png/logo.png"));
+        let naming = OutputNaming { per_format: Some("dist/{format}".to_string()), append: Some("@2x".to_string()), ..Default::default() };
+        assert_eq!(naming.output_path(Path::new("hero.png"), &Extension::Png, "webp", 1), Path::new("dist/webp/hero@2x.webp"));
+        assert!(!naming.is_in_place());
+    }
+}
@@ -0,0 +1,311 @@
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use image::{DynamicImage, GrayImage, ImageDecoder, ImageFormat, ImageReader, RgbImage};
+use lopdf::{Dictionary, Document, Object, Stream};
+
+/// Density of the pages if --dpi is not specified (the usual density of document scans).
+pub const DEFAULT_DPI: u32 = 300;
+
+/// Pixels of a page image in a PDF.
+/// - Jpeg: A jpeg file embedded as it is (DCTDecode). components is 1 (gray) or 3 (RGB).
+/// - Bilevel: 1 bit per pixel (black and white), rows padded to bytes.
+/// - Gray: 8-bit grayscale.
+/// - Rgb: 8-bit RGB.
+enum PageImage {
+    Jpeg { data: Vec<u8>, components: u8 },
+    Bilevel(Vec<u8>),
+    Gray(Vec<u8>),
+    Rgb(Vec<u8>),
+}
+
+/// Compress data with the RunLengthDecode filter of PDF.
+/// It needs no dependency, and compresses black and white documents (long runs of white bytes) well.
+fn run_length(data: &[u8]) -> Vec<u8> {
+    let mut out = Vec::new();
+    let mut i = 0;
+    while i < data.len() {
+        let mut run = 1;
+        while i + run < data.len() && run < 128 && data[i + run] == data[i] {
+            run += 1;
+        }
+        if run > 1 {
+            // 257 - n: 次の 1 バイトを n 回繰り返す
+            out.push((257 - run) as u8);
+            out.push(data[i]);
+            i += run;
+        }
+        else {
+            // n - 1: 続く n バイトをそのままコピーする
+            let start = i;
+            while i < data.len() && i - start < 128 && !(i + 1 < data.len() && data[i + 1] == data[i]) {
+                i += 1;
+            }
+            out.push((i - start - 1) as u8);
+            out.extend_from_slice(&data[start..i]);
+        }
+    }
+    out.push(128);
+    out
+}
+
+/// Expand data compressed with the RunLengthDecode filter (e.g. the pages written by write_pdf()).
+fn run_length_decode(data: &[u8]) -> Vec<u8> {
+    let mut out = Vec::new();
+    let mut i = 0;
+    while i < data.len() {
+        let length = data[i] as usize;
+        match length {
+            128 => break,
+            0..=127 => {
+                out.extend_from_slice(data.get(i + 1..i + 2 + length).unwrap_or_default());
+                i += 2 + length;
+            },
+            _ => {
+                if let Some(byte) = data.get(i + 1) {
+                    out.extend(std::iter::repeat_n(*byte, 257 - length));
+                }
+                i += 2;
+            },
+        }
+    }
+    out
+}
+
+/// Load an image for a page. Jpeg files are embedded without decoding, and the others are decoded and stored losslessly.
+/// Transparent pixels are composed onto white, as PDF viewers show the page white.
+fn load_page(path: &Path) -> Result<(u32, u32, PageImage), String> {
+    let err = |e: &dyn std::fmt::Display| format!("{}: {}", path.display(), e);
+    let reader = ImageReader::open(path).map_err(|e| err(&e))?.with_guessed_format().map_err(|e| err(&e))?;
+
+    if reader.format() == Some(ImageFormat::Jpeg) {
+        let decoder = reader.into_decoder().map_err(|e| err(&e))?;
+        let (width, height) = decoder.dimensions();
+        let components = decoder.color_type().channel_count();
+        if components == 1 || components == 3 {
+            return Ok((width, height, PageImage::Jpeg { data: fs::read(path).map_err(|e| err(&e))?, components: components }));
+        }
+    }
+    // CMYK の jpeg などは展開して格納する
+    load_decoded(path)
+}
+
+/// Decode an image for a page. Gray images (e.g. the outputs of --threshold --depth 1) are stored with 1 or 8 bits per pixel.
+fn load_decoded(path: &Path) -> Result<(u32, u32, PageImage), String> {
+    let image = image::open(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    let mut rgba = image.to_rgba8();
+    for pixel in rgba.pixels_mut() {
+        let [r, g, b, a] = pixel.0;
+        let over_white = |c: u8| ((c as u32 * a as u32 + 255 * (255 - a as u32)) / 255) as u8;
+        pixel.0 = [over_white(r), over_white(g), over_white(b), 255];
+    }
+    let (width, height) = rgba.dimensions();
+    let rgb = DynamicImage::ImageRgba8(rgba).to_rgb8();
+
+    let gray = rgb.pixels().all(|p| p.0[0] == p.0[1] && p.0[1] == p.0[2]);
+    if !gray {
+        return Ok((width, height, PageImage::Rgb(rgb.into_raw())));
+    }
+    let values = rgb.pixels().map(|p| p.0[0]).collect::<Vec<_>>();
+    if !values.iter().all(|v| *v == 0 || *v == 255) {
+        return Ok((width, height, PageImage::Gray(values)));
+    }
+
+    // 1 ビット: 行ごとにバイト境界まで詰める (1 = 白)
+    let mut bits = Vec::with_capacity(height as usize * (width as usize).div_ceil(8));
+    for row in values.chunks(width as usize) {
+        for byte in row.chunks(8) {
+            bits.push(byte.iter().enumerate().fold(0u8, |acc, (i, v)| if *v == 255 { acc | (0x80 >> i) } else { acc }));
+        }
+    }
+    Ok((width, height, PageImage::Bilevel(bits)))
+}
+
+/// Objects of a PDF document being written. Each object is numbered from 1 in the order of add().
+struct PdfWriter {
+    buffer: Vec<u8>,
+    offsets: Vec<usize>,
+}
+
+impl PdfWriter {
+    fn new() -> PdfWriter {
+        PdfWriter {
+            // バイナリを含むことを示すコメント
+            buffer: b"%PDF-1.4\n%\xE2\xE3\xCF\xD3\n".to_vec(),
+            offsets: Vec::new(),
+        }
+    }
+
+    /// Add an object, with a stream if given.
+    fn add(&mut self, dictionary: &str, stream: Option<&[u8]>) {
+        self.offsets.push(self.buffer.len());
+        let _ = writeln!(self.buffer, "{} 0 obj\n{}", self.offsets.len(), dictionary);
+        if let Some(stream) = stream {
+            self.buffer.extend_from_slice(b"stream\n");
+            self.buffer.extend_from_slice(stream);
+            self.buffer.extend_from_slice(b"\nendstream\n");
+        }
+        self.buffer.extend_from_slice(b"endobj\n");
+    }
+
+    /// Write the cross-reference table and the trailer, and return the document.
+    fn finish(mut self, root: usize) -> Vec<u8> {
+        let xref = self.buffer.len();
+        let _ = writeln!(self.buffer, "xref\n0 {}\n0000000000 65535 f ", self.offsets.len() + 1);
+        for offset in &self.offsets {
+            let _ = writeln!(self.buffer, "{:010} 00000 n ", offset);
+        }
+        let _ = writeln!(self.buffer, "trailer\n<< /Size {} /Root {} 0 R >>\nstartxref\n{}\n%%EOF", self.offsets.len() + 1, root, xref);
+        self.buffer
+    }
+}
+
+/// Write the outputs to a PDF document, one image per page, e.g. to send scanned receipts as one file.
+/// The size of each page is the size of its image at dpi. Jpeg files are embedded as they are, and the others are stored losslessly.
+pub fn write_pdf(pdf: &Path, files: &[PathBuf], dpi: u32) -> Result<(), String> {
+    let mut writer = PdfWriter::new();
+    // 1: カタログ, 2: ページツリー, 3 以降: ページごとに (ページ, 内容, 画像)
+    writer.add("<< /Type /Catalog /Pages 2 0 R >>", None);
+    let kids = (0..files.len()).map(|i| format!("{} 0 R", 3 + i * 3)).collect::<Vec<_>>().join(" ");
+    writer.add(&format!("<< /Type /Pages /Kids [{}] /Count {} >>", kids, files.len()), None);
+
+    for (i, path) in files.iter().enumerate() {
+        let (width, height, image) = load_page(path)?;
+        let (page_width, page_height) = (width as f32 * 72.0 / dpi as f32, height as f32 * 72.0 / dpi as f32);
+        let (content, image_number) = (4 + i * 3, 5 + i * 3);
+
+        writer.add(&format!("<< /Type /Page /Parent 2 0 R /MediaBox [0 0 {:.2} {:.2}] /Resources << /XObject << /Im0 {} 0 R >> >> /Contents {} 0 R >>",
+            page_width, page_height, image_number, content), None);
+        let drawing = format!("q {:.2} 0 0 {:.2} 0 0 cm /Im0 Do Q", page_width, page_height);
+        writer.add(&format!("<< /Length {} >>", drawing.len()), Some(drawing.as_bytes()));
+
+        let (color_space, bits, filter, data) = match image {
+            PageImage::Jpeg { data, components } => (if components == 1 { "/DeviceGray" } else { "/DeviceRGB" }, 8, "/DCTDecode", data),
+            PageImage::Bilevel(data) => ("/DeviceGray", 1, "/RunLengthDecode", run_length(&data)),
+            PageImage::Gray(data) => ("/DeviceGray", 8, "/RunLengthDecode", run_length(&data)),
+            PageImage::Rgb(data) => ("/DeviceRGB", 8, "/RunLengthDecode", run_length(&data)),
+        };
+        writer.add(&format!("<< /Type /XObject /Subtype /Image /Width {} /Height {} /ColorSpace {} /BitsPerComponent {} /Filter {} /Length {} >>",
+            width, height, color_space, bits, filter, data.len()), Some(&data));
+    }
+
+    fs::write(pdf, writer.finish(1)).map_err(|e| format!("{}: {}", pdf.display(), e))
+}
+
+/// Resolve a reference to its object. Other objects are returned as they are.
+fn resolve<'a>(document: &'a Document, object: &'a Object) -> &'a Object {
+    match object.as_reference() {
+        Ok(id) => document.get_object(id).unwrap_or(object),
+        Err(_) => object,
+    }
+}
+
+/// Count the pages of a PDF document.
+pub fn page_count(pdf: &Path) -> Result<usize, String> {
+    let document = Document::load(pdf).map_err(|e| format!("{}: {}", pdf.display(), e))?;
+    Ok(document.get_pages().len())
+}
+
+/// Get the image streams (XObjects) in the resources of a page, including the resources inherited from the page tree.
+fn page_images(document: &Document, page_id: lopdf::ObjectId) -> Vec<&Stream> {
+    let Ok((resources, inherited)) = document.get_page_resources(page_id) else { return Vec::new() };
+    let dictionaries = resources.into_iter().chain(inherited.into_iter().filter_map(|id| document.get_dictionary(id).ok()));
+
+    let mut images = Vec::new();
+    for dictionary in dictionaries {
+        let Ok(xobjects) = dictionary.get(b"XObject").map(|x| resolve(document, x)).and_then(Object::as_dict) else { continue };
+        for (_, xobject) in xobjects.iter() {
+            if let Ok(stream) = resolve(document, xobject).as_stream() {
+                if stream.dict.get(b"Subtype").and_then(Object::as_name).is_ok_and(|subtype| subtype == b"Image") {
+                    images.push(stream);
+                }
+            }
+        }
+    }
+    images
+}
+
+/// Get the number of the color components of an image: 1 (gray), 3 (RGB) or 4 (CMYK).
+/// ICC based color spaces have the number in their stream. Other color spaces (e.g. indexed colors) are not supported.
+fn color_components(document: &Document, dictionary: &Dictionary) -> Option<u8> {
+    let color_space = resolve(document, dictionary.get(b"ColorSpace").ok()?);
+    let (name, parameter) = match color_space {
+        Object::Array(array) => (array.first()?.as_name().ok()?, array.get(1)),
+        _ => (color_space.as_name().ok()?, None),
+    };
+    match name {
+        b"DeviceGray" | b"CalGray" => Some(1),
+        b"DeviceRGB" | b"CalRGB" => Some(3),
+        b"DeviceCMYK" => Some(4),
+        b"ICCBased" => resolve(document, parameter?).as_stream().ok()?.dict.get(b"N").ok()?.as_i64().ok().map(|n| n as u8),
+        _ => None,
+    }
+}
+
+/// Decode an image stream of a PDF document.
+/// Jpeg images (DCTDecode) are decoded as they are, and the pixels of the others are expanded (FlateDecode, RunLengthDecode or no filter).
+fn decode_image(document: &Document, stream: &Stream) -> Result<DynamicImage, String> {
+    let dictionary = &stream.dict;
+    let number = |key: &[u8]| dictionary.get(key).map(|n| resolve(document, n)).and_then(Object::as_i64).ok();
+    let filters = match dictionary.get(b"Filter").map(|f| resolve(document, f)) {
+        Ok(Object::Array(filters)) => filters.iter().filter_map(|f| f.as_name().ok()).collect::<Vec<_>>(),
+        Ok(filter) => filter.as_name().ok().into_iter().collect(),
+        Err(_) => Vec::new(),
+    };
+
+    match filters.last().copied() {
+        Some(b"DCTDecode") => return image::load_from_memory_with_format(&stream.content, ImageFormat::Jpeg).map_err(|e| e.to_string()),
+        Some(b"FlateDecode") | Some(b"RunLengthDecode") | None => {},
+        Some(filter) => return Err(format!("The images compressed with {} are not supported", String::from_utf8_lossy(filter))),
+    }
+    let data = match filters.last().copied() {
+        Some(b"RunLengthDecode") => run_length_decode(&stream.content),
+        Some(_) => stream.decompressed_content().map_err(|e| e.to_string())?,
+        None => stream.content.clone(),
+    };
+
+    let (Some(width), Some(height)) = (number(b"Width"), number(b"Height")) else {
+        return Err("The size of the image is missing".to_string());
+    };
+    let (width, height) = (width as u32, height as u32);
+    let bits = number(b"BitsPerComponent").unwrap_or(8);
+    // ImageMask (ステンシル) は 1 ビットのグレーとして扱う
+    let image_mask = dictionary.get(b"ImageMask").and_then(Object::as_bool).unwrap_or(false);
+    let components = if image_mask { Some(1) } else { color_components(document, dictionary) };
+    let unsupported = || format!("Unsupported image: {} bits per component, color space {:?}", bits, dictionary.get(b"ColorSpace").ok());
+
+    match (components, bits) {
+        (Some(1), 1) => {
+            // 1 ビット: 各行はバイト境界まで詰められている (1 = 白、ImageMask では 1 = 塗らない)
+            let row_bytes = (width as usize).div_ceil(8);
+            Ok(DynamicImage::ImageLuma8(GrayImage::from_fn(width, height, |x, y| {
+                let bit = data.get(y as usize * row_bytes + x as usize / 8).is_some_and(|byte| byte & (0x80 >> (x % 8)) != 0);
+                image::Luma([if bit { 255 } else { 0 }])
+            })))
+        },
+        (Some(1), 8) => GrayImage::from_raw(width, height, data).map(DynamicImage::ImageLuma8).ok_or_else(unsupported),
+        (Some(3), 8) => RgbImage::from_raw(width, height, data).map(DynamicImage::ImageRgb8).ok_or_else(unsupported),
+        (Some(4), 8) => {
+            // CMYK -> RGB (ICC プロファイルは使わない)
+            let rgb = data.chunks_exact(4).flat_map(|p| {
+                let k = 255 - p[3] as u32;
+                [(255 - p[0] as u32) * k / 255, (255 - p[1] as u32) * k / 255, (255 - p[2] as u32) * k / 255].map(|c| c as u8)
+            }).collect::<Vec<u8>>();
+            RgbImage::from_raw(width, height, rgb).map(DynamicImage::ImageRgb8).ok_or_else(unsupported)
+        },
+        _ => Err(unsupported()),
+    }
+}
+
+/// Read the image of a page of a PDF document (page numbers start from 1), e.g. a page of a scanned document.
+/// The largest image on the page is the page; text and vector graphics are not rendered.
+pub fn read_page(pdf: &Path, page: usize) -> Result<DynamicImage, String> {
+    let err = |e: &dyn std::fmt::Display| format!("{}: page {}: {}", pdf.display(), page, e);
+    let document = Document::load(pdf).map_err(|e| err(&e))?;
+    let page_id = *document.get_pages().get(&(page as u32)).ok_or_else(|| err(&"not found"))?;
+
+    let number = |stream: &Stream, key: &[u8]| stream.dict.get(key).and_then(Object::as_i64).unwrap_or(0);
+    let largest = page_images(&document, page_id).into_iter().max_by_key(|stream| number(stream, b"Width") * number(stream, b"Height"));
+    let stream = largest.ok_or_else(|| err(&"no images (only pages of scanned documents can be read)"))?;
+    decode_image(&document, stream).map_err(|e| err(&e))
+}
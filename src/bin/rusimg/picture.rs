@@ -0,0 +1,135 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use serde_json::{json, Map, Value};
+use librusimg::Extension;
+
+/// Order of the <source> elements: the browser uses the first format it supports, so the smaller formats come first.
+const SOURCE_ORDER: [&str; 4] = ["webp", "png", "jpeg", "bmp"];
+/// Formats used for the <img> fallback, in the order of preference (supported by all browsers).
+const FALLBACK_ORDER: [&str; 3] = ["jpeg", "png", "bmp"];
+
+/// Variant is an output of a source listed in the --picture-manifest.
+/// - path: Path of the output, relative to the directory of the manifest if it is inside it ('/' separated).
+/// - format: Format of the output (e.g. webp).
+/// - width, height: Size of the output in pixels.
+/// - bytes: File size of the output.
+#[derive(Debug, Clone)]
+struct Variant {
+    path: String,
+    format: String,
+    width: u32,
+    height: u32,
+    bytes: u64,
+}
+
+impl Variant {
+    /// Read the format and the size of an output file.
+    fn read(output_path: &Path, manifest_dir: &Path) -> Result<Variant, String> {
+        let error = |e: String| format!("{}: {}", output_path.display(), e);
+        let format = Extension::from_path(output_path).map_err(|e| error(e.to_string()))?;
+        let (width, height) = image::image_dimensions(output_path).map_err(|e| error(e.to_string()))?;
+        let bytes = fs::metadata(output_path).map_err(|e| error(e.to_string()))?.len();
+        Ok(Variant {
+            path: manifest_path(output_path, manifest_dir),
+            format: format.to_string(),
+            width: width,
+            height: height,
+            bytes: bytes,
+        })
+    }
+
+    fn to_json(&self) -> Value {
+        json!({
+            "path": self.path,
+            "format": self.format,
+            "width": self.width,
+            "height": self.height,
+            "bytes": self.bytes,
+        })
+    }
+}
+
+/// Get the path written to the manifest: relative to the directory of the manifest if the file is inside it, with '/' as the separator (as in URLs).
+fn manifest_path(path: &Path, manifest_dir: &Path) -> String {
+    let relative = path.strip_prefix(manifest_dir).unwrap_or(path);
+    relative.components().map(|c| c.as_os_str().to_string_lossy()).collect::<Vec<_>>().join("/")
+}
+
+/// Write the manifest of the outputs (pairs of the source and the output path) for <picture> elements and srcset attributes.
+/// - JSON (default): An object mapping each source to its variants. An existing manifest is merged (variants with the same path are replaced),
+///   so the sizes and formats made by several runs (e.g. one run per width) end up in one manifest.
+/// - HTML (.html, .htm): A <picture> element for each source with the variants of this run.
+pub fn write(manifest: &Path, outputs: &[(PathBuf, PathBuf)]) -> Result<(), String> {
+    let manifest_dir = manifest.parent().unwrap_or(Path::new(""));
+    let mut sources: BTreeMap<String, Vec<Variant>> = BTreeMap::new();
+    for (source, output) in outputs {
+        let variant = Variant::read(output, manifest_dir)?;
+        sources.entry(manifest_path(source, manifest_dir)).or_default().push(variant);
+    }
+    for variants in sources.values_mut() {
+        variants.sort_by(|a, b| (&a.format, a.width).cmp(&(&b.format, b.width)));
+    }
+
+    let html = manifest.extension().is_some_and(|e| e.eq_ignore_ascii_case("html") || e.eq_ignore_ascii_case("htm"));
+    let content = if html {
+        sources.iter().map(|(source, variants)| picture_element(source, variants)).collect::<Vec<_>>().join("\n")
+    }
+    else {
+        let merged = merge_json(manifest, &sources)?;
+        serde_json::to_string_pretty(&merged).map_err(|e| e.to_string())? + "\n"
+    };
+    fs::write(manifest, content).map_err(|e| format!("{}: {}", manifest.display(), e))
+}
+
+/// Merge the variants into the existing JSON manifest (if any).
+fn merge_json(manifest: &Path, sources: &BTreeMap<String, Vec<Variant>>) -> Result<Map<String, Value>, String> {
+    let mut merged = match fs::read_to_string(manifest) {
+        Ok(content) => serde_json::from_str::<Map<String, Value>>(&content).map_err(|e| format!("{}: {}", manifest.display(), e))?,
+        Err(_) => Map::new(),
+    };
+    for (source, variants) in sources {
+        let mut entries = match merged.remove(source) {
+            Some(Value::Array(entries)) => entries,
+            _ => Vec::new(),
+        };
+        // 同じパスの出力は今回の結果で置き換える
+        entries.retain(|entry| !variants.iter().any(|variant| entry.get("path").and_then(|p| p.as_str()) == Some(variant.path.as_str())));
+        entries.extend(variants.iter().map(|variant| variant.to_json()));
+        entries.sort_by_key(|entry| (entry.get("format").and_then(|f| f.as_str()).unwrap_or_default().to_string(), entry.get("width").and_then(|w| w.as_u64()).unwrap_or(0)));
+        merged.insert(source.clone(), Value::Array(entries));
+    }
+    Ok(merged)
+}
+
+/// Make the <picture> element of a source.
+/// Each format other than the fallback gets a <source> with a srcset of its widths, and the <img> gets the fallback format (jpeg, png or bmp if any)
+/// with the size of its largest variant, so the browser can reserve the space before loading.
+fn picture_element(source: &str, variants: &[Variant]) -> String {
+    let mut formats = variants.iter().map(|v| v.format.as_str()).collect::<Vec<_>>();
+    formats.dedup();
+    formats.sort_by_key(|format| SOURCE_ORDER.iter().position(|f| f == format).unwrap_or(SOURCE_ORDER.len()));
+    let fallback = FALLBACK_ORDER.iter().copied().find(|f| formats.contains(f)).unwrap_or(formats[0]);
+
+    let srcset = |format: &str| {
+        variants.iter()
+            .filter(|v| v.format == format)
+            .map(|v| format!("{} {}w", escape(&v.path), v.width))
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+
+    let mut html = format!("<!-- {} -->\n<picture>\n", escape(source));
+    for format in formats.iter().filter(|f| **f != fallback) {
+        html.push_str(&format!("  <source type=\"image/{}\" srcset=\"{}\">\n", format, srcset(format)));
+    }
+    let largest = variants.iter().filter(|v| v.format == fallback).max_by_key(|v| v.width).unwrap();
+    html.push_str(&format!("  <img src=\"{}\" srcset=\"{}\" width=\"{}\" height=\"{}\" alt=\"\">\n", escape(&largest.path), srcset(fallback), largest.width, largest.height));
+    html.push_str("</picture>\n");
+    html
+}
+
+/// Escape a text for HTML attributes and comments.
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('"', "&quot;").replace('<', "&lt;").replace('>', "&gt;").replace("--", "&#45;&#45;")
+}
@@ -0,0 +1,79 @@
+use std::fs;
+use std::path::PathBuf;
+use image::{DynamicImage, RgbaImage};
+use librusimg::{Extension, SaveOptions};
+
+use crate::budget;
+use crate::montage;
+
+/// Size of the crop shown for each quality. The crop is not scaled, so that the compression artifacts are visible.
+const TILE_SIZE: u32 = 320;
+/// Number of tiles per row of the sheet.
+const COLUMNS: u32 = 3;
+/// Qualities compared when --qualities is not specified.
+pub const DEFAULT_QUALITIES: [f32; 4] = [60.0, 70.0, 80.0, 90.0];
+
+/// PreviewOptions is the options of the preview-quality subcommand.
+/// - image: The image to be compared.
+/// - qualities: Qualities to be compared (0.0 <= q <= 100.0).
+/// - output: Path of the sheet. If None, <image name>_qualities.png next to the image.
+/// - view: Show the sheet in the terminal.
+#[derive(Debug, Clone)]
+pub struct PreviewOptions {
+    pub image: PathBuf,
+    pub qualities: Vec<f32>,
+    pub output: Option<PathBuf>,
+    pub view: bool,
+}
+
+/// Crop the center of the image to a tile.
+fn center_tile(image: &DynamicImage) -> RgbaImage {
+    let (width, height) = (image.width().min(TILE_SIZE), image.height().min(TILE_SIZE));
+    let (x, y) = ((image.width() - width) / 2, (image.height() - height) / 2);
+    image.crop_imm(x, y, width, height).to_rgba8()
+}
+
+/// Format a file size for the labels (e.g. 123.4KB, 1.2MB).
+fn label_size(bytes: u64) -> String {
+    if bytes >= 1_000_000 {
+        format!("{:.1}MB", bytes as f64 / 1e6)
+    }
+    else {
+        format!("{:.1}KB", bytes as f64 / 1e3)
+    }
+}
+
+/// Encode the image at each quality and write a labeled comparison sheet.
+/// The first tile is the source, and the others are the outputs at each quality with their sizes.
+/// Jpeg and webp images are compared in their own format, and other images as jpeg.
+pub fn run(options: &PreviewOptions) -> Result<(), String> {
+    let mut image = librusimg::open_image(&options.image).map_err(|e| e.to_string())?;
+    if !budget::supports(&image.extension) {
+        image.convert(&Extension::Jpeg).map_err(|e| e.to_string())?;
+    }
+    let source_size = fs::metadata(&options.image).map_err(|e| e.to_string())?.len();
+
+    let mut tiles = vec![(center_tile(image.as_dynamic_image()), format!("SRC {}", label_size(source_size)))];
+    for quality in &options.qualities {
+        let (size, decoded) = budget::trial_encode(&image, *quality, &SaveOptions::default(), true).map_err(|e| e.to_string())?;
+        let decoded = decoded.ok_or(format!("Failed to decode the output at quality {}", quality))?;
+        tiles.push((center_tile(&decoded), format!("Q{} {}", quality, label_size(size))));
+        println!("quality {:>5}: {} bytes", quality, size);
+    }
+
+    let sheet = DynamicImage::ImageRgba8(montage::compose(&tiles, COLUMNS));
+    let output = match &options.output {
+        Some(output) => output.clone(),
+        None => {
+            let stem = options.image.file_stem().and_then(|s| s.to_str()).unwrap_or("image");
+            options.image.with_file_name(format!("{}_qualities.png", stem))
+        },
+    };
+    sheet.save(&output).map_err(|e| e.to_string())?;
+    println!("{}", output.display());
+
+    if options.view {
+        crate::viewer::view(&sheet).map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
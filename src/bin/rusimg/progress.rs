@@ -0,0 +1,104 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use serde_json::{json, Value};
+use librusimg::warning::Warning;
+
+/// Destination of the progress events.
+/// - File: A file (created or truncated) or a named pipe (--progress-file).
+/// - Fd: An inherited file descriptor, e.g. 3 (--progress-fd, unix only).
+#[derive(Debug, Clone, PartialEq)]
+pub enum ProgressTarget {
+    File(PathBuf),
+    Fd(i32),
+}
+
+/// Progress writes machine-readable progress events as JSON lines, one object per line.
+/// Each event has an "event" key: batch_started, started, finished, error or batch_finished.
+/// The writer is shared by the threads, and each line is flushed immediately so that readers see the events as they happen.
+pub struct Progress {
+    writer: Mutex<Box<dyn Write + Send>>,
+}
+
+impl Progress {
+    /// Open the destination of the progress events.
+    pub fn open(target: &ProgressTarget) -> Result<Self, String> {
+        let writer: Box<dyn Write + Send> = match target {
+            ProgressTarget::File(path) => Box::new(File::create(path).map_err(|e| format!("{}: {}", path.display(), e))?),
+            ProgressTarget::Fd(fd) => Box::new(open_fd(*fd)?),
+        };
+        Ok(Self {
+            writer: Mutex::new(Box::new(BufWriter::new(writer))),
+        })
+    }
+
+    /// Write an event. Errors are ignored, so that a closed reader does not stop the batch.
+    fn emit(&self, event: Value) {
+        let mut writer = self.writer.lock().unwrap();
+        let _ = writeln!(writer, "{}", event);
+        let _ = writer.flush();
+    }
+
+    /// The batch has started with `total` files.
+    pub fn batch_started(&self, total: usize) {
+        self.emit(json!({ "event": "batch_started", "total": total }));
+    }
+
+    /// Processing of the file has started.
+    pub fn started(&self, file: &Path) {
+        self.emit(json!({ "event": "started", "file": file.display().to_string() }));
+    }
+
+    /// Processing of the file has finished.
+    /// - status: success, canceled, not_needed or skipped_blank.
+    /// - warnings: Non-fatal issues of the file, each with the kind (e.g. trim_clamped) and the message.
+    pub fn finished(&self, file: &Path, output: Option<&Path>, status: &str, before_size: u64, after_size: Option<u64>, warnings: &[Warning]) {
+        let warnings: Vec<Value> = warnings.iter().map(|w| json!({ "kind": w.kind(), "message": w.to_string() })).collect();
+        self.emit(json!({
+            "event": "finished",
+            "file": file.display().to_string(),
+            "output": output.map(|o| o.display().to_string()),
+            "status": status,
+            "before_size": before_size,
+            "after_size": after_size,
+            "warnings": warnings,
+        }));
+    }
+
+    /// Processing of the file has failed.
+    pub fn error(&self, file: Option<&Path>, message: &str) {
+        self.emit(json!({
+            "event": "error",
+            "file": file.map(|f| f.display().to_string()),
+            "message": message,
+        }));
+    }
+
+    /// The batch has finished.
+    pub fn batch_finished(&self, processed: usize, failed: usize) {
+        self.emit(json!({ "event": "batch_finished", "processed": processed, "failed": failed }));
+    }
+}
+
+/// Open an inherited file descriptor for writing.
+#[cfg(unix)]
+fn open_fd(fd: i32) -> Result<File, String> {
+    use std::os::unix::io::FromRawFd;
+
+    // 標準入出力は使わない
+    if fd <= 2 {
+        return Err(format!("Progress file descriptor must be >= 3 ({})", fd));
+    }
+    // fcntl がないので /dev/fd で存在を確認
+    if !Path::new(&format!("/dev/fd/{}", fd)).exists() {
+        return Err(format!("Progress file descriptor {} is not open", fd));
+    }
+    Ok(unsafe { File::from_raw_fd(fd) })
+}
+
+/// File descriptors are not supported on this platform.
+#[cfg(not(unix))]
+fn open_fd(fd: i32) -> Result<File, String> {
+    Err(format!("--progress-fd is not supported on this platform ({})", fd))
+}
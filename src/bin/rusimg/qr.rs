@@ -0,0 +1,32 @@
+use image::DynamicImage;
+use librusimg::Rect;
+
+/// Whether QR code detection is compiled into this build (qr-detect feature).
+pub const AVAILABLE: bool = cfg!(feature = "qr-detect");
+
+/// Detect the QR codes in an image with rqrr.
+/// Returns the bounding boxes of the codes in pixels of the image, widened by 5% on each side to cover the anti-aliased edges.
+/// The image is not downscaled, because QR codes in screenshots are often small.
+#[cfg(feature = "qr-detect")]
+pub fn detect(image: &DynamicImage) -> Result<Vec<Rect>, String> {
+    let mut prepared = rqrr::PreparedImage::prepare(image.to_luma8());
+    Ok(prepared.detect_grids().iter().map(|grid| {
+        let xs = grid.bounds.iter().map(|point| point.x.max(0) as u32);
+        let ys = grid.bounds.iter().map(|point| point.y.max(0) as u32);
+        let (left, right) = (xs.clone().min().unwrap_or(0), xs.max().unwrap_or(0));
+        let (top, bottom) = (ys.clone().min().unwrap_or(0), ys.max().unwrap_or(0));
+        let margin = (right - left).max(bottom - top) / 20 + 1;
+        Rect {
+            x: left.saturating_sub(margin),
+            y: top.saturating_sub(margin),
+            w: right - left + margin * 2,
+            h: bottom - top + margin * 2,
+        }
+    }).collect())
+}
+
+/// QR code detection is not compiled into this build.
+#[cfg(not(feature = "qr-detect"))]
+pub fn detect(_image: &DynamicImage) -> Result<Vec<Rect>, String> {
+    Err("rusimg was built without QR code detection. Build it with --features qr-detect.".to_string())
+}
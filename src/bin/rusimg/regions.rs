@@ -0,0 +1,32 @@
+use std::fs;
+use std::path::Path;
+
+/// Convert one entry of a trim file to the format of the trim option.
+/// An entry is a string in the format of --trim (e.g. "100x100+50x50", "10%x10%+50%x50%"),
+/// or an object with the area in pixels (e.g. {"x": 100, "y": 100, "w": 50, "h": 50}).
+fn to_trim(entry: &serde_json::Value) -> Option<String> {
+    match entry {
+        serde_json::Value::String(s) => Some(s.trim().to_string()),
+        serde_json::Value::Object(object) => {
+            let value = |key: &str| object.get(key).and_then(|v| v.as_u64());
+            Some(format!("{}x{}+{}x{}", value("x")?, value("y")?, value("w")?, value("h")?))
+        },
+        _ => None,
+    }
+}
+
+/// Load the trim areas of a trim file (--trim-file).
+/// The file is a JSON array of areas (e.g. ["0x0+1200x900", {"x": 1200, "y": 0, "w": 1200, "h": 900}]).
+/// The areas are returned in the format of the trim option, so that they are checked in the same way as --trim.
+pub fn load(path: &Path) -> Result<Vec<String>, String> {
+    let text = fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    let value: serde_json::Value = serde_json::from_str(&text).map_err(|e| format!("{}: {}", path.display(), e))?;
+    let entries = value.as_array().ok_or(format!("{}: the trim file must be an array of areas", path.display()))?;
+    if entries.is_empty() {
+        return Err(format!("{}: the trim file has no areas", path.display()));
+    }
+
+    entries.iter().enumerate().map(|(i, entry)| {
+        to_trim(entry).ok_or(format!("{}: area {}: must be a trim string (e.g. \"100x100+50x50\") or an object with x, y, w and h", path.display(), i + 1))
+    }).collect()
+}
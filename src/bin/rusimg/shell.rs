@@ -0,0 +1,173 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Action of the install-shell-integration subcommand.
+/// - Install: Register the context-menu entries.
+/// - Uninstall: Remove the context-menu entries.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ShellAction {
+    Install,
+    Uninstall,
+}
+
+/// Context-menu entries: id, label and the options passed to rusimg with the selected files.
+const PRESETS: [(&str, &str, &[&str]); 2] = [
+    ("webp", "Convert to WebP with rusimg", &["-c", "webp"]),
+    ("compress", "Compress with rusimg", &["-q", "75", "--in-place"]),
+];
+/// MIME types that get the entries (file managers on Linux).
+const MIME_TYPES: [&str; 4] = ["image/jpeg", "image/png", "image/webp", "image/bmp"];
+
+/// Get the home directory.
+fn home_dir() -> Result<PathBuf, String> {
+    std::env::var_os("HOME").or(std::env::var_os("USERPROFILE")).map(PathBuf::from).ok_or("Failed to get the home directory".to_string())
+}
+
+/// Write a file and make it executable (file managers only run executable scripts and service menus).
+fn write_executable(path: &Path, content: &str) -> Result<(), String> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(|e| format!("{}: {}", parent.display(), e))?;
+    }
+    fs::write(path, content).map_err(|e| format!("{}: {}", path.display(), e))?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(path, fs::Permissions::from_mode(0o755)).map_err(|e| format!("{}: {}", path.display(), e))?;
+    }
+    println!("{}", path.display());
+    Ok(())
+}
+
+/// Remove a file or a directory if it exists.
+fn remove(path: &Path) -> Result<(), String> {
+    let result = if path.is_dir() {
+        fs::remove_dir_all(path)
+    }
+    else if path.exists() {
+        fs::remove_file(path)
+    }
+    else {
+        return Ok(());
+    };
+    result.map_err(|e| format!("{}: {}", path.display(), e))?;
+    println!("{}", path.display());
+    Ok(())
+}
+
+/// Run reg.exe.
+fn reg(args: &[&str]) -> Result<(), String> {
+    let status = Command::new("reg").args(args).status().map_err(|e| format!("reg: {}", e))?;
+    if !status.success() {
+        return Err(format!("reg {} failed", args.join(" ")));
+    }
+    Ok(())
+}
+
+/// Windows Explorer: register verbs under HKCU\Software\Classes\SystemFileAssociations\.<ext>\shell (no administrator rights needed).
+fn windows(action: ShellAction, exe: &Path) -> Result<(), String> {
+    // 画像形式の拡張子すべてに登録
+    let extensions = librusimg::Extension::builtin().iter().flat_map(|format| format.aliases().iter().copied()).collect::<Vec<_>>();
+    for extension in extensions {
+        for (id, label, options) in PRESETS {
+            let key = format!("HKCU\\Software\\Classes\\SystemFileAssociations\\.{}\\shell\\rusimg.{}", extension, id);
+            match action {
+                ShellAction::Install => {
+                    let command = format!("\"{}\" \"%1\" {}", exe.display(), options.join(" "));
+                    reg(&["add", &key, "/ve", "/d", label, "/f"])?;
+                    reg(&["add", &format!("{}\\command", key), "/ve", "/d", &command, "/f"])?;
+                },
+                ShellAction::Uninstall => {
+                    // 登録されていないキーの削除は失敗するので無視
+                    let _ = reg(&["delete", &key, "/f"]);
+                },
+            }
+            println!("{}", key);
+        }
+    }
+    Ok(())
+}
+
+/// Linux: a KDE service menu (Dolphin) and Nautilus scripts (GNOME Files).
+fn linux(action: ShellAction, exe: &Path) -> Result<(), String> {
+    let data_dir = match std::env::var_os("XDG_DATA_HOME") {
+        Some(dir) => PathBuf::from(dir),
+        None => home_dir()?.join(".local").join("share"),
+    };
+    let service_menu = data_dir.join("kio").join("servicemenus").join("rusimg.desktop");
+    let scripts_dir = data_dir.join("nautilus").join("scripts");
+
+    if action == ShellAction::Uninstall {
+        remove(&service_menu)?;
+        for (_, label, _) in PRESETS {
+            remove(&scripts_dir.join(label))?;
+        }
+        return Ok(());
+    }
+
+    let mut desktop = format!(
+        "[Desktop Entry]\nType=Service\nMimeType={};\nActions={};\nX-KDE-ServiceTypes=KonqPopupMenu/Plugin\n",
+        MIME_TYPES.join(";"),
+        PRESETS.iter().map(|(id, _, _)| *id).collect::<Vec<_>>().join(";"),
+    );
+    for (id, label, options) in PRESETS {
+        desktop.push_str(&format!("\n[Desktop Action {}]\nName={}\nIcon=image-x-generic\nExec=\"{}\" %F {}\n", id, label, exe.display(), options.join(" ")));
+    }
+    write_executable(&service_menu, &desktop)?;
+
+    for (_, label, options) in PRESETS {
+        let script = format!("#!/bin/sh\nexec \"{}\" \"$@\" {}\n", exe.display(), options.join(" "));
+        write_executable(&scripts_dir.join(label), &script)?;
+    }
+    Ok(())
+}
+
+/// Escape a string for a property list.
+fn xml_escape(s: &str) -> String {
+    s.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+/// macOS: Finder Quick Actions (Automator services running a shell script) in ~/Library/Services.
+fn macos(action: ShellAction, exe: &Path) -> Result<(), String> {
+    let services_dir = home_dir()?.join("Library").join("Services");
+    for (_, label, options) in PRESETS {
+        let workflow = services_dir.join(format!("{}.workflow", label));
+        if action == ShellAction::Uninstall {
+            remove(&workflow)?;
+            continue;
+        }
+
+        let contents = workflow.join("Contents");
+        let info = format!(
+            include_str!("shell/Info.plist"),
+            label = xml_escape(label),
+        );
+        let command = format!("\"{}\" \"$@\" {}", exe.display(), options.join(" "));
+        let document = format!(
+            include_str!("shell/document.wflow"),
+            command = xml_escape(&command),
+        );
+        fs::create_dir_all(&contents).map_err(|e| format!("{}: {}", contents.display(), e))?;
+        fs::write(contents.join("Info.plist"), info).map_err(|e| format!("{}: {}", workflow.display(), e))?;
+        fs::write(contents.join("document.wflow"), document).map_err(|e| format!("{}: {}", workflow.display(), e))?;
+        println!("{}", workflow.display());
+    }
+    Ok(())
+}
+
+/// Register (or remove) the context-menu entries of the file manager, which call this executable with the presets.
+/// - Windows: Explorer verbs in the registry of the current user.
+/// - macOS: Finder Quick Actions.
+/// - Linux: KDE service menu and Nautilus scripts.
+pub fn run(action: ShellAction) -> Result<(), String> {
+    let exe = std::env::current_exe().map_err(|e| e.to_string())?;
+    if cfg!(target_os = "windows") {
+        windows(action, &exe)
+    }
+    else if cfg!(target_os = "macos") {
+        macos(action, &exe)
+    }
+    else {
+        linux(action, &exe)
+    }
+}
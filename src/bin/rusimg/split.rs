@@ -0,0 +1,136 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use glob::{glob_with, MatchOptions};
+use image::{DynamicImage, GrayImage, Luma};
+use librusimg::Extension;
+
+use crate::analysis;
+use crate::filters;
+
+/// Color tolerance of the background when --tolerance is not specified.
+/// Scanners add noise to the lid, so it is larger than the tolerance of --autocrop.
+pub const DEFAULT_TOLERANCE: u8 = 24;
+/// Minimum area of a photo (in percent of the scan) when --min-area is not specified.
+pub const DEFAULT_MIN_AREA: f32 = 1.0;
+
+/// SplitScanOptions is the options of the split-scan subcommand.
+/// - images: Scans (file paths or wildcard patterns).
+/// - output: Output directory. If None, the directory of each scan.
+/// - convert: Format of the outputs. If None, the format of the scan, or png if the library does not support it (e.g. tiff).
+/// - tolerance: Allowed difference of each channel from the background color (0-255).
+/// - min_area: Minimum area of a photo in percent of the scan. Smaller regions (e.g. dust, scratches) are ignored.
+/// - deskew: Straighten each photo.
+#[derive(Debug, Clone)]
+pub struct SplitScanOptions {
+    pub images: Vec<PathBuf>,
+    pub output: Option<PathBuf>,
+    pub convert: Option<String>,
+    pub tolerance: u8,
+    pub min_area: f32,
+    pub deskew: bool,
+}
+
+/// Get the scans of a path: the file itself, or the files matched by a wildcard pattern.
+/// Patterns are expanded here, because the shell does not expand them on Windows.
+pub fn expand(path: &Path) -> Result<Vec<PathBuf>, String> {
+    if path.is_file() {
+        return Ok(vec![path.to_path_buf()]);
+    }
+    let pattern = path.to_str().ok_or(format!("{}: not found", path.display()))?;
+    let options = MatchOptions {
+        case_sensitive: false,
+        require_literal_separator: false,
+        require_literal_leading_dot: false,
+    };
+    let files = glob_with(&pattern.replace('\\', "/"), options).map_err(|e| e.to_string())?
+        .filter_map(|entry| entry.ok())
+        .filter(|path| path.is_file())
+        .collect::<Vec<_>>();
+    if files.is_empty() {
+        return Err(format!("{}: not found", path.display()));
+    }
+    Ok(files)
+}
+
+/// Straighten a photo cropped from a scan.
+/// The skew is detected on the mask of the photo (dark on a white background): the edges of a photo are straight, unlike its content.
+fn deskew(photo: &DynamicImage, tolerance: u8) -> DynamicImage {
+    let rgba = photo.to_rgba8();
+    let background = analysis::edge_color(&rgba);
+    let mask = analysis::foreground_mask(&rgba, &background, tolerance);
+    let mask = GrayImage::from_fn(rgba.width(), rgba.height(), |x, y| {
+        Luma([if mask[(y * rgba.width() + x) as usize] { 0 } else { 255 }])
+    });
+    let angle = analysis::detect_skew_angle(&DynamicImage::ImageLuma8(mask));
+    if angle == 0.0 {
+        return photo.clone();
+    }
+    filters::rotate(photo, angle, background)
+}
+
+/// Get the extension of the outputs of a scan.
+fn output_extension(scan: &Path, convert: Option<&str>) -> String {
+    match (convert, Extension::from_path(scan)) {
+        (Some(convert), _) => convert.to_string(),
+        (None, Ok(format)) => format.extension_for(scan),
+        // tiff などライブラリが扱えない形式は png で保存
+        (None, Err(_)) => Extension::Png.preferred_extension(),
+    }
+}
+
+/// Detect the photos on a scan and save each of them, straightened and cropped, as <scan name>_<n>.<extension>.
+/// Returns the number of the photos.
+fn split(scan: &Path, options: &SplitScanOptions) -> Result<usize, String> {
+    let image = image::open(scan).map_err(|e| format!("{}: {}", scan.display(), e))?;
+    let photos = analysis::detect_photos(&image, options.tolerance, options.min_area);
+
+    let directory = match &options.output {
+        Some(output) => output.clone(),
+        None => scan.parent().map(|p| p.to_path_buf()).unwrap_or_default(),
+    };
+    fs::create_dir_all(&directory).map_err(|e| format!("{}: {}", directory.display(), e))?;
+    let stem = scan.file_stem().map(|s| s.to_string_lossy().to_string()).unwrap_or_default();
+    let extension = output_extension(scan, options.convert.as_deref());
+
+    for (index, area) in photos.iter().enumerate() {
+        let photo = image.crop_imm(area.x, area.y, area.w, area.h);
+        let photo = if options.deskew { deskew(&photo, options.tolerance) } else { photo };
+        // 傾き補正で余った背景を取り除く
+        let photo = match analysis::detect_border(&photo, options.tolerance) {
+            Some(border) => photo.crop_imm(border.x, border.y, border.w, border.h),
+            None => photo,
+        };
+        // jpeg はアルファチャンネルを持てない
+        let photo = if Extension::from_extension(&extension).ok() == Some(Extension::Jpeg) {
+            DynamicImage::ImageRgb8(photo.to_rgb8())
+        }
+        else {
+            photo
+        };
+
+        let path = directory.join(format!("{}_{}.{}", stem, index + 1, extension));
+        photo.save(&path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        println!("{} ({}x{})", path.display(), photo.width(), photo.height());
+    }
+    Ok(photos.len())
+}
+
+/// Split the scans into the photos on them.
+/// A scan without photos is reported and skipped, and the other scans are processed.
+pub fn run(options: &SplitScanOptions) -> Result<(), String> {
+    let mut scans = Vec::new();
+    for path in &options.images {
+        scans.append(&mut expand(path)?);
+    }
+
+    let mut total = 0;
+    for scan in &scans {
+        let count = split(scan, options)?;
+        if count == 0 {
+            println!("{}: no photos found. Try a smaller --tolerance or --min-area.", scan.display());
+        }
+        total += count;
+    }
+    println!("\n{} photos from {} scans", total, scans.len());
+    Ok(())
+}
@@ -0,0 +1,105 @@
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::sync::OnceLock;
+use image::DynamicImage;
+
+use crate::ProcessingError;
+use crate::parse::ViewProtocol;
+
+/// ViewConfig is the configuration of the terminal preview.
+/// - size: Maximum size of the preview in terminal cells (columns, rows). If None, the terminal size is used.
+/// - protocol: Graphics protocol of the terminal.
+#[derive(Debug, Clone, Copy)]
+struct ViewConfig {
+    size: Option<(u32, Option<u32>)>,
+    protocol: ViewProtocol,
+}
+
+static CONFIG: OnceLock<ViewConfig> = OnceLock::new();
+
+/// Rows kept free below the preview for the result messages.
+const RESERVED_ROWS: u32 = 2;
+
+/// Set the configuration of the terminal preview (--view-size, --view-protocol).
+pub fn init(size: Option<(u32, Option<u32>)>, protocol: ViewProtocol) {
+    let _ = CONFIG.set(ViewConfig { size, protocol });
+}
+
+/// Get the configuration of the terminal preview.
+fn config() -> ViewConfig {
+    *CONFIG.get().unwrap_or(&ViewConfig { size: None, protocol: ViewProtocol::Auto })
+}
+
+/// Get the maximum size of the preview in terminal cells.
+/// The size not specified by --view-size is taken from the terminal.
+fn max_cells(size: Option<(u32, Option<u32>)>) -> (u32, u32) {
+    let (columns, rows) = viuer::terminal_size();
+    let (columns, rows) = (columns as u32, (rows as u32).saturating_sub(RESERVED_ROWS));
+    match size {
+        Some((width, Some(height))) => (width, height),
+        Some((width, None)) => (width, rows),
+        None => (columns, rows),
+    }
+}
+
+/// Fit the image in the cells, keeping the aspect ratio.
+/// A cell shows 1x2 pixels (half blocks), so the image takes half as many rows as its width in columns.
+fn fit(image: &DynamicImage, max_columns: u32, max_rows: u32) -> (u32, u32) {
+    let ratio = image.width().max(1) as f64 / image.height().max(1) as f64;
+    let columns = (max_columns.max(1) as f64).min(max_rows.max(1) as f64 * 2.0 * ratio);
+    let rows = columns / ratio / 2.0;
+    ((columns.round() as u32).max(1), (rows.round() as u32).max(1))
+}
+
+/// Show the image in the terminal using viuer.
+/// Read the image data from memory and display it.
+/// Stdout is locked while the image is printed, so that messages from other threads do not break the preview.
+pub fn view(image: &DynamicImage) -> Result<(), ProcessingError> {
+    let config = config();
+    let (max_columns, max_rows) = max_cells(config.size);
+    let (width, height) = fit(image, max_columns, max_rows);
+    let graphics = config.protocol == ViewProtocol::Auto;
+    let conf = viuer::Config {
+        absolute_offset: false,
+        width: Some(width),
+        height: Some(height),
+        use_kitty: graphics,
+        use_iterm: graphics,
+        ..Default::default()
+    };
+
+    let stdout = std::io::stdout();
+    let mut lock = stdout.lock();
+    let _ = lock.flush();
+    let result = viuer::print(image, &conf);
+    let _ = lock.flush();
+    match result {
+        Ok(_) => Ok(()),
+        Err(e) => Err(ProcessingError::FailedToViewImage(e.to_string())),
+    }
+}
+
+/// Open the file in the default image viewer of the OS.
+/// The viewer is started in the background, and rusimg does not wait for it.
+pub fn open(path: &Path) -> Result<(), ProcessingError> {
+    let mut command = if cfg!(target_os = "windows") {
+        // start の第 1 引数はウィンドウタイトル
+        let mut command = Command::new("cmd");
+        command.args(["/C", "start", ""]);
+        command
+    }
+    else if cfg!(target_os = "macos") {
+        Command::new("open")
+    }
+    else {
+        Command::new("xdg-open")
+    };
+    command.arg(path)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .map(|_| ())
+        .map_err(|e| ProcessingError::FailedToOpenImage(e.to_string()))
+}
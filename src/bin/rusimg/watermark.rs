@@ -0,0 +1,233 @@
+use std::path::PathBuf;
+use image::{DynamicImage, RgbaImage};
+use sha2::{Digest, Sha256};
+
+use crate::filters;
+use crate::split;
+
+/// Maximum length of a watermark message in bytes.
+pub const MAX_MESSAGE_LEN: usize = 32;
+/// Size of the blocks carrying the bits. It is the block size of JPEG, so that the blocks match those of a recompressed JPEG.
+const BLOCK: u32 = 8;
+/// DCT coefficient (horizontal, vertical frequency) carrying the bit of a block.
+/// Mid frequencies survive JPEG compression and are hard to see.
+const COEFFICIENT: (u32, u32) = (2, 1);
+/// Quantization step of the coefficient. Larger steps survive lower JPEG qualities but are more visible.
+const STEP: f32 = 24.0;
+/// Number of bits of a watermark: the length of the message (8 bits), the message padded to MAX_MESSAGE_LEN, and the checksum (16 bits).
+const FRAME_BITS: usize = 8 + MAX_MESSAGE_LEN * 8 + 16;
+/// Minimum number of blocks per bit. Smaller images cannot carry a watermark reliably.
+const MIN_REPEAT: usize = 4;
+
+/// Watermark read from an image.
+/// - message: The embedded message.
+/// - confidence: The ratio of the blocks agreeing with the decoded bits (about 0.5 for random noise, 1.0 for an intact watermark).
+#[derive(Debug, Clone, PartialEq)]
+pub struct Watermark {
+    pub message: String,
+    pub confidence: f32,
+}
+
+/// VerifyWatermarkOptions is the options of the verify-watermark subcommand.
+/// - images: Images to verify (file paths or wildcard patterns).
+/// - key: Key of the watermark.
+/// - expect: If specified, the message must also match it.
+#[derive(Debug, Clone)]
+pub struct VerifyWatermarkOptions {
+    pub images: Vec<PathBuf>,
+    pub key: String,
+    pub expect: Option<String>,
+}
+
+/// Pseudo-random numbers derived from the key (SplitMix64 seeded with SHA-256 of the key).
+/// The generator is defined here rather than taken from rand, so that watermarks stay readable across versions of rusimg.
+struct KeyStream(u64);
+impl KeyStream {
+    fn new(key: &str, purpose: &str) -> KeyStream {
+        let digest = Sha256::new().chain_update(purpose).chain_update(key).finalize();
+        let mut seed = [0u8; 8];
+        seed.copy_from_slice(&digest[..8]);
+        KeyStream(u64::from_le_bytes(seed))
+    }
+
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+}
+
+/// Get the origins of the blocks of an image in the order given by the key.
+/// The n-th block carries the bit n % FRAME_BITS, so each bit is spread over the whole image.
+fn blocks(width: u32, height: u32, key: &str) -> Result<Vec<(u32, u32)>, String> {
+    let columns = (width / BLOCK) as usize;
+    let count = columns * (height / BLOCK) as usize;
+    if count < FRAME_BITS * MIN_REPEAT {
+        return Err(format!("the image is too small for a watermark ({}x{}, at least {} blocks of {}x{} pixels are required)", width, height, FRAME_BITS * MIN_REPEAT, BLOCK, BLOCK));
+    }
+
+    // Fisher-Yates shuffle
+    let mut order = (0..count).collect::<Vec<_>>();
+    let mut stream = KeyStream::new(key, "blocks");
+    for i in (1..count).rev() {
+        let j = (stream.next() % (i as u64 + 1)) as usize;
+        order.swap(i, j);
+    }
+    Ok(order.into_iter().map(|block| ((block % columns) as u32 * BLOCK, (block / columns) as u32 * BLOCK)).collect())
+}
+
+/// Get the checksum of a message. A wrong key gives a wrong checksum, so random bits are not reported as a watermark.
+fn checksum(message: &[u8], key: &str) -> [u8; 2] {
+    let digest = Sha256::new().chain_update(key).chain_update(message).finalize();
+    [digest[0], digest[1]]
+}
+
+/// Get the mask of the bits. The bits are XORed with it, so the message cannot be read without the key.
+fn mask(key: &str) -> Vec<bool> {
+    let mut stream = KeyStream::new(key, "mask");
+    (0..FRAME_BITS).map(|_| stream.next() & 1 == 1).collect()
+}
+
+/// Encode a message into the masked bits of a watermark.
+fn encode(message: &str, key: &str) -> Vec<bool> {
+    let mut bytes = vec![message.len() as u8];
+    bytes.extend_from_slice(message.as_bytes());
+    bytes.resize(1 + MAX_MESSAGE_LEN, 0);
+    bytes.extend_from_slice(&checksum(message.as_bytes(), key));
+
+    let bits = bytes.iter().flat_map(|byte| (0..8).rev().map(move |i| (byte >> i) & 1 == 1));
+    bits.zip(mask(key)).map(|(bit, mask)| bit ^ mask).collect()
+}
+
+/// Decode the masked bits of a watermark. Returns None if the length or the checksum is wrong.
+fn decode(bits: &[bool], key: &str) -> Option<String> {
+    let bits = bits.iter().zip(mask(key)).map(|(bit, mask)| bit ^ mask).collect::<Vec<_>>();
+    let bytes = bits.chunks(8).map(|byte| byte.iter().fold(0u8, |acc, bit| (acc << 1) | *bit as u8)).collect::<Vec<_>>();
+
+    let len = bytes[0] as usize;
+    if len > MAX_MESSAGE_LEN {
+        return None;
+    }
+    let message = &bytes[1..1 + len];
+    if bytes[1 + MAX_MESSAGE_LEN..] != checksum(message, key) {
+        return None;
+    }
+    String::from_utf8(message.to_vec()).ok()
+}
+
+/// Get the DCT basis function of COEFFICIENT (orthonormal 8x8 DCT-II), indexed by [y][x].
+fn basis() -> [[f32; BLOCK as usize]; BLOCK as usize] {
+    let (u, v) = COEFFICIENT;
+    let scale = |k: u32| if k == 0 { (1.0 / BLOCK as f32).sqrt() } else { (2.0 / BLOCK as f32).sqrt() };
+    let wave = |k: u32, i: usize| ((2 * i + 1) as f32 * k as f32 * std::f32::consts::PI / (2 * BLOCK) as f32).cos();
+    let mut basis = [[0.0; BLOCK as usize]; BLOCK as usize];
+    for (y, row) in basis.iter_mut().enumerate() {
+        for (x, value) in row.iter_mut().enumerate() {
+            *value = scale(u) * scale(v) * wave(u, x) * wave(v, y);
+        }
+    }
+    basis
+}
+
+/// Get the value of COEFFICIENT of the brightness of a block.
+fn coefficient(image: &RgbaImage, origin: (u32, u32), basis: &[[f32; BLOCK as usize]; BLOCK as usize]) -> f32 {
+    let mut sum = 0.0;
+    for (y, row) in basis.iter().enumerate() {
+        for (x, value) in row.iter().enumerate() {
+            let [r, g, b, _] = image.get_pixel(origin.0 + x as u32, origin.1 + y as u32).0;
+            sum += (0.299 * r as f32 + 0.587 * g as f32 + 0.114 * b as f32) * value;
+        }
+    }
+    sum
+}
+
+/// Quantize a coefficient to the lattice of the bit (quantization index modulation).
+/// The lattices of 0 and 1 are offset by -STEP/4 and +STEP/4, so a bit survives errors smaller than STEP/4.
+fn quantize(coefficient: f32, bit: bool) -> f32 {
+    let offset = if bit { STEP / 4.0 } else { -STEP / 4.0 };
+    ((coefficient - offset) / STEP).round() * STEP + offset
+}
+
+/// Embed an invisible watermark into an image.
+/// The message is spread over the 8x8 blocks chosen by the key, in a mid-frequency DCT coefficient of the brightness.
+/// It survives recompression (e.g. saving as JPEG), but not resizing, cropping or rotation, so it must be embedded after them.
+pub fn embed(image: &DynamicImage, message: &str, key: &str) -> Result<DynamicImage, String> {
+    if message.len() > MAX_MESSAGE_LEN {
+        return Err(format!("the watermark message is too long ({} bytes, at most {} bytes)", message.len(), MAX_MESSAGE_LEN));
+    }
+    let mut rgba = image.to_rgba8();
+    let blocks = blocks(rgba.width(), rgba.height(), key)?;
+    let bits = encode(message, key);
+    let basis = basis();
+
+    for (i, origin) in blocks.iter().enumerate() {
+        let current = coefficient(&rgba, *origin, &basis);
+        let delta = quantize(current, bits[i % FRAME_BITS]) - current;
+        // 明るさだけを変えるため R, G, B に同じ値を加える
+        for (y, row) in basis.iter().enumerate() {
+            for (x, value) in row.iter().enumerate() {
+                let pixel = rgba.get_pixel_mut(origin.0 + x as u32, origin.1 + y as u32);
+                for channel in pixel.0.iter_mut().take(3) {
+                    *channel = (*channel as f32 + delta * value).round().clamp(0.0, 255.0) as u8;
+                }
+            }
+        }
+    }
+    Ok(filters::to_original_color(image, rgba))
+}
+
+/// Read the watermark of an image with the key.
+/// Each bit is decided by the majority of its blocks. Returns None if there is no watermark, or the key is wrong.
+pub fn extract(image: &DynamicImage, key: &str) -> Result<Option<Watermark>, String> {
+    let rgba = image.to_rgba8();
+    let blocks = blocks(rgba.width(), rgba.height(), key)?;
+    let basis = basis();
+
+    let mut votes = vec![0i64; FRAME_BITS];
+    for (i, origin) in blocks.iter().enumerate() {
+        let current = coefficient(&rgba, *origin, &basis);
+        let one = (quantize(current, true) - current).abs() < (quantize(current, false) - current).abs();
+        votes[i % FRAME_BITS] += if one { 1 } else { -1 };
+    }
+
+    let bits = votes.iter().map(|vote| *vote > 0).collect::<Vec<_>>();
+    // 多数派に一致したブロックの割合
+    let agreeing = votes.iter().map(|vote| vote.unsigned_abs()).sum::<u64>() as f32;
+    let confidence = (blocks.len() as f32 + agreeing) / 2.0 / blocks.len() as f32;
+    Ok(decode(&bits, key).map(|message| Watermark { message: message, confidence: confidence }))
+}
+
+/// Verify the watermarks of the images.
+/// Returns an error if any image has no watermark (or a different message than --expect), so that scripts can check the exit status.
+pub fn run(options: &VerifyWatermarkOptions) -> Result<(), String> {
+    let mut images = Vec::new();
+    for path in &options.images {
+        images.append(&mut split::expand(path)?);
+    }
+
+    let mut failed = 0;
+    for path in &images {
+        let image = image::open(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        let watermark = extract(&image, &options.key).map_err(|e| format!("{}: {}", path.display(), e))?;
+        match watermark {
+            Some(watermark) if options.expect.as_ref().is_some_and(|expect| *expect != watermark.message) => {
+                println!("{}: \"{}\" does not match the expected message", path.display(), watermark.message);
+                failed += 1;
+            },
+            Some(watermark) => {
+                println!("{}: \"{}\" (confidence: {:.0}%)", path.display(), watermark.message, watermark.confidence * 100.0);
+            },
+            None => {
+                println!("{}: no watermark (or wrong key)", path.display());
+                failed += 1;
+            },
+        }
+    }
+
+    if failed > 0 {
+        return Err(format!("{} of {} images failed the verification", failed, images.len()));
+    }
+    Ok(())
+}
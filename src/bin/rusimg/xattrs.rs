@@ -0,0 +1,98 @@
+use std::io;
+use std::path::Path;
+
+/// FileAttributes is the extended attributes and the access control list of a source file.
+/// They are read before saving, because saving in place or --delete may replace the source.
+/// - Unix: The extended attributes (e.g. user.*, com.apple.*). On Linux, the POSIX ACLs are the system.posix_acl_* attributes.
+/// - Windows: The DACL of the security descriptor (the ACL shown in the Security tab).
+#[derive(Debug, Clone, Default)]
+pub struct FileAttributes {
+    #[cfg(unix)]
+    xattrs: Vec<(std::ffi::OsString, Vec<u8>)>,
+    #[cfg(windows)]
+    security_descriptor: Vec<u8>,
+}
+
+impl FileAttributes {
+    /// Read the extended attributes of a file.
+    #[cfg(unix)]
+    pub fn read(path: &Path) -> io::Result<FileAttributes> {
+        if !xattr::SUPPORTED_PLATFORM {
+            return Ok(FileAttributes::default());
+        }
+        let mut xattrs = Vec::new();
+        for name in xattr::list(path)? {
+            if let Some(value) = xattr::get(path, &name)? {
+                xattrs.push((name, value));
+            }
+        }
+        Ok(FileAttributes {
+            xattrs: xattrs,
+        })
+    }
+
+    /// Copy the extended attributes to a file.
+    /// Returns the names of the attributes that could not be written (e.g. security.selinux without privileges, or a file system without xattrs).
+    #[cfg(unix)]
+    pub fn apply(&self, path: &Path) -> Vec<String> {
+        self.xattrs.iter()
+            .filter(|(name, value)| xattr::set(path, name, value).is_err())
+            .map(|(name, _)| name.to_string_lossy().into_owned())
+            .collect()
+    }
+
+    /// Read the ACL of a file.
+    #[cfg(windows)]
+    pub fn read(path: &Path) -> io::Result<FileAttributes> {
+        use std::os::windows::ffi::OsStrExt;
+        use windows_sys::Win32::Security::{GetFileSecurityW, DACL_SECURITY_INFORMATION};
+
+        let wide_path = path.as_os_str().encode_wide().chain(Some(0)).collect::<Vec<u16>>();
+        // 1 回目で必要なサイズを取得し、2 回目で読み込む
+        let mut needed = 0u32;
+        unsafe { GetFileSecurityW(wide_path.as_ptr(), DACL_SECURITY_INFORMATION, std::ptr::null_mut(), 0, &mut needed) };
+        if needed == 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let mut security_descriptor = vec![0u8; needed as usize];
+        let ok = unsafe { GetFileSecurityW(wide_path.as_ptr(), DACL_SECURITY_INFORMATION, security_descriptor.as_mut_ptr().cast(), needed, &mut needed) };
+        if ok == 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(FileAttributes {
+            security_descriptor: security_descriptor,
+        })
+    }
+
+    /// Copy the ACL to a file.
+    /// Returns ["acl"] if the ACL could not be written (e.g. without the permission to change it).
+    #[cfg(windows)]
+    pub fn apply(&self, path: &Path) -> Vec<String> {
+        use std::os::windows::ffi::OsStrExt;
+        use windows_sys::Win32::Security::{SetFileSecurityW, DACL_SECURITY_INFORMATION};
+
+        if self.security_descriptor.is_empty() {
+            return Vec::new();
+        }
+        let wide_path = path.as_os_str().encode_wide().chain(Some(0)).collect::<Vec<u16>>();
+        let ok = unsafe { SetFileSecurityW(wide_path.as_ptr(), DACL_SECURITY_INFORMATION, self.security_descriptor.as_ptr() as *mut _) };
+        if ok == 0 {
+            vec!["acl".to_string()]
+        }
+        else {
+            Vec::new()
+        }
+    }
+
+    /// Extended attributes are not supported on this platform.
+    #[cfg(not(any(unix, windows)))]
+    pub fn read(_path: &Path) -> io::Result<FileAttributes> {
+        Ok(FileAttributes::default())
+    }
+
+    /// Extended attributes are not supported on this platform.
+    #[cfg(not(any(unix, windows)))]
+    pub fn apply(&self, _path: &Path) -> Vec<String> {
+        Vec::new()
+    }
+}